@@ -0,0 +1,223 @@
+// src/abi_library.rs
+use anyhow::{anyhow, Result};
+use ethers::abi::{Abi, Function, HumanReadableParser, ParamType, RawLog, Token};
+use ethers::types::Log;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+use crate::contract::detokenize;
+
+/// 解码交易和日志用的常见合约 ABI：(名字, ABI)
+///
+/// 同一个 selector / topic 出现在多个 ABI 里时，靠前的优先。
+pub static ABI_LIBRARY: LazyLock<Vec<(&'static str, Abi)>> = LazyLock::new(|| {
+    let parse = |name: &'static str, items: &[&str]| {
+        (name, human_readable(items).unwrap_or_else(|e| panic!("invalid {} ABI: {}", name, e)))
+    };
+    vec![
+        parse(
+            "ERC20",
+            &[
+                "function transfer(address to, uint256 amount) returns (bool)",
+                "function transferFrom(address from, address to, uint256 amount) returns (bool)",
+                "function approve(address spender, uint256 amount) returns (bool)",
+                "event Transfer(address indexed from, address indexed to, uint256 value)",
+                "event Approval(address indexed owner, address indexed spender, uint256 value)",
+            ],
+        ),
+        parse(
+            "WETH",
+            &[
+                "function deposit() payable",
+                "function withdraw(uint256 wad)",
+                "event Deposit(address indexed dst, uint256 wad)",
+                "event Withdrawal(address indexed src, uint256 wad)",
+            ],
+        ),
+        parse(
+            "UniswapV2Router02",
+            &[
+                "function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) returns (uint256[] amounts)",
+                "function swapTokensForExactTokens(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline) returns (uint256[] amounts)",
+                "function swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) payable returns (uint256[] amounts)",
+                "function swapTokensForExactETH(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline) returns (uint256[] amounts)",
+                "function swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) returns (uint256[] amounts)",
+                "function swapETHForExactTokens(uint256 amountOut, address[] path, address to, uint256 deadline) payable returns (uint256[] amounts)",
+                "function swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)",
+                "function swapExactETHForTokensSupportingFeeOnTransferTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) payable",
+                "function swapExactTokensForETHSupportingFeeOnTransferTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)",
+                "function addLiquidity(address tokenA, address tokenB, uint256 amountADesired, uint256 amountBDesired, uint256 amountAMin, uint256 amountBMin, address to, uint256 deadline) returns (uint256 amountA, uint256 amountB, uint256 liquidity)",
+                "function addLiquidityETH(address token, uint256 amountTokenDesired, uint256 amountTokenMin, uint256 amountETHMin, address to, uint256 deadline) payable returns (uint256 amountToken, uint256 amountETH, uint256 liquidity)",
+                "function removeLiquidity(address tokenA, address tokenB, uint256 liquidity, uint256 amountAMin, uint256 amountBMin, address to, uint256 deadline) returns (uint256 amountA, uint256 amountB)",
+                "function removeLiquidityETH(address token, uint256 liquidity, uint256 amountTokenMin, uint256 amountETHMin, address to, uint256 deadline) returns (uint256 amountToken, uint256 amountETH)",
+            ],
+        ),
+        parse(
+            "UniswapV2Pair",
+            &[
+                "function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes data)",
+                "function sync()",
+                "event Swap(address indexed sender, uint256 amount0In, uint256 amount1In, uint256 amount0Out, uint256 amount1Out, address indexed to)",
+                "event Sync(uint112 reserve0, uint112 reserve1)",
+                "event Mint(address indexed sender, uint256 amount0, uint256 amount1)",
+                "event Burn(address indexed sender, uint256 amount0, uint256 amount1, address indexed to)",
+            ],
+        ),
+        // HumanReadableParser 不支持给 tuple 字段命名，字段顺序：
+        // exactInputSingle (tokenIn, tokenOut, fee, recipient, deadline, amountIn, amountOutMinimum, sqrtPriceLimitX96)
+        // exactInput (path, recipient, deadline, amountIn, amountOutMinimum)
+        parse(
+            "UniswapV3SwapRouter",
+            &[
+                "function exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160) params) payable returns (uint256 amountOut)",
+                "function exactInput((bytes,address,uint256,uint256,uint256) params) payable returns (uint256 amountOut)",
+                "function exactOutputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160) params) payable returns (uint256 amountIn)",
+                "function exactOutput((bytes,address,uint256,uint256,uint256) params) payable returns (uint256 amountIn)",
+                "function multicall(bytes[] data) payable returns (bytes[] results)",
+                "function unwrapWETH9(uint256 amountMinimum, address recipient) payable",
+                "function refundETH() payable",
+                "function sweepToken(address token, uint256 amountMinimum, address recipient) payable",
+            ],
+        ),
+        // SwapRouter02：同上但参数里没有 deadline，deadline 放在 multicall 上
+        parse(
+            "UniswapV3SwapRouter02",
+            &[
+                "function exactInputSingle((address,address,uint24,address,uint256,uint256,uint160) params) payable returns (uint256 amountOut)",
+                "function exactInput((bytes,address,uint256,uint256) params) payable returns (uint256 amountOut)",
+                "function exactOutputSingle((address,address,uint24,address,uint256,uint256,uint160) params) payable returns (uint256 amountIn)",
+                "function exactOutput((bytes,address,uint256,uint256) params) payable returns (uint256 amountIn)",
+                "function multicall(uint256 deadline, bytes[] data) payable returns (bytes[] results)",
+                "function multicall(bytes32 previousBlockhash, bytes[] data) payable returns (bytes[] results)",
+            ],
+        ),
+        parse(
+            "UniswapV3Pool",
+            &[
+                "event Initialize(uint160 sqrtPriceX96, int24 tick)",
+                "event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick)",
+                "event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)",
+                "event Burn(address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)",
+                "event Collect(address indexed owner, address recipient, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount0, uint128 amount1)",
+            ],
+        ),
+        parse(
+            "ChainlinkAggregator",
+            &[
+                "event AnswerUpdated(int256 indexed current, uint256 indexed roundId, uint256 updatedAt)",
+                "event NewRound(uint256 indexed roundId, address indexed startedBy, uint256 startedAt)",
+            ],
+        ),
+    ]
+});
+
+/// `abi::parse_abi` 不支持内联 tuple 参数（V3 router 的 params），逐条用 HumanReadableParser 解析
+fn human_readable(items: &[&str]) -> Result<Abi> {
+    let mut abi = Abi::default();
+    for item in items {
+        if item.starts_with("event ") {
+            let event = HumanReadableParser::parse_event(item).map_err(|e| anyhow!("{}: {}", item, e))?;
+            abi.events.entry(event.name.clone()).or_default().push(event);
+        } else {
+            let function = HumanReadableParser::parse_function(item).map_err(|e| anyhow!("{}: {}", item, e))?;
+            abi.functions.entry(function.name.clone()).or_default().push(function);
+        }
+    }
+    Ok(abi)
+}
+
+/// multicall 嵌套的最大深度
+const MAX_CALL_DEPTH: usize = 2;
+
+/// 一个解码后的参数：(名字, 类型, 值)
+pub type DecodedParam = (String, String, Value);
+
+/// 解码后的 calldata
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedCall {
+    pub abi: &'static str,
+    pub function: String,
+    pub args: Vec<DecodedParam>,
+    /// multicall 里打包的子调用
+    pub calls: Vec<DecodedCall>,
+}
+
+/// 解码后的事件
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedEvent {
+    pub abi: &'static str,
+    pub event: String,
+    pub args: Vec<DecodedParam>,
+}
+
+fn signature(name: &str, kinds: impl Iterator<Item = ParamType>) -> String {
+    let kinds: Vec<String> = kinds.map(|k| k.to_string()).collect();
+    format!("{}({})", name, kinds.join(","))
+}
+
+/// 按 selector 在库里找函数并解码；不认识或解不开时为 None
+pub fn decode_calldata(data: &[u8]) -> Option<DecodedCall> {
+    decode_calldata_at(data, 0)
+}
+
+fn decode_calldata_at(data: &[u8], depth: usize) -> Option<DecodedCall> {
+    let selector = data.get(..4)?;
+    ABI_LIBRARY.iter().find_map(|(abi, contract)| {
+        contract
+            .functions()
+            .filter(|f| f.short_signature() == selector)
+            .find_map(|f| decode_function(abi, f, &data[4..], depth))
+    })
+}
+
+fn decode_function(abi: &'static str, f: &Function, input: &[u8], depth: usize) -> Option<DecodedCall> {
+    let tokens = f.decode_input(input).ok()?;
+
+    // multicall(bytes[]) 的每个元素本身也是 calldata
+    let mut calls = Vec::new();
+    if f.name == "multicall" && depth < MAX_CALL_DEPTH {
+        if let Some(Token::Array(inner)) = tokens.last() {
+            for call in inner {
+                if let Token::Bytes(data) = call {
+                    calls.extend(decode_calldata_at(data, depth + 1));
+                }
+            }
+        }
+    }
+
+    Some(DecodedCall {
+        abi,
+        function: signature(&f.name, f.inputs.iter().map(|p| p.kind.clone())),
+        args: f
+            .inputs
+            .iter()
+            .zip(&tokens)
+            .map(|(p, t)| (p.name.clone(), p.kind.to_string(), detokenize(&p.kind, t)))
+            .collect(),
+        calls,
+    })
+}
+
+/// 按 topic0 和 indexed 参数个数在库里找事件并解码
+pub fn decode_log(log: &Log) -> Option<DecodedEvent> {
+    let topic0 = *log.topics.first()?;
+    ABI_LIBRARY.iter().find_map(|(abi, contract)| {
+        contract
+            .events()
+            .filter(|e| !e.anonymous && e.signature() == topic0)
+            .find_map(|e| {
+                let raw = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
+                let parsed = e.parse_log(raw).ok()?;
+                Some(DecodedEvent {
+                    abi,
+                    event: signature(&e.name, e.inputs.iter().map(|p| p.kind.clone())),
+                    args: e
+                        .inputs
+                        .iter()
+                        .zip(parsed.params)
+                        .map(|(p, param)| (p.name.clone(), p.kind.to_string(), detokenize(&p.kind, &param.value)))
+                        .collect(),
+                })
+            })
+    })
+}
@@ -0,0 +1,206 @@
+// src/approvals.rs
+use anyhow::{anyhow, Result};
+use ethers::abi::{self, Abi, Token};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::balance::BalanceModule;
+use crate::rpc::{scan_logs, view_call};
+
+/// 授权相关的 ERC20 / ERC721 / ERC1155 函数
+static APPROVAL_ABI: LazyLock<Abi> = LazyLock::new(|| {
+    abi::parse_abi(&[
+        "function allowance(address owner, address spender) view returns (uint256)",
+        "function getApproved(uint256 tokenId) view returns (address)",
+        "function ownerOf(uint256 tokenId) view returns (address)",
+        "function isApprovedForAll(address owner, address operator) view returns (bool)",
+        "function approve(address spender, uint256 value) returns (bool)",
+        "function setApprovalForAll(address operator, bool approved)",
+    ])
+    .expect("valid approval ABI")
+});
+
+/// 一次最多处理的授权事件数，每个候选授权都要一次 eth_call；超过时分页，用 next_from_block 接着查
+pub const MAX_APPROVALS: usize = 500;
+
+/// 不给 from_block 时往回扫的块数（主网约 9 个月）
+pub const DEFAULT_APPROVAL_LOOKBACK: u64 = 2_000_000;
+
+/// 不低于这个值的 allowance 视为无限授权（远超任何代币的实际供应量）
+pub static UNLIMITED_ALLOWANCE: LazyLock<U256> = LazyLock::new(|| U256::one() << 128);
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalKind {
+    /// ERC20 approve(spender, amount)
+    Erc20,
+    /// ERC721 approve(spender, tokenId)，只对单个 tokenId 生效
+    Erc721,
+    /// ERC721 / ERC1155 setApprovalForAll(operator, true)，整个合约
+    ApprovalForAll,
+}
+
+/// 仍然有效的一条授权
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenApproval {
+    pub kind: ApprovalKind,
+    pub token: Address,
+    pub spender: Address,
+    /// 只有 Erc721 有
+    pub token_id: Option<U256>,
+    /// 只有 Erc20 有：当前 allowance（原始值）
+    pub allowance: Option<U256>,
+    /// Erc20 的 decimals；读不到时为 None
+    pub decimals: Option<u8>,
+    /// 最近一次授权事件所在块
+    pub last_block: u64,
+}
+
+impl TokenApproval {
+    pub fn is_unlimited(&self) -> bool {
+        match self.kind {
+            ApprovalKind::Erc20 => self.allowance.is_some_and(|a| a >= *UNLIMITED_ALLOWANCE),
+            // 整个合约的授权等同于无限
+            ApprovalKind::ApprovalForAll => true,
+            ApprovalKind::Erc721 => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Approvals {
+    pub approvals: Vec<TokenApproval>,
+    /// 实际扫描的起始块
+    pub from_block: u64,
+    /// 授权事件超过 MAX_APPROVALS，只扫到了 next_from_block 之前
+    pub truncated: bool,
+    pub next_from_block: Option<u64>,
+    /// 读取时的块号
+    pub block: U64,
+}
+
+/// 撤销授权的 calldata，发给授权所在的合约
+///
+/// - Erc20：approve(spender, 0)
+/// - Erc721：approve(address(0), tokenId)
+/// - ApprovalForAll：setApprovalForAll(operator, false)
+pub fn revoke_calldata(kind: ApprovalKind, spender: Address, token_id: Option<U256>) -> Result<Bytes> {
+    let data = match kind {
+        ApprovalKind::Erc20 => APPROVAL_ABI
+            .function("approve")?
+            .encode_input(&[Token::Address(spender), Token::Uint(U256::zero())])?,
+        ApprovalKind::Erc721 => {
+            let token_id = token_id.ok_or_else(|| anyhow!("ERC721 approvals need a token_id"))?;
+            APPROVAL_ABI
+                .function("approve")?
+                .encode_input(&[Token::Address(Address::zero()), Token::Uint(token_id)])?
+        }
+        ApprovalKind::ApprovalForAll => APPROVAL_ABI
+            .function("setApprovalForAll")?
+            .encode_input(&[Token::Address(spender), Token::Bool(false)])?,
+    };
+    Ok(data.into())
+}
+
+impl BalanceModule {
+    /// 列出 `owner` 当前仍然有效的授权
+    ///
+    /// 扫描 owner 发出的 Approval / ApprovalForAll 日志找出候选（不限合约），
+    /// 再按当前链上状态核对：allowance > 0、getApproved 仍是该地址且 owner 仍持有、isApprovedForAll 为 true。
+    /// 不给 `from_block` 时只扫最近 DEFAULT_APPROVAL_LOOKBACK 块；事件太多时分页，见 `next_from_block`。
+    pub async fn get_approvals(&self, owner: Address, from_block: Option<u64>) -> Result<Approvals> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let from_block = from_block.unwrap_or_else(|| block.as_u64().saturating_sub(DEFAULT_APPROVAL_LOOKBACK));
+
+        let approval = H256::from(ethers::utils::keccak256("Approval(address,address,uint256)"));
+        let approval_for_all = H256::from(ethers::utils::keccak256("ApprovalForAll(address,address,bool)"));
+        let filter = Filter::new()
+            .topic0(vec![approval, approval_for_all])
+            .topic1(H256::from(owner));
+        let scan = scan_logs(&self.provider, filter, from_block, block.as_u64(), Some(MAX_APPROVALS)).await?;
+        let logs = scan.logs;
+
+        // (合约, 类型, spender, tokenId) → 最近一次事件所在块；日志按时间顺序返回
+        let mut candidates: BTreeMap<(Address, ApprovalKind, Address, Option<U256>), u64> = BTreeMap::new();
+        for log in &logs {
+            let Some(spender) = log.topics.get(2).map(|t| Address::from(*t)) else { continue };
+            // approve(0x0, tokenId) 是撤销：getApproved 之后也是 0x0，不能当成给零地址的授权
+            if spender == Address::zero() {
+                continue;
+            }
+            // ERC20 和 ERC721 的 Approval 签名相同，ERC721 的 tokenId 是第三个 indexed 参数
+            let (kind, token_id) = match (log.topics[0], log.topics.get(3)) {
+                (t, None) if t == approval => (ApprovalKind::Erc20, None),
+                (t, Some(id)) if t == approval => {
+                    (ApprovalKind::Erc721, Some(U256::from_big_endian(id.as_bytes())))
+                }
+                _ => (ApprovalKind::ApprovalForAll, None),
+            };
+            let last_block = log.block_number.map_or(0, |b| b.as_u64());
+            candidates.insert((log.address, kind, spender, token_id), last_block);
+        }
+
+        let mut approvals = Vec::new();
+        for ((token, kind, spender, token_id), last_block) in candidates {
+            let (live, allowance) = match kind {
+                ApprovalKind::Erc20 => {
+                    let allowance = self
+                        .approval_call(token, "allowance", &[Token::Address(owner), Token::Address(spender)], block)
+                        .await?
+                        .and_then(Token::into_uint);
+                    (allowance.is_some_and(|a| !a.is_zero()), allowance)
+                }
+                ApprovalKind::Erc721 => {
+                    let id = Token::Uint(token_id.unwrap_or_default());
+                    let approved = self.approval_call(token, "getApproved", std::slice::from_ref(&id), block).await?;
+                    // 转手之后旧的单个授权自动失效
+                    let holder = self.approval_call(token, "ownerOf", &[id], block).await?;
+                    (
+                        approved == Some(Token::Address(spender)) && holder == Some(Token::Address(owner)),
+                        None,
+                    )
+                }
+                ApprovalKind::ApprovalForAll => {
+                    let approved = self
+                        .approval_call(token, "isApprovedForAll", &[Token::Address(owner), Token::Address(spender)], block)
+                        .await?;
+                    (approved == Some(Token::Bool(true)), None)
+                }
+            };
+            if !live {
+                continue;
+            }
+
+            let decimals = match kind {
+                ApprovalKind::Erc20 => self.decimals(chain, token).await.ok(),
+                _ => None,
+            };
+            approvals.push(TokenApproval { kind, token, spender, token_id, allowance, decimals, last_block });
+        }
+
+        Ok(Approvals {
+            approvals,
+            from_block,
+            truncated: scan.next_block.is_some(),
+            next_from_block: scan.next_block,
+            block,
+        })
+    }
+
+    /// revert 或返回值解不开时为 None
+    async fn approval_call(&self, to: Address, name: &str, args: &[Token], block: U64) -> Result<Option<Token>> {
+        let f = APPROVAL_ABI.function(name)?;
+        let tx: TypedTransaction = TransactionRequest::new().to(to).data(f.encode_input(args)?).into();
+        let Some(data) = view_call(&self.provider, &tx, Some(block)).await? else {
+            return Ok(None);
+        };
+        Ok(f.decode_output(&data).ok().and_then(|mut out| out.pop()))
+    }
+}
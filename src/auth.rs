@@ -0,0 +1,192 @@
+// src/auth.rs
+use anyhow::{bail, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ethers::types::Address;
+use ethers::utils::keccak256;
+use rmcp::{service::RequestContext, ErrorData, RoleServer};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::{ClientConfig, RateLimits};
+
+/// 会构造链上交易的工具，需要客户端显式开启 `allow_execution`
+pub const EXECUTION_TOOLS: &[&str] = &["swap_tokens", "simulate_swap", "revoke_approvals"];
+
+/// 调用方身份及其权限
+#[derive(Clone, Debug)]
+pub struct ClientIdentity {
+    pub id: String,
+    tools: Option<HashSet<String>>,
+    wallets: Option<HashSet<Address>>,
+    allow_execution: bool,
+    allow_registry: bool,
+    /// 单独配置的限流；None 表示使用全局默认值
+    pub limits: Option<RateLimits>,
+}
+
+impl ClientIdentity {
+    /// 本地调用方（stdio 或未开启鉴权的 HTTP），拥有全部权限
+    pub fn local() -> Self {
+        Self {
+            id: "local".to_string(),
+            tools: None,
+            wallets: None,
+            allow_execution: true,
+            allow_registry: true,
+            limits: None,
+        }
+    }
+
+    /// 取出当前请求的调用方
+    ///
+    /// HTTP 模式下身份由 [`require_bearer`] 写进请求扩展；
+    /// 没有身份说明是 stdio 或没有配置客户端，按本地调用处理。
+    pub fn from_context(context: &RequestContext<RoleServer>) -> Self {
+        context
+            .extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.extensions.get::<ClientIdentity>())
+            .cloned()
+            .unwrap_or_else(Self::local)
+    }
+
+    pub fn can_use_tool(&self, tool: &str) -> bool {
+        if EXECUTION_TOOLS.contains(&tool) && !self.allow_execution {
+            return false;
+        }
+        match &self.tools {
+            Some(tools) => tools.contains(tool),
+            None => true,
+        }
+    }
+
+    /// 能否使用会构造或模拟交易的功能
+    pub fn can_execute(&self) -> bool {
+        self.allow_execution
+    }
+
+    /// 能否往共用的代币注册表里注册代币
+    pub fn can_register_tokens(&self) -> bool {
+        self.allow_registry
+    }
+
+    pub fn can_query_wallet(&self, wallet: &Address) -> bool {
+        match &self.wallets {
+            Some(wallets) => wallets.contains(wallet),
+            None => true,
+        }
+    }
+
+    /// 是否只能查询指定的钱包
+    pub fn is_wallet_scoped(&self) -> bool {
+        self.wallets.is_some()
+    }
+
+    /// 钱包不在授权范围内时返回 MCP 错误
+    pub fn check_wallet(&self, wallet: &Address) -> Result<(), ErrorData> {
+        if self.can_query_wallet(wallet) {
+            Ok(())
+        } else {
+            Err(ErrorData::invalid_params(
+                format!("Client '{}' is not allowed to query wallet {:?}", self.id, wallet),
+                None,
+            ))
+        }
+    }
+}
+
+impl From<&ClientConfig> for ClientIdentity {
+    fn from(config: &ClientConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            tools: config.tools.as_ref().map(|t| t.iter().cloned().collect()),
+            wallets: config.wallets.as_ref().map(|w| w.iter().cloned().collect()),
+            allow_execution: config.allow_execution,
+            allow_registry: config.allow_registry,
+            limits: config.limits.clone(),
+        }
+    }
+}
+
+/// token → 客户端身份
+///
+/// 只保存 token 的 keccak256 哈希，内存里不留明文。
+#[derive(Clone, Debug, Default)]
+pub struct AuthConfig {
+    clients: HashMap<[u8; 32], ClientIdentity>,
+}
+
+impl AuthConfig {
+    pub fn new(clients: &[ClientConfig]) -> Result<Self> {
+        let mut by_token = HashMap::new();
+        let mut ids = HashSet::new();
+
+        for client in clients {
+            if client.token.is_empty() {
+                bail!("Client '{}' has an empty token", client.id);
+            }
+            if !ids.insert(client.id.as_str()) {
+                bail!("Duplicate client id '{}'", client.id);
+            }
+            if by_token
+                .insert(keccak256(client.token.as_bytes()), ClientIdentity::from(client))
+                .is_some()
+            {
+                bail!("Client '{}' reuses another client's token", client.id);
+            }
+        }
+
+        Ok(Self { clients: by_token })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<&ClientIdentity> {
+        self.clients.get(&keccak256(token.as_bytes()))
+    }
+}
+
+/// axum 中间件：校验 `Authorization: Bearer <token>`，并把 [`ClientIdentity`] 放进请求扩展
+pub async fn require_bearer(
+    State(auth): State<Arc<AuthConfig>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let header = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+
+    let result = match header {
+        None => Err("missing Authorization header"),
+        Some(value) => match value.strip_prefix("Bearer ") {
+            None => Err("malformed Authorization header"),
+            Some(token) => auth.authenticate(token.trim()).ok_or("unknown token"),
+        },
+    };
+
+    match result {
+        Ok(client) => {
+            request.extensions_mut().insert(client.clone());
+            next.run(request).await
+        }
+        Err(reason) => {
+            // 只记录原因，不记录 token 本身
+            warn!(
+                method = %request.method(),
+                path = %request.uri().path(),
+                "Rejected MCP request: {}",
+                reason
+            );
+            (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+        }
+    }
+}
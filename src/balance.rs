@@ -1,50 +1,103 @@
 // src/balance.rs
 use ethers::prelude::*;
-use ethers::abi::Abi;
+use ethers::abi::{Abi, Token};
+use ethers::contract::{Multicall, MulticallVersion, MULTICALL_ADDRESS};
 use rust_decimal::Decimal;
 use anyhow::Result;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use tracing::warn;
 
-pub struct BalanceModule {
-    pub provider: Arc<Provider<Http>>,
+use crate::cache::RpcCache;
+use crate::rpc::{metered, RpcProvider};
+
+/// ERC20 ABI 只解析一次
+pub static ERC20_ABI: LazyLock<Abi> = LazyLock::new(|| {
+    serde_json::from_slice(include_bytes!("../abis/erc20_abi.json")).expect("valid ERC20 ABI")
+});
+
+/// 一个 Multicall 最多打包的余额查询数，避免单个 eth_call 超出节点的 gas / 响应大小限制
+pub const MULTICALL_BATCH_SIZE: usize = 300;
+
+/// 批量查询中的一格；单格失败不影响其他格
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceCell {
+    pub wallet: Address,
+    /// None 表示 ETH
+    pub token: Option<Address>,
+    pub balance: Result<Decimal, String>,
+}
+
+/// 余额查询；provider 默认是计数的 HTTP provider，测试里可以换成任意 `Middleware`
+pub struct BalanceModule<M = RpcProvider> {
+    pub provider: Arc<M>,
+    pub cache: Arc<RpcCache>,
 }
 
 impl BalanceModule {
     pub fn new(provider: Provider<Http>) -> Self {
-        Self {
-            provider: Arc::new(provider),
-        }
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+}
+
+impl<M: Middleware + 'static> BalanceModule<M> {
+    /// 复用已经包好的 provider
+    pub fn shared(provider: Arc<M>, cache: Arc<RpcCache>) -> Self {
+        Self { provider, cache }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
     }
 
     /// 获取钱包余额
     /// - address: 钱包地址
     /// - token: None -> ETH, Some(token_addr) -> ERC20
     pub async fn get_balance(&self, address: Address, token: Option<Address>) -> Result<Decimal> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.get_balance_at(address, token, block).await
+    }
+
+    /// 同 `get_balance`，读取固定在 `block` 上
+    pub async fn get_balance_at(&self, address: Address, token: Option<Address>, block: U64) -> Result<Decimal> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+
         let balance_decimal = match token {
             None => {
                 // ETH 余额
-                let balance_wei = self.provider.get_balance(address, None).await?;
+                let balance_wei: U256 = self
+                    .cache
+                    .at_block(chain, format!("eth_getBalance:{:?}", address), block, || async {
+                        Ok(self.provider.get_balance(address, Some(block.into())).await?)
+                    })
+                    .await?;
                 let balance_str = ethers::utils::format_units(balance_wei, 18).unwrap(); // ETH 固定 18 decimals
                 balance_str.parse::<Decimal>()?
             }
             Some(token_addr) => {
                 // ERC20 余额
-                let erc20_abi: Abi =
-                    serde_json::from_slice(include_bytes!("../abis/erc20_abi.json"))?;
-                let erc20 = Contract::new(token_addr, erc20_abi, self.provider.clone());
+                let erc20 = Contract::new(token_addr, ERC20_ABI.clone(), self.provider.clone());
 
                 // ERC20 balance
-                let balance_wei: U256 = erc20
-                    .method::<_, U256>("balanceOf", address)?
-                    .call()
+                let balance_wei: U256 = self
+                    .cache
+                    .at_block(
+                        chain,
+                        format!("erc20.balanceOf:{:?}:{:?}", token_addr, address),
+                        block,
+                        || async {
+                            Ok(erc20
+                                .method::<_, U256>("balanceOf", address)?
+                                .block(block)
+                                .call()
+                                .await?)
+                        },
+                    )
                     .await?;
 
-                // ERC20 decimals
-                let decimals: u8 = erc20
-                    .method::<_, u8>("decimals", ())?
-                    .call()
-                    .await?;
-                let decimals_u32 = decimals as u32;
+                let decimals_u32 = self.decimals(chain, token_addr).await? as u32;
 
                 let balance_str = ethers::utils::format_units(balance_wei, decimals_u32)
                     .unwrap(); // 安全，因为 Infallible 永远不会失败
@@ -55,4 +108,140 @@ impl BalanceModule {
 
         Ok(balance_decimal)
     }
+
+    /// 批量查询：wallets × tokens 的余额矩阵，按钱包优先的顺序返回
+    ///
+    /// 所有格子读同一个块，通过 Multicall3 打包成少量 eth_call；
+    /// 链上没有 Multicall3 时退回逐个查询。
+    pub async fn get_balances(
+        &self,
+        wallets: &[Address],
+        tokens: &[Option<Address>],
+    ) -> Result<Vec<BalanceCell>> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.get_balances_at(wallets, tokens, block).await
+    }
+
+    /// 同 `get_balances`，读取固定在 `block` 上
+    pub async fn get_balances_at(
+        &self,
+        wallets: &[Address],
+        tokens: &[Option<Address>],
+        block: U64,
+    ) -> Result<Vec<BalanceCell>> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+
+        // decimals 查不到的代币，整列都报错
+        let mut decimals: HashMap<Option<Address>, Result<u32, String>> = HashMap::new();
+        for token in tokens {
+            let d = match token {
+                None => Ok(18),
+                Some(t) => self.decimals(chain, *t).await.map(u32::from).map_err(|e| e.to_string()),
+            };
+            decimals.insert(*token, d);
+        }
+
+        let mut cells = Vec::with_capacity(wallets.len() * tokens.len());
+        let mut pending = Vec::new();
+        for wallet in wallets {
+            for token in tokens {
+                match &decimals[token] {
+                    Ok(_) => pending.push((*wallet, *token)),
+                    Err(e) => cells.push(BalanceCell {
+                        wallet: *wallet,
+                        token: *token,
+                        balance: Err(format!("cannot read decimals: {}", e)),
+                    }),
+                }
+            }
+        }
+
+        for chunk in pending.chunks(MULTICALL_BATCH_SIZE) {
+            let raw = match self.multicall_balances(chunk, block).await {
+                Ok(raw) => raw,
+                Err(e) => {
+                    warn!("Multicall failed ({}), querying {} balances one by one", e, chunk.len());
+                    let mut raw = Vec::with_capacity(chunk.len());
+                    for (wallet, token) in chunk {
+                        raw.push(self.get_balance_at(*wallet, *token, block).await.map_err(|e| e.to_string()));
+                    }
+                    cells.extend(chunk.iter().zip(raw).map(|((wallet, token), balance)| BalanceCell {
+                        wallet: *wallet,
+                        token: *token,
+                        balance,
+                    }));
+                    continue;
+                }
+            };
+
+            for ((wallet, token), value) in chunk.iter().zip(raw) {
+                let decimals = *decimals[token].as_ref().expect("checked above");
+                let balance = value.and_then(|v| {
+                    ethers::utils::format_units(v, decimals)
+                        .map_err(|e| e.to_string())?
+                        .parse::<Decimal>()
+                        .map_err(|e| e.to_string())
+                });
+                cells.push(BalanceCell {
+                    wallet: *wallet,
+                    token: *token,
+                    balance,
+                });
+            }
+        }
+
+        // 恢复成输入顺序
+        let position = |c: &BalanceCell| {
+            let w = wallets.iter().position(|w| *w == c.wallet).unwrap_or(usize::MAX);
+            let t = tokens.iter().position(|t| *t == c.token).unwrap_or(usize::MAX);
+            (w, t)
+        };
+        cells.sort_by_key(position);
+        Ok(cells)
+    }
+
+    /// ERC20 decimals（不会变，永久缓存）
+    pub(crate) async fn decimals(&self, chain: u64, token: Address) -> Result<u8> {
+        self.cache
+            .immutable(chain, format!("erc20.decimals:{:?}", token), || async {
+                let erc20 = Contract::new(token, ERC20_ABI.clone(), self.provider.clone());
+                Ok(erc20.method::<_, u8>("decimals", ())?.call().await?)
+            })
+            .await
+    }
+
+    /// 一次 Multicall3 aggregate3；每个子调用都允许失败
+    async fn multicall_balances(
+        &self,
+        pairs: &[(Address, Option<Address>)],
+        block: U64,
+    ) -> Result<Vec<Result<U256, String>>> {
+        let mut multicall = Multicall::new(self.provider.clone(), Some(MULTICALL_ADDRESS))
+            .await?
+            .version(MulticallVersion::Multicall3)
+            .block(block);
+
+        for (wallet, token) in pairs {
+            match token {
+                None => {
+                    multicall.add_get_eth_balance(*wallet, true);
+                }
+                Some(token) => {
+                    let erc20 = Contract::new(*token, ERC20_ABI.clone(), self.provider.clone());
+                    multicall.add_call(erc20.method::<_, U256>("balanceOf", *wallet)?, true);
+                }
+            }
+        }
+
+        Ok(multicall
+            .call_raw()
+            .await?
+            .into_iter()
+            .map(|result| match result {
+                Ok(Token::Uint(value)) => Ok(value),
+                Ok(other) => Err(format!("unexpected return value {:?}", other)),
+                Err(_) => Err("balance call reverted or returned no data".to_string()),
+            })
+            .collect())
+    }
 }
@@ -12,7 +12,7 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::env;
 use tokio::process::Command;
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -98,6 +98,36 @@ async fn main() -> Result<(), RmcpError> {
 
     info!("swap_tokens result: {:#?}", swap_result);
 
+    // ===== Snapshot =====
+    // 配合 MCP_RPC_RECORD / MCP_RPC_REPLAY：录制时写快照，回放时逐字节比对（回放时快照必须已存在）
+    if let Ok(path) = env::var("MCP_TEST_CLIENT_SNAPSHOT") {
+        let results: Vec<_> = [
+            ("get_balance", &balance_result),
+            ("get_price ETH/USD", &eth_price_result),
+            ("get_price BTC/USD", &btc_price_result),
+            ("swap_tokens", &swap_result),
+        ]
+        .into_iter()
+        .map(|(name, result)| serde_json::json!({ "call": name, "result": result }))
+        .collect();
+        let snapshot = serde_json::to_string_pretty(&results).expect("results are JSON");
+        match std::fs::read_to_string(&path) {
+            Ok(expected) if expected == snapshot => info!("Results match snapshot {}", path),
+            Ok(_) => {
+                error!("Results differ from snapshot {}:\n{}", path, snapshot);
+                std::process::exit(1);
+            }
+            Err(e) if env::var("MCP_RPC_REPLAY").is_ok() => {
+                error!("Cannot read snapshot {} to compare the replay against: {}", path, e);
+                std::process::exit(1);
+            }
+            Err(_) => {
+                std::fs::write(&path, snapshot).expect("cannot write snapshot");
+                info!("Snapshot written to {}", path);
+            }
+        }
+    }
+
     // ===== Shutdown client =====
     client.cancel().await?;
     Ok(())
@@ -0,0 +1,357 @@
+// src/cache.rs
+use anyhow::{anyhow, Result};
+use ethers::providers::Middleware;
+use ethers::types::{BlockNumber, H256, U64};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+use crate::config::Confidence;
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+/// 按块缓存的条目：块号，加上这个块号固定时看到的块哈希（没固定过的块为 None）
+type BlockKey = (CacheKey, U64, Option<H256>);
+
+/// 最多记住多少个固定过的块的哈希；旧于最新块的条目只在这些块上保留
+const PINNED_BLOCKS_KEPT: usize = 32;
+
+/// 缓存 key：(链, 调用)，块相关的数据再加上块号
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    chain: u64,
+    call: String,
+}
+
+/// 命中/未命中统计
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CacheStats {
+    pub immutable_hits: u64,
+    pub immutable_misses: u64,
+    pub block_hits: u64,
+    pub block_misses: u64,
+    pub immutable_entries: usize,
+    pub block_entries: usize,
+}
+
+impl CacheStats {
+    /// Prometheus 文本格式
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# TYPE eth_mcp_cache_hits_total counter\n");
+        out.push_str(&format!("eth_mcp_cache_hits_total{{kind=\"immutable\"}} {}\n", self.immutable_hits));
+        out.push_str(&format!("eth_mcp_cache_hits_total{{kind=\"block\"}} {}\n", self.block_hits));
+        out.push_str("# TYPE eth_mcp_cache_misses_total counter\n");
+        out.push_str(&format!("eth_mcp_cache_misses_total{{kind=\"immutable\"}} {}\n", self.immutable_misses));
+        out.push_str(&format!("eth_mcp_cache_misses_total{{kind=\"block\"}} {}\n", self.block_misses));
+        out.push_str("# TYPE eth_mcp_cache_entries gauge\n");
+        out.push_str(&format!("eth_mcp_cache_entries{{kind=\"immutable\"}} {}\n", self.immutable_entries));
+        out.push_str(&format!("eth_mcp_cache_entries{{kind=\"block\"}} {}\n", self.block_entries));
+        out
+    }
+}
+
+/// 一次读取固定使用的块；结果里带上块哈希，下游可以据此发现重组
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinnedBlock {
+    pub number: U64,
+    pub hash: H256,
+    /// finalized 块不会被重组，读完不用再确认
+    pub finalized: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    immutable_hits: AtomicU64,
+    immutable_misses: AtomicU64,
+    block_hits: AtomicU64,
+    block_misses: AtomicU64,
+}
+
+/// 只读调用的缓存
+///
+/// - 不可变数据（decimals / symbol / name）永久缓存
+/// - 其余数据按块缓存：读取时固定在某个块上，出现新块后旧块的条目被清掉
+/// - 固定过的块（如 safe / finalized / n 个确认）按块哈希缓存，出现新块后仍然保留；重组后哈希变了自然不命中
+pub struct RpcCache {
+    block_poll_interval: Duration,
+    chain_id: OnceCell<u64>,
+    latest: Mutex<Option<(U64, Instant)>>,
+    immutable: Mutex<HashMap<CacheKey, Value>>,
+    by_block: Mutex<HashMap<BlockKey, Value>>,
+    /// 固定过的块：块号 → 块哈希
+    pinned: Mutex<BTreeMap<U64, H256>>,
+    counters: Counters,
+}
+
+impl Default for RpcCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl RpcCache {
+    /// `block_poll_interval`：两次 `eth_blockNumber` 之间至少间隔多久
+    pub fn new(block_poll_interval: Duration) -> Self {
+        Self {
+            block_poll_interval,
+            chain_id: OnceCell::new(),
+            latest: Mutex::new(None),
+            immutable: Mutex::new(HashMap::new()),
+            by_block: Mutex::new(HashMap::new()),
+            pinned: Mutex::new(BTreeMap::new()),
+            counters: Counters::default(),
+        }
+    }
+
+    /// 最新块号只查一次，之后整个进程都落在这个块上
+    ///
+    /// 录制 / 回放 fixture 时用：请求序列不再取决于调用间隔，换个节奏回放也能对上。
+    pub fn frozen() -> Self {
+        Self::new(Duration::MAX)
+    }
+
+    /// 链 ID，只查一次
+    pub async fn chain_id<M: Middleware>(&self, provider: &M) -> Result<u64>
+    where
+        M::Error: 'static,
+    {
+        let id = self
+            .chain_id
+            .get_or_try_init(|| async { provider.get_chainid().await.map(|id| id.low_u64()) })
+            .await?;
+        Ok(*id)
+    }
+
+    /// 最新块号；`block_poll_interval` 内复用上一次的结果
+    ///
+    /// 发现新块时，旧块上的缓存条目一并清掉。
+    pub async fn latest_block<M: Middleware>(&self, provider: &M) -> Result<U64>
+    where
+        M::Error: 'static,
+    {
+        if let Some((block, fetched)) = *self.latest.lock().unwrap() {
+            if fetched.elapsed() < self.block_poll_interval {
+                return Ok(block);
+            }
+        }
+
+        let block = provider.get_block_number().await?;
+
+        // 并发请求可能拿到更旧的块号，不能让块号倒退
+        let previous = {
+            let mut latest = self.latest.lock().unwrap();
+            let previous = latest.map(|(b, _)| b);
+            if previous.is_none_or(|p| block >= p) {
+                *latest = Some((block, Instant::now()));
+            }
+            previous
+        };
+
+        match previous {
+            Some(p) if block < p => return Ok(p),
+            Some(p) if block == p => {}
+            _ => {
+                let pinned = self.pinned.lock().unwrap();
+                self.by_block
+                    .lock()
+                    .unwrap()
+                    .retain(|(_, b, hash), _| *b >= block || hash.is_some_and(|h| pinned.get(b) == Some(&h)));
+            }
+        }
+        Ok(block)
+    }
+
+    /// 按确认程度选出要读取的块
+    ///
+    /// `Confirmations(n)` 取最新块往前 n 个块；`Safe` / `Finalized` 直接用节点的块标签。
+    /// 结果按最新块缓存，同一块内只查一次块头；固定下来的块号和哈希记下来，之后在这个块上的读取按哈希缓存。
+    pub async fn pinned_block<M: Middleware>(&self, provider: &M, confidence: Confidence) -> Result<PinnedBlock>
+    where
+        M::Error: 'static,
+    {
+        let chain = self.chain_id(provider).await?;
+        let latest = self.latest_block(provider).await?;
+        let (call, id) = match confidence {
+            Confidence::Latest => (format!("block.header:{}", latest), BlockNumber::Number(latest)),
+            Confidence::Confirmations(n) => {
+                let number = latest.saturating_sub(n.into());
+                (format!("block.header:{}", number), BlockNumber::Number(number))
+            }
+            Confidence::Safe => ("block.safe".to_string(), BlockNumber::Safe),
+            Confidence::Finalized => ("block.finalized".to_string(), BlockNumber::Finalized),
+        };
+
+        // 块头本身按最新块缓存，不跟着哈希走
+        let pinned = self.cached_at(chain, call, latest, None, || async {
+            let block = provider
+                .get_block(id)
+                .await?
+                .ok_or_else(|| anyhow!("Node returned no {} block", confidence))?;
+            match (block.number, block.hash) {
+                (Some(number), Some(hash)) => {
+                    Ok(PinnedBlock { number, hash, finalized: confidence == Confidence::Finalized })
+                }
+                _ => Err(anyhow!("Node returned a pending block for {}", confidence)),
+            }
+        })
+        .await?;
+        self.remember_pinned(pinned);
+        Ok(pinned)
+    }
+
+    /// 记下固定的块；同一块号换了哈希（重组）时，旧哈希下的条目清掉
+    fn remember_pinned(&self, block: PinnedBlock) {
+        let mut pinned = self.pinned.lock().unwrap();
+        if let Some(previous) = pinned.insert(block.number, block.hash) {
+            if previous != block.hash {
+                self.by_block
+                    .lock()
+                    .unwrap()
+                    .retain(|(_, b, hash), _| *b != block.number || *hash != Some(previous));
+            }
+        }
+        while pinned.len() > PINNED_BLOCKS_KEPT {
+            pinned.pop_first();
+        }
+    }
+
+    /// 读完后确认 `pinned` 还在链上：读取按块号进行，期间重组的话读到的是另一个块
+    ///
+    /// 块已经换掉时清掉这个块号上的缓存（连同固定块的块头）并报错，调用方重试即可。
+    pub async fn confirm_pinned<M: Middleware>(&self, provider: &M, pinned: PinnedBlock) -> Result<()>
+    where
+        M::Error: 'static,
+    {
+        if pinned.finalized {
+            return Ok(());
+        }
+        let current = provider.get_block(pinned.number).await?.and_then(|b| b.hash);
+        if current == Some(pinned.hash) {
+            return Ok(());
+        }
+        self.pinned.lock().unwrap().remove(&pinned.number);
+        self.by_block
+            .lock()
+            .unwrap()
+            .retain(|(key, block, _), _| *block != pinned.number && !key.call.starts_with("block."));
+        Err(anyhow!(
+            "Block {} was reorganized while reading ({:?} is no longer on chain); retry the request",
+            pinned.number,
+            pinned.hash
+        ))
+    }
+
+    /// 不可变数据：第一次查到后永久缓存
+    pub async fn immutable<T, F, Fut>(&self, chain: u64, call: String, fetch: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let key = CacheKey { chain, call };
+        if let Some(value) = self.immutable.lock().unwrap().get(&key) {
+            if let Some(value) = value.downcast_ref::<T>() {
+                self.counters.immutable_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(value.clone());
+            }
+        }
+
+        self.counters.immutable_misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch().await?;
+        self.immutable
+            .lock()
+            .unwrap()
+            .insert(key, Arc::new(value.clone()));
+        Ok(value)
+    }
+
+    /// 同 `immutable`，但只缓存查到的值；None（如节点还没索引到的回执）下次重新查
+    pub async fn immutable_once_found<T, F, Fut>(&self, chain: u64, call: String, fetch: F) -> Result<Option<T>>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<T>>>,
+    {
+        let key = CacheKey { chain, call };
+        if let Some(value) = self.immutable.lock().unwrap().get(&key) {
+            if let Some(value) = value.downcast_ref::<T>() {
+                self.counters.immutable_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(value.clone()));
+            }
+        }
+
+        self.counters.immutable_misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch().await?;
+        if let Some(value) = &value {
+            self.immutable
+                .lock()
+                .unwrap()
+                .insert(key, Arc::new(value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// 固定在 `block` 上读取的数据：同一块内复用
+    ///
+    /// 固定过的块按哈希缓存，比最新块旧也照样缓存；其余旧块上的读取不缓存（新块一来就会被清掉）。
+    pub async fn at_block<T, F, Fut>(&self, chain: u64, call: String, block: U64, fetch: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let hash = self.pinned.lock().unwrap().get(&block).copied();
+        self.cached_at(chain, call, block, hash, fetch).await
+    }
+
+    async fn cached_at<T, F, Fut>(&self, chain: u64, call: String, block: U64, hash: Option<H256>, fetch: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let key = (CacheKey { chain, call }, block, hash);
+        if let Some(value) = self.by_block.lock().unwrap().get(&key) {
+            if let Some(value) = value.downcast_ref::<T>() {
+                self.counters.block_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(value.clone());
+            }
+        }
+
+        self.counters.block_misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch().await?;
+
+        // 写入前块可能已经过期（期间发现了新块），过期又没有哈希的就不存了
+        let stale = hash.is_none()
+            && self
+                .latest
+                .lock()
+                .unwrap()
+                .is_some_and(|(latest, _)| block < latest);
+        if !stale {
+            self.by_block
+                .lock()
+                .unwrap()
+                .insert(key, Arc::new(value.clone()));
+        }
+        Ok(value)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            immutable_hits: self.counters.immutable_hits.load(Ordering::Relaxed),
+            immutable_misses: self.counters.immutable_misses.load(Ordering::Relaxed),
+            block_hits: self.counters.block_hits.load(Ordering::Relaxed),
+            block_misses: self.counters.block_misses.load(Ordering::Relaxed),
+            immutable_entries: self.immutable.lock().unwrap().len(),
+            block_entries: self.by_block.lock().unwrap().len(),
+        }
+    }
+}
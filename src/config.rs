@@ -1,13 +1,267 @@
 use std::collections::HashMap;
 use std::env;
+use std::net::SocketAddr;
+use std::time::Duration;
 use ethers::types::Address;
+use serde::Deserialize;
+use tracing::warn;
 
+use crate::registry::TokenEntry;
+use crate::tokenlist::load_token_lists;
+
+/// MCP 传输方式
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransportMode {
+    /// 作为子进程，通过 stdin/stdout 通信（默认）
+    Stdio,
+    /// Streamable HTTP（SSE 推送），监听 bind 地址，可被多个 agent 共享
+    Http { bind: SocketAddr },
+}
+
+impl TransportMode {
+    pub const DEFAULT_BIND: &'static str = "127.0.0.1:8080";
+
+    /// 解析命令行参数，未指定的部分回退到环境变量
+    /// - `--transport stdio|http` / `MCP_TRANSPORT`
+    /// - `--bind 127.0.0.1:8080` / `MCP_BIND`
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> anyhow::Result<Self> {
+        let mut transport = env::var("MCP_TRANSPORT").ok();
+        let mut bind = env::var("MCP_BIND").ok();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--transport" => transport = args.next(),
+                "--bind" => bind = args.next(),
+                other => anyhow::bail!("Unknown argument: {}", other),
+            }
+        }
+
+        match transport.as_deref().unwrap_or("stdio") {
+            "stdio" => Ok(TransportMode::Stdio),
+            "http" => {
+                let bind = bind.as_deref().unwrap_or(Self::DEFAULT_BIND);
+                let bind = bind
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid bind address: {}", bind))?;
+                Ok(TransportMode::Http { bind })
+            }
+            other => anyhow::bail!("Unknown transport: {} (expected stdio or http)", other),
+        }
+    }
+}
+
+/// 限流与 RPC 预算，不填的项表示不限制
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct RateLimits {
+    /// 每分钟最多调用次数（所有工具合计）
+    #[serde(default)]
+    pub calls_per_minute: Option<u32>,
+    /// 单个工具每分钟最多调用次数，例如 `{"get_price": 10}`
+    #[serde(default)]
+    pub tools: HashMap<String, u32>,
+    /// 每小时允许触发的 RPC 请求数
+    #[serde(default)]
+    pub rpc_calls_per_hour: Option<u32>,
+}
+
+/// HTTP 客户端（API key / bearer token → 客户端身份与权限）
+///
+/// 从 `MCP_CLIENTS_FILE` 指向的 JSON 数组加载，例如：
+/// `[{"id": "treasury-bot", "token": "...", "tools": ["get_balance"], "wallets": ["0x..."]}]`
+#[derive(Clone, Deserialize)]
+pub struct ClientConfig {
+    /// 客户端名称，用于日志和权限判断
+    pub id: String,
+    /// Bearer token，请求头 `Authorization: Bearer <token>`
+    pub token: String,
+    /// 可见/可调用的工具；不填表示全部
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// 允许查询的钱包地址；不填表示不限制
+    #[serde(default)]
+    pub wallets: Option<Vec<Address>>,
+    /// 是否开放会构造交易的工具（如 swap_tokens）
+    #[serde(default)]
+    pub allow_execution: bool,
+    /// 能否往所有客户端共用的代币注册表里注册代币（get_token_info 的 register）
+    #[serde(default)]
+    pub allow_registry: bool,
+    /// 该客户端的限流配置；不填则使用全局默认值
+    #[serde(default)]
+    pub limits: Option<RateLimits>,
+}
+
+// token 不能出现在日志里
+impl std::fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("id", &self.id)
+            .field("token", &"<redacted>")
+            .field("tools", &self.tools)
+            .field("wallets", &self.wallets)
+            .field("allow_execution", &self.allow_execution)
+            .field("allow_registry", &self.allow_registry)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+/// 读取余额 / 价格 / 报价时要求的确认程度
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Confidence {
+    /// 最新块，可能被重组
+    #[default]
+    Latest,
+    /// 最新块往前 N 个块
+    Confirmations(u64),
+    /// 节点的 `safe` 块
+    Safe,
+    /// 节点的 `finalized` 块
+    Finalized,
+}
+
+impl std::str::FromStr for Confidence {
+    type Err = anyhow::Error;
+
+    /// "latest" / "safe" / "finalized"，或确认数（如 "12"）
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "latest" | "0" => Ok(Self::Latest),
+            "safe" => Ok(Self::Safe),
+            "finalized" => Ok(Self::Finalized),
+            other => other.parse().map(Self::Confirmations).map_err(|_| {
+                anyhow::anyhow!(
+                    "Unknown confidence: {} (expected latest, safe, finalized or a number of confirmations)",
+                    other
+                )
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Latest => f.write_str("latest"),
+            Self::Confirmations(n) => write!(f, "{} confirmations", n),
+            Self::Safe => f.write_str("safe"),
+            Self::Finalized => f.write_str("finalized"),
+        }
+    }
+}
+
+/// JSON-RPC 录制 / 回放（回归测试用）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcFixture {
+    /// 照常连节点，把请求和响应写进这个文件
+    Record(String),
+    /// 不连节点，响应全部来自这个文件，按录制顺序严格回放
+    Replay(String),
+    /// 同 `Replay`，但不要求请求顺序和录制一致
+    ReplayUnordered(String),
+}
+
+/// 外部 HTTP 价格接口：GET `url`（`{address}` / `{symbol}` 会被替换），按 `field` 从 JSON 里取 USD 价格
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriceApiConfig {
+    pub url: String,
+    /// 点分隔的路径，数组用下标，如 "data.0.price"；同样可以带 `{address}` / `{symbol}`
+    pub field: String,
+}
+
+/// 原生 ETH 转账的来源
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NativeTransfers {
+    /// 不索引 ETH 转账
+    #[default]
+    Off,
+    /// `trace_filter`（Erigon / Nethermind / Reth），能看到合约内部转账
+    Traces,
+    /// 逐块读取交易，只有顶层转账；适合没有 trace API 的开发链
+    Blocks,
+}
+
+impl std::str::FromStr for NativeTransfers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "off" => Ok(Self::Off),
+            "traces" => Ok(Self::Traces),
+            "blocks" => Ok(Self::Blocks),
+            other => anyhow::bail!("Unknown native transfer source: {} (expected off, traces or blocks)", other),
+        }
+    }
+}
+
+/// 本地转账索引（SQLite）；设置了 `MCP_INDEXER_DB` 才启用
 #[derive(Clone, Debug)]
+pub struct IndexerConfig {
+    /// SQLite 文件路径；`:memory:` 表示只放内存
+    pub db_path: String,
+    /// 跟踪的钱包
+    pub wallets: Vec<Address>,
+    /// 只索引这些 ERC20；为空时索引钱包收发的所有 ERC20
+    pub tokens: Vec<Address>,
+    /// 从这个块开始索引
+    pub start_block: u64,
+    pub native: NativeTransfers,
+    /// 追新块的轮询间隔
+    pub poll_interval: Duration,
+}
+
+impl IndexerConfig {
+    pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+    pub fn new(db_path: impl Into<String>, wallets: Vec<Address>) -> Self {
+        Self {
+            db_path: db_path.into(),
+            wallets,
+            tokens: Vec::new(),
+            start_block: 0,
+            native: NativeTransfers::Off,
+            poll_interval: Self::DEFAULT_POLL_INTERVAL,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct AppConfig {
     pub infura_url: String,
     pub wallet_address: Address,
     pub token_addresses: HashMap<String, Address>,
     pub uniswap_v2_router: Address,
+    /// V2 factory；None 时向 router 的 `factory()` 查
+    pub uniswap_v2_factory: Option<Address>,
+    /// V3 factory；None 时 V3 工具不可用
+    pub uniswap_v3_factory: Option<Address>,
+    /// `get_price` 用 TWAP 时的默认窗口（秒）；None 时用 `pool::DEFAULT_TWAP_WINDOW_SECS`
+    pub twap_window_secs: Option<u32>,
+    /// HTTP 会话空闲多久后关闭，None 表示不超时
+    pub session_idle_timeout: Option<Duration>,
+    /// HTTP 客户端列表；为空时 HTTP 模式不做鉴权
+    pub clients: Vec<ClientConfig>,
+    /// 没有单独配置限流的客户端（包括 stdio）使用的默认限流
+    pub default_limits: RateLimits,
+    /// 两次查询最新块号的最小间隔，期间的读取都落在同一个块上（共用缓存）
+    pub block_poll_interval: Duration,
+    /// 当前链 ID，用来从 token list 里挑代币
+    pub chain_id: u64,
+    /// 从 token list 加载的代币，已和 token_addresses 合并去重
+    pub token_list: Vec<TokenEntry>,
+    /// 转账索引；None 表示不启用
+    pub indexer: Option<IndexerConfig>,
+    /// 余额 / 价格 / 报价默认读取的块，调用时可以单独指定
+    pub confidence: Confidence,
+    /// 录制或回放 JSON-RPC；None 表示直连节点
+    pub rpc_fixture: Option<RpcFixture>,
+    /// 单次 eth_getLogs 最多查多少块；None 时用 `rpc::DEFAULT_MAX_LOG_RANGE`
+    pub max_log_range: Option<u64>,
+    /// 多来源价格里的外部 HTTP 接口；None 表示不用
+    pub price_api: Option<PriceApiConfig>,
+    /// 多来源价格偏离中位数多少（基点）算异常；None 时用 `price::DEFAULT_MAX_DEVIATION_BPS`
+    pub max_price_deviation_bps: Option<u64>,
 }
 
 impl AppConfig {
@@ -33,11 +287,179 @@ impl AppConfig {
         // Add Uniswap Router
         let uniswap_v2_router = read_address("UNISWAP_V2_ROUTER");
 
+        // 可选：V2 factory，不填时从 router 读
+        let uniswap_v2_factory = env::var("UNISWAP_V2_FACTORY").ok().map(|_| read_address("UNISWAP_V2_FACTORY"));
+
+        // 可选：V3 factory（主网 0x1F98431c8aD98523631AE4a59f267346ea31F984）
+        let uniswap_v3_factory = env::var("UNISWAP_V3_FACTORY").ok().map(|_| read_address("UNISWAP_V3_FACTORY"));
+
+        // 可选：V3 TWAP 默认窗口（秒）
+        let twap_window_secs = env::var("MCP_TWAP_WINDOW_SECS").ok().map(|s| {
+            s.parse()
+                .unwrap_or_else(|_| panic!("Invalid number in MCP_TWAP_WINDOW_SECS"))
+        });
+
+        // 可选：HTTP 会话空闲超时（秒）
+        let session_idle_timeout = env::var("MCP_SESSION_IDLE_SECS")
+            .ok()
+            .map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("Invalid number in MCP_SESSION_IDLE_SECS"))
+            })
+            .map(Duration::from_secs);
+
+        // 可选：HTTP 客户端鉴权配置
+        let clients = env::var("MCP_CLIENTS_FILE")
+            .ok()
+            .map(|path| {
+                let raw = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Cannot read MCP_CLIENTS_FILE {}: {}", path, e));
+                serde_json::from_str(&raw)
+                    .unwrap_or_else(|e| panic!("Invalid clients in {}: {}", path, e))
+            })
+            .unwrap_or_default();
+
+        // 可选：默认限流
+        fn read_optional_u32(env_key: &str) -> Option<u32> {
+            env::var(env_key).ok().map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("Invalid number in {}", env_key))
+            })
+        }
+        let default_limits = RateLimits {
+            calls_per_minute: read_optional_u32("MCP_CALLS_PER_MINUTE"),
+            tools: HashMap::new(),
+            rpc_calls_per_hour: read_optional_u32("MCP_RPC_CALLS_PER_HOUR"),
+        };
+
+        // 可选：最新块号轮询间隔（毫秒），默认 1 秒
+        let block_poll_interval = env::var("MCP_BLOCK_POLL_MS")
+            .ok()
+            .map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("Invalid number in MCP_BLOCK_POLL_MS"))
+            })
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(1));
+
+        // 可选：链 ID，默认主网
+        let chain_id = env::var("MCP_CHAIN_ID")
+            .ok()
+            .map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("Invalid number in MCP_CHAIN_ID"))
+            })
+            .unwrap_or(1);
+
+        // 可选：Uniswap token list 文件，逗号分隔；前面的优先
+        let token_list = env::var("MCP_TOKEN_LISTS")
+            .ok()
+            .map(|paths| {
+                let paths: Vec<String> = paths
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                let merged = load_token_lists(&paths, chain_id, &token_addresses)
+                    .unwrap_or_else(|e| panic!("{:#}", e));
+                for issue in &merged.issues {
+                    warn!("Token list: {}", issue);
+                }
+                merged.tokens
+            })
+            .unwrap_or_default();
+
+        // 可选：本地转账索引
+        let indexer = env::var("MCP_INDEXER_DB").ok().map(|db_path| {
+            fn read_addresses(env_key: &str) -> Vec<Address> {
+                env::var(env_key)
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse().unwrap_or_else(|_| panic!("Invalid address in {}: {}", env_key, s)))
+                    .collect()
+            }
+            let wallets = read_addresses("MCP_INDEXER_WALLETS");
+            if wallets.is_empty() {
+                panic!("MCP_INDEXER_WALLETS must be set when MCP_INDEXER_DB is set");
+            }
+            let mut indexer = IndexerConfig::new(db_path, wallets);
+            indexer.tokens = read_addresses("MCP_INDEXER_TOKENS");
+            if let Ok(s) = env::var("MCP_INDEXER_START_BLOCK") {
+                indexer.start_block = s
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid number in MCP_INDEXER_START_BLOCK"));
+            }
+            if let Ok(s) = env::var("MCP_INDEXER_NATIVE") {
+                indexer.native = s.parse().unwrap_or_else(|e| panic!("MCP_INDEXER_NATIVE: {}", e));
+            }
+            if let Ok(s) = env::var("MCP_INDEXER_POLL_SECS") {
+                indexer.poll_interval = Duration::from_secs(
+                    s.parse()
+                        .unwrap_or_else(|_| panic!("Invalid number in MCP_INDEXER_POLL_SECS")),
+                );
+            }
+            indexer
+        });
+
+        // 可选：默认确认程度，默认 latest
+        let confidence = env::var("MCP_CONFIDENCE")
+            .ok()
+            .map(|s| s.parse().unwrap_or_else(|e| panic!("MCP_CONFIDENCE: {}", e)))
+            .unwrap_or_default();
+
+        // 可选：录制 / 回放 JSON-RPC
+        let rpc_fixture = match (env::var("MCP_RPC_RECORD").ok(), env::var("MCP_RPC_REPLAY").ok()) {
+            (Some(_), Some(_)) => panic!("MCP_RPC_RECORD and MCP_RPC_REPLAY cannot both be set"),
+            (Some(path), None) => Some(RpcFixture::Record(path)),
+            (None, Some(path)) if env::var("MCP_RPC_REPLAY_UNORDERED").is_ok_and(|v| v == "1" || v == "true") => {
+                Some(RpcFixture::ReplayUnordered(path))
+            }
+            (None, Some(path)) => Some(RpcFixture::Replay(path)),
+            (None, None) => None,
+        };
+        // 索引器按自己的节奏轮询节点，录制下来的请求没法按原样回放
+        if rpc_fixture.is_some() && indexer.is_some() {
+            panic!("MCP_INDEXER_DB cannot be combined with MCP_RPC_RECORD or MCP_RPC_REPLAY");
+        }
+
+        // 可选：节点允许的 eth_getLogs 块数上限
+        let max_log_range = env::var("MCP_MAX_LOG_RANGE").ok().map(|s| {
+            s.parse()
+                .unwrap_or_else(|_| panic!("Invalid number in MCP_MAX_LOG_RANGE"))
+        });
+
+        // 可选：外部 HTTP 价格接口，默认取 JSON 的 "price" 字段
+        let price_api = env::var("MCP_PRICE_API_URL").ok().map(|url| PriceApiConfig {
+            url,
+            field: env::var("MCP_PRICE_API_FIELD").unwrap_or_else(|_| "price".to_string()),
+        });
+        let max_price_deviation_bps = env::var("MCP_PRICE_MAX_DEVIATION_BPS").ok().map(|s| {
+            s.parse()
+                .unwrap_or_else(|_| panic!("Invalid number in MCP_PRICE_MAX_DEVIATION_BPS"))
+        });
+
         Self {
             infura_url,
             wallet_address,
             token_addresses,
             uniswap_v2_router,
+            uniswap_v2_factory,
+            uniswap_v3_factory,
+            twap_window_secs,
+            session_idle_timeout,
+            clients,
+            default_limits,
+            block_poll_interval,
+            chain_id,
+            token_list,
+            indexer,
+            confidence,
+            rpc_fixture,
+            max_log_range,
+            price_api,
+            max_price_deviation_bps,
         }
     }
 
@@ -0,0 +1,315 @@
+// src/contract.rs
+use anyhow::{anyhow, bail, Context, Result};
+use ethers::abi::{self, Abi, Function, HumanReadableParser, ParamType, StateMutability, Token};
+use ethers::prelude::*;
+use ethers::providers::RpcError;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::cache::RpcCache;
+use crate::rpc::{metered, RpcProvider};
+
+/// 解析函数签名："balanceOf(address) view returns (uint256)"，`function` 前缀可省略
+pub fn parse_signature(signature: &str) -> Result<Function> {
+    let signature = signature.trim();
+    HumanReadableParser::parse_function(signature)
+        .map_err(|e| anyhow!("Invalid function signature {:?}: {}", signature, e))
+}
+
+/// 从 JSON ABI 片段里取函数：单个函数对象，或整个 ABI 数组（此时按 `name` 挑选）
+pub fn function_from_abi(fragment: &Value, name: Option<&str>) -> Result<Function> {
+    let items = match fragment {
+        Value::Array(_) => fragment.clone(),
+        Value::Object(_) => Value::Array(vec![fragment.clone()]),
+        _ => bail!("ABI fragment must be a JSON object or array"),
+    };
+    let abi: Abi = serde_json::from_value(items).context("Invalid JSON ABI")?;
+
+    let mut candidates: Vec<&Function> = match name {
+        Some(name) => abi.functions_by_name(name).map(|f| f.iter().collect()).unwrap_or_default(),
+        None => abi.functions().collect(),
+    };
+    match candidates.len() {
+        0 => bail!("No function {} in the ABI fragment", name.unwrap_or("")),
+        1 => Ok(candidates.remove(0).clone()),
+        n => bail!("{} functions match; pass a single fragment or a unique function name", n),
+    }
+}
+
+/// 规范签名，如 "balanceOf(address)"
+pub fn signature(function: &Function) -> String {
+    let inputs: Vec<String> = function.inputs.iter().map(|p| p.kind.to_string()).collect();
+    format!("{}({})", function.name, inputs.join(","))
+}
+
+/// view / pure 以外的函数可能改状态，只允许模拟
+pub fn is_read_only(function: &Function) -> bool {
+    #[allow(deprecated)]
+    let constant = function.constant.unwrap_or(false);
+    constant || matches!(function.state_mutability, StateMutability::View | StateMutability::Pure)
+}
+
+/// JSON 参数 → ABI token
+///
+/// 整数可以是 JSON 数字、十进制字符串或 0x 十六进制；bytes 用 0x 十六进制；tuple 用数组。
+pub fn tokenize(kind: &ParamType, value: &Value) -> Result<Token> {
+    let mismatch = || anyhow!("expected {} but got {}", kind, value);
+    Ok(match kind {
+        ParamType::Address => Token::Address(value.as_str().ok_or_else(mismatch)?.trim().parse().map_err(|_| mismatch())?),
+        ParamType::Bool => Token::Bool(value.as_bool().ok_or_else(mismatch)?),
+        ParamType::String => Token::String(value.as_str().ok_or_else(mismatch)?.to_string()),
+        ParamType::Uint(bits) => {
+            let n = parse_uint(value).ok_or_else(mismatch)?;
+            if *bits < 256 && n >> *bits != U256::zero() {
+                bail!("{} does not fit in uint{}", value, bits);
+            }
+            Token::Uint(n)
+        }
+        ParamType::Int(bits) => {
+            let n = parse_int(value).ok_or_else(mismatch)?;
+            if *bits < 256 {
+                let limit = I256::one() << (*bits - 1);
+                if n >= limit || n < -limit {
+                    bail!("{} does not fit in int{}", value, bits);
+                }
+            }
+            Token::Int(n.into_raw())
+        }
+        ParamType::Bytes => Token::Bytes(parse_hex(value).ok_or_else(mismatch)?),
+        ParamType::FixedBytes(len) => {
+            let bytes = parse_hex(value).ok_or_else(mismatch)?;
+            if bytes.len() != *len {
+                bail!("expected {} bytes for bytes{}, got {}", len, len, bytes.len());
+            }
+            Token::FixedBytes(bytes)
+        }
+        ParamType::Array(inner) => Token::Array(
+            value
+                .as_array()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|v| tokenize(inner, v))
+                .collect::<Result<_>>()?,
+        ),
+        ParamType::FixedArray(inner, len) => {
+            let items = value.as_array().ok_or_else(mismatch)?;
+            if items.len() != *len {
+                bail!("expected {} items for {}, got {}", len, kind, items.len());
+            }
+            Token::FixedArray(items.iter().map(|v| tokenize(inner, v)).collect::<Result<_>>()?)
+        }
+        ParamType::Tuple(fields) => {
+            let items = value.as_array().ok_or_else(mismatch)?;
+            if items.len() != fields.len() {
+                bail!("expected {} fields for {}, got {}", fields.len(), kind, items.len());
+            }
+            Token::Tuple(
+                fields
+                    .iter()
+                    .zip(items)
+                    .map(|(f, v)| tokenize(f, v))
+                    .collect::<Result<_>>()?,
+            )
+        }
+    })
+}
+
+/// ABI token → JSON；整数一律转成十进制字符串，避免超过 JSON 数字精度
+pub fn detokenize(kind: &ParamType, token: &Token) -> Value {
+    match (kind, token) {
+        (_, Token::Address(a)) => json!(format!("{:?}", a)),
+        (_, Token::Bool(b)) => json!(b),
+        (_, Token::String(s)) => json!(s),
+        (_, Token::Uint(n)) => json!(n.to_string()),
+        (_, Token::Int(n)) => json!(I256::from_raw(*n).to_string()),
+        (_, Token::Bytes(b)) | (_, Token::FixedBytes(b)) => json!(Bytes::from(b.clone()).to_string()),
+        (ParamType::Array(inner), Token::Array(items)) | (ParamType::FixedArray(inner, _), Token::FixedArray(items)) => {
+            Value::Array(items.iter().map(|t| detokenize(inner, t)).collect())
+        }
+        (ParamType::Tuple(fields), Token::Tuple(items)) => {
+            Value::Array(fields.iter().zip(items).map(|(f, t)| detokenize(f, t)).collect())
+        }
+        // 类型和值对不上只会是 decode 的 bug，按值的形状输出
+        (_, Token::Array(items)) | (_, Token::FixedArray(items)) | (_, Token::Tuple(items)) => {
+            Value::Array(items.iter().map(|t| detokenize(&ParamType::Bool, t)).collect())
+        }
+    }
+}
+
+fn parse_uint(value: &Value) -> Option<U256> {
+    match value {
+        Value::Number(n) => n.as_u64().map(U256::from),
+        Value::String(s) => {
+            let s = s.trim();
+            match s.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16).ok(),
+                None => U256::from_dec_str(s).ok(),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_int(value: &Value) -> Option<I256> {
+    match value {
+        Value::Number(n) => n.as_i64().map(I256::from),
+        Value::String(s) => {
+            let s = s.trim();
+            match s.strip_prefix('-') {
+                Some(abs) => parse_uint(&json!(abs)).and_then(|n| I256::checked_from_sign_and_abs(Sign::Negative, n)),
+                None => parse_uint(&json!(s)).and_then(|n| I256::checked_from_sign_and_abs(Sign::Positive, n)),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_hex(value: &Value) -> Option<Vec<u8>> {
+    let s = value.as_str()?.trim();
+    ethers::utils::hex::decode(s.strip_prefix("0x").unwrap_or(s)).ok()
+}
+
+/// revert 数据里的 Error(string) / Panic(uint256)
+pub(crate) fn revert_reason(data: &[u8]) -> Option<String> {
+    match data.get(..4)? {
+        [0x08, 0xc3, 0x79, 0xa0] => match abi::decode(&[ParamType::String], &data[4..]).ok()?.pop()? {
+            Token::String(reason) => Some(reason),
+            _ => None,
+        },
+        [0x4e, 0x48, 0x7b, 0x71] => match abi::decode(&[ParamType::Uint(256)], &data[4..]).ok()?.pop()? {
+            Token::Uint(code) => Some(format!("panic 0x{:x}", code)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// 一次 call_contract 的调用参数
+#[derive(Clone, Debug, Default)]
+pub struct ContractCall {
+    pub to: Address,
+    pub from: Option<Address>,
+    /// 只有模拟 payable 函数时有值
+    pub value: Option<U256>,
+    /// None 表示最新块
+    pub block: Option<u64>,
+}
+
+/// 解码后的一次调用结果
+#[derive(Clone, Debug)]
+pub struct CallOutput {
+    pub calldata: Bytes,
+    pub raw: Bytes,
+    /// (名字, 类型, 值)
+    pub outputs: Vec<(String, String, Value)>,
+    pub block: U64,
+}
+
+/// 用调用方给的 ABI 读任意合约
+pub struct ContractModule {
+    pub provider: Arc<RpcProvider>,
+    pub cache: Arc<RpcCache>,
+}
+
+impl ContractModule {
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+
+    /// 复用其他模块已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, cache: Arc<RpcCache>) -> Self {
+        Self { provider, cache }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// ABI 编码 → eth_call → 解码
+    ///
+    /// 只有 view / pure 函数可以直接调用；其他函数必须 `simulate`，结果不会上链。
+    pub async fn call(&self, function: &Function, args: &[Value], call: ContractCall, simulate: bool) -> Result<CallOutput> {
+        if !simulate && !is_read_only(function) {
+            bail!(
+                "{} is {:?}, not view/pure; pass simulate: true to eth_call it without sending a transaction",
+                function.name,
+                function.state_mutability
+            );
+        }
+        if call.value.is_some_and(|v| !v.is_zero()) && function.state_mutability != StateMutability::Payable {
+            bail!("{} is not payable", function.name);
+        }
+        if args.len() != function.inputs.len() {
+            bail!("{} takes {} arguments, got {}", function.name, function.inputs.len(), args.len());
+        }
+
+        let tokens = function
+            .inputs
+            .iter()
+            .zip(args)
+            .enumerate()
+            .map(|(i, (param, value))| {
+                tokenize(&param.kind, value).with_context(|| format!("argument {} ({})", i, param.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let calldata: Bytes = function.encode_input(&tokens)?.into();
+
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = match call.block {
+            Some(b) => U64::from(b),
+            None => self.cache.latest_block(&*self.provider).await?,
+        };
+
+        let mut tx = TransactionRequest::new().to(call.to).data(calldata.clone());
+        if let Some(from) = call.from {
+            tx = tx.from(from);
+        }
+        if let Some(value) = call.value {
+            tx = tx.value(value);
+        }
+        let tx: TypedTransaction = tx.into();
+
+        // 最新块的结果按块缓存；指定历史块时直接查
+        let fetch = || async {
+            match self.provider.call(&tx, Some(block.into())).await {
+                Ok(data) => Ok(data),
+                Err(e) => match RpcError::as_error_response(&e) {
+                    Some(err) => {
+                        let reason = err
+                            .data
+                            .as_ref()
+                            .and_then(parse_hex)
+                            .and_then(|d| revert_reason(&d))
+                            .unwrap_or_else(|| err.message.clone());
+                        Err(anyhow!("{} reverted: {}", function.name, reason))
+                    }
+                    None => Err(e.into()),
+                },
+            }
+        };
+        let raw: Bytes = if call.block.is_none() {
+            let key = format!("eth_call:{:?}:{:?}:{:?}:{}", call.to, call.from, call.value, calldata);
+            self.cache.at_block(chain, key, block, fetch).await?
+        } else {
+            fetch().await?
+        };
+
+        if raw.is_empty() && !function.outputs.is_empty() {
+            bail!("{:?} returned no data; is it a contract with {}?", call.to, signature(function));
+        }
+        let values = function
+            .decode_output(&raw)
+            .with_context(|| format!("Cannot decode {} output {}", function.name, raw))?;
+        let outputs = function
+            .outputs
+            .iter()
+            .zip(&values)
+            .map(|(param, token)| (param.name.clone(), param.kind.to_string(), detokenize(&param.kind, token)))
+            .collect();
+
+        Ok(CallOutput { calldata, raw, outputs, block })
+    }
+}
@@ -0,0 +1,234 @@
+// src/ens.rs
+use anyhow::{anyhow, bail, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::prelude::*;
+use ethers::providers::ens;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::cache::RpcCache;
+use crate::rpc::{metered, view_call, RpcProvider};
+
+/// 是否应当按 ENS 名处理：不是 0x 地址，且最后一个 label 像顶级域（`.eth` 或 DNS TLD，至少两个字母）
+///
+/// "USDC.e"、"BTC.b" 这类带点的代币 symbol 最后一段只有一个字母，不会被当成 ENS 名；
+/// 调用方仍应先查已配置的 symbol。
+pub fn is_ens_name(input: &str) -> bool {
+    let input = input.trim();
+    if input.starts_with("0x") {
+        return false;
+    }
+    match input.rsplit_once('.') {
+        Some((_, tld)) => tld.len() >= 2 && tld.chars().all(|c| c.is_alphabetic()),
+        None => false,
+    }
+}
+
+/// ENSIP-15 规定的 label 分隔符只有 U+002E；UTS-46 会把这些也当成点
+const FOREIGN_STOPS: [char; 3] = ['\u{3002}', '\u{FF0E}', '\u{FF61}'];
+
+/// emoji 变体选择符，ENSIP-15 规范化时直接去掉
+const FE0F: char = '\u{FE0F}';
+
+/// ENS 名规范化，按 ENSIP-15 的规则
+///
+/// 字符映射（大小写折叠、全角转半角）借用 UTS-46，在此之上补 ENSIP-15 更严的部分：
+/// 只认 `.` 作分隔符，不做 Punycode 解码（`xn--` 这类第 3、4 位是 `--` 的 ASCII label 直接拒绝），
+/// `_` 只能出现在 label 开头，label 不能以组合附加符开头，同一 label 里不能混用拉丁 / 希腊 / 西里尔字母
+/// （整体易混淆的 "аpple" 之类）。不合法的名字直接报错，避免解析到别人的名下。
+pub fn normalize(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.contains(FOREIGN_STOPS) {
+        bail!("Invalid ENS name: {} (only \".\" separates labels)", name);
+    }
+    let labels = name
+        .split('.')
+        .map(|label| normalize_label(label).map_err(|e| anyhow!("Invalid ENS name: {} ({})", name, e)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(labels.join("."))
+}
+
+fn normalize_label(label: &str) -> Result<String> {
+    let label: String = label.chars().filter(|c| *c != FE0F).collect();
+    if label.is_empty() {
+        bail!("empty label");
+    }
+    let label = if label.is_ascii() {
+        label.to_ascii_lowercase()
+    } else {
+        let (mapped, result) = idna::domain_to_unicode(&label);
+        if result.is_err() || mapped.contains('.') {
+            bail!("disallowed character");
+        }
+        mapped
+    };
+
+    // UTS-46 放行了空格和 ASCII 标点，ENS 不允许
+    if label
+        .chars()
+        .any(|c| c.is_ascii() && !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_')))
+    {
+        bail!("disallowed character");
+    }
+    if label.trim_start_matches('_').contains('_') {
+        bail!("underscore allowed only at the start of a label");
+    }
+    if label.is_ascii() && label.get(2..4) == Some("--") {
+        bail!("invalid label extension");
+    }
+    if label.chars().next().is_some_and(is_combining_mark) {
+        bail!("label starts with a combining mark");
+    }
+
+    let scripts: BTreeSet<&str> = label.chars().filter_map(script).collect();
+    if scripts.len() > 1 {
+        bail!("label mixes {} letters", scripts.into_iter().collect::<Vec<_>>().join(" and "));
+    }
+    Ok(label)
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// 容易互相冒充的三种字母表
+fn script(c: char) -> Option<&'static str> {
+    match c {
+        'a'..='z' | '\u{00DF}'..='\u{00F6}' | '\u{00F8}'..='\u{024F}' => Some("Latin"),
+        '\u{0370}'..='\u{03FF}' => Some("Greek"),
+        '\u{0400}'..='\u{04FF}' => Some("Cyrillic"),
+        _ => None,
+    }
+}
+
+pub struct EnsModule {
+    pub provider: Arc<RpcProvider>,
+    pub cache: Arc<RpcCache>,
+    /// ENS registry 合约；主网和主要测试网是同一个地址
+    pub registry: Address,
+}
+
+impl EnsModule {
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+
+    /// 复用其他模块已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, cache: Arc<RpcCache>) -> Self {
+        Self {
+            provider,
+            cache,
+            registry: ens::ENS_ADDRESS,
+        }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 非官方部署（私链、测试链）的 registry 地址
+    pub fn with_registry(mut self, registry: Address) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// 正向解析：name → registry.resolver(node) → resolver.addr(node)
+    pub async fn resolve(&self, name: &str) -> Result<Address> {
+        let name = normalize(name)?;
+        let Some(resolver) = self.resolver(&name).await? else {
+            bail!("ENS name {} has no resolver", name);
+        };
+
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let address: Option<Address> = self
+            .cache
+            .at_block(chain, format!("ens.addr:{}", name), block, || async {
+                let tx: TypedTransaction = ens::resolve(resolver, ens::ADDR_SELECTOR, &name, None).into();
+                let data = view_call(&self.provider, &tx, Some(block)).await?;
+                Ok(data.as_deref().and_then(decode_address))
+            })
+            .await?;
+
+        address
+            .filter(|a| !a.is_zero())
+            .ok_or_else(|| anyhow!("ENS name {} does not resolve to an address", name))
+    }
+
+    /// 主名称（primary name）：反向解析后再正向校验，两边一致才算数
+    ///
+    /// 反向记录谁都能设，不校验的话任何人都能把自己的地址显示成 vitalik.eth。
+    pub async fn lookup(&self, address: Address) -> Result<Option<String>> {
+        let reverse = ens::reverse_address(address);
+        let Some(resolver) = self.resolver(&reverse).await? else {
+            return Ok(None);
+        };
+
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let name: Option<String> = self
+            .cache
+            .at_block(chain, format!("ens.name:{:?}", address), block, || async {
+                let tx: TypedTransaction = ens::resolve(resolver, ens::NAME_SELECTOR, &reverse, None).into();
+                let data = view_call(&self.provider, &tx, Some(block)).await?;
+                Ok(data.as_deref().and_then(decode_string))
+            })
+            .await?;
+
+        let Some(name) = name.and_then(|n| normalize(&n).ok()) else {
+            return Ok(None);
+        };
+        match self.resolve(&name).await {
+            Ok(forward) if forward == address => Ok(Some(name)),
+            _ => Ok(None),
+        }
+    }
+
+    /// 校验 `name` 是 `address` 的主名称
+    pub async fn verify_reverse(&self, name: &str, address: Address) -> Result<()> {
+        let name = normalize(name)?;
+        match self.lookup(address).await? {
+            Some(primary) if primary == name => Ok(()),
+            Some(primary) => bail!(
+                "{} resolves to {:?}, but its primary name is {}",
+                name,
+                address,
+                primary
+            ),
+            None => bail!("{} resolves to {:?}, which has no primary name", name, address),
+        }
+    }
+
+    /// registry.resolver(namehash(name))；registry 不存在或没有设置 resolver 时为 None
+    async fn resolver(&self, name: &str) -> Result<Option<Address>> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.cache
+            .at_block(chain, format!("ens.resolver:{}", name), block, || async {
+                let tx: TypedTransaction = ens::get_resolver(self.registry, name).into();
+                let data = view_call(&self.provider, &tx, Some(block)).await?;
+                Ok(data
+                    .as_deref()
+                    .and_then(decode_address)
+                    .filter(|a| !a.is_zero()))
+            })
+            .await
+    }
+}
+
+fn decode_address(data: &[u8]) -> Option<Address> {
+    match abi::decode(&[ParamType::Address], data).ok()?.pop()? {
+        Token::Address(a) => Some(a),
+        _ => None,
+    }
+}
+
+fn decode_string(data: &[u8]) -> Option<String> {
+    match abi::decode(&[ParamType::String], data).ok()?.pop()? {
+        Token::String(s) => Some(s).filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
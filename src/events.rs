@@ -0,0 +1,200 @@
+// src/events.rs
+use anyhow::{anyhow, bail, Context, Result};
+use ethers::abi::{self, Event, HumanReadableParser, ParamType, RawLog};
+use ethers::prelude::*;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+use crate::abi_library::DecodedParam;
+use crate::cache::RpcCache;
+use crate::contract::{detokenize, tokenize};
+use crate::rpc::{metered, scan_logs, RpcProvider};
+
+/// 解析事件签名："Transfer(address indexed from, address indexed to, uint256 value)"，`event` 前缀可省略
+pub fn parse_event(signature: &str) -> Result<Event> {
+    let signature = signature.trim();
+    let full = if signature.starts_with("event ") {
+        signature.to_string()
+    } else {
+        format!("event {}", signature)
+    };
+    let event = HumanReadableParser::parse_event(&full)
+        .map_err(|e| anyhow!("Invalid event signature {:?}: {}", signature, e))?;
+    if event.anonymous {
+        bail!("Anonymous events have no topic0 and are not supported");
+    }
+    Ok(event)
+}
+
+/// 规范签名，如 "Transfer(address,address,uint256)"
+pub fn signature(event: &Event) -> String {
+    let inputs: Vec<String> = event.inputs.iter().map(|p| p.kind.to_string()).collect();
+    format!("{}({})", event.name, inputs.join(","))
+}
+
+/// indexed 参数的取值 → topic
+///
+/// 静态类型按 ABI 编码成 32 字节；string / bytes 在 topic 里是 keccak256，这里同样取哈希。
+fn topic_value(kind: &ParamType, value: &Value) -> Result<H256> {
+    match kind {
+        ParamType::String => {
+            let s = value.as_str().ok_or_else(|| anyhow!("expected string but got {}", value))?;
+            Ok(H256::from(ethers::utils::keccak256(s.as_bytes())))
+        }
+        ParamType::Bytes => match tokenize(kind, value)? {
+            abi::Token::Bytes(bytes) => Ok(H256::from(ethers::utils::keccak256(bytes))),
+            _ => unreachable!("tokenize(bytes) always returns Token::Bytes"),
+        },
+        ParamType::Array(_) | ParamType::FixedArray(..) | ParamType::Tuple(_) => {
+            bail!("filtering on indexed {} is not supported", kind)
+        }
+        _ => Ok(H256::from_slice(&abi::encode(&[tokenize(kind, value)?]))),
+    }
+}
+
+/// 按 indexed 参数名过滤：值为单个取值或取值数组（任一匹配），null 表示不限
+///
+/// 返回 topic1..topic3 的过滤条件。
+pub fn topic_filters(event: &Event, filters: &Map<String, Value>) -> Result<[Option<Vec<H256>>; 3]> {
+    let indexed: Vec<_> = event.inputs.iter().filter(|p| p.indexed).collect();
+    let mut topics: [Option<Vec<H256>>; 3] = Default::default();
+    for (name, value) in filters {
+        let position = indexed.iter().position(|p| p.name == *name).ok_or_else(|| {
+            let names: Vec<&str> = indexed.iter().map(|p| p.name.as_str()).collect();
+            anyhow!("{} is not an indexed parameter of {} (indexed: {})", name, event.name, names.join(", "))
+        })?;
+        let kind = &indexed[position].kind;
+        let values = match value {
+            Value::Null => continue,
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        let encoded = values
+            .into_iter()
+            .map(|v| topic_value(kind, v))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid filter {}", name))?;
+        topics[position] = Some(encoded);
+    }
+    Ok(topics)
+}
+
+/// 一条解码后的事件日志
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    pub log: Log,
+    /// 所在块的时间戳（Unix 秒）
+    pub timestamp: Option<u64>,
+    /// indexed 的 string / bytes 只有哈希，按 bytes32 给出
+    pub args: Vec<DecodedParam>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventLogs {
+    pub logs: Vec<EventLog>,
+    /// topic0 相同但解不开的日志条数（如 ERC721 的 Transfer 多一个 indexed 参数）
+    pub skipped: usize,
+    /// 达到条数上限时，下次从这个块接着查
+    pub next_block: Option<u64>,
+}
+
+pub struct EventModule {
+    pub provider: Arc<RpcProvider>,
+    pub cache: Arc<RpcCache>,
+}
+
+impl EventModule {
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+
+    /// 复用其他模块已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, cache: Arc<RpcCache>) -> Self {
+        Self { provider, cache }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 查 `from_block..=to_block` 内的事件，按块分段翻页，最多返回约 `limit` 条
+    pub async fn get_logs(
+        &self,
+        event: &Event,
+        addresses: &[Address],
+        filters: &Map<String, Value>,
+        from_block: u64,
+        to_block: u64,
+        limit: usize,
+    ) -> Result<EventLogs> {
+        let mut filter = Filter::new().topic0(event.signature());
+        if !addresses.is_empty() {
+            filter = filter.address(addresses.to_vec());
+        }
+        for (i, topic) in topic_filters(event, filters)?.into_iter().enumerate() {
+            if let Some(values) = topic {
+                filter.topics[i + 1] = Some(values.into());
+            }
+        }
+
+        let scan = scan_logs(&self.provider, filter, from_block, to_block, Some(limit)).await?;
+
+        let mut logs = Vec::with_capacity(scan.logs.len());
+        let mut skipped = 0;
+        for log in scan.logs {
+            let raw = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
+            let Ok(parsed) = event.parse_log(raw) else {
+                skipped += 1;
+                continue;
+            };
+            let args = event
+                .inputs
+                .iter()
+                .zip(parsed.params)
+                .map(|(p, param)| (p.name.clone(), p.kind.to_string(), detokenize(&p.kind, &param.value)))
+                .collect();
+
+            // 同一块的时间戳走缓存，只查一次
+            let timestamp = match log.block_number {
+                Some(block) => Some(self.block_timestamp(block.as_u64()).await?),
+                None => None,
+            };
+            logs.push(EventLog { log, timestamp, args });
+        }
+
+        Ok(EventLogs { logs, skipped, next_block: scan.next_block })
+    }
+
+    /// 时间戳不早于 `timestamp` 的第一个块；`latest` 之前都更早时为 `latest + 1`
+    ///
+    /// 块时间戳单调递增，按块号二分查找。
+    pub async fn first_block_at_or_after(&self, timestamp: u64, latest: u64) -> Result<u64> {
+        let (mut lo, mut hi) = (0, latest + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.block_timestamp(mid).await? >= timestamp {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        Ok(lo)
+    }
+
+    /// 块时间戳，查到后永久缓存
+    pub async fn block_timestamp(&self, block: u64) -> Result<u64> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        self.cache
+            .immutable(chain, format!("block.timestamp:{}", block), || async {
+                let header = self
+                    .provider
+                    .get_block(block)
+                    .await?
+                    .ok_or_else(|| anyhow!("Block {} not found", block))?;
+                Ok(header.timestamp.as_u64())
+            })
+            .await
+    }
+}
@@ -0,0 +1,266 @@
+// src/fixture.rs
+//! JSON-RPC 录制 / 回放：对着真实节点跑一次，把每个请求和响应存成 fixture，之后离线回放做回归测试
+use anyhow::Context;
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// fixture 文件里的一行：一个请求和节点的响应
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// JSON-RPC 错误对象 `{code, message, data}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+impl Exchange {
+    /// 回放这条响应；`result: null` 反序列化后是 None，按 null 处理
+    fn reply(&self) -> Result<Value, FixtureError> {
+        match &self.error {
+            Some(error) => Err(FixtureError::Rpc(
+                serde_json::from_value(error.clone()).map_err(FixtureError::Json)?,
+            )),
+            None => Ok(self.result.clone().unwrap_or(Value::Null)),
+        }
+    }
+}
+
+/// 读取 fixture 文件（JSON Lines，每行一个 [`Exchange`]）
+pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Vec<Exchange>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Cannot open RPC fixture {}", path.display()))?;
+    let mut exchanges = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let exchange = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid RPC fixture {} line {}", path.display(), i + 1))?;
+        exchanges.push(exchange);
+    }
+    Ok(exchanges)
+}
+
+#[derive(Debug)]
+pub enum FixtureError {
+    /// 节点返回（或回放）的 JSON-RPC 错误
+    Rpc(JsonRpcError),
+    /// 回放时遇到录制里没有的请求
+    Unexpected {
+        method: String,
+        params: Value,
+        /// 下一条还没用过的录制
+        next: Option<(String, Value)>,
+    },
+    /// 被录制的 transport 自身出错（网络等）
+    Client(ProviderError),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{}", e),
+            Self::Unexpected { method, params, next } => {
+                write!(f, "Unexpected RPC request {} {}", method, params)?;
+                match next {
+                    Some((m, p)) => write!(f, " (next recorded request: {} {})", m, p),
+                    None => write!(f, " (all recorded requests have been replayed)"),
+                }
+            }
+            Self::Client(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "{}", e),
+            Self::Io(e) => write!(f, "Cannot write RPC fixture: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+impl RpcError for FixtureError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            Self::Rpc(e) => Some(e),
+            Self::Client(e) => RpcError::as_error_response(e),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            Self::Json(e) => Some(e),
+            Self::Client(e) => RpcError::as_serde_error(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FixtureError> for ProviderError {
+    fn from(e: FixtureError) -> Self {
+        match e {
+            FixtureError::Client(e) => e,
+            other => ProviderError::JsonRpcClientError(Box::new(other)),
+        }
+    }
+}
+
+/// 录制：请求照常发给 `inner`，请求和响应逐行追加到 fixture 文件
+///
+/// 只录 JSON-RPC 层面的结果（含错误响应）；网络错误不录，直接返回。
+#[derive(Clone)]
+pub struct Recorder<C> {
+    inner: C,
+    file: Arc<Mutex<File>>,
+}
+
+impl<C> Recorder<C> {
+    /// 新建（覆盖）fixture 文件
+    pub fn create(inner: C, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Cannot create RPC fixture {}", path.display()))?;
+        Ok(Self { inner, file: Arc::new(Mutex::new(file)) })
+    }
+
+    fn write(&self, exchange: &Exchange) -> Result<(), FixtureError> {
+        let line = serde_json::to_string(exchange).map_err(FixtureError::Json)?;
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", line).and_then(|_| file.flush()).map_err(FixtureError::Io)
+    }
+}
+
+impl<C: Debug> Debug for Recorder<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for Recorder<C> {
+    type Error = FixtureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let recorded = serde_json::to_value(&params).map_err(FixtureError::Json)?;
+        // 原样转发 params：无参数的请求不能变成 "params": null
+        let reply = self.inner.request::<T, Value>(method, params).await;
+        let mut exchange = Exchange { method: method.to_string(), params: recorded, result: None, error: None };
+        match reply {
+            Ok(result) => {
+                exchange.result = Some(result.clone());
+                self.write(&exchange)?;
+                serde_json::from_value(result).map_err(FixtureError::Json)
+            }
+            Err(e) => {
+                let e: ProviderError = e.into();
+                if let Some(rpc) = RpcError::as_error_response(&e) {
+                    exchange.error = Some(json!({ "code": rpc.code, "message": rpc.message, "data": rpc.data }));
+                    self.write(&exchange)?;
+                }
+                Err(FixtureError::Client(e))
+            }
+        }
+    }
+}
+
+/// 回放：不连节点，响应全部来自 fixture
+///
+/// 默认按录制顺序严格回放：每个请求必须和下一条录制完全一致（方法和参数都相同）。
+/// 并发请求的顺序不固定时，用 [`Replay::unordered`] 改为匹配任意一条还没用过的录制（相同的请求仍按录制顺序）。
+/// 对不上时报错并打印这个请求和下一条录制。
+#[derive(Clone, Default)]
+pub struct Replay {
+    exchanges: Arc<Mutex<Vec<(Exchange, bool)>>>,
+    unordered: bool,
+}
+
+impl Replay {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            exchanges: Arc::new(Mutex::new(exchanges.into_iter().map(|e| (e, false)).collect())),
+            unordered: false,
+        }
+    }
+
+    /// 不要求按录制顺序：请求和任意一条还没用过的录制一致即可
+    pub fn unordered(mut self) -> Self {
+        self.unordered = true;
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self::new(load(path)?))
+    }
+
+    /// 还没被回放的录制；回归测试结束时应为空
+    pub fn remaining(&self) -> Vec<Exchange> {
+        self.exchanges
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, used)| !used)
+            .map(|(e, _)| e.clone())
+            .collect()
+    }
+
+    fn next(&self, method: &str, params: Value) -> Result<Value, FixtureError> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let mut unused = exchanges.iter_mut().filter(|(_, used)| !used);
+        let found = if self.unordered {
+            unused.find(|(e, _)| e.method == method && e.params == params)
+        } else {
+            unused.next().filter(|(e, _)| e.method == method && e.params == params)
+        };
+        match found {
+            Some((exchange, used)) => {
+                *used = true;
+                exchange.reply()
+            }
+            None => {
+                let next = exchanges
+                    .iter()
+                    .find(|(_, used)| !used)
+                    .map(|(e, _)| (e.method.clone(), e.params.clone()));
+                let e = FixtureError::Unexpected { method: method.to_string(), params, next };
+                error!("{}", e);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Debug for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replay").field("remaining", &self.remaining().len()).finish()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Replay {
+    type Error = FixtureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(&params).map_err(FixtureError::Json)?;
+        let result = self.next(method, params)?;
+        serde_json::from_value(result).map_err(FixtureError::Json)
+    }
+}
@@ -0,0 +1,123 @@
+// src/http.rs
+use anyhow::Result;
+use rmcp::transport::streamable_http_server::{
+    session::local::{LocalSessionManager, SessionConfig},
+    session::SessionManager,
+    StreamableHttpServerConfig, StreamableHttpService,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::auth::{require_bearer, AuthConfig};
+use crate::service::TokenService;
+
+/// MCP endpoint 路径
+pub const MCP_PATH: &str = "/mcp";
+
+/// Prometheus metrics 路径
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Streamable HTTP 服务端
+///
+/// 每个 MCP 会话都会从 `template` 克隆一份 TokenService：
+/// 模块（provider、缓存等）通过 Arc 共享，会话级别的状态各自独立。
+pub struct HttpServer {
+    template: TokenService,
+    session_manager: Arc<LocalSessionManager>,
+    sse_keep_alive: Option<Duration>,
+    auth: Arc<AuthConfig>,
+}
+
+impl HttpServer {
+    pub fn new(template: TokenService, session_idle_timeout: Option<Duration>) -> Self {
+        let session_manager = LocalSessionManager {
+            session_config: SessionConfig {
+                keep_alive: session_idle_timeout,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Self {
+            template,
+            session_manager: Arc::new(session_manager),
+            sse_keep_alive: Some(Duration::from_secs(15)),
+            auth: Arc::new(AuthConfig::default()),
+        }
+    }
+
+    /// 开启 bearer token 鉴权；未调用时所有请求都按本地调用处理
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// 构建 axum Router（测试里可以直接挂到任意 listener 上）
+    pub fn router(&self) -> axum::Router {
+        let template = self.template.clone();
+        let service = StreamableHttpService::new(
+            move || Ok(template.clone()),
+            self.session_manager.clone(),
+            StreamableHttpServerConfig {
+                sse_keep_alive: self.sse_keep_alive,
+                stateful_mode: true,
+            },
+        );
+
+        // 鉴权只管 MCP endpoint；metrics 只有缓存计数，抓取时不需要客户端 token
+        let mut router = axum::Router::new().nest_service(MCP_PATH, service);
+        if self.auth.is_enabled() {
+            router = router.layer(axum::middleware::from_fn_with_state(self.auth.clone(), require_bearer));
+        }
+        let cache = self.template.cache.clone();
+        router.route(
+            METRICS_PATH,
+            axum::routing::get(move || async move { cache.stats().to_prometheus() }),
+        )
+    }
+
+    /// 当前存活的 MCP 会话数
+    pub async fn session_count(&self) -> usize {
+        self.session_manager.sessions.read().await.len()
+    }
+
+    /// 在已绑定的 listener 上运行，直到 `shutdown` 被取消
+    pub async fn serve(&self, listener: TcpListener, shutdown: CancellationToken) -> Result<()> {
+        info!("MCP HTTP server listening on http://{}{}", listener.local_addr()?, MCP_PATH);
+        if !self.auth.is_enabled() {
+            warn!("No MCP clients configured: HTTP endpoint is open to anyone who can reach it");
+        }
+
+        let router = self.router();
+        let session_manager = self.session_manager.clone();
+
+        axum::serve(listener, router)
+            .with_graceful_shutdown(async move {
+                shutdown.cancelled().await;
+                // SSE 长连接不会自己结束，先关闭所有会话，graceful shutdown 才能完成
+                close_all_sessions(&session_manager).await;
+            })
+            .await?;
+
+        info!("MCP HTTP server stopped");
+        Ok(())
+    }
+
+    /// 绑定地址并运行
+    pub async fn bind_and_serve(&self, bind: SocketAddr, shutdown: CancellationToken) -> Result<()> {
+        let listener = TcpListener::bind(bind).await?;
+        self.serve(listener, shutdown).await
+    }
+}
+
+async fn close_all_sessions(session_manager: &LocalSessionManager) {
+    let ids: Vec<_> = session_manager.sessions.read().await.keys().cloned().collect();
+    info!("Closing {} MCP session(s)", ids.len());
+    for id in ids {
+        let _ = session_manager.close_session(&id).await;
+    }
+}
@@ -0,0 +1,635 @@
+// src/indexer.rs
+use anyhow::{anyhow, bail, Result};
+use ethers::prelude::*;
+use ethers::types::{Action, CallType, Res, Trace, TraceFilter};
+use rmcp::schemars;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, ToSql};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::{IndexerConfig, NativeTransfers};
+use crate::rpc::{metered, scan_logs, RpcProvider};
+
+/// 每批索引的块数：日志 / trace 按范围查询，逐块扫描要一块一块读
+const LOG_BATCH: u64 = 100_000;
+const BLOCK_SCAN_BATCH: u64 = 100;
+
+/// 保留最近多少个已索引块的哈希，用来找重组的分叉点
+const REORG_WINDOW: usize = 128;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS blocks (
+    number INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transfers (
+    block INTEGER NOT NULL,
+    block_hash TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    tx_hash TEXT NOT NULL,
+    tx_index INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    position TEXT NOT NULL,
+    token TEXT,
+    from_addr TEXT NOT NULL,
+    to_addr TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    PRIMARY KEY (tx_hash, position)
+);
+CREATE INDEX IF NOT EXISTS transfers_from ON transfers (from_addr, block);
+CREATE INDEX IF NOT EXISTS transfers_to ON transfers (to_addr, block);
+";
+
+/// 一笔已索引的转账：ERC20 Transfer 事件或原生 ETH 转账
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub block: u64,
+    pub block_hash: H256,
+    pub timestamp: u64,
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    /// 交易内的顺序：ERC20 为 log index，ETH 转账为 -1（先于事件）
+    pub seq: i64,
+    /// 交易内的唯一位置："log:<index>"，或 "call:<trace address>"（顶层调用为 "call:"）
+    pub position: String,
+    /// None 表示 ETH
+    pub token: Option<Address>,
+    pub from: Address,
+    pub to: Address,
+    pub amount: U256,
+}
+
+impl Transfer {
+    pub fn cursor(&self) -> TransferCursor {
+        TransferCursor {
+            block: self.block,
+            transaction_index: self.transaction_index,
+            seq: self.seq,
+            position: self.position.clone(),
+        }
+    }
+}
+
+/// 分页游标：结果按 (块, 交易序号, seq, position) 倒序，下一页从游标之后开始
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferCursor {
+    pub block: u64,
+    pub transaction_index: u64,
+    pub seq: i64,
+    pub position: String,
+}
+
+impl fmt::Display for TransferCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.block, self.transaction_index, self.seq, self.position)
+    }
+}
+
+impl FromStr for TransferCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(4, ':');
+        let mut next = || parts.next().ok_or_else(|| anyhow!("Invalid cursor: {}", s));
+        let cursor = Self {
+            block: next()?.parse()?,
+            transaction_index: next()?.parse()?,
+            seq: next()?.parse()?,
+            position: next()?.to_string(),
+        };
+        Ok(cursor)
+    }
+}
+
+/// 相对查询钱包的方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// 转入
+    In,
+    /// 转出
+    Out,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TransferQuery {
+    pub wallet: Address,
+    /// None 不限；Some(None) 只要 ETH
+    pub token: Option<Option<Address>>,
+    pub direction: Option<Direction>,
+    pub counterparty: Option<Address>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// 上一页最后一条的游标
+    pub after: Option<TransferCursor>,
+    pub limit: usize,
+}
+
+fn hex(address: &Address) -> String {
+    format!("{:?}", address)
+}
+
+/// 转账记录的 SQLite 存储
+pub struct TransferStore {
+    conn: Mutex<Connection>,
+}
+
+impl TransferStore {
+    /// 打开（或新建）数据库；`fingerprint` 和上次不同（跟踪的钱包 / 代币变了）时清空重建
+    pub fn open(path: &str, fingerprint: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        let store = Self { conn: Mutex::new(conn) };
+
+        let previous = store.meta("config")?;
+        if previous.as_deref() != Some(fingerprint) {
+            if previous.is_some() {
+                warn!("Indexer configuration changed, rebuilding {}", path);
+            }
+            let conn = store.conn.lock().unwrap();
+            conn.execute_batch("DELETE FROM transfers; DELETE FROM blocks; DELETE FROM meta;")?;
+            conn.execute("INSERT INTO meta (key, value) VALUES ('config', ?1)", [fingerprint])?;
+        }
+        Ok(store)
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// 已经索引到的块（含）
+    pub fn cursor(&self) -> Result<Option<u64>> {
+        Ok(self.meta("cursor")?.map(|v| v.parse()).transpose()?)
+    }
+
+    /// 最近记录的块哈希，从新到旧
+    pub fn recent_blocks(&self) -> Result<Vec<(u64, H256)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT number, hash FROM blocks ORDER BY number DESC LIMIT ?1")?;
+        let rows = stmt.query_map([REORG_WINDOW as i64], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get::<_, String>(1)?))
+        })?;
+        rows.map(|row| {
+            let (number, hash) = row?;
+            Ok((number, hash.parse()?))
+        })
+        .collect()
+    }
+
+    /// 一个事务里写入一批转账、块哈希，并把游标推进到 `indexed_to`
+    pub fn commit(&self, indexed_to: u64, blocks: &[(u64, H256)], transfers: &[Transfer]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for (number, hash) in blocks {
+            tx.execute(
+                "INSERT OR REPLACE INTO blocks (number, hash) VALUES (?1, ?2)",
+                params![*number as i64, format!("{:?}", hash)],
+            )?;
+        }
+        for t in transfers {
+            tx.execute(
+                "INSERT OR REPLACE INTO transfers
+                 (block, block_hash, timestamp, tx_hash, tx_index, seq, position, token, from_addr, to_addr, amount)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    t.block as i64,
+                    format!("{:?}", t.block_hash),
+                    t.timestamp as i64,
+                    format!("{:?}", t.transaction_hash),
+                    t.transaction_index as i64,
+                    t.seq,
+                    t.position,
+                    t.token.as_ref().map(hex),
+                    hex(&t.from),
+                    hex(&t.to),
+                    t.amount.to_string(),
+                ],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM blocks WHERE number NOT IN (SELECT number FROM blocks ORDER BY number DESC LIMIT ?1)",
+            [REORG_WINDOW as i64],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('cursor', ?1)",
+            [indexed_to.to_string()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删掉 `block` 之后的所有数据，游标退回 `block`；返回删掉的转账条数
+    pub fn rollback(&self, block: u64) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let removed = tx.execute("DELETE FROM transfers WHERE block > ?1", [block as i64])?;
+        tx.execute("DELETE FROM blocks WHERE number > ?1", [block as i64])?;
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('cursor', ?1)", [block.to_string()])?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// 按条件查转账，从新到旧
+    pub fn query(&self, query: &TransferQuery) -> Result<Vec<Transfer>> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        let mut bind = |value: Box<dyn ToSql>| {
+            values.push(value);
+            format!("?{}", values.len())
+        };
+
+        let wallet = bind(Box::new(hex(&query.wallet)));
+        let counterparty = query.counterparty.map(|c| bind(Box::new(hex(&c))));
+        conditions.push(match (query.direction, counterparty) {
+            (Some(Direction::In), None) => format!("to_addr = {}", wallet),
+            (Some(Direction::In), Some(c)) => format!("to_addr = {} AND from_addr = {}", wallet, c),
+            (Some(Direction::Out), None) => format!("from_addr = {}", wallet),
+            (Some(Direction::Out), Some(c)) => format!("from_addr = {} AND to_addr = {}", wallet, c),
+            (None, None) => format!("(from_addr = {w} OR to_addr = {w})", w = wallet),
+            (None, Some(c)) => format!(
+                "((from_addr = {w} AND to_addr = {c}) OR (from_addr = {c} AND to_addr = {w}))",
+                w = wallet,
+                c = c
+            ),
+        });
+
+        match query.token {
+            Some(Some(token)) => conditions.push(format!("token = {}", bind(Box::new(hex(&token))))),
+            Some(None) => conditions.push("token IS NULL".to_string()),
+            None => {}
+        }
+        if let Some(from) = query.from_block {
+            conditions.push(format!("block >= {}", bind(Box::new(from as i64))));
+        }
+        if let Some(to) = query.to_block {
+            conditions.push(format!("block <= {}", bind(Box::new(to as i64))));
+        }
+        if let Some(after) = &query.after {
+            conditions.push(format!(
+                "(block, tx_index, seq, position) < ({}, {}, {}, {})",
+                bind(Box::new(after.block as i64)),
+                bind(Box::new(after.transaction_index as i64)),
+                bind(Box::new(after.seq)),
+                bind(Box::new(after.position.clone())),
+            ));
+        }
+        let limit = bind(Box::new(query.limit as i64));
+
+        let sql = format!(
+            "SELECT block, block_hash, timestamp, tx_hash, tx_index, seq, position, token, from_addr, to_addr, amount
+             FROM transfers WHERE {}
+             ORDER BY block DESC, tx_index DESC, seq DESC, position DESC
+             LIMIT {}",
+            conditions.join(" AND "),
+            limit
+        );
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, String>(8)?,
+                row.get::<_, String>(9)?,
+                row.get::<_, String>(10)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (block, block_hash, timestamp, tx_hash, tx_index, seq, position, token, from, to, amount) = row?;
+            Ok(Transfer {
+                block: block as u64,
+                block_hash: block_hash.parse()?,
+                timestamp: timestamp as u64,
+                transaction_hash: tx_hash.parse()?,
+                transaction_index: tx_index as u64,
+                seq,
+                position,
+                token: token.map(|t| t.parse()).transpose()?,
+                from: from.parse()?,
+                to: to.parse()?,
+                amount: U256::from_dec_str(&amount)?,
+            })
+        })
+        .collect()
+    }
+}
+
+/// 一次同步的结果
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// 发现重组时回滚到的块
+    pub rolled_back_to: Option<u64>,
+    /// 同步后已索引到的块
+    pub indexed_to: Option<u64>,
+    /// 新写入的转账条数
+    pub transfers: usize,
+}
+
+/// 后台转账索引：跟踪配置的钱包，把 ERC20 Transfer 和 ETH 转账存进 SQLite
+pub struct Indexer {
+    pub provider: Arc<RpcProvider>,
+    pub config: IndexerConfig,
+    store: TransferStore,
+}
+
+impl Indexer {
+    pub fn new(provider: Provider<Http>, config: IndexerConfig) -> Result<Self> {
+        Self::shared(Arc::new(metered(provider)), config)
+    }
+
+    /// 复用其他模块已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, config: IndexerConfig) -> Result<Self> {
+        let mut wallets: Vec<String> = config.wallets.iter().map(hex).collect();
+        let mut tokens: Vec<String> = config.tokens.iter().map(hex).collect();
+        wallets.sort();
+        tokens.sort();
+        let fingerprint = format!(
+            "wallets={};tokens={};native={:?};start={}",
+            wallets.join(","),
+            tokens.join(","),
+            config.native,
+            config.start_block
+        );
+        let store = TransferStore::open(&config.db_path, &fingerprint)?;
+        Ok(Self { provider, config, store })
+    }
+
+    pub fn store(&self) -> &TransferStore {
+        &self.store
+    }
+
+    pub fn follows(&self, wallet: &Address) -> bool {
+        self.config.wallets.contains(wallet)
+    }
+
+    /// 先处理重组，再从游标索引到最新块
+    pub async fn sync(&self) -> Result<SyncReport> {
+        let head = self.provider.get_block_number().await?.as_u64();
+        let mut report = SyncReport { rolled_back_to: self.handle_reorg().await?, ..Default::default() };
+
+        let batch = match self.config.native {
+            NativeTransfers::Blocks => BLOCK_SCAN_BATCH,
+            _ => LOG_BATCH,
+        };
+        let mut next = self.store.cursor()?.map_or(self.config.start_block, |c| c + 1);
+        while next <= head {
+            let end = head.min(next + batch - 1);
+            report.transfers += self.index_range(next, end).await?;
+            next = end + 1;
+        }
+        report.indexed_to = self.store.cursor()?;
+        Ok(report)
+    }
+
+    /// 后台循环：每 `poll_interval` 同步一次，直到 `shutdown`
+    pub fn spawn(self: Arc<Self>, shutdown: CancellationToken) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match self.sync().await {
+                    Ok(report) if report.transfers > 0 || report.rolled_back_to.is_some() => {
+                        info!("Transfer indexer: {:?}", report)
+                    }
+                    Ok(report) => debug!("Transfer indexer: {:?}", report),
+                    Err(e) => warn!("Transfer indexer sync failed: {:#}", e),
+                }
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(self.config.poll_interval) => {}
+                }
+            }
+        })
+    }
+
+    /// 从新到旧对比记录的块哈希，找到还在链上的最近一块；之后的数据全部回滚
+    async fn handle_reorg(&self) -> Result<Option<u64>> {
+        let recent = self.store.recent_blocks()?;
+        let Some(&(newest, _)) = recent.first() else {
+            return Ok(None);
+        };
+        let mut fork = None;
+        for (number, hash) in &recent {
+            let current = self.provider.get_block(*number).await?.and_then(|b| b.hash);
+            if current == Some(*hash) {
+                fork = Some(*number);
+                break;
+            }
+        }
+        let fork = match fork {
+            Some(n) if n == newest => return Ok(None),
+            Some(n) => n,
+            // 整个窗口都被替换了，从窗口之前重新索引
+            None => recent.last().map_or(0, |(n, _)| n.saturating_sub(1)),
+        };
+        let removed = self.store.rollback(fork)?;
+        warn!("Chain reorganization after block {}: rolled back {} transfers", fork, removed);
+        Ok(Some(fork))
+    }
+
+    /// 索引 `from..=to`，一个事务写入
+    async fn index_range(&self, from: u64, to: u64) -> Result<usize> {
+        // 块号 → (哈希, 时间戳)
+        let mut headers: HashMap<u64, (H256, u64)> = HashMap::new();
+        let mut transfers = self.erc20_transfers(from, to).await?;
+        match self.config.native {
+            NativeTransfers::Off => {}
+            NativeTransfers::Traces => transfers.extend(self.trace_transfers(from, to).await?),
+            NativeTransfers::Blocks => transfers.extend(self.block_transfers(from, to, &mut headers).await?),
+        }
+
+        // 补上时间戳；日志 / trace 所在块的哈希和现在的链对不上说明期间发生了重组，下一轮重来
+        for transfer in &mut transfers {
+            let (hash, timestamp) = match headers.get(&transfer.block) {
+                Some(header) => *header,
+                None => {
+                    let header = self.header(transfer.block).await?;
+                    headers.insert(transfer.block, header);
+                    header
+                }
+            };
+            if hash != transfer.block_hash {
+                bail!("Block {} was reorganized while indexing", transfer.block);
+            }
+            transfer.timestamp = timestamp;
+        }
+        let end = match headers.get(&to) {
+            Some(header) => *header,
+            None => self.header(to).await?,
+        };
+
+        let mut blocks: BTreeMap<u64, H256> = transfers.iter().map(|t| (t.block, t.block_hash)).collect();
+        blocks.insert(to, end.0);
+        let blocks: Vec<(u64, H256)> = blocks.into_iter().collect();
+        self.store.commit(to, &blocks, &transfers)?;
+        Ok(transfers.len())
+    }
+
+    async fn header(&self, number: u64) -> Result<(H256, u64)> {
+        let block = self
+            .provider
+            .get_block(number)
+            .await?
+            .ok_or_else(|| anyhow!("Block {} not found", number))?;
+        let hash = block.hash.ok_or_else(|| anyhow!("Block {} is pending", number))?;
+        Ok((hash, block.timestamp.as_u64()))
+    }
+
+    /// 钱包收发的 ERC20 Transfer：from 和 to 各查一次
+    async fn erc20_transfers(&self, from: u64, to: u64) -> Result<Vec<Transfer>> {
+        let wallets: Vec<H256> = self.config.wallets.iter().map(|w| H256::from(*w)).collect();
+        let mut base = Filter::new().topic0(H256::from(ethers::utils::keccak256("Transfer(address,address,uint256)")));
+        if !self.config.tokens.is_empty() {
+            base = base.address(self.config.tokens.clone());
+        }
+
+        // (交易, log index) 去重：钱包之间互转两次查询都会返回
+        let mut found: BTreeMap<(H256, u64), Transfer> = BTreeMap::new();
+        for filter in [base.clone().topic1(wallets.clone()), base.topic2(wallets)] {
+            for log in scan_logs(&self.provider, filter, from, to, None).await?.logs {
+                // ERC721 的 Transfer 签名相同但 tokenId 也是 indexed
+                if log.topics.len() != 3 || log.data.len() < 32 || log.removed == Some(true) {
+                    continue;
+                }
+                let (Some(block), Some(block_hash), Some(tx_hash), Some(tx_index), Some(log_index)) = (
+                    log.block_number,
+                    log.block_hash,
+                    log.transaction_hash,
+                    log.transaction_index,
+                    log.log_index,
+                ) else {
+                    continue;
+                };
+                found.insert(
+                    (tx_hash, log_index.as_u64()),
+                    Transfer {
+                        block: block.as_u64(),
+                        block_hash,
+                        timestamp: 0,
+                        transaction_hash: tx_hash,
+                        transaction_index: tx_index.as_u64(),
+                        seq: log_index.as_u64() as i64,
+                        position: format!("log:{}", log_index),
+                        token: Some(log.address),
+                        from: Address::from(log.topics[1]),
+                        to: Address::from(log.topics[2]),
+                        amount: U256::from_big_endian(&log.data[..32]),
+                    },
+                );
+            }
+        }
+        Ok(found.into_values().collect())
+    }
+
+    /// trace_filter 里涉及钱包、带 ETH 的调用 / 创建 / 自毁；出错（revert）的跳过
+    async fn trace_transfers(&self, from: u64, to: u64) -> Result<Vec<Transfer>> {
+        let base = TraceFilter::default().from_block(from).to_block(to);
+        let wallets = self.config.wallets.clone();
+
+        let mut found: BTreeMap<(H256, String), Transfer> = BTreeMap::new();
+        for filter in [base.clone().from_address(wallets.clone()), base.to_address(wallets)] {
+            for trace in self.provider.trace_filter(filter).await? {
+                if let Some(transfer) = trace_transfer(&trace) {
+                    found.insert((transfer.transaction_hash, transfer.position.clone()), transfer);
+                }
+            }
+        }
+        Ok(found
+            .into_values()
+            .filter(|t| self.follows(&t.from) || self.follows(&t.to))
+            .collect())
+    }
+
+    /// 逐块读交易，只看顶层 ETH 转账；失败的交易不算
+    async fn block_transfers(
+        &self,
+        from: u64,
+        to: u64,
+        headers: &mut HashMap<u64, (H256, u64)>,
+    ) -> Result<Vec<Transfer>> {
+        let mut transfers = Vec::new();
+        for number in from..=to {
+            let block = self
+                .provider
+                .get_block_with_txs(number)
+                .await?
+                .ok_or_else(|| anyhow!("Block {} not found", number))?;
+            let hash = block.hash.ok_or_else(|| anyhow!("Block {} is pending", number))?;
+            headers.insert(number, (hash, block.timestamp.as_u64()));
+
+            for tx in block.transactions {
+                let involved = self.follows(&tx.from) || tx.to.as_ref().is_some_and(|to| self.follows(to));
+                if tx.value.is_zero() || !involved {
+                    continue;
+                }
+                let Some(receipt) = self.provider.get_transaction_receipt(tx.hash).await? else {
+                    continue;
+                };
+                if receipt.status != Some(U64::one()) {
+                    continue;
+                }
+                let Some(recipient) = tx.to.or(receipt.contract_address) else { continue };
+                transfers.push(Transfer {
+                    block: number,
+                    block_hash: hash,
+                    timestamp: block.timestamp.as_u64(),
+                    transaction_hash: tx.hash,
+                    transaction_index: tx.transaction_index.map_or(0, |i| i.as_u64()),
+                    seq: -1,
+                    position: "call:".to_string(),
+                    token: None,
+                    from: tx.from,
+                    to: recipient,
+                    amount: tx.value,
+                });
+            }
+        }
+        Ok(transfers)
+    }
+}
+
+fn trace_transfer(trace: &Trace) -> Option<Transfer> {
+    if trace.error.is_some() {
+        return None;
+    }
+    let (from, to, amount) = match (&trace.action, &trace.result) {
+        (Action::Call(call), _) if call.call_type == CallType::Call => (call.from, call.to, call.value),
+        (Action::Create(create), Some(Res::Create(result))) => (create.from, result.address, create.value),
+        (Action::Suicide(suicide), _) => (suicide.address, suicide.refund_address, suicide.balance),
+        _ => return None,
+    };
+    if amount.is_zero() {
+        return None;
+    }
+    let address: Vec<String> = trace.trace_address.iter().map(|i| i.to_string()).collect();
+    Some(Transfer {
+        block: trace.block_number,
+        block_hash: trace.block_hash,
+        timestamp: 0,
+        transaction_hash: trace.transaction_hash?,
+        transaction_index: trace.transaction_position? as u64,
+        seq: -1,
+        position: format!("call:{}", address.join(".")),
+        token: None,
+        from,
+        to,
+        amount,
+    })
+}
@@ -3,3 +3,23 @@ pub mod price;
 pub mod swap;
 pub mod service;
 pub mod config;
+pub mod http;
+pub mod auth;
+pub mod rpc;
+pub mod usage;
+pub mod cache;
+pub mod registry;
+pub mod token;
+pub mod tokenlist;
+pub mod ens;
+pub mod nft;
+pub mod approvals;
+pub mod contract;
+pub mod abi_library;
+pub mod transaction;
+pub mod events;
+pub mod indexer;
+pub mod fixture;
+pub mod simulate;
+pub mod risk;
+pub mod pool;
@@ -1,14 +1,24 @@
 use dotenv::dotenv;
+use eth_mcp_server::auth::AuthConfig;
 use eth_mcp_server::balance::BalanceModule;
-use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::cache::RpcCache;
+use eth_mcp_server::config::{AppConfig, RpcFixture, TransportMode};
+use eth_mcp_server::http::HttpServer;
+use eth_mcp_server::indexer::Indexer;
 use eth_mcp_server::price::PriceModule;
+use eth_mcp_server::registry::TokenRegistry;
+use eth_mcp_server::rpc;
 use eth_mcp_server::service::TokenService;
 use eth_mcp_server::swap::SwapModule;
+use eth_mcp_server::token::TokenModule;
+use eth_mcp_server::usage::UsageTracker;
 use ethers::providers::{Http, Provider, ProviderExt};
 use rmcp::ServiceExt;
 use std::env;
 use std::sync::Arc;
 use tokio::io::{stdin, stdout};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 use tracing_subscriber;
 
 #[tokio::main]
@@ -16,31 +26,99 @@ async fn main() -> anyhow::Result<()> {
     // 初始化环境变量
     dotenv().ok();
 
-    // 初始化日志
-    tracing_subscriber::fmt::init();
+    // 初始化日志（stdio 模式下 stdout 是协议通道，日志只能写 stderr）
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    // 解析传输方式：--transport stdio|http --bind addr
+    let transport_mode = TransportMode::from_args(env::args().skip(1))?;
 
     // 获取 RPC URL
     let rpc_url = env::var("INFURA_URL").expect("ETH_NODE_URL not set");
 
-    // 创建 Provider (ethers 2.x 推荐用 connect)
-    let provider = Provider::<Http>::connect(rpc_url.as_str()).await;
-
     // 初始化配置
     let config = AppConfig::load();
 
-    // 初始化各模块（模块内部会把 provider 包成 Arc）
-    let balance_module = Arc::new(BalanceModule::new(provider.clone()));
-    let price_module = Arc::new(PriceModule::new(provider.clone(), config.clone()));
-    let swap_module = Arc::new(SwapModule::new(provider, config.clone()));
+    // 创建 Provider (ethers 2.x 推荐用 connect)；回放 fixture 时不连节点
+    let provider = match &config.rpc_fixture {
+        Some(RpcFixture::Replay(_) | RpcFixture::ReplayUnordered(_)) => Provider::<Http>::try_from(rpc_url.as_str())?,
+        _ => Provider::<Http>::connect(rpc_url.as_str()).await,
+    };
+    if let Some(fixture) = &config.rpc_fixture {
+        info!("RPC fixture: {:?}", fixture);
+    }
+    let provider = Arc::new(rpc::connect(provider, config.rpc_fixture.as_ref(), config.max_log_range)?);
+
+    // 各模块共用一个只读缓存和代币注册表；录制 / 回放时最新块固定不变，请求序列与调用节奏无关
+    let cache = Arc::new(match config.rpc_fixture {
+        Some(_) => RpcCache::frozen(),
+        None => RpcCache::new(config.block_poll_interval),
+    });
+    let registry = Arc::new(TokenRegistry::default());
+    for token in &config.token_list {
+        registry.register(token.clone())?;
+    }
+    if !registry.is_empty() {
+        info!("Loaded {} tokens from token lists", registry.len());
+    }
+
+    // 初始化各模块，共用同一个 provider
+    let balance_module = Arc::new(BalanceModule::shared(provider.clone(), cache.clone()));
+    let price_module = Arc::new(
+        PriceModule::shared(provider.clone(), config.clone())
+            .with_cache(cache.clone())
+            .with_registry(registry.clone()),
+    );
+    let swap_module = Arc::new(
+        SwapModule::shared(provider.clone(), config.clone())
+            .with_cache(cache.clone())
+            .with_registry(registry.clone()),
+    );
+    let token_module = Arc::new(TokenModule::shared(provider, cache).with_registry(registry));
+
+    let mut service = TokenService::new(balance_module, price_module, swap_module, token_module)
+        .with_usage(UsageTracker::new(config.default_limits.clone()))
+        .with_confidence(config.confidence);
+
+    // Ctrl-C → 取消，所有 transport 都据此优雅退出
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutdown signal received");
+            shutdown.cancel();
+        }
+    });
 
-    let service = TokenService::new(balance_module, price_module, swap_module);
+    // 可选：后台转账索引
+    if let Some(indexer_config) = config.indexer.clone() {
+        let indexer = Arc::new(Indexer::shared(service.balance.provider.clone(), indexer_config)?);
+        info!(
+            "Transfer indexer: {} wallets, database {}",
+            indexer.config.wallets.len(),
+            indexer.config.db_path
+        );
+        indexer.clone().spawn(shutdown.child_token());
+        service = service.with_indexer(indexer);
+    }
 
-    // 构建 transport (stdin/stdout)
-    let transport = (stdin(), stdout());
+    match transport_mode {
+        TransportMode::Stdio => {
+            // 构建 transport (stdin/stdout)
+            let transport = (stdin(), stdout());
 
-    // 启动 MCP server
-    let server = service.serve(transport).await?;
-    server.waiting().await?;
+            // 启动 MCP server
+            let server = service.serve_with_ct(transport, shutdown.child_token()).await?;
+            server.waiting().await?;
+        }
+        TransportMode::Http { bind } => {
+            let auth = AuthConfig::new(&config.clients)?;
+            HttpServer::new(service, config.session_idle_timeout)
+                .with_auth(auth)
+                .bind_and_serve(bind, shutdown)
+                .await?;
+        }
+    }
 
     Ok(())
 }
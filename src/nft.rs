@@ -0,0 +1,479 @@
+// src/nft.rs
+use anyhow::{anyhow, bail, Result};
+use ethers::abi::{self, Abi, ParamType, Token};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+use crate::balance::{BalanceModule, MULTICALL_BATCH_SIZE};
+use crate::rpc::{batch_view_calls, scan_logs, view_call};
+
+/// ERC721 / ERC1155 / ERC165 里用到的函数
+static NFT_ABI: LazyLock<Abi> = LazyLock::new(|| {
+    abi::parse_abi(&[
+        "function supportsInterface(bytes4 interfaceId) view returns (bool)",
+        "function balanceOf(address owner) view returns (uint256)",
+        "function ownerOf(uint256 tokenId) view returns (address)",
+        "function tokenURI(uint256 tokenId) view returns (string)",
+        "function tokenOfOwnerByIndex(address owner, uint256 index) view returns (uint256)",
+        "function uri(uint256 id) view returns (string)",
+        "function balanceOfBatch(address[] owners, uint256[] ids) view returns (uint256[])",
+    ])
+    .expect("valid NFT ABI")
+});
+
+/// ERC1155 的 balanceOf 和 ERC721 同名不同参，单独解析
+static ERC1155_BALANCE_OF: LazyLock<abi::Function> = LazyLock::new(|| {
+    abi::parse_abi(&["function balanceOf(address owner, uint256 id) view returns (uint256)"])
+        .expect("valid ERC1155 ABI")
+        .function("balanceOf")
+        .expect("balanceOf")
+        .clone()
+});
+
+const ERC721_INTERFACE: [u8; 4] = [0x80, 0xac, 0x58, 0xcd];
+const ERC721_ENUMERABLE_INTERFACE: [u8; 4] = [0x78, 0x0e, 0x9d, 0x63];
+const ERC721_METADATA_INTERFACE: [u8; 4] = [0x5b, 0x5e, 0x13, 0x9f];
+const ERC1155_INTERFACE: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+
+/// 一次最多返回的 NFT 数，防止大户把响应撑爆
+pub const MAX_NFT_HOLDINGS: usize = 500;
+
+/// 不给 from_block 时往回扫的块数（主网约 9 个月）
+pub const DEFAULT_NFT_LOOKBACK: u64 = 2_000_000;
+
+/// 一次最多扫的转入日志条数（默认 / 上限）；超过时分页，用 next_from_block 接着查
+pub const DEFAULT_NFT_LOG_LIMIT: usize = 1000;
+pub const MAX_NFT_LOG_LIMIT: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NftStandard {
+    Erc721,
+    Erc1155,
+}
+
+/// ERC165 探测结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NftInterfaces {
+    pub standard: NftStandard,
+    /// ERC721Enumerable：可以直接按下标列出持有的 tokenId
+    pub enumerable: bool,
+    /// ERC721Metadata：有 tokenURI
+    pub metadata: bool,
+}
+
+/// 持有的一个 NFT（ERC1155 可以持有多份）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NftHolding {
+    pub token_id: U256,
+    pub amount: U256,
+    pub token_uri: Option<String>,
+}
+
+/// tokenId 是怎么找出来的
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingsSource {
+    /// ERC721Enumerable.tokenOfOwnerByIndex
+    Enumerable,
+    /// 扫描转入的 Transfer 日志，再按当前链上状态核对
+    TransferLogs,
+}
+
+#[derive(Clone, Debug)]
+pub struct NftHoldings {
+    pub interfaces: NftInterfaces,
+    pub holdings: Vec<NftHolding>,
+    pub source: HoldingsSource,
+    /// 扫描转入日志的起始块；按下标列出时为 None
+    pub from_block: Option<u64>,
+    /// 超过 MAX_NFT_HOLDINGS，或转入日志超过 limit 只扫到了 next_from_block 之前
+    pub truncated: bool,
+    pub next_from_block: Option<u64>,
+    /// 读取时的块号
+    pub block: U64,
+}
+
+impl BalanceModule {
+    /// 用 ERC165 判断合约是 ERC721 还是 ERC1155（结果永久缓存）
+    pub async fn nft_interfaces(&self, collection: Address) -> Result<NftInterfaces> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let found: Option<(NftStandard, bool, bool)> = self
+            .cache
+            .immutable(chain, format!("nft.interfaces:{:?}", collection), || async {
+                if self.supports_interface(collection, ERC721_INTERFACE).await? {
+                    let enumerable = self.supports_interface(collection, ERC721_ENUMERABLE_INTERFACE).await?;
+                    let metadata = self.supports_interface(collection, ERC721_METADATA_INTERFACE).await?;
+                    Ok(Some((NftStandard::Erc721, enumerable, metadata)))
+                } else if self.supports_interface(collection, ERC1155_INTERFACE).await? {
+                    Ok(Some((NftStandard::Erc1155, false, true)))
+                } else {
+                    Ok(None)
+                }
+            })
+            .await?;
+
+        let (standard, enumerable, metadata) =
+            found.ok_or_else(|| anyhow!("{:?} is neither ERC721 nor ERC1155 (ERC165)", collection))?;
+        Ok(NftInterfaces { standard, enumerable, metadata })
+    }
+
+    /// ERC721 持有数量
+    pub async fn erc721_balance_of(&self, collection: Address, owner: Address) -> Result<U256> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let out = self
+            .nft_call(collection, NFT_ABI.function("balanceOf")?, &[Token::Address(owner)], block)
+            .await?;
+        into_uint(out)
+    }
+
+    /// ERC721 当前持有人；已销毁或不存在的 tokenId 返回 None
+    pub async fn owner_of(&self, collection: Address, token_id: U256) -> Result<Option<Address>> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let f = NFT_ABI.function("ownerOf")?;
+        match self.try_nft_call(collection, f, &[Token::Uint(token_id)], block).await? {
+            Some(Token::Address(owner)) => Ok(Some(owner)),
+            _ => Ok(None),
+        }
+    }
+
+    /// ERC721 tokenURI / ERC1155 uri；没有实现时返回 None
+    pub async fn token_uri(&self, collection: Address, token_id: U256) -> Result<Option<String>> {
+        let interfaces = self.nft_interfaces(collection).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let f = uri_function(interfaces.standard)?;
+        let uri = self.try_nft_call(collection, f, &[Token::Uint(token_id)], block).await?;
+        Ok(expand_uri(interfaces.standard, uri, token_id))
+    }
+
+    /// ERC1155 单个余额
+    pub async fn erc1155_balance_of(&self, collection: Address, owner: Address, id: U256) -> Result<U256> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let out = self
+            .nft_call(collection, &ERC1155_BALANCE_OF, &[Token::Address(owner), Token::Uint(id)], block)
+            .await?;
+        into_uint(out)
+    }
+
+    /// ERC1155 批量余额：owners 和 ids 一一对应
+    pub async fn erc1155_balance_of_batch(
+        &self,
+        collection: Address,
+        owners: &[Address],
+        ids: &[U256],
+    ) -> Result<Vec<U256>> {
+        if owners.len() != ids.len() {
+            bail!("owners and ids must have the same length");
+        }
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.balance_of_batch_at(collection, owners, ids, block).await
+    }
+
+    /// 列出 `owner` 在 `collection` 里持有的 NFT
+    ///
+    /// ERC721Enumerable 直接按下标读；其他情况扫描转入 `owner` 的 Transfer 日志，
+    /// 再用 ownerOf / balanceOfBatch 核对当前是否仍然持有。
+    /// 不给 `from_block` 时只扫最近 DEFAULT_NFT_LOOKBACK 块；转入日志超过 `limit` 条时分页，见 `next_from_block`。
+    pub async fn get_nft_holdings(
+        &self,
+        owner: Address,
+        collection: Address,
+        from_block: Option<u64>,
+        limit: usize,
+    ) -> Result<NftHoldings> {
+        let interfaces = self.nft_interfaces(collection).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let from_block = from_block.unwrap_or_else(|| block.as_u64().saturating_sub(DEFAULT_NFT_LOOKBACK));
+
+        if interfaces.standard == NftStandard::Erc721 && interfaces.enumerable {
+            let (mut holdings, truncated) = self.enumerate_erc721(collection, owner, block).await?;
+            self.fill_token_uris(collection, interfaces, &mut holdings, block).await?;
+            return Ok(NftHoldings {
+                interfaces,
+                holdings,
+                source: HoldingsSource::Enumerable,
+                from_block: None,
+                truncated,
+                next_from_block: None,
+                block,
+            });
+        }
+
+        let (mut holdings, truncated, next_from_block) = match interfaces.standard {
+            NftStandard::Erc721 => {
+                let (candidates, next_from_block) =
+                    self.received_erc721(collection, owner, from_block, block, limit).await?;
+                let candidates: Vec<U256> = candidates.into_iter().collect();
+                // 分批用 Multicall 核对 ownerOf，凑够 MAX_NFT_HOLDINGS + 1 个（足以判断截断）就不再往下查
+                let mut holdings = Vec::new();
+                for ids in candidates.chunks(MULTICALL_BATCH_SIZE) {
+                    let owners = self.batch_nft_calls(collection, NFT_ABI.function("ownerOf")?, ids, block).await?;
+                    holdings.extend(
+                        ids.iter()
+                            .zip(owners)
+                            .filter(|(_, current)| *current == Some(Token::Address(owner)))
+                            .map(|(token_id, _)| NftHolding { token_id: *token_id, amount: U256::one(), token_uri: None }),
+                    );
+                    if holdings.len() > MAX_NFT_HOLDINGS {
+                        break;
+                    }
+                }
+                let truncated = holdings.len() > MAX_NFT_HOLDINGS;
+                holdings.truncate(MAX_NFT_HOLDINGS);
+                (holdings, truncated, next_from_block)
+            }
+            NftStandard::Erc1155 => {
+                let (ids, next_from_block) = self.received_erc1155(collection, owner, from_block, block, limit).await?;
+                let ids: Vec<U256> = ids.into_iter().collect();
+                let owners = vec![owner; ids.len()];
+                let mut holdings = Vec::new();
+                for (ids, owners) in ids.chunks(MAX_NFT_HOLDINGS).zip(owners.chunks(MAX_NFT_HOLDINGS)) {
+                    let amounts = self.balance_of_batch_at(collection, owners, ids, block).await?;
+                    holdings.extend(
+                        ids.iter()
+                            .zip(amounts)
+                            .filter(|(_, amount)| !amount.is_zero())
+                            .map(|(id, amount)| NftHolding { token_id: *id, amount, token_uri: None }),
+                    );
+                    if holdings.len() > MAX_NFT_HOLDINGS {
+                        break;
+                    }
+                }
+                let truncated = holdings.len() > MAX_NFT_HOLDINGS;
+                holdings.truncate(MAX_NFT_HOLDINGS);
+                (holdings, truncated, next_from_block)
+            }
+        };
+        self.fill_token_uris(collection, interfaces, &mut holdings, block).await?;
+
+        Ok(NftHoldings {
+            interfaces,
+            holdings,
+            source: HoldingsSource::TransferLogs,
+            from_block: Some(from_block),
+            truncated: truncated || next_from_block.is_some(),
+            next_from_block,
+            block,
+        })
+    }
+
+    /// 有 Metadata 扩展时经 Multicall 读 tokenURI / uri
+    async fn fill_token_uris(
+        &self,
+        collection: Address,
+        interfaces: NftInterfaces,
+        holdings: &mut [NftHolding],
+        block: U64,
+    ) -> Result<()> {
+        if !interfaces.metadata {
+            return Ok(());
+        }
+        let f = uri_function(interfaces.standard)?;
+        for chunk in holdings.chunks_mut(MULTICALL_BATCH_SIZE) {
+            let ids: Vec<U256> = chunk.iter().map(|h| h.token_id).collect();
+            let uris = self.batch_nft_calls(collection, f, &ids, block).await?;
+            for (holding, uri) in chunk.iter_mut().zip(uris) {
+                holding.token_uri = expand_uri(interfaces.standard, uri, holding.token_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn enumerate_erc721(
+        &self,
+        collection: Address,
+        owner: Address,
+        block: U64,
+    ) -> Result<(Vec<NftHolding>, bool)> {
+        let balance = into_uint(
+            self.nft_call(collection, NFT_ABI.function("balanceOf")?, &[Token::Address(owner)], block)
+                .await?,
+        )?;
+        let count = balance.min(U256::from(MAX_NFT_HOLDINGS)).as_usize();
+
+        // tokenOfOwnerByIndex 经 Multicall 分批读
+        let f = NFT_ABI.function("tokenOfOwnerByIndex")?;
+        let calls = (0..count)
+            .map(|index| Ok((collection, f.encode_input(&[Token::Address(owner), Token::Uint(index.into())])?.into())))
+            .collect::<Result<Vec<_>>>()?;
+        let mut holdings = Vec::with_capacity(count);
+        for (chunk, batch) in calls.chunks(MULTICALL_BATCH_SIZE).enumerate() {
+            let outputs = batch_view_calls(&self.provider, batch, block).await?;
+            for (i, out) in outputs.into_iter().enumerate() {
+                let index = chunk * MULTICALL_BATCH_SIZE + i;
+                let token_id = out
+                    .and_then(|data| f.decode_output(&data).ok())
+                    .and_then(|mut out| out.pop())
+                    .ok_or_else(|| anyhow!("tokenOfOwnerByIndex({}) reverted on {:?}", index, collection))?;
+                holdings.push(NftHolding { token_id: into_uint(token_id)?, amount: U256::one(), token_uri: None });
+            }
+        }
+        Ok((holdings, balance > U256::from(count)))
+    }
+
+    /// 转入 owner 的 ERC721 tokenId（Transfer 的第三个 indexed 参数），以及日志超过 `limit` 时下次的起始块
+    async fn received_erc721(
+        &self,
+        collection: Address,
+        owner: Address,
+        from_block: u64,
+        to_block: U64,
+        limit: usize,
+    ) -> Result<(BTreeSet<U256>, Option<u64>)> {
+        let filter = Filter::new()
+            .address(collection)
+            .event("Transfer(address,address,uint256)")
+            .topic2(H256::from(owner));
+        let scan = scan_logs(&self.provider, filter, from_block, to_block.as_u64(), Some(limit)).await?;
+        let ids = scan
+            .logs
+            .iter()
+            .filter_map(|log| log.topics.get(3))
+            .map(|topic| U256::from_big_endian(topic.as_bytes()))
+            .collect();
+        Ok((ids, scan.next_block))
+    }
+
+    /// 转入 owner 的 ERC1155 id（TransferSingle / TransferBatch），以及日志超过 `limit` 时下次的起始块
+    async fn received_erc1155(
+        &self,
+        collection: Address,
+        owner: Address,
+        from_block: u64,
+        to_block: U64,
+        limit: usize,
+    ) -> Result<(BTreeSet<U256>, Option<u64>)> {
+        let single = ethers::utils::keccak256("TransferSingle(address,address,address,uint256,uint256)");
+        let batch = ethers::utils::keccak256("TransferBatch(address,address,address,uint256[],uint256[])");
+        let filter = Filter::new()
+            .address(collection)
+            .topic0(vec![H256::from(single), H256::from(batch)])
+            .topic3(H256::from(owner));
+        let scan = scan_logs(&self.provider, filter, from_block, to_block.as_u64(), Some(limit)).await?;
+
+        let mut ids = BTreeSet::new();
+        for log in scan.logs {
+            if log.topics.first() == Some(&H256::from(single)) {
+                if let [Token::Uint(id), _] =
+                    abi::decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data)?.as_slice()
+                {
+                    ids.insert(*id);
+                }
+            } else {
+                let array = ParamType::Array(Box::new(ParamType::Uint(256)));
+                if let [Token::Array(batch_ids), _] = abi::decode(&[array.clone(), array], &log.data)?.as_slice() {
+                    ids.extend(batch_ids.iter().filter_map(|t| t.clone().into_uint()));
+                }
+            }
+        }
+        Ok((ids, scan.next_block))
+    }
+
+    async fn balance_of_batch_at(
+        &self,
+        collection: Address,
+        owners: &[Address],
+        ids: &[U256],
+        block: U64,
+    ) -> Result<Vec<U256>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let out = self
+            .nft_call(
+                collection,
+                NFT_ABI.function("balanceOfBatch")?,
+                &[
+                    Token::Array(owners.iter().map(|o| Token::Address(*o)).collect()),
+                    Token::Array(ids.iter().map(|id| Token::Uint(*id)).collect()),
+                ],
+                block,
+            )
+            .await?;
+        match out {
+            Token::Array(values) => values.into_iter().map(into_uint).collect(),
+            other => bail!("unexpected balanceOfBatch output {:?}", other),
+        }
+    }
+
+    async fn supports_interface(&self, contract: Address, interface: [u8; 4]) -> Result<bool> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        let f = NFT_ABI.function("supportsInterface")?;
+        // 没实现 ERC165 的合约会 revert，按不支持处理
+        Ok(matches!(
+            self.try_nft_call(contract, f, &[Token::FixedBytes(interface.to_vec())], block).await?,
+            Some(Token::Bool(true))
+        ))
+    }
+
+    /// 对每个 tokenId 调一次单参数的 `f`，经 Multicall 打包；revert 或解不开的为 None
+    async fn batch_nft_calls(
+        &self,
+        to: Address,
+        f: &abi::Function,
+        token_ids: &[U256],
+        block: U64,
+    ) -> Result<Vec<Option<Token>>> {
+        let calls = token_ids
+            .iter()
+            .map(|id| Ok((to, f.encode_input(&[Token::Uint(*id)])?.into())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(batch_view_calls(&self.provider, &calls, block)
+            .await?
+            .into_iter()
+            .map(|out| out.and_then(|data| f.decode_output(&data).ok()).and_then(|mut out| out.pop()))
+            .collect())
+    }
+
+    /// 必须成功的调用
+    async fn nft_call(&self, to: Address, f: &abi::Function, args: &[Token], block: U64) -> Result<Token> {
+        self.try_nft_call(to, f, args, block)
+            .await?
+            .ok_or_else(|| anyhow!("{}() reverted on {:?}", f.name, to))
+    }
+
+    /// revert 或返回值解不开时为 None
+    async fn try_nft_call(
+        &self,
+        to: Address,
+        f: &abi::Function,
+        args: &[Token],
+        block: U64,
+    ) -> Result<Option<Token>> {
+        let tx: TypedTransaction = TransactionRequest::new().to(to).data(f.encode_input(args)?).into();
+        let Some(data) = view_call(&self.provider, &tx, Some(block)).await? else {
+            return Ok(None);
+        };
+        Ok(f.decode_output(&data).ok().and_then(|mut out| out.pop()))
+    }
+}
+
+/// ERC721 tokenURI / ERC1155 uri
+fn uri_function(standard: NftStandard) -> Result<&'static abi::Function> {
+    Ok(match standard {
+        NftStandard::Erc721 => NFT_ABI.function("tokenURI")?,
+        NftStandard::Erc1155 => NFT_ABI.function("uri")?,
+    })
+}
+
+/// 空 URI 当作没有；ERC1155 约定 {id} 替换成 64 位小写十六进制
+fn expand_uri(standard: NftStandard, uri: Option<Token>, token_id: U256) -> Option<String> {
+    let uri = match uri {
+        Some(Token::String(uri)) if !uri.is_empty() => uri,
+        _ => return None,
+    };
+    Some(match standard {
+        NftStandard::Erc1155 => uri.replace("{id}", &format!("{:064x}", token_id)),
+        NftStandard::Erc721 => uri,
+    })
+}
+
+fn into_uint(token: Token) -> Result<U256> {
+    token
+        .into_uint()
+        .ok_or_else(|| anyhow!("expected uint256 return value"))
+}
@@ -0,0 +1,631 @@
+// src/pool.rs
+//! Uniswap 池子：V2 通过 factory 找 pair，读储备、LP 总量，算现价和价格冲击；
+//! V3 读 slot0 / 流动性，沿已初始化的 tick 算把价格推动 ±1% / ±2% / ±5% 需要的数量
+use anyhow::{anyhow, Result};
+use ethers::abi::AbiDecode;
+use ethers::prelude::*;
+use ethers::utils::format_units;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::cache::RpcCache;
+use crate::config::AppConfig;
+use crate::registry::TokenRegistry;
+use crate::rpc::{batch_view_calls, metered, RpcProvider};
+
+abigen!(
+    UniswapV2Factory,
+    r#"[
+        function getPair(address tokenA, address tokenB) external view returns (address pair)
+    ]"#
+);
+
+abigen!(
+    UniswapV2Pair,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+        function totalSupply() external view returns (uint256)
+    ]"#
+);
+
+abigen!(
+    UniswapV3Factory,
+    r#"[
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool)
+    ]"#
+);
+
+abigen!(
+    UniswapV3Pool,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function fee() external view returns (uint24)
+        function tickSpacing() external view returns (int24)
+        function liquidity() external view returns (uint128)
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked)
+        function tickBitmap(int16 wordPosition) external view returns (uint256)
+        function observe(uint32[] secondsAgos) external view returns (int56[] tickCumulatives, uint160[] secondsPerLiquidityCumulativeX128s)
+        function ticks(int24 tick) external view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
+    ]"#
+);
+
+/// 不指定费率时依次尝试的 V3 费率（百万分之一），选流动性最大的池子
+pub const V3_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10_000];
+
+/// 深度表的价格变化档位（基点）：负数是 token0 价格下跌（卖 token0），正数是上涨（买 token0）
+pub const DEPTH_LEVELS_BPS: [i64; 6] = [-500, -200, -100, 100, 200, 500];
+
+/// 每侧最多读取的已初始化 tick 数；再远的档位标记为算不出
+const MAX_TICKS_PER_SIDE: usize = 100;
+
+/// 没有单独配置时的 TWAP 窗口
+pub const DEFAULT_TWAP_WINDOW_SECS: u32 = 1800;
+
+/// TWAP 价格保留的有效数字：一个 tick 是 0.01%，再多没有意义
+const TWAP_SIGNIFICANT_DIGITS: u32 = 8;
+
+/// 最小单位的整数 → 按 decimals 缩放的 Decimal；超出 Decimal 精度时为 None
+fn to_units(amount: U256, decimals: u32) -> Option<Decimal> {
+    Decimal::from_str(&format_units(amount, decimals).ok()?).ok()
+}
+
+/// 某个块上的 V2 pair
+#[derive(Clone, Debug, PartialEq)]
+pub struct V2Pool {
+    pub pair: Address,
+    pub token0: Address,
+    pub token1: Address,
+    pub reserve0: U256,
+    pub reserve1: U256,
+    pub decimals0: u32,
+    pub decimals1: u32,
+    /// LP 代币总量（18 位小数）
+    pub total_supply: U256,
+    /// 储备最后更新的区块时间戳（mod 2^32）
+    pub block_timestamp_last: u32,
+}
+
+impl V2Pool {
+    /// `token` 是不是池子里的 token0；不在池子里时为 None
+    fn is_token0(&self, token: Address) -> Option<bool> {
+        if token == self.token0 {
+            Some(true)
+        } else if token == self.token1 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// `token` 的储备（最小单位）和 decimals
+    fn side(&self, token0: bool) -> (U256, u32) {
+        if token0 {
+            (self.reserve0, self.decimals0)
+        } else {
+            (self.reserve1, self.decimals1)
+        }
+    }
+
+    /// `token` 的储备，按代币单位
+    pub fn reserve(&self, token: Address) -> Option<Decimal> {
+        let (reserve, decimals) = self.side(self.is_token0(token)?);
+        to_units(reserve, decimals)
+    }
+
+    /// 1 个 `base` 值多少个另一边的代币（按储备比例的现价，不含手续费和冲击）；池子空时为 None
+    pub fn spot_price(&self, base: Address) -> Option<Decimal> {
+        let base0 = self.is_token0(base)?;
+        let (base_reserve, base_decimals) = self.side(base0);
+        let (quote_reserve, quote_decimals) = self.side(!base0);
+        let base_units = to_units(base_reserve, base_decimals)?;
+        let quote_units = to_units(quote_reserve, quote_decimals)?;
+        quote_units.checked_div(base_units)
+    }
+
+    /// 用 `amount_in` 个 `token_in` 换到 `amount_out`（都按代币单位）时，比按现价少拿的部分，基点；含 0.3% 手续费
+    pub fn price_impact_bps(&self, token_in: Address, amount_in: Decimal, amount_out: Decimal) -> Option<u64> {
+        let spot_out = amount_in.checked_mul(self.spot_price(token_in)?)?;
+        if spot_out.is_zero() {
+            return None;
+        }
+        let shortfall = (spot_out - amount_out).max(Decimal::ZERO);
+        let bps = (shortfall / spot_out * Decimal::from(10_000)).round();
+        u64::try_from(bps).ok()
+    }
+}
+
+/// Uniswap V2 / V3 池子查询；provider 默认是计数的 HTTP provider，测试里可以换成任意 `Middleware`
+pub struct PoolModule<M = RpcProvider> {
+    pub provider: Arc<M>,
+    pub config: AppConfig,
+    pub cache: Arc<RpcCache>,
+    pub registry: Arc<TokenRegistry>,
+}
+
+impl PoolModule {
+    pub fn new(provider: Provider<Http>, config: AppConfig) -> Self {
+        Self::shared(Arc::new(metered(provider)), config)
+    }
+}
+
+impl<M: Middleware + 'static> PoolModule<M> {
+    /// 复用已经包好的 provider
+    pub fn shared(provider: Arc<M>, config: AppConfig) -> Self {
+        Self {
+            provider,
+            config,
+            cache: Arc::new(RpcCache::default()),
+            registry: Arc::new(TokenRegistry::default()),
+        }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 和 SwapModule / PriceModule 共用同一个注册表
+    pub fn with_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// V2 factory：配置了就用配置的，否则问 router 的 `factory()`（永久缓存）
+    pub async fn factory(&self) -> Result<Address> {
+        abigen!(
+            UniswapV2RouterFactory,
+            r#"[
+                function factory() external view returns (address)
+            ]"#
+        );
+
+        if let Some(factory) = self.config.uniswap_v2_factory {
+            return Ok(factory);
+        }
+        let router = self.config.uniswap_v2_router;
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        self.cache
+            .immutable(chain, format!("v2.factory:{:?}", router), || async {
+                let contract = UniswapV2RouterFactory::new(router, self.provider.clone());
+                Ok(contract.factory().call().await?)
+            })
+            .await
+    }
+
+    /// ERC20 decimals，永久缓存（和 SwapModule 共用缓存键）
+    async fn decimals(&self, token: Address) -> Result<u32> {
+        abigen!(
+            ERC20,
+            r#"[
+                function decimals() view returns (uint8)
+            ]"#
+        );
+
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let decimals: u8 = self
+            .cache
+            .immutable(chain, format!("erc20.decimals:{:?}", token), || async {
+                Ok(ERC20::new(token, self.provider.clone()).decimals().call().await?)
+            })
+            .await?;
+        Ok(decimals as u32)
+    }
+
+    /// `token_a` / `token_b` 的 V2 pair 在 `block` 上的状态；还没有 pair 时为 None
+    pub async fn pair_at(&self, token_a: Address, token_b: Address, block: U64) -> Result<Option<V2Pool>> {
+        if token_a == token_b {
+            return Err(anyhow!("A pair needs two different tokens"));
+        }
+        let factory = UniswapV2Factory::new(self.factory().await?, self.provider.clone());
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        // pair 可能在之后的块上才创建，按块缓存
+        let (first, second) = if token_a < token_b { (token_a, token_b) } else { (token_b, token_a) };
+        let pair: Address = self
+            .cache
+            .at_block(chain, format!("v2.getPair:{:?}:{:?}", first, second), block, || async {
+                Ok(factory.get_pair(first, second).block(block).call().await?)
+            })
+            .await?;
+        if pair.is_zero() {
+            return Ok(None);
+        }
+
+        let contract = UniswapV2Pair::new(pair, self.provider.clone());
+        let (token0, token1): (Address, Address) = self
+            .cache
+            .immutable(chain, format!("v2.tokens:{:?}", pair), || async {
+                Ok((contract.token_0().call().await?, contract.token_1().call().await?))
+            })
+            .await?;
+        let (reserve0, reserve1, block_timestamp_last) = contract.get_reserves().block(block).call().await?;
+        let total_supply = contract.total_supply().block(block).call().await?;
+
+        Ok(Some(V2Pool {
+            pair,
+            token0,
+            token1,
+            reserve0: reserve0.into(),
+            reserve1: reserve1.into(),
+            decimals0: self.decimals(token0).await?,
+            decimals1: self.decimals(token1).await?,
+            total_supply,
+            block_timestamp_last,
+        }))
+    }
+
+    /// `token_a` / `token_b` 的 V3 池子地址和活跃流动性；`fee` 为 None 时选流动性最大的费率
+    async fn v3_pool_address(
+        &self,
+        token_a: Address,
+        token_b: Address,
+        fee: Option<u32>,
+        block: U64,
+    ) -> Result<Option<(Address, u128)>> {
+        let factory = self
+            .config
+            .uniswap_v3_factory
+            .ok_or_else(|| anyhow!("UNISWAP_V3_FACTORY is not set"))?;
+        let factory = UniswapV3Factory::new(factory, self.provider.clone());
+
+        let fees = match fee {
+            Some(fee) => vec![fee],
+            None => V3_FEE_TIERS.to_vec(),
+        };
+        let mut best: Option<(Address, u128)> = None;
+        for fee in fees {
+            let pool = factory.get_pool(token_a, token_b, fee).block(block).call().await?;
+            if pool.is_zero() {
+                continue;
+            }
+            let liquidity = UniswapV3Pool::new(pool, self.provider.clone()).liquidity().block(block).call().await?;
+            if best.is_none_or(|(_, l)| liquidity > l) {
+                best = Some((pool, liquidity));
+            }
+        }
+        Ok(best)
+    }
+
+    /// `base` 对 `quote` 在 `block` 之前 `window_secs` 秒内的 TWAP，用流动性最大的 V3 池子的 `observe()`；
+    /// 没有池子时为 None，池子的预言机历史不够长时报错
+    pub async fn twap_at(&self, base: Address, quote: Address, window_secs: u32, block: U64) -> Result<Option<Twap>> {
+        if base == quote {
+            return Err(anyhow!("A pool needs two different tokens"));
+        }
+        if window_secs == 0 {
+            return Err(anyhow!("The TWAP window must be at least one second"));
+        }
+        let Some((address, _)) = self.v3_pool_address(base, quote, None, block).await? else {
+            return Ok(None);
+        };
+
+        let pool = UniswapV3Pool::new(address, self.provider.clone());
+        let (cumulatives, _) = pool
+            .observe(vec![window_secs, 0])
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| anyhow!("Pool {:?} cannot observe a {}s window: {}", address, window_secs, e))?;
+        let [start, end] = cumulatives[..] else {
+            return Err(anyhow!("Pool {:?} returned {} observations", address, cumulatives.len()));
+        };
+        // 和 OracleLibrary.consult 一样向负无穷取整
+        let delta = end - start;
+        let window = window_secs as i64;
+        let mut mean_tick = delta / window;
+        if delta < 0 && delta % window != 0 {
+            mean_tick -= 1;
+        }
+        let mean_tick = mean_tick as i32;
+
+        // V3 的 token0 是地址小的那个；1.0001^tick 是 token1 / token0 的最小单位之比
+        let (base_decimals, quote_decimals) = (self.decimals(base).await?, self.decimals(quote).await?);
+        let raw = 1.0001f64.powi(mean_tick);
+        let raw = if base < quote { raw } else { 1.0 / raw };
+        let price = raw * 10f64.powi(base_decimals as i32 - quote_decimals as i32);
+        let price = Decimal::try_from(price)
+            .ok()
+            .and_then(|p| p.round_sf(TWAP_SIGNIFICANT_DIGITS))
+            .ok_or_else(|| anyhow!("TWAP of pool {:?} is out of range (tick {})", address, mean_tick))?;
+
+        Ok(Some(Twap { pool: address, base, quote, window_secs, mean_tick, price: price.normalize() }))
+    }
+
+    /// `token` 的 USD 价格：V2 pair 的储备比例，对 USDC（当作 1 USD），没有 USDC pair 时经 WETH 换算
+    pub async fn usd_spot_at(&self, token: Address, block: U64) -> Result<Decimal> {
+        let usdc = self.config.token_address("USDC").ok_or_else(|| anyhow!("USDC is not configured"))?;
+        let weth = self.config.token_address("WETH").ok_or_else(|| anyhow!("WETH is not configured"))?;
+        if token == usdc {
+            return Ok(Decimal::ONE);
+        }
+        if let Some(price) = self.spot_at(token, usdc, block).await? {
+            return Ok(price);
+        }
+        if token != weth {
+            if let Some(to_weth) = self.spot_at(token, weth, block).await? {
+                let to_usdc = self
+                    .spot_at(weth, usdc, block)
+                    .await?
+                    .ok_or_else(|| anyhow!("No Uniswap V2 liquidity for WETH/USDC"))?;
+                return Ok(to_weth * to_usdc);
+            }
+        }
+        Err(anyhow!("No Uniswap V2 pair prices {:?} against USDC or WETH", token))
+    }
+
+    /// 1 个 `base` 在 V2 pair 里值多少个 `quote`；没有 pair 或池子空时为 None
+    async fn spot_at(&self, base: Address, quote: Address, block: U64) -> Result<Option<Decimal>> {
+        Ok(self.pair_at(base, quote, block).await?.and_then(|pool| pool.spot_price(base)))
+    }
+
+    /// `token` 的 USD 价格：V3 TWAP，对 USDC（当作 1 USD），没有 USDC 池子时经 WETH 换算
+    ///
+    /// WETH / USDC 按配置里的地址；返回用到的每一段。
+    pub async fn usd_twap_at(&self, token: Address, window_secs: u32, block: U64) -> Result<Vec<Twap>> {
+        let usdc = self.config.token_address("USDC").ok_or_else(|| anyhow!("USDC is not configured"))?;
+        let weth = self.config.token_address("WETH").ok_or_else(|| anyhow!("WETH is not configured"))?;
+        if token == usdc {
+            return Ok(Vec::new());
+        }
+        if let Some(direct) = self.twap_at(token, usdc, window_secs, block).await? {
+            return Ok(vec![direct]);
+        }
+        if token != weth {
+            if let Some(to_weth) = self.twap_at(token, weth, window_secs, block).await? {
+                let to_usdc = self
+                    .twap_at(weth, usdc, window_secs, block)
+                    .await?
+                    .ok_or_else(|| anyhow!("No Uniswap V3 pool for WETH/USDC"))?;
+                return Ok(vec![to_weth, to_usdc]);
+            }
+        }
+        Err(anyhow!("No Uniswap V3 pool prices {:?} against USDC or WETH", token))
+    }
+
+    /// `token_a` / `token_b` 的 V3 池子在 `block` 上的状态，带 ±5% 价格范围内的已初始化 tick；
+    /// `fee` 为 None 时在常见费率里选活跃流动性最大的池子。没有池子时为 None
+    pub async fn v3_pool_at(
+        &self,
+        token_a: Address,
+        token_b: Address,
+        fee: Option<u32>,
+        block: U64,
+    ) -> Result<Option<V3Pool>> {
+        if token_a == token_b {
+            return Err(anyhow!("A pool needs two different tokens"));
+        }
+        let Some((address, liquidity)) = self.v3_pool_address(token_a, token_b, fee, block).await? else {
+            return Ok(None);
+        };
+
+        let pool = UniswapV3Pool::new(address, self.provider.clone());
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        // token0 / token1 / fee / tickSpacing 建池后不会变
+        let (token0, token1, fee, tick_spacing): (Address, Address, u32, i32) = self
+            .cache
+            .immutable(chain, format!("v3.immutables:{:?}", address), || async {
+                Ok((
+                    pool.token_0().call().await?,
+                    pool.token_1().call().await?,
+                    pool.fee().call().await?,
+                    pool.tick_spacing().call().await?,
+                ))
+            })
+            .await?;
+        let (sqrt_price_x96, tick, ..) = pool.slot_0().block(block).call().await?;
+
+        // ±5% 对应的 tick 范围，按 tickSpacing 向外对齐
+        let spacing = tick_spacing.max(1);
+        let lowest = DEPTH_LEVELS_BPS.iter().min().copied().unwrap_or_default();
+        let highest = DEPTH_LEVELS_BPS.iter().max().copied().unwrap_or_default();
+        let lower = (tick + tick_offset(lowest)).div_euclid(spacing) - 1;
+        let upper = (tick + tick_offset(highest)).div_euclid(spacing) + 1;
+
+        // tickBitmap 每个字覆盖 256 个压缩后的 tick；所有字一次 Multicall3 读完
+        let words: Vec<i32> = (lower.div_euclid(256)..=upper.div_euclid(256)).collect();
+        let calls: Vec<(Address, Bytes)> = words
+            .iter()
+            .map(|word| (address, pool.tick_bitmap(*word as i16).calldata().expect("abigen call has calldata")))
+            .collect();
+        let bitmaps = batch_view_calls(&self.provider, &calls, block).await?;
+        let mut initialized = Vec::new();
+        for (word, bitmap) in words.iter().zip(bitmaps) {
+            let bitmap = bitmap.ok_or_else(|| anyhow!("Cannot read tickBitmap({}) of V3 pool {:?}", word, address))?;
+            let TickBitmapReturn(bitmap) = TickBitmapReturn::decode(bitmap)?;
+            for bit in 0..256 {
+                let compressed = word * 256 + bit;
+                if bitmap.bit(bit as usize) && (lower..=upper).contains(&compressed) {
+                    initialized.push(compressed * spacing);
+                }
+            }
+        }
+
+        // 从当前价格往两边读，每边最多 MAX_TICKS_PER_SIDE 个；读不完的一侧缩小可算的范围
+        let mut scanned = (lower * spacing, upper * spacing);
+        let above: Vec<i32> = initialized.iter().copied().filter(|t| *t > tick).collect();
+        let mut below: Vec<i32> = initialized.iter().copied().filter(|t| *t <= tick).collect();
+        below.reverse();
+        if let Some(first_unread) = above.get(MAX_TICKS_PER_SIDE) {
+            scanned.1 = *first_unread;
+        }
+        if let Some(first_unread) = below.get(MAX_TICKS_PER_SIDE) {
+            scanned.0 = *first_unread;
+        }
+        let wanted: Vec<i32> = above
+            .iter()
+            .take(MAX_TICKS_PER_SIDE)
+            .chain(below.iter().take(MAX_TICKS_PER_SIDE))
+            .copied()
+            .collect();
+        let calls: Vec<(Address, Bytes)> = wanted
+            .iter()
+            .map(|t| (address, pool.ticks(*t).calldata().expect("abigen call has calldata")))
+            .collect();
+        let mut ticks = BTreeMap::new();
+        for (t, out) in wanted.iter().zip(batch_view_calls(&self.provider, &calls, block).await?) {
+            let out = out.ok_or_else(|| anyhow!("Cannot read ticks({}) of V3 pool {:?}", t, address))?;
+            ticks.insert(*t, TicksReturn::decode(out)?.liquidity_net);
+        }
+
+        Ok(Some(V3Pool {
+            pool: address,
+            token0,
+            token1,
+            decimals0: self.decimals(token0).await?,
+            decimals1: self.decimals(token1).await?,
+            fee,
+            tick_spacing,
+            sqrt_price_x96,
+            tick,
+            liquidity,
+            ticks,
+            scanned,
+        }))
+    }
+}
+
+/// tick 对应的 sqrt(价格)，价格是 token1 / token0 的最小单位之比
+fn tick_sqrt_price(tick: i32) -> f64 {
+    1.0001f64.powf(tick as f64 / 2.0)
+}
+
+/// 价格变化 `bps` 对应的 tick 偏移（向外取整）
+fn tick_offset(bps: i64) -> i32 {
+    let ticks = (1.0 + bps as f64 / 10_000.0).ln() / 1.0001f64.ln();
+    if bps >= 0 {
+        ticks.ceil() as i32
+    } else {
+        ticks.floor() as i32
+    }
+}
+
+/// 一个 V3 池子在时间窗口内的平均价格（按平均 tick 算，即几何平均）
+#[derive(Clone, Debug, PartialEq)]
+pub struct Twap {
+    pub pool: Address,
+    pub base: Address,
+    pub quote: Address,
+    pub window_secs: u32,
+    pub mean_tick: i32,
+    /// 1 个 base 值多少个 quote（代币单位）
+    pub price: Decimal,
+}
+
+/// 按顺序换算的几段 TWAP 相乘，即第一段 base 对最后一段 quote 的价格；没有段时为 1
+pub fn route_price(legs: &[Twap]) -> Decimal {
+    let price = legs.iter().fold(Decimal::ONE, |price, leg| price * leg.price);
+    price.round_sf(TWAP_SIGNIFICANT_DIGITS).unwrap_or(price).normalize()
+}
+
+/// 把价格推动一个档位的代价
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthLevel {
+    /// 相对当前价格的变化（基点），正数为 token0 涨价
+    pub price_change_bps: i64,
+    /// 目标价格：1 个 token0 值多少个 token1（代币单位）
+    pub price: f64,
+    pub token_in: Address,
+    /// 需要付出的数量（代币单位，含手续费）；超出已读取的 tick 范围时为 None
+    pub amount_in: Option<f64>,
+    pub token_out: Address,
+    /// 能换到的数量（代币单位）
+    pub amount_out: Option<f64>,
+}
+
+/// 某个块上的 V3 池子
+#[derive(Clone, Debug, PartialEq)]
+pub struct V3Pool {
+    pub pool: Address,
+    pub token0: Address,
+    pub token1: Address,
+    pub decimals0: u32,
+    pub decimals1: u32,
+    /// 费率，百万分之一（3000 = 0.3%）
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    /// 当前价格上的活跃流动性
+    pub liquidity: u128,
+    /// 读到的已初始化 tick → liquidityNet
+    pub ticks: BTreeMap<i32, i128>,
+    /// 这个 tick 区间内的已初始化 tick 都读到了，深度只在区间内计算
+    pub scanned: (i32, i32),
+}
+
+impl V3Pool {
+    /// 当前的 sqrt(价格)（最小单位之比）
+    fn sqrt_price(&self) -> f64 {
+        let x96 = self.sqrt_price_x96.to_string().parse::<f64>().unwrap_or_default();
+        x96 / 2f64.powi(96)
+    }
+
+    /// 最小单位之比 → 1 个 token0 值多少个 token1（代币单位）
+    fn unit_price(&self, sqrt_price: f64) -> f64 {
+        sqrt_price * sqrt_price * 10f64.powi(self.decimals0 as i32 - self.decimals1 as i32)
+    }
+
+    /// 1 个 token0 值多少个 token1
+    pub fn price(&self) -> f64 {
+        self.unit_price(self.sqrt_price())
+    }
+
+    /// 把价格推动 `bps` 基点：沿路跨过已初始化的 tick，逐段累计 L × Δ√P
+    pub fn depth(&self, bps: i64) -> DepthLevel {
+        let current = self.sqrt_price();
+        let target = current * (1.0 + bps as f64 / 10_000.0).sqrt();
+        let mut liquidity = self.liquidity as f64;
+        let mut at = current;
+        // 原始单位：向上时付 token1、得 token0；向下相反
+        let (mut paid, mut got) = (0.0, 0.0);
+        let reachable = if bps >= 0 {
+            for (&tick, &net) in self.ticks.range(self.tick + 1..) {
+                let next = tick_sqrt_price(tick);
+                if next >= target {
+                    break;
+                }
+                paid += liquidity * (next - at);
+                got += liquidity * (1.0 / at - 1.0 / next);
+                liquidity = (liquidity + net as f64).max(0.0);
+                at = next;
+            }
+            paid += liquidity * (target - at);
+            got += liquidity * (1.0 / at - 1.0 / target);
+            target <= tick_sqrt_price(self.scanned.1)
+        } else {
+            for (&tick, &net) in self.ticks.range(..=self.tick).rev() {
+                let next = tick_sqrt_price(tick);
+                if next <= target {
+                    break;
+                }
+                paid += liquidity * (1.0 / next - 1.0 / at);
+                got += liquidity * (at - next);
+                liquidity = (liquidity - net as f64).max(0.0);
+                at = next;
+            }
+            paid += liquidity * (1.0 / target - 1.0 / at);
+            got += liquidity * (at - target);
+            target >= tick_sqrt_price(self.scanned.0)
+        };
+
+        let ((token_in, decimals_in), (token_out, decimals_out)) = if bps >= 0 {
+            ((self.token1, self.decimals1), (self.token0, self.decimals0))
+        } else {
+            ((self.token0, self.decimals0), (self.token1, self.decimals1))
+        };
+        let gross = paid / (1.0 - self.fee as f64 / 1_000_000.0);
+        DepthLevel {
+            price_change_bps: bps,
+            price: self.unit_price(target),
+            token_in,
+            amount_in: reachable.then(|| gross / 10f64.powi(decimals_in as i32)),
+            token_out,
+            amount_out: reachable.then(|| got / 10f64.powi(decimals_out as i32)),
+        }
+    }
+}
@@ -3,25 +3,220 @@ use anyhow::{anyhow, Result};
 use ethers::abi::Abi;
 use ethers::prelude::*;
 use rust_decimal::Decimal;
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tracing::debug;
 
+use crate::cache::RpcCache;
 use crate::config::AppConfig;
+use crate::registry::TokenRegistry;
+use crate::rpc::{metered, RpcProvider};
 
 const AGGREGATOR_ABI_JSON: &[u8] = include_bytes!("../abis/aggregatorv3_abi.json");
 
-pub struct PriceModule {
-    pub provider: Arc<Provider<Http>>,
+/// Chainlink AggregatorV3 ABI 只解析一次
+static AGGREGATOR_ABI: LazyLock<Abi> =
+    LazyLock::new(|| serde_json::from_slice(AGGREGATOR_ABI_JSON).expect("valid aggregator ABI"));
+
+/// 多来源价格里偏离中位数超过这个值（基点）的来源会被标出来
+pub const DEFAULT_MAX_DEVIATION_BPS: u64 = 200;
+
+/// HTTP 价格接口的超时
+const PRICE_API_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 配置里指向 Chainlink feed（而不是代币）的键
+const FEED_KEYS: &[&str] = &["ETH", "BTC"];
+
+/// 单个来源给出的 USD 价格
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourcePrice {
+    /// "chainlink" / "v2_spot" / "v3_twap" / "api"
+    pub source: &'static str,
+    pub price: Option<Decimal>,
+    /// 这个来源拿不到价格的原因
+    pub error: Option<String>,
+}
+
+impl SourcePrice {
+    pub fn new(source: &'static str, result: Result<Decimal>) -> Self {
+        match result {
+            Ok(price) => Self { source, price: Some(price), error: None },
+            Err(e) => Self { source, price: None, error: Some(e.to_string()) },
+        }
+    }
+}
+
+/// 中位数；偶数个时取中间两个的平均
+pub fn median(mut values: Vec<Decimal>) -> Option<Decimal> {
+    values.sort();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(values[mid]),
+        _ => Some((values[mid - 1] + values[mid]) / Decimal::TWO),
+    }
+}
+
+/// 多个来源的价格和它们的中位数
+#[derive(Clone, Debug, PartialEq)]
+pub struct AggregatedPrice {
+    /// 所有拿到价格的来源的中位数；一个都没有时为 None
+    pub median: Option<Decimal>,
+    pub sources: Vec<SourcePrice>,
+    pub max_deviation_bps: u64,
+}
+
+impl AggregatedPrice {
+    pub fn new(sources: Vec<SourcePrice>, max_deviation_bps: u64) -> Self {
+        let median = median(sources.iter().filter_map(|s| s.price).collect());
+        Self { median, sources, max_deviation_bps }
+    }
+
+    /// `price` 偏离中位数多少基点
+    pub fn deviation_bps(&self, price: Decimal) -> Option<u64> {
+        let median = self.median.filter(|m| !m.is_zero())?;
+        let bps = ((price - median).abs() / median * Decimal::from(10_000)).round();
+        u64::try_from(bps).ok()
+    }
+
+    /// 偏离中位数超过阈值的来源
+    pub fn outliers(&self) -> Vec<&SourcePrice> {
+        self.sources
+            .iter()
+            .filter(|s| s.price.and_then(|p| self.deviation_bps(p)).is_some_and(|bps| bps > self.max_deviation_bps))
+            .collect()
+    }
+
+    /// 给人看的偏离提示，`token` 是提示里的代币名
+    pub fn warnings(&self, token: &str) -> Vec<String> {
+        let (Some(median), outliers) = (self.median, self.outliers()) else {
+            return Vec::new();
+        };
+        outliers
+            .into_iter()
+            .filter_map(|s| {
+                let price = s.price?;
+                let bps = self.deviation_bps(price)?;
+                Some(format!(
+                    "Price sources disagree on {}: {} says {} USD, {}.{:02}% off the median {} USD",
+                    token,
+                    s.source,
+                    price.normalize(),
+                    bps / 100,
+                    bps % 100,
+                    median.normalize()
+                ))
+            })
+            .collect()
+    }
+}
+
+/// 计价单位的规范写法：大写，WETH 当作 ETH
+pub fn currency(symbol: &str) -> String {
+    let symbol = symbol.trim().to_uppercase();
+    if symbol == "WETH" {
+        "ETH".to_string()
+    } else {
+        symbol
+    }
+}
+
+/// feed 的 `description()`，如 "UNI / ETH" → ("UNI", "ETH")
+fn parse_pair(description: &str) -> Option<(String, String)> {
+    let (base, quote) = description.split_once('/')?;
+    let (base, quote) = (currency(base), currency(quote));
+    (!base.is_empty() && !quote.is_empty()).then_some((base, quote))
+}
+
+/// 换算路径上的一段：1 个 `from` 值 `rate` 个 `to`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateHop {
+    pub feed: Address,
+    pub from: String,
+    pub to: String,
+    /// 逆着 feed 的方向走（rate = 1 / answer）
+    pub inverted: bool,
+    pub rate: Decimal,
+    /// feed 最近一次更新的时间戳
+    pub updated_at: u64,
+}
+
+/// 沿 feed 换算出的价格：1 个 `base` 值 `price` 个 `quote`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossRate {
+    pub base: String,
+    pub quote: String,
+    pub price: Decimal,
+    /// base 和 quote 相同时为空
+    pub path: Vec<RateHop>,
+    /// 路径上最旧的一次 feed 更新；没有路径时为块时间
+    pub updated_at: u64,
+    /// 块时间减去 `updated_at`
+    pub staleness_secs: u64,
+}
+
+/// 按点分隔的路径取 JSON 里的数字（数字或数字字符串），数组用下标
+fn json_number(value: &serde_json::Value, field: &str) -> Option<Decimal> {
+    let mut value = value;
+    for key in field.split('.').filter(|k| !k.is_empty()) {
+        value = match key.parse::<usize>() {
+            Ok(index) if value.is_array() => value.get(index)?,
+            _ => value.get(key)?,
+        };
+    }
+    match value {
+        serde_json::Value::Number(n) => Decimal::from_str(&n.to_string())
+            .or_else(|_| Decimal::from_scientific(&n.to_string()))
+            .ok(),
+        serde_json::Value::String(s) => Decimal::from_str(s).ok(),
+        _ => None,
+    }
+}
+
+/// Chainlink 价格（外加可选的 HTTP 价格接口）；provider 默认是计数的 HTTP provider，测试里可以换成任意 `Middleware`
+pub struct PriceModule<M = RpcProvider> {
+    pub provider: Arc<M>,
     pub config: AppConfig,
+    pub cache: Arc<RpcCache>,
+    pub registry: Arc<TokenRegistry>,
+    http: reqwest::Client,
 }
 
 impl PriceModule {
     pub fn new(provider: Provider<Http>, config: AppConfig) -> Self {
+        Self::shared(Arc::new(metered(provider)), config)
+    }
+}
+
+impl<M: Middleware + 'static> PriceModule<M> {
+    /// 复用已经包好的 provider
+    pub fn shared(provider: Arc<M>, config: AppConfig) -> Self {
         Self {
-            provider: Arc::new(provider),
+            provider,
             config,
+            cache: Arc::new(RpcCache::default()),
+            registry: Arc::new(TokenRegistry::default()),
+            http: reqwest::Client::builder()
+                .timeout(PRICE_API_TIMEOUT)
+                .build()
+                .expect("HTTP client"),
         }
     }
 
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 和 TokenModule / SwapModule 共用同一个注册表
+    pub fn with_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
     // ----------------------------------------
     // Public API
     // ----------------------------------------
@@ -36,8 +231,40 @@ impl PriceModule {
         let key = token.unwrap_or("ETH");
 
         let feed_address = self.resolve_feed_address(key)?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+
+        self.fetch_price(feed_address, block).await
+    }
+
+    /// 同 `get_price`，读取 `block` 上的 feed 数据
+    pub async fn get_price_at(&self, token: Option<&str>, block: U64) -> Result<Decimal> {
+        let feed_address = self.resolve_feed_address(token.unwrap_or("ETH"))?;
+        self.fetch_price(feed_address, block).await
+    }
+
+    /// 同 `get_price`，换算成 USD
+    pub async fn usd_price(&self, token: Option<&str>) -> Result<Decimal> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.usd_price_at(token, block).await
+    }
 
-        self.fetch_price(feed_address).await
+    /// 同 `get_price_at`，换算成 USD：代币的 feed 不按 USD 计价时（如 UNI / ETH）再乘上计价单位到 USD 的汇率
+    ///
+    /// 默认的 ETH feed 就是 ETH / USD，不再读 `description()`。
+    pub async fn usd_price_at(&self, token: Option<&str>, block: U64) -> Result<Decimal> {
+        let Some(token) = token else {
+            return self.get_price_at(None, block).await;
+        };
+        let feed = self.resolve_feed_address(token)?;
+        let (_, quote) = self.feed_pair(feed).await?;
+        let price = self.fetch_price(feed, block).await?;
+        if quote == "USD" {
+            return Ok(price);
+        }
+        let rate = self.cross_rate_at(&quote, "USD", block).await?;
+        price
+            .checked_mul(rate.price)
+            .ok_or_else(|| anyhow!("USD price of feed {:?} overflows", feed))
     }
 
     pub async fn eth_price(&self) -> Result<Decimal> {
@@ -48,6 +275,113 @@ impl PriceModule {
         self.get_price(Some(symbol)).await
     }
 
+    /// 配置的 HTTP 价格接口给出的 USD 价格；接口不看区块，拿到的是它当前的报价
+    pub async fn api_price(&self, token: Address, symbol: Option<&str>) -> Result<Decimal> {
+        let api = self.config.price_api.as_ref().ok_or_else(|| anyhow!("No price API configured"))?;
+        let fill = |template: &str| {
+            template
+                .replace("{address}", &format!("{:?}", token))
+                .replace("{symbol}", symbol.unwrap_or_default())
+        };
+        if (api.url.contains("{symbol}") || api.field.contains("{symbol}")) && symbol.is_none() {
+            return Err(anyhow!("The price API needs a symbol for {:?}", token));
+        }
+        let field = fill(&api.field);
+        let body: serde_json::Value = self.http.get(fill(&api.url)).send().await?.error_for_status()?.json().await?;
+        json_number(&body, &field).ok_or_else(|| anyhow!("No number at \"{}\" in the price API response", field))
+    }
+
+    /// feed 的计价对，如 ("UNI", "ETH")
+    pub async fn feed_pair(&self, feed: Address) -> Result<(String, String)> {
+        let description = self
+            .feed_description(feed)
+            .await?
+            .ok_or_else(|| anyhow!("{:?} has no description(), not a price feed", feed))?;
+        parse_pair(&description).ok_or_else(|| anyhow!("Cannot read a pair from feed description \"{}\"", description))
+    }
+
+    /// 同 `get_price_at`，附带 feed 的计价对和更新时间
+    pub async fn feed_rate_at(&self, token: Option<&str>, block: U64) -> Result<CrossRate> {
+        let feed = self.resolve_feed_address(token.unwrap_or("ETH"))?;
+        let (base, quote) = self.feed_pair(feed).await?;
+        let (price, updated_at) = self.fetch_round(feed, block).await?;
+        let timestamp = self.block_timestamp(block).await?;
+        let hop = RateHop { feed, from: base.clone(), to: quote.clone(), inverted: false, rate: price, updated_at };
+        Ok(CrossRate { base, quote, price, path: vec![hop], updated_at, staleness_secs: timestamp.saturating_sub(updated_at) })
+    }
+
+    /// 1 个 `base` 值多少个 `quote`：在配置和注册表的 feed 上找经过 feed 最少的换算路径（可以逆着 feed 走）
+    ///
+    /// 如 UNI → BTC 走 UNI/ETH × ETH/USD ÷ BTC/USD。
+    pub async fn cross_rate_at(&self, base: &str, quote: &str, block: U64) -> Result<CrossRate> {
+        let (base, quote) = (currency(base), currency(quote));
+        let timestamp = self.block_timestamp(block).await?;
+        if base == quote {
+            return Ok(CrossRate { base, quote, price: Decimal::ONE, path: Vec::new(), updated_at: timestamp, staleness_secs: 0 });
+        }
+
+        // 广度优先：第一次到达某个计价单位时记下从哪条 feed、哪个方向来的
+        let feeds = self.feed_graph().await;
+        let mut reached: HashMap<String, (usize, bool)> = HashMap::new();
+        let mut seen = HashSet::from([base.clone()]);
+        let mut queue = VecDeque::from([base.clone()]);
+        while let Some(node) = queue.pop_front() {
+            if node == quote {
+                break;
+            }
+            for (i, (_, from, to)) in feeds.iter().enumerate() {
+                let (next, inverted) = if *from == node {
+                    (to, false)
+                } else if *to == node {
+                    (from, true)
+                } else {
+                    continue;
+                };
+                if seen.insert(next.clone()) {
+                    reached.insert(next.clone(), (i, inverted));
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+        if !reached.contains_key(&quote) {
+            return Err(anyhow!("No price feed path from {} to {}", base, quote));
+        }
+
+        let mut edges = Vec::new();
+        let mut node = quote.clone();
+        while node != base {
+            let (i, inverted) = reached[&node];
+            edges.push((i, inverted));
+            let (_, from, to) = &feeds[i];
+            node = if inverted { to.clone() } else { from.clone() };
+        }
+        edges.reverse();
+
+        let mut path = Vec::with_capacity(edges.len());
+        let mut price = Decimal::ONE;
+        for (i, inverted) in edges {
+            let (feed, from, to) = feeds[i].clone();
+            let (answer, updated_at) = self.fetch_round(feed, block).await?;
+            let rate = if inverted {
+                Decimal::ONE.checked_div(answer).ok_or_else(|| anyhow!("Feed {:?} answered {}", feed, answer))?
+            } else {
+                answer
+            };
+            price = price.checked_mul(rate).ok_or_else(|| anyhow!("Cross rate {} → {} overflows", base, quote))?;
+            let (from, to) = if inverted { (to, from) } else { (from, to) };
+            path.push(RateHop { feed, from, to, inverted, rate, updated_at });
+        }
+        let updated_at = path.iter().map(|hop| hop.updated_at).min().unwrap_or(timestamp);
+        Ok(CrossRate {
+            base,
+            quote,
+            price: price.round_dp(18).normalize(),
+            path,
+            updated_at,
+            staleness_secs: timestamp.saturating_sub(updated_at),
+        })
+    }
+
     // ----------------------------------------
     // Internal
     // ----------------------------------------
@@ -64,26 +398,100 @@ impl PriceModule {
             return Ok(addr);
         }
 
+        // 3. 运行时注册的代币（get_token_info 注册时可以带 price feed）
+        if let Some(token) = self.registry.get(input) {
+            return token
+                .price_feed
+                .ok_or_else(|| anyhow!("No price feed registered for token {}", token.symbol));
+        }
+
         Err(anyhow!("Unknown token or feed address: {}", input))
     }
 
     /// 调用链上 price feed 获取价格
     /// 根据 feed 地址获取价格
-     async fn fetch_price(&self, feed_addr: Address) -> Result<Decimal> {
-        // 从 JSON 加载 ABI
-        let abi: Abi = serde_json::from_slice(include_bytes!("../abis/aggregatorv3_abi.json"))?;
-        let contract = Contract::new(feed_addr, abi, self.provider.clone());
+    async fn fetch_price(&self, feed_addr: Address, block: U64) -> Result<Decimal> {
+        Ok(self.fetch_round(feed_addr, block).await?.0)
+    }
+
+    /// feed 在 `block` 上的价格和更新时间戳
+    async fn fetch_round(&self, feed_addr: Address, block: U64) -> Result<(Decimal, u64)> {
+        let contract = Contract::new(feed_addr, AGGREGATOR_ABI.clone(), self.provider.clone());
+        let chain = self.cache.chain_id(&*self.provider).await?;
 
-        // 调用 latestRoundData() 获取最新价格
-        let (_round_id, answer, _started_at, _updated_at, _answered_in_round): (u128, i128, u64, u64, u128) =
-            contract.method("latestRoundData", ())?.call().await?;
+        // 调用 latestRoundData() 获取最新价格（同一块内复用）
+        let (_round_id, answer, _started_at, updated_at, _answered_in_round): (u128, i128, u64, u64, u128) = self
+            .cache
+            .at_block(chain, format!("feed.latestRoundData:{:?}", feed_addr), block, || async {
+                Ok(contract.method("latestRoundData", ())?.block(block).call().await?)
+            })
+            .await?;
 
-        // 动态获取 decimals
-        let decimals: u8 = contract.method("decimals", ())?.call().await?;
+        // 动态获取 decimals（不会变，永久缓存）
+        let decimals: u8 = self
+            .cache
+            .immutable(chain, format!("feed.decimals:{:?}", feed_addr), || async {
+                Ok(contract.method("decimals", ())?.call().await?)
+            })
+            .await?;
 
         // 转成 Decimal 并按 decimals 缩放
         let price = Decimal::from_i128_with_scale(answer, decimals.into());
 
-        Ok(price)
+        Ok((price, updated_at))
+    }
+
+    /// `block` 的时间戳
+    async fn block_timestamp(&self, block: U64) -> Result<u64> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        self.cache
+            .at_block(chain, "block.timestamp".to_string(), block, || async {
+                let header = self.provider.get_block(block).await?.ok_or_else(|| anyhow!("Block {} not found", block))?;
+                Ok(header.timestamp.as_u64())
+            })
+            .await
+    }
+
+    /// `description()` 的返回；不是 feed 时（调用被拒、返回的不是字符串）为 None
+    ///
+    /// 两种结果都不会变，永久缓存；连不上节点之类的错误不缓存。
+    async fn feed_description(&self, feed: Address) -> Result<Option<String>> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        self.cache
+            .immutable(chain, format!("feed.description:{:?}", feed), || async {
+                let contract = Contract::new(feed, AGGREGATOR_ABI.clone(), self.provider.clone());
+                match contract.method::<_, String>("description", ())?.call().await {
+                    Ok(description) => Ok(Some(description)),
+                    Err(ContractError::MiddlewareError { e })
+                        if ethers::providers::MiddlewareError::as_error_response(&e).is_none() =>
+                    {
+                        Err(e.into())
+                    }
+                    Err(ContractError::ProviderError { e })
+                        if ethers::providers::MiddlewareError::as_error_response(&e).is_none() =>
+                    {
+                        Err(e.into())
+                    }
+                    Err(_) => Ok(None),
+                }
+            })
+            .await
+    }
+
+    /// 配置里的 feed 键和注册表登记的 feed，按 `description()` 认出的 (feed, base, quote)；不是 feed 的地址跳过
+    async fn feed_graph(&self) -> Vec<(Address, String, String)> {
+        let candidates: BTreeSet<Address> = FEED_KEYS
+            .iter()
+            .filter_map(|key| self.config.token_address(key))
+            .chain(self.registry.entries().into_iter().filter_map(|t| t.price_feed))
+            .collect();
+        let mut feeds = Vec::new();
+        for feed in candidates {
+            match self.feed_pair(feed).await {
+                Ok((base, quote)) => feeds.push((feed, base, quote)),
+                Err(e) => debug!("{:?} is not a usable price feed: {}", feed, e),
+            }
+        }
+        feeds
     }
 }
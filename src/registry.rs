@@ -0,0 +1,105 @@
+// src/registry.rs
+use anyhow::{bail, Result};
+use ethers::types::Address;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// 运行时注册的代币
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenEntry {
+    pub symbol: String,
+    pub address: Address,
+    pub decimals: Option<u8>,
+    /// Chainlink price feed；没有则 get_price 无法直接报价
+    pub price_feed: Option<Address>,
+}
+
+/// 代币注册表：symbol → 代币，symbol 不区分大小写
+///
+/// 配置里的 token 地址优先；这里补充 agent 在运行时通过 get_token_info 注册的代币。
+/// 一个地址只对应一个 symbol，注册后不能改 price feed（注册表所有客户端共用）。
+#[derive(Debug, Default)]
+pub struct TokenRegistry {
+    tokens: RwLock<Tokens>,
+}
+
+#[derive(Debug, Default)]
+struct Tokens {
+    by_symbol: HashMap<String, TokenEntry>,
+    /// 地址 → symbol 的 key
+    by_address: HashMap<Address, String>,
+}
+
+impl TokenRegistry {
+    fn key(symbol: &str) -> String {
+        symbol.trim().to_uppercase()
+    }
+
+    /// 注册代币
+    ///
+    /// symbol 已指向别的地址、地址已用别的 symbol 注册、或要改已登记的 price feed 时报错；
+    /// 原样重复注册（不带 price feed 也算）没有影响。
+    pub fn register(&self, entry: TokenEntry) -> Result<()> {
+        if entry.symbol.trim().is_empty() {
+            bail!("Cannot register token {:?} without a symbol", entry.address);
+        }
+
+        let mut tokens = self.tokens.write().unwrap();
+        let key = Self::key(&entry.symbol);
+        if let Some(existing) = tokens.by_symbol.get(&key) {
+            if existing.address != entry.address {
+                bail!(
+                    "Symbol {} is already registered for {:?}",
+                    entry.symbol,
+                    existing.address
+                );
+            }
+            if entry.price_feed.is_some() && entry.price_feed != existing.price_feed {
+                bail!(
+                    "Token {} is already registered with price feed {:?}",
+                    entry.symbol,
+                    existing.price_feed
+                );
+            }
+            return Ok(());
+        }
+        if let Some(symbol) = tokens.by_address.get(&entry.address) {
+            bail!("Token {:?} is already registered as {}", entry.address, symbol);
+        }
+        tokens.by_address.insert(entry.address, key.clone());
+        tokens.by_symbol.insert(key, entry);
+        Ok(())
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<TokenEntry> {
+        self.tokens.read().unwrap().by_symbol.get(&Self::key(symbol)).cloned()
+    }
+
+    pub fn address(&self, symbol: &str) -> Option<Address> {
+        self.get(symbol).map(|t| t.address)
+    }
+
+    /// 按合约地址反查
+    pub fn by_address(&self, address: Address) -> Option<TokenEntry> {
+        let tokens = self.tokens.read().unwrap();
+        let key = tokens.by_address.get(&address)?;
+        tokens.by_symbol.get(key).cloned()
+    }
+
+    /// 所有注册的代币
+    pub fn entries(&self) -> Vec<TokenEntry> {
+        self.tokens.read().unwrap().by_symbol.values().cloned().collect()
+    }
+
+    pub fn price_feed(&self, symbol: &str) -> Option<Address> {
+        self.get(symbol).and_then(|t| t.price_feed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.read().unwrap().by_symbol.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,181 @@
+// src/risk.rs
+//! 代币风险检查的结果类型，以及从 ABI / 字节码里找 owner 能控制的函数（暂停、黑名单、增发、改税…）
+//!
+//! 买卖模拟本身在 `SwapModule::check_token_risk_at`。
+use ethers::abi::Abi;
+use ethers::types::{Address, Bytes, U256};
+use ethers::utils::id;
+use std::collections::HashSet;
+
+use crate::contract::{is_read_only, signature};
+use crate::simulate::StepReport;
+
+/// 常见的 owner 函数：(签名, 类别)；字节码里只能按 selector 认这些
+const KNOWN_OWNER_FUNCTIONS: &[(&str, &str)] = &[
+    ("pause()", "pause"),
+    ("unpause()", "pause"),
+    ("setPaused(bool)", "pause"),
+    ("blacklist(address)", "blacklist"),
+    ("addToBlacklist(address)", "blacklist"),
+    ("setBlacklist(address,bool)", "blacklist"),
+    ("blacklistAddress(address,bool)", "blacklist"),
+    ("addBots(address[])", "blacklist"),
+    ("setBots(address[])", "blacklist"),
+    ("mint(address,uint256)", "mint"),
+    ("mint(uint256)", "mint"),
+    ("setFee(uint256)", "fee"),
+    ("setFees(uint256,uint256)", "fee"),
+    ("setTaxFeePercent(uint256)", "fee"),
+    ("setBuyFee(uint256)", "fee"),
+    ("setSellFee(uint256)", "fee"),
+    ("updateFees(uint256,uint256)", "fee"),
+    ("setMaxTxAmount(uint256)", "limit"),
+    ("setMaxWalletSize(uint256)", "limit"),
+    ("enableTrading()", "trading"),
+    ("openTrading()", "trading"),
+    ("setTradingEnabled(bool)", "trading"),
+];
+
+/// 按函数名归类 ABI 里的函数（小写后匹配，按顺序取第一个）
+const NAME_PATTERNS: &[(&str, &str)] = &[
+    ("pause", "pause"),
+    ("blacklist", "blacklist"),
+    ("blocklist", "blacklist"),
+    ("bots", "blacklist"),
+    ("mint", "mint"),
+    ("fee", "fee"),
+    ("tax", "fee"),
+    ("maxtx", "limit"),
+    ("maxwallet", "limit"),
+    ("trading", "trading"),
+];
+
+/// 转账税超过这个值（基点）时报警
+const HIGH_TAX_BPS: u64 = 1000;
+
+/// 一个 owner 可能拿来控制持有人的函数
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnerFunction {
+    /// 如 "blacklist(address)"
+    pub signature: String,
+    /// "pause" / "blacklist" / "mint" / "fee" / "limit" / "trading"
+    pub category: &'static str,
+    /// "abi"（调用方提供的已验证 ABI）或 "bytecode"（按已知 selector 认出）
+    pub source: &'static str,
+}
+
+/// 字节码里所有 PUSH4 的立即数：Solidity / Vyper 的函数分发表就是一串 PUSH4 selector
+fn push4_immediates(code: &[u8]) -> HashSet<[u8; 4]> {
+    let mut found = HashSet::new();
+    let mut i = 0;
+    while i < code.len() {
+        let op = code[i];
+        // PUSH1..PUSH32 后面跟 n 字节数据，跳过去，免得把数据当指令
+        if (0x60..=0x7f).contains(&op) {
+            let n = (op - 0x5f) as usize;
+            if n == 4 && i + 5 <= code.len() {
+                found.insert([code[i + 1], code[i + 2], code[i + 3], code[i + 4]]);
+            }
+            i += n;
+        }
+        i += 1;
+    }
+    found
+}
+
+/// 找出 owner 函数：先看调用方给的 ABI（按函数名，只看会改状态的函数），再按已知 selector 扫字节码
+///
+/// 代理合约的字节码里只有代理本身的函数，实现合约的函数要靠 ABI。
+pub fn owner_functions(code: &[u8], abi: Option<&Abi>) -> Vec<OwnerFunction> {
+    let mut found: Vec<OwnerFunction> = Vec::new();
+    if let Some(abi) = abi {
+        for function in abi.functions().filter(|f| !is_read_only(f)) {
+            let name = function.name.to_lowercase();
+            if let Some((_, category)) = NAME_PATTERNS.iter().find(|(pattern, _)| name.contains(pattern)) {
+                found.push(OwnerFunction { signature: signature(function), category, source: "abi" });
+            }
+        }
+    }
+
+    let selectors = push4_immediates(code);
+    for (known, category) in KNOWN_OWNER_FUNCTIONS {
+        if selectors.contains(&id(known)) && !found.iter().any(|f| f.signature == *known) {
+            found.push(OwnerFunction { signature: known.to_string(), category, source: "bytecode" });
+        }
+    }
+    found
+}
+
+/// `check_token_risk_at` 的结果：小额买入、转账、卖出的模拟，加上 owner 函数
+#[derive(Clone, Debug)]
+pub struct TokenRisk {
+    pub token: Address,
+    /// 模拟用的临时地址（预置了买入所需的 ETH）
+    pub trader: Address,
+    /// 买入花的 ETH（wei）
+    pub amount_in: U256,
+    /// router 对买入的报价 / 实际到账的代币数量
+    pub buy_quote: U256,
+    pub bought: U256,
+    /// 对卖出全部 `bought` 的报价（买入后的状态上） / 实际收到的 ETH
+    pub sell_quote: U256,
+    pub sold_for: U256,
+    /// 买入 / 卖出的有效税率（基点），那一步失败时为 None
+    pub buy_tax_bps: Option<u64>,
+    pub sell_tax_bps: Option<u64>,
+    /// 各步失败的原因；None 表示成功（或没跑到）
+    pub buy_error: Option<String>,
+    pub transfer_error: Option<String>,
+    pub sell_error: Option<String>,
+    /// `owner()` 的返回值；读不到时为 None，零地址表示已放弃所有权
+    pub owner: Option<Address>,
+    pub owner_functions: Vec<OwnerFunction>,
+    /// 按顺序：买入报价、买入、转账、approve、卖出报价、卖出；买入失败时后面的不跑
+    pub steps: Vec<StepReport>,
+    /// 代币合约的字节码，空说明地址上没有合约
+    pub code: Bytes,
+}
+
+impl TokenRisk {
+    /// 买得进但卖不出（或卖出拿不回 ETH）
+    pub fn is_honeypot(&self) -> bool {
+        self.buy_error.is_none() && !self.bought.is_zero() && (self.sell_error.is_some() || self.sold_for.is_zero())
+    }
+
+    /// 给人看的风险提示
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.code.is_empty() {
+            warnings.push("No contract code at the token address".to_string());
+            return warnings;
+        }
+        if let Some(e) = &self.buy_error {
+            warnings.push(format!("Buy failed: {}", e));
+        }
+        if self.is_honeypot() {
+            warnings.push(format!(
+                "Honeypot: tokens can be bought but not sold ({})",
+                self.sell_error.as_deref().unwrap_or("sell returned no ETH")
+            ));
+        }
+        if let Some(e) = &self.transfer_error {
+            warnings.push(format!("Wallet-to-wallet transfer blocked: {}", e));
+        }
+        for (side, tax) in [("Buy", self.buy_tax_bps), ("Sell", self.sell_tax_bps)] {
+            if let Some(tax) = tax.filter(|t| *t >= HIGH_TAX_BPS) {
+                warnings.push(format!("{} tax is {}.{:02}%", side, tax / 100, tax % 100));
+            }
+        }
+        let renounced = self.owner == Some(Address::zero());
+        if !self.owner_functions.is_empty() && !renounced {
+            let mut categories: Vec<&str> = Vec::new();
+            for function in &self.owner_functions {
+                if !categories.contains(&function.category) {
+                    categories.push(function.category);
+                }
+            }
+            warnings.push(format!("Owner-controlled functions: {}", categories.join(", ")));
+        }
+        warnings
+    }
+}
@@ -0,0 +1,273 @@
+// src/rpc.rs
+use async_trait::async_trait;
+use ethers::contract::multicall_contract::Call3;
+use ethers::contract::{MulticallContract, MULTICALL_ADDRESS};
+use ethers::providers::{Http, JsonRpcClient, Middleware, Provider, ProviderError, RpcError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, Filter, Log, TransactionRequest, U64};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::balance::MULTICALL_BATCH_SIZE;
+use crate::config::RpcFixture;
+use crate::fixture::{Recorder, Replay};
+
+/// 各模块共用的 provider 类型：在 transport 外面包一层计数
+pub type RpcProvider = Provider<Metered<RpcTransport>>;
+
+/// 把普通的 HTTP provider 包成可计数的 provider
+pub fn metered(provider: Provider<Http>) -> RpcProvider {
+    Provider::new(Metered::new(RpcTransport::Http(provider.as_ref().clone())))
+}
+
+/// 同 `metered`，可选录制或回放 JSON-RPC（回放时不会访问节点）；`max_log_range` 是单次 eth_getLogs 的块数上限
+pub fn connect(
+    provider: Provider<Http>,
+    fixture: Option<&RpcFixture>,
+    max_log_range: Option<u64>,
+) -> anyhow::Result<RpcProvider> {
+    let http = provider.as_ref().clone();
+    let transport = match fixture {
+        None => RpcTransport::Http(http),
+        Some(RpcFixture::Record(path)) => RpcTransport::Record(Recorder::create(http, path)?),
+        Some(RpcFixture::Replay(path)) => RpcTransport::Replay(Replay::load(path)?),
+        Some(RpcFixture::ReplayUnordered(path)) => RpcTransport::Replay(Replay::load(path)?.unordered()),
+    };
+    let metered = Metered::new(transport).with_max_log_range(max_log_range.unwrap_or(DEFAULT_MAX_LOG_RANGE));
+    Ok(Provider::new(metered))
+}
+
+/// 节点 transport：直连 HTTP，或者录制 / 回放 fixture
+#[derive(Clone, Debug)]
+pub enum RpcTransport {
+    Http(Http),
+    Record(Recorder<Http>),
+    Replay(Replay),
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcTransport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Self::Http(http) => http.request(method, params).await.map_err(Into::into),
+            Self::Record(recorder) => recorder.request(method, params).await.map_err(Into::into),
+            Self::Replay(replay) => replay.request(method, params).await.map_err(Into::into),
+        }
+    }
+}
+
+/// 只读 eth_call；合约 revert 或没有返回值时为 None，网络错误照常返回 Err
+pub async fn view_call(
+    provider: &RpcProvider,
+    tx: &TypedTransaction,
+    block: Option<U64>,
+) -> anyhow::Result<Option<Bytes>> {
+    match provider.call(tx, block.map(Into::into)).await {
+        Ok(data) => Ok(Some(data).filter(|d| !d.is_empty())),
+        Err(e) if RpcError::as_error_response(&e).is_some() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 同一个块上的一批只读调用 `(to, calldata)`，按 `MULTICALL_BATCH_SIZE` 打包进 Multicall3 aggregate3
+///
+/// 每个子调用都允许失败；结果和 `calls` 一一对应，revert 或没有返回值的为 None。
+/// 链上没有 Multicall3（或打包调用本身失败）时退回逐个 eth_call。
+pub async fn batch_view_calls<M: Middleware + 'static>(
+    provider: &Arc<M>,
+    calls: &[(Address, Bytes)],
+    block: U64,
+) -> anyhow::Result<Vec<Option<Bytes>>> {
+    let multicall = MulticallContract::new(MULTICALL_ADDRESS, provider.clone());
+    let mut results = Vec::with_capacity(calls.len());
+    for chunk in calls.chunks(MULTICALL_BATCH_SIZE) {
+        let batch = chunk
+            .iter()
+            .map(|(target, data)| Call3 { target: *target, allow_failure: true, call_data: data.clone() })
+            .collect();
+        match multicall.aggregate_3(batch).block(block).call().await {
+            Ok(out) if out.len() == chunk.len() => {
+                results.extend(
+                    out.into_iter()
+                        .map(|r| Some(r.return_data).filter(|d| r.success && !d.is_empty())),
+                );
+            }
+            outcome => {
+                let reason = outcome.err().map_or("wrong number of results".to_string(), |e| e.to_string());
+                warn!("Multicall failed ({}), making {} calls one by one", reason, chunk.len());
+                for (to, data) in chunk {
+                    let tx: TypedTransaction = TransactionRequest::new().to(*to).data(data.clone()).into();
+                    let out = match provider.call(&tx, Some(block.into())).await {
+                        Ok(data) => Some(data).filter(|d| !d.is_empty()),
+                        Err(e) if ethers::providers::MiddlewareError::as_error_response(&e).is_some() => None,
+                        Err(e) => return Err(anyhow::anyhow!("{}", e)),
+                    };
+                    results.push(out);
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// eth_getLogs 单次请求块数的默认上限
+pub const DEFAULT_MAX_LOG_RANGE: u64 = 500_000;
+/// 节点报范围太大 / 结果太多时减半重试，最小缩到这么多块
+const MIN_LOG_SCAN_CHUNK: u64 = 1_000;
+/// 连续成功这么多次后把分段翻倍（不超过上限），日志稀疏的区段不用一直小步走
+const LOG_SCAN_GROW_AFTER: u32 = 4;
+
+/// 节点因为范围太大或结果太多拒绝 eth_getLogs；网络错误等其他错误缩小范围也没用
+fn is_log_range_error(error: &ProviderError) -> bool {
+    const HINTS: [&str; 9] =
+        ["range", "limit", "too many", "too large", "exceed", "more than", "response size", "timeout", "timed out"];
+    error.as_error_response().is_some_and(|e| {
+        let message = e.message.to_lowercase();
+        e.code == -32005 || HINTS.iter().any(|h| message.contains(h))
+    })
+}
+
+/// 分段扫描的结果
+#[derive(Clone, Debug, Default)]
+pub struct LogScan {
+    pub logs: Vec<Log>,
+    /// 达到 `max_logs` 提前停下时，下次从这个块接着扫
+    pub next_block: Option<u64>,
+}
+
+/// 分段 eth_getLogs：`from_block..=to_block` 按块切段，分段最大为 provider 配置的 `max_log_range`
+///
+/// 节点嫌范围太大或结果太多时把分段减半重试，之后连续成功再逐步放大；其他错误直接返回。
+/// 给了 `max_logs` 时，攒够就停；同一个块的日志不会被拆开，`next_block` 接着扫不会重复也不会漏。
+pub async fn scan_logs(
+    provider: &RpcProvider,
+    filter: Filter,
+    from_block: u64,
+    to_block: u64,
+    max_logs: Option<usize>,
+) -> anyhow::Result<LogScan> {
+    let max_logs = max_logs.unwrap_or(usize::MAX);
+    let mut scan = LogScan::default();
+    let max_chunk = provider.as_ref().max_log_range();
+    let mut chunk = max_chunk;
+    let mut successes = 0;
+    let mut start = from_block;
+    while start <= to_block {
+        let end = to_block.min(start.saturating_add(chunk - 1));
+        let range = filter.clone().from_block(start).to_block(end);
+        match provider.get_logs(&range).await {
+            Ok(found) => {
+                scan.logs.extend(found);
+                start = end + 1;
+                successes += 1;
+                if successes >= LOG_SCAN_GROW_AFTER && chunk < max_chunk {
+                    chunk = chunk.saturating_mul(2).min(max_chunk);
+                    successes = 0;
+                }
+            }
+            Err(e) if chunk > MIN_LOG_SCAN_CHUNK && is_log_range_error(&e) => {
+                debug!("eth_getLogs {}..{} failed ({}), retrying with smaller range", start, end, e);
+                chunk = (chunk / 2).max(MIN_LOG_SCAN_CHUNK);
+                successes = 0;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        if scan.logs.len() >= max_logs {
+            let next = if scan.logs.len() == max_logs {
+                start
+            } else {
+                let block_of = |log: &Log| log.block_number.map_or(0, |b| b.as_u64());
+                // 截在第 max_logs + 1 条所在块之前；第一个块就超了时整块保留
+                let cut = block_of(&scan.logs[max_logs]);
+                let keep = scan.logs.iter().take_while(|l| block_of(l) < cut).count();
+                if keep == 0 {
+                    scan.logs.retain(|l| block_of(l) == cut);
+                    cut + 1
+                } else {
+                    scan.logs.truncate(keep);
+                    cut
+                }
+            };
+            if next <= to_block {
+                scan.next_block = Some(next);
+            }
+            break;
+        }
+    }
+    Ok(scan)
+}
+
+tokio::task_local! {
+    static RPC_COUNTER: RpcCounter;
+}
+
+/// 一次工具调用期间发出的 JSON-RPC 请求数
+#[derive(Clone, Debug, Default)]
+pub struct RpcCounter(Arc<AtomicU64>);
+
+impl RpcCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 当前作用域的计数器；不在工具调用里时为 None
+    pub fn current() -> Option<Self> {
+        RPC_COUNTER.try_with(Clone::clone).ok()
+    }
+
+    /// 在计数作用域内运行 `fut`：期间经过 [`Metered`] 的请求都会记到这个计数器上
+    pub async fn scope<F: Future>(&self, fut: F) -> F::Output {
+        RPC_COUNTER.scope(self.clone(), fut).await
+    }
+}
+
+/// JSON-RPC transport 包装：每发一个请求，给当前作用域的 [`RpcCounter`] 加一
+///
+/// 计数器放在 task-local 里，多个客户端并发共用同一个 provider 也能各算各的。
+#[derive(Clone, Debug)]
+pub struct Metered<C> {
+    inner: C,
+    max_log_range: u64,
+}
+
+impl<C> Metered<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner, max_log_range: DEFAULT_MAX_LOG_RANGE }
+    }
+
+    /// 节点允许的 eth_getLogs 块数上限（`scan_logs` 的最大分段）
+    pub fn with_max_log_range(mut self, blocks: u64) -> Self {
+        self.max_log_range = blocks.max(1);
+        self
+    }
+
+    pub fn max_log_range(&self) -> u64 {
+        self.max_log_range
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for Metered<C> {
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let _ = RPC_COUNTER.try_with(|counter| counter.0.fetch_add(1, Ordering::Relaxed));
+        self.inner.request(method, params).await
+    }
+}
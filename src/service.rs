@@ -1,43 +1,532 @@
 use rmcp::{
-    ServerHandler,
+    ErrorData, RoleServer, ServerHandler,
     handler::server::{
         router::tool::ToolRouter,
+        tool::{Extension, ToolCallContext},
         wrapper::{Json, Parameters},
     },
-    model::{ServerCapabilities, ServerInfo},
-    schemars, tool, tool_handler, tool_router,
+    model::{
+        CallToolRequestParam, CallToolResult, ListToolsResult, PaginatedRequestParam,
+        ServerCapabilities, ServerInfo,
+    },
+    schemars,
+    service::RequestContext,
+    tool, tool_router,
 };
-use ethers::types::Address;
+use ethers::abi::ParamType;
+use ethers::types::{Address, Log, H256, U256, U64};
 use rust_decimal::Decimal;
-use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::str::FromStr;
+use tracing::debug;
 
+use crate::abi_library::{DecodedCall, DecodedEvent};
+use crate::approvals::{self, ApprovalKind};
+use crate::auth::ClientIdentity;
 use crate::balance::BalanceModule;
-use crate::price::PriceModule;
+use crate::cache::{PinnedBlock, RpcCache};
+use crate::config::{Confidence, RateLimits};
+use crate::contract::{self, ContractCall, ContractModule};
+use crate::ens::{self, EnsModule};
+use crate::events::{self, EventModule};
+use crate::indexer::{Direction, Indexer, TransferCursor, TransferQuery};
+use crate::nft::{HoldingsSource, NftStandard, DEFAULT_NFT_LOG_LIMIT, MAX_NFT_LOG_LIMIT};
+use crate::pool::{self, PoolModule, DEFAULT_TWAP_WINDOW_SECS, DEPTH_LEVELS_BPS, V3_FEE_TIERS};
+use crate::price::{self, AggregatedPrice, PriceModule, RateHop, SourcePrice, DEFAULT_MAX_DEVIATION_BPS};
+use crate::risk::OwnerFunction;
+use crate::rpc::RpcCounter;
+use crate::simulate::{BalanceDelta, CallFrame, StepReport};
 use crate::swap::SwapModule;
+use crate::token::TokenModule;
+use crate::transaction::{TransactionModule, TxStatus};
+use crate::usage::{RateLimited, UsageReport, UsageTracker};
 
 // 输入输出类型
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct BalanceArgs {
+    /// 钱包地址或 ENS 名（如 vitalik.eth）
     pub address: String,
+    /// ERC20 合约地址或 ENS 名；不填查 ETH
     pub token: Option<String>,
+    /// 传入 ENS 名时，要求它同时是该地址的主名称（反向解析一致）
+    pub verify_ens: Option<bool>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct BalanceResult {
     pub balance: String,
+    /// 解析后的钱包地址
+    pub address: String,
+    /// 钱包的 ENS 主名称
+    pub ens_name: Option<String>,
+    /// 读取所用的块；块哈希变了说明发生了重组
+    pub block: u64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BalancesArgs {
+    /// 钱包地址或 ENS 名
+    pub addresses: Vec<String>,
+    /// 代币合约地址或 ENS 名，"ETH" 表示原生 ETH；不填只查 ETH
+    pub tokens: Option<Vec<String>>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+/// 表格的一行：一个钱包持有的一种代币
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BalanceRow {
+    pub address: String,
+    pub token: String,
+    pub balance: Option<String>,
+    /// 这一格查询失败的原因
+    pub error: Option<String>,
+}
+
+/// 每个钱包的小计（按 USD 计；查不到价格的代币不计入）
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct WalletSubtotal {
+    pub address: String,
+    pub usd_value: String,
+    /// 有余额但没有价格的代币
+    pub unpriced_tokens: Vec<String>,
+    pub errors: usize,
+}
+
+/// 每种代币在所有钱包里的合计
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TokenSubtotal {
+    pub token: String,
+    pub total: String,
+    pub usd_price: Option<String>,
+    pub usd_value: Option<String>,
+    pub errors: usize,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BalancesResult {
+    pub rows: Vec<BalanceRow>,
+    pub wallets: Vec<WalletSubtotal>,
+    pub tokens: Vec<TokenSubtotal>,
+    /// 所有有价格的持仓合计
+    pub usd_total: String,
+    /// 余额和价格都读自这个块
+    pub block: u64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct NftHoldingsArgs {
+    /// 持有人地址或 ENS 名
+    pub address: String,
+    /// NFT 合约地址或 ENS 名（ERC721 / ERC1155）
+    pub collection: String,
+    /// 扫描 Transfer 日志的起始块（合约部署块即可）；不填只看最近 2,000,000 块，更早的转入要显式给
+    pub from_block: Option<u64>,
+    /// 一次最多扫描的转入日志条数，默认 1000，上限 10000
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct NftHoldingResult {
+    pub token_id: String,
+    /// ERC721 恒为 1
+    pub amount: String,
+    pub token_uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct NftHoldingsResult {
+    pub address: String,
+    pub collection: String,
+    pub standard: NftStandard,
+    pub source: HoldingsSource,
+    pub holdings: Vec<NftHoldingResult>,
+    /// 实际扫描的起始块；按 ERC721Enumerable 列出时为空
+    pub from_block: Option<u64>,
+    /// 持有数超过上限，或转入日志超过 limit；有 next_from_block 时用它作为 from_block 继续查
+    pub truncated: bool,
+    pub next_from_block: Option<u64>,
+    pub block: u64,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ApprovalsArgs {
+    /// 授权人地址或 ENS 名
+    pub address: String,
+    /// 扫描 Approval 日志的起始块；不填只看最近 2,000,000 块，更早的授权要显式给
+    pub from_block: Option<u64>,
+}
+
+/// 一条仍然有效的授权
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ApprovalResult {
+    pub kind: ApprovalKind,
+    /// 授权所在的合约
+    pub token: String,
+    /// 注册表里的 symbol
+    pub symbol: Option<String>,
+    pub spender: String,
+    /// 只有 erc721 有
+    pub token_id: Option<String>,
+    /// 只有 erc20 有：按 decimals 格式化的 allowance
+    pub allowance: Option<String>,
+    pub unlimited: bool,
+    /// spender 现在最多能转走的价值：min(allowance, 余额) × 多来源中位价；没有价格时为 None
+    pub usd_at_risk: Option<String>,
+    pub last_block: u64,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ApprovalsResult {
+    pub address: String,
+    pub approvals: Vec<ApprovalResult>,
+    /// 所有有价格的授权合计
+    pub usd_at_risk: String,
+    /// 实际扫描的起始块
+    pub from_block: u64,
+    /// 授权事件超过上限；用 next_from_block 作为 from_block 继续查
+    pub truncated: bool,
+    pub next_from_block: Option<u64>,
+    pub block: u64,
+}
+
+/// 要撤销的授权，字段和 list_approvals 的输出一致
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RevokeItem {
+    pub kind: ApprovalKind,
+    pub token: String,
+    pub spender: String,
+    /// kind 为 erc721 时必填
+    pub token_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RevokeArgs {
+    /// 授权人地址或 ENS 名（交易的 from）
+    pub address: String,
+    pub approvals: Vec<RevokeItem>,
+}
+
+/// 未签名的撤销交易
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RevokeTransaction {
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub data: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RevokeResult {
+    pub transactions: Vec<RevokeTransaction>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CallContractArgs {
+    /// 合约地址或 ENS 名
+    pub address: String,
+    /// 函数签名，如 "balanceOf(address owner) view returns (uint256)"；给了 abi 时是要调用的函数名
+    pub function: Option<String>,
+    /// JSON ABI 片段：单个函数对象或 ABI 数组
+    pub abi: Option<serde_json::Value>,
+    /// 按顺序的参数；整数用字符串，bytes 用 0x 十六进制，tuple 用数组
+    pub args: Option<Vec<serde_json::Value>>,
+    /// 允许调用非 view 函数（只做 eth_call 模拟，不发交易）
+    pub simulate: Option<bool>,
+    /// 模拟时的 msg.sender
+    pub from: Option<String>,
+    /// 模拟 payable 函数时附带的 wei
+    pub value: Option<String>,
+    /// 在指定块上读取；不填用最新块
+    pub block: Option<u64>,
+}
+
+/// 一个返回值
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ContractOutput {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// 整数为十进制字符串，bytes 为 0x 十六进制，tuple / 数组为 JSON 数组
+    pub value: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CallContractResult {
+    pub address: String,
+    pub function: String,
+    pub calldata: String,
+    pub outputs: Vec<ContractOutput>,
+    pub raw: String,
+    pub block: u64,
+    pub simulated: bool,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransactionArgs {
+    /// 交易哈希
+    pub hash: String,
+}
+
+/// 用内置 ABI 库解码出的函数调用
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DecodedCallResult {
+    /// 匹配到的 ABI，如 "UniswapV2Router02"
+    pub abi: String,
+    pub function: String,
+    pub args: Vec<ContractOutput>,
+    /// multicall 里打包的子调用
+    pub calls: Vec<DecodedCallResult>,
+}
+
+impl From<DecodedCall> for DecodedCallResult {
+    fn from(call: DecodedCall) -> Self {
+        Self {
+            abi: call.abi.to_string(),
+            function: call.function,
+            args: call
+                .args
+                .into_iter()
+                .map(|(name, kind, value)| ContractOutput { name, kind, value })
+                .collect(),
+            calls: call.calls.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// 一条日志；不认识的事件只有 topics / data
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransactionLogResult {
+    pub log_index: Option<u64>,
+    pub address: String,
+    pub abi: Option<String>,
+    pub event: Option<String>,
+    pub args: Vec<ContractOutput>,
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+impl From<(Log, Option<DecodedEvent>)> for TransactionLogResult {
+    fn from((log, decoded): (Log, Option<DecodedEvent>)) -> Self {
+        Self {
+            log_index: log.log_index.map(|i| i.as_u64()),
+            address: format!("{:?}", log.address),
+            abi: decoded.as_ref().map(|d| d.abi.to_string()),
+            event: decoded.as_ref().map(|d| d.event.clone()),
+            args: decoded
+                .map(|d| d.args)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, kind, value)| ContractOutput { name, kind, value })
+                .collect(),
+            topics: log.topics.iter().map(|t| format!("{:?}", t)).collect(),
+            data: log.data.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransactionResult {
+    pub hash: String,
+    pub status: TxStatus,
+    pub block: Option<u64>,
+    /// 所在块的时间（RFC 3339）；pending 时为 None
+    pub timestamp: Option<String>,
+    pub from: String,
+    /// 创建合约时为 None
+    pub to: Option<String>,
+    pub contract_address: Option<String>,
+    /// 转账金额（ETH）
+    pub value: String,
+    pub nonce: String,
+    pub gas_limit: String,
+    pub gas_used: Option<String>,
+    pub effective_gas_price_gwei: Option<String>,
+    /// 手续费（ETH）
+    pub fee: Option<String>,
+    /// 手续费按交易所在块的 ETH 价格折算的 USD；节点读不到那个块的价格（非归档节点）时为 None
+    pub fee_usd: Option<String>,
+    pub input: String,
+    /// 解码后的 calldata；不认识的函数为 None
+    pub call: Option<DecodedCallResult>,
+    pub logs: Vec<TransactionLogResult>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct GetLogsArgs {
+    /// 事件签名，如 "Transfer(address indexed from, address indexed to, uint256 value)"
+    pub event: String,
+    /// 合约地址或 ENS 名；不填则查所有合约
+    pub addresses: Option<Vec<String>>,
+    /// indexed 参数名 → 取值或取值数组（任一匹配），如 {"to": "0x..."}
+    pub filters: Option<serde_json::Map<String, serde_json::Value>>,
+    /// 起始块（含），默认 0
+    pub from_block: Option<u64>,
+    /// 结束块（含），默认最新块
+    pub to_block: Option<u64>,
+    /// 起始时间（RFC 3339），与 from_block 二选一
+    pub from_time: Option<String>,
+    /// 结束时间（RFC 3339），与 to_block 二选一
+    pub to_time: Option<String>,
+    /// 最多返回的日志条数，默认 1000，上限 10000
+    pub limit: Option<usize>,
+}
+
+/// 一条事件日志
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct EventLogResult {
+    pub block: Option<u64>,
+    /// 所在块的时间（RFC 3339）
+    pub timestamp: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub log_index: Option<u64>,
+    pub address: String,
+    pub args: Vec<ContractOutput>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct LogsResult {
+    pub event: String,
+    pub topic0: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub logs: Vec<EventLogResult>,
+    /// topic0 相同但参数布局不同、解不开的日志条数
+    pub skipped: usize,
+    /// 达到 limit 提前停下；用 next_from_block 作为 from_block 继续查
+    pub truncated: bool,
+    pub next_from_block: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransferHistoryArgs {
+    /// 被索引的钱包地址或 ENS 名
+    pub address: String,
+    /// "ETH"、代币地址或 ENS 名；不填为全部
+    pub token: Option<String>,
+    /// in / out；不填为双向
+    pub direction: Option<Direction>,
+    /// 只看和这个地址之间的转账
+    pub counterparty: Option<String>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// 每页条数，默认 100，上限 1000
+    pub limit: Option<usize>,
+    /// 上一页返回的 next_cursor
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransferRecord {
+    pub block: u64,
+    /// 所在块的时间（RFC 3339）
+    pub timestamp: String,
+    pub transaction_hash: String,
+    /// "ETH" 或代币地址
+    pub token: String,
+    pub from: String,
+    pub to: String,
+    pub direction: Direction,
+    /// 按 decimals 格式化；读不到 decimals 时为 None
+    pub amount: Option<String>,
+    pub amount_raw: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransferHistoryResult {
+    pub address: String,
+    /// 从新到旧
+    pub transfers: Vec<TransferRecord>,
+    /// 还有更早的记录时，作为 cursor 传入取下一页
+    pub next_cursor: Option<String>,
+    /// 索引已同步到的块
+    pub indexed_to_block: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct PriceArgs {
+    /// chainlink：代币 symbol、price feed 地址或 ENS 名（如 eth-usd.data.eth）；twap：代币 symbol、合约地址或 ENS 名
     pub token: Option<String>,
+    /// "chainlink"（默认）、"twap"（Uniswap V3 预言机的时间加权平均价格）或 "aggregate"（所有来源的中位数）
+    pub source: Option<String>,
+    /// TWAP 窗口（秒），只用于 twap / aggregate；不填用服务配置
+    pub window_secs: Option<u32>,
+    /// aggregate 时偏离中位数多少基点算异常；不填用服务配置
+    pub max_deviation_bps: Option<u64>,
+    /// chainlink 时按什么计价："USD"、"ETH"、"BTC" 或代币 symbol / 地址；不填时是 feed 自己的计价单位
+    pub quote: Option<String>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RateHopResult {
+    pub feed: String,
+    pub from: String,
+    pub to: String,
+    /// 逆着 feed 的方向换算（取倒数）
+    pub inverted: bool,
+    /// 1 个 from 值多少个 to
+    pub rate: String,
+    /// feed 最近一次更新的时间戳
+    pub updated_at: u64,
+}
+
+impl From<RateHop> for RateHopResult {
+    fn from(hop: RateHop) -> Self {
+        Self {
+            feed: format!("{:?}", hop.feed),
+            from: hop.from,
+            to: hop.to,
+            inverted: hop.inverted,
+            rate: hop.rate.normalize().to_string(),
+            updated_at: hop.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SourcePriceResult {
+    /// "chainlink" / "v2_spot" / "v3_twap" / "api"
+    pub source: String,
+    pub price: Option<String>,
+    /// 偏离中位数多少基点
+    pub deviation_bps: Option<u64>,
+    /// 偏离超过阈值
+    pub outlier: bool,
+    /// 这个来源拿不到价格的原因
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct PriceResult {
     pub price: String,
+    /// 价格的计价单位，如 "USD"、"ETH"；读不出 feed 的计价对时为空
+    pub quote: Option<String>,
+    /// "chainlink"、"twap" 或 "aggregate"
+    pub source: String,
+    /// chainlink 时经过的 feed，按换算顺序
+    pub path: Vec<RateHopResult>,
+    /// 块时间减去路径上最旧的一次 feed 更新（秒）
+    pub staleness_secs: Option<u64>,
+    /// TWAP 窗口（秒）；chainlink 时为空
+    pub window_secs: Option<u32>,
+    /// TWAP 用到的 V3 池子，按换算顺序（代币 → WETH → USDC）
+    pub pools: Vec<String>,
+    /// aggregate 时每个来源的价格
+    pub sources: Vec<SourcePriceResult>,
+    /// aggregate 时偏离中位数过多的来源
+    pub warnings: Vec<String>,
+    /// 读取所用的块；块哈希变了说明发生了重组
+    pub block: u64,
+    pub block_hash: String,
 }
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
@@ -46,19 +535,369 @@ pub struct SwapArgs {
     pub to_token: String,
     pub amount_in: String,
     pub slippage: f64,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct SwapResult {
     pub estimated_output: String,
     pub gas: String,
+    /// 比按两边多来源中位价（有一边没有时按 V2 池子现价）少拿的部分（基点，含 0.3% 手续费）；找不到池子或报价失败时为空
+    pub price_impact_bps: Option<u64>,
+    /// 报价失败、没有池子、池子太浅等说明
+    pub warnings: Vec<String>,
+    /// 读取所用的块；块哈希变了说明发生了重组
+    pub block: u64,
+    pub block_hash: String,
+}
+
+/// 调用栈里的一帧
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CallFrameResult {
+    /// 0 是交易本身的调用
+    pub depth: usize,
+    /// "CALL" / "STATICCALL" / "DELEGATECALL" …
+    pub kind: String,
+    pub from: String,
+    pub to: String,
+    pub selector: Option<String>,
+    pub success: bool,
+    pub reason: Option<String>,
+}
+
+impl From<CallFrame> for CallFrameResult {
+    fn from(frame: CallFrame) -> Self {
+        Self {
+            depth: frame.depth,
+            kind: frame.kind,
+            from: format!("{:?}", frame.from),
+            to: format!("{:?}", frame.to),
+            selector: frame.selector,
+            success: frame.success,
+            reason: frame.reason,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SimulatedStep {
+    /// "approve" / "swap"
+    pub label: String,
+    pub success: bool,
+    pub gas_used: u64,
+    /// 返回数据（revert 时是 revert 数据），0x 十六进制
+    pub output: String,
+    pub revert_reason: Option<String>,
+    pub logs: Vec<TransactionLogResult>,
+    /// 失败时的调用栈；成功时为空
+    pub trace: Vec<CallFrameResult>,
+}
+
+impl From<StepReport> for SimulatedStep {
+    fn from(step: StepReport) -> Self {
+        Self {
+            label: step.label,
+            success: step.success,
+            gas_used: step.gas_used,
+            output: step.output.to_string(),
+            revert_reason: step.revert_reason,
+            logs: step.logs.into_iter().map(Into::into).collect(),
+            trace: step.trace.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// 模拟前后的余额，都是最小单位的整数
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BalanceChange {
+    pub holder: String,
+    /// "ETH" 或代币地址
+    pub token: String,
+    pub before: String,
+    pub after: String,
+    /// after - before，带符号
+    pub delta: String,
+    /// 按 Transfer 事件累计的转入 / 转出；ETH 没有
+    pub transferred_in: Option<String>,
+    pub transferred_out: Option<String>,
+    /// 事件解释不了的余额变化（非 0 说明转账时被偷偷扣了钱）；ETH 没有
+    pub unexplained: Option<String>,
+}
+
+impl From<BalanceDelta> for BalanceChange {
+    fn from(d: BalanceDelta) -> Self {
+        Self {
+            holder: format!("{:?}", d.holder),
+            token: d.token.map_or_else(|| "ETH".to_string(), |t| format!("{:?}", t)),
+            before: d.before.to_string(),
+            after: d.after.to_string(),
+            delta: d.delta().to_string(),
+            transferred_in: d.transfers.map(|(received, _)| received.to_string()),
+            transferred_out: d.transfers.map(|(_, sent)| sent.to_string()),
+            unexplained: d.unexplained().map(|u| u.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SimulateSwapArgs {
+    pub from_token: String,
+    pub to_token: String,
+    pub amount_in: String,
+    pub slippage: f64,
+    /// 收款地址或 ENS 名；不填为 WALLET_ADDRESS
+    pub recipient: Option<String>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SimulationResult {
+    /// 所有步骤都成功
+    pub success: bool,
+    pub steps: Vec<SimulatedStep>,
+    /// router 报价（按输出代币 decimals 格式化）
+    pub quoted_output: String,
+    /// 收款方实际到账
+    pub received_output: String,
+    /// 实际到账比报价少的部分（基点）；明显大于 0 说明输出代币转账扣税
+    pub shortfall_bps: Option<u64>,
+    /// 钱包和收款方在 ETH 和涉及的代币上的前后余额
+    pub balances: Vec<BalanceChange>,
+    /// fork 的块
+    pub block: u64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct PoolArgs {
+    /// 代币 symbol（"ETH" 按 WETH 算）、合约地址或 ENS 名
+    pub token_a: String,
+    pub token_b: String,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct PoolTokenResult {
+    pub address: String,
+    /// 配置或注册表里的 symbol
+    pub symbol: Option<String>,
+    pub decimals: u32,
+    /// 储备，按代币单位
+    pub reserve: Option<String>,
+    /// 1 个该代币按储备比例值多少个另一边的代币；池子空时为空
+    pub price_in_other: Option<String>,
+    pub usd_price: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct PoolResult {
+    pub pair: String,
+    pub token0: PoolTokenResult,
+    pub token1: PoolTokenResult,
+    /// LP 代币总量
+    pub total_supply: String,
+    /// 两边储备的 USD 价值；只有一边有价格时按两边等值推算，都没有时为空
+    pub tvl_usd: Option<String>,
+    /// 读取所用的块；块哈希变了说明发生了重组
+    pub block: u64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct V3PoolArgs {
+    /// 代币 symbol（"ETH" 按 WETH 算）、合约地址或 ENS 名
+    pub token_a: String,
+    pub token_b: String,
+    /// 费率，百万分之一（100 / 500 / 3000 / 10000）；不填时选活跃流动性最大的池子
+    pub fee: Option<u32>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DepthResult {
+    /// 相对当前价格的变化（基点），正数为 token0 涨价
+    pub price_change_bps: i64,
+    /// 目标价格：1 个 token0 值多少个 token1
+    pub price: Option<String>,
+    pub token_in: String,
+    /// 推到目标价格要付出的数量（含手续费）；超出已读取的 tick 范围时为空
+    pub amount_in: Option<String>,
+    pub token_out: String,
+    pub amount_out: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct V3PoolResult {
+    pub pool: String,
+    pub token0: String,
+    pub token0_symbol: Option<String>,
+    pub token1: String,
+    pub token1_symbol: Option<String>,
+    /// 费率，百万分之一
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub tick: i32,
+    pub sqrt_price_x96: String,
+    /// 当前价格上的活跃流动性
+    pub liquidity: String,
+    /// 1 个 token0 值多少个 token1，以及反过来
+    pub price0_in_1: Option<String>,
+    pub price1_in_0: Option<String>,
+    /// 把价格推动 -5% / -2% / -1% / +1% / +2% / +5% 需要的数量
+    pub depth: Vec<DepthResult>,
+    /// 深度计算用到的已初始化 tick 数
+    pub initialized_ticks: usize,
+    /// 读取所用的块；块哈希变了说明发生了重组
+    pub block: u64,
+    pub block_hash: String,
+}
+
+/// 浮点估算值 → 保留 8 位有效数字的字符串；NaN / 无穷时为空
+fn significant(value: f64) -> Option<String> {
+    let value = Decimal::try_from(value).ok()?;
+    Some(value.round_sf(8)?.normalize().to_string())
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TokenRiskArgs {
+    /// 代币 symbol、合约地址或 ENS 名
+    pub token: String,
+    /// 试买花的 ETH，默认 0.01
+    pub amount_eth: Option<String>,
+    /// 区块浏览器上已验证的 JSON ABI（数组）；代理合约请给实现合约的 ABI
+    pub abi: Option<serde_json::Value>,
+    /// 确认程度："latest"、"safe"、"finalized" 或确认数（如 "12"）；不填用服务默认值
+    pub confidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct OwnerFunctionResult {
+    pub signature: String,
+    /// "pause" / "blacklist" / "mint" / "fee" / "limit" / "trading"
+    pub category: String,
+    /// "abi" 或 "bytecode"
+    pub source: String,
+}
+
+impl From<OwnerFunction> for OwnerFunctionResult {
+    fn from(f: OwnerFunction) -> Self {
+        Self { signature: f.signature, category: f.category.to_string(), source: f.source.to_string() }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TokenRiskResult {
+    pub token: String,
+    /// 买得进、卖不出
+    pub honeypot: bool,
+    /// 有效买入 / 卖出税（基点），按 router 报价和实际到账的差额算；那一步失败时为空
+    pub buy_tax_bps: Option<u64>,
+    pub sell_tax_bps: Option<u64>,
+    pub buy_error: Option<String>,
+    /// 买到的代币转给另一个地址失败的原因
+    pub transfer_error: Option<String>,
+    pub sell_error: Option<String>,
+    /// 试买花的 ETH
+    pub amount_in: String,
+    /// 买到的代币，最小单位的整数
+    pub bought: String,
+    /// 全部卖回拿到的 ETH
+    pub sold_for: String,
+    /// `owner()`；零地址表示已放弃所有权，读不到为空
+    pub owner: Option<String>,
+    pub owner_functions: Vec<OwnerFunctionResult>,
+    pub warnings: Vec<String>,
+    pub steps: Vec<SimulatedStep>,
+    /// fork 的块
+    pub block: u64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct UsageArgs {}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TokenInfoArgs {
+    /// ERC20 合约地址或 ENS 名
+    pub address: String,
+    /// 是否注册进运行时注册表，之后 swap_tokens / get_price 可以按 symbol 使用
+    pub register: Option<bool>,
+    /// 注册用的 symbol；不填则用链上的 symbol
+    pub symbol: Option<String>,
+    /// 注册时附带的 Chainlink price feed 地址或 ENS 名
+    pub price_feed: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TokenInfoResult {
+    pub address: String,
+    /// 合约的 ENS 主名称
+    pub ens_name: Option<String>,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    /// 按 decimals 格式化；decimals 缺失时为 None
+    pub total_supply: Option<String>,
+    pub total_supply_raw: Option<String>,
+    pub warnings: Vec<String>,
+    /// 注册成功时的 symbol
+    pub registered_as: Option<String>,
+}
+
+/// get_balances 单次最多查询的 钱包 × 代币 组合数
+const MAX_BALANCE_CELLS: usize = 2000;
+
+/// get_transfer_history 默认 / 最多每页条数
+const DEFAULT_TRANSFER_PAGE: usize = 100;
+const MAX_TRANSFER_PAGE: usize = 1000;
+
+/// get_logs 默认 / 最多返回的日志条数
+const DEFAULT_LOG_LIMIT: usize = 1000;
+const MAX_LOG_LIMIT: usize = 10_000;
+
+/// swap 的价格冲击（含手续费）超过这个值（基点）时提示池子太浅
+const THIN_POOL_IMPACT_BPS: u64 = 300;
+
+/// 参数解析失败
+fn invalid_params(what: &str, value: &str) -> ErrorData {
+    ErrorData::invalid_params(format!("Invalid {}: {}", what, value), None)
+}
+
+/// 限流，附带 retry_after_secs 方便调用方退避
+fn rate_limited(e: RateLimited) -> ErrorData {
+    ErrorData::invalid_request(
+        e.to_string(),
+        Some(serde_json::json!({ "retry_after_secs": e.retry_after.as_secs().max(1) })),
+    )
+}
+
+/// 模块内部错误（RPC 失败等）
+fn internal_error(e: anyhow::Error) -> ErrorData {
+    ErrorData::internal_error(e.to_string(), None)
 }
 
 // MCP 服务
+#[derive(Clone)]
 pub struct TokenService {
     pub balance: Arc<BalanceModule>,
     pub price: Arc<PriceModule>,
     pub swap: Arc<SwapModule>,
+    pub tokens: Arc<TokenModule>,
+    pub ens: Arc<EnsModule>,
+    pub contract: Arc<ContractModule>,
+    pub transactions: Arc<TransactionModule>,
+    pub events: Arc<EventModule>,
+    pub pool: Arc<PoolModule>,
+    /// 本地转账索引；未配置时 get_transfer_history 不可用
+    pub indexer: Option<Arc<Indexer>>,
+    /// 余额 / 价格 / 报价默认的确认程度
+    pub confidence: Confidence,
+    pub usage: Arc<UsageTracker>,
+    pub cache: Arc<RpcCache>,
     pub tool_router: ToolRouter<TokenService>,
 }
 
@@ -68,56 +907,1489 @@ impl TokenService {
     async fn get_balance(
         &self,
         Parameters(args): Parameters<BalanceArgs>,
-    ) -> Json<BalanceResult> {
-        let address: Address = args.address.parse().unwrap();
-        let token: Option<Address> = args.token.map(|s| s.parse().ok()).flatten();
-        let bal: Decimal = self.balance.get_balance(address, token).await.unwrap();
-        Json(BalanceResult { balance: bal.to_string() })
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<BalanceResult>, ErrorData> {
+        let address = self.resolve_address("address", &args.address).await?;
+        if args.verify_ens.unwrap_or(false) && ens::is_ens_name(&args.address) {
+            self.ens
+                .verify_reverse(&args.address, address)
+                .await
+                .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+        }
+        client.check_wallet(&address)?;
+
+        let token: Option<Address> = match args.token.as_deref() {
+            None => None,
+            Some(s) => Some(self.resolve_address("token", s).await?),
+        };
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+        let bal: Decimal = self
+            .balance
+            .get_balance_at(address, token, block.number)
+            .await
+            .map_err(internal_error)?;
+        Ok(Json(BalanceResult {
+            balance: bal.to_string(),
+            address: format!("{:?}", address),
+            ens_name: self.primary_name(address).await,
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 批量查询多个钱包 × 多个代币的余额，附带每个钱包、每种代币的小计
+    #[tool]
+    async fn get_balances(
+        &self,
+        Parameters(args): Parameters<BalancesArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<BalancesResult>, ErrorData> {
+        let token_inputs = args.tokens.unwrap_or_else(|| vec!["ETH".to_string()]);
+        if args.addresses.is_empty() || token_inputs.is_empty() {
+            return Err(ErrorData::invalid_params("addresses and tokens must not be empty", None));
+        }
+        if args.addresses.len() * token_inputs.len() > MAX_BALANCE_CELLS {
+            return Err(ErrorData::invalid_params(
+                format!("At most {} wallet/token pairs per call", MAX_BALANCE_CELLS),
+                None,
+            ));
+        }
+
+        // 代币解析失败是参数错误；钱包解析失败只影响那一行
+        let mut tokens: Vec<(String, Option<Address>)> = Vec::new();
+        for input in &token_inputs {
+            let token = if input.eq_ignore_ascii_case("ETH") {
+                ("ETH".to_string(), None)
+            } else {
+                let address = self.resolve_address("token", input).await?;
+                (format!("{:?}", address), Some(address))
+            };
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+
+        let mut wallets: Vec<(String, Result<Address, String>)> = Vec::new();
+        for input in &args.addresses {
+            let wallet = match self.resolve_address("address", input).await {
+                Ok(address) => client
+                    .check_wallet(&address)
+                    .map(|_| address)
+                    .map_err(|e| e.message.to_string()),
+                Err(e) => Err(e.message.to_string()),
+            };
+            let label = wallet.as_ref().map_or_else(|_| input.clone(), |a| format!("{:?}", a));
+            if !wallets.iter().any(|(l, _)| *l == label) {
+                wallets.push((label, wallet));
+            }
+        }
+
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+        let valid: Vec<Address> = wallets.iter().filter_map(|(_, w)| w.clone().ok()).collect();
+        let token_addresses: Vec<Option<Address>> = tokens.iter().map(|(_, t)| *t).collect();
+        let cells: HashMap<(Address, Option<Address>), Result<Decimal, String>> = if valid.is_empty() {
+            HashMap::new()
+        } else {
+            self.balance
+                .get_balances_at(&valid, &token_addresses, block.number)
+                .await
+                .map_err(internal_error)?
+                .into_iter()
+                .map(|c| ((c.wallet, c.token), c.balance))
+                .collect()
+        };
+
+        // 每种代币查一次价格，查不到就不计入 USD 小计
+        let mut prices: Vec<Option<Decimal>> = Vec::with_capacity(tokens.len());
+        for (_, token) in &tokens {
+            prices.push(self.valuation_usd_price(*token, block.number).await);
+        }
+
+        let mut rows = Vec::new();
+        let mut wallet_totals = Vec::new();
+        let mut token_totals: Vec<(Decimal, usize)> = vec![(Decimal::ZERO, 0); tokens.len()];
+        for (label, wallet) in &wallets {
+            let mut subtotal = WalletSubtotal {
+                address: label.clone(),
+                usd_value: String::new(),
+                unpriced_tokens: Vec::new(),
+                errors: 0,
+            };
+            let mut usd = Decimal::ZERO;
+
+            for (i, (token_label, token)) in tokens.iter().enumerate() {
+                let balance = match wallet {
+                    Err(e) => Err(e.clone()),
+                    Ok(w) => cells
+                        .get(&(*w, *token))
+                        .cloned()
+                        .unwrap_or_else(|| Err("missing from batch".to_string())),
+                };
+
+                match &balance {
+                    Ok(amount) => {
+                        token_totals[i].0 += *amount;
+                        match prices[i] {
+                            Some(price) => usd += *amount * price,
+                            None if !amount.is_zero() => subtotal.unpriced_tokens.push(token_label.clone()),
+                            None => {}
+                        }
+                    }
+                    Err(_) => {
+                        token_totals[i].1 += 1;
+                        subtotal.errors += 1;
+                    }
+                }
+
+                rows.push(BalanceRow {
+                    address: label.clone(),
+                    token: token_label.clone(),
+                    balance: balance.as_ref().ok().map(|b| b.to_string()),
+                    error: balance.err(),
+                });
+            }
+
+            subtotal.usd_value = usd.round_dp(2).to_string();
+            wallet_totals.push((usd, subtotal));
+        }
+
+        let usd_total: Decimal = wallet_totals.iter().map(|(usd, _)| *usd).sum();
+        let tokens = tokens
+            .into_iter()
+            .zip(prices)
+            .zip(token_totals)
+            .map(|(((label, _), price), (total, errors))| TokenSubtotal {
+                token: label,
+                total: total.to_string(),
+                usd_price: price.map(|p| p.to_string()),
+                usd_value: price.map(|p| (total * p).round_dp(2).to_string()),
+                errors,
+            })
+            .collect();
+
+        Ok(Json(BalancesResult {
+            rows,
+            wallets: wallet_totals.into_iter().map(|(_, w)| w).collect(),
+            tokens,
+            usd_total: usd_total.round_dp(2).to_string(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 列出地址在某个 NFT 合约里持有的 tokenId（ERC721 / ERC1155）
+    #[tool]
+    async fn get_nft_holdings(
+        &self,
+        Parameters(args): Parameters<NftHoldingsArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<NftHoldingsResult>, ErrorData> {
+        let owner = self.resolve_address("address", &args.address).await?;
+        client.check_wallet(&owner)?;
+        let collection = self.resolve_address("collection", &args.collection).await?;
+        let limit = args.limit.unwrap_or(DEFAULT_NFT_LOG_LIMIT);
+        if limit == 0 || limit > MAX_NFT_LOG_LIMIT {
+            return Err(ErrorData::invalid_params(
+                format!("limit must be between 1 and {}", MAX_NFT_LOG_LIMIT),
+                None,
+            ));
+        }
+
+        let result = self
+            .balance
+            .get_nft_holdings(owner, collection, args.from_block, limit)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(Json(NftHoldingsResult {
+            address: format!("{:?}", owner),
+            collection: format!("{:?}", collection),
+            standard: result.interfaces.standard,
+            source: result.source,
+            holdings: result
+                .holdings
+                .into_iter()
+                .map(|h| NftHoldingResult {
+                    token_id: h.token_id.to_string(),
+                    amount: h.amount.to_string(),
+                    token_uri: h.token_uri,
+                })
+                .collect(),
+            from_block: result.from_block,
+            truncated: result.truncated,
+            next_from_block: result.next_from_block,
+            block: result.block.as_u64(),
+        }))
+    }
+
+    /// 列出地址当前仍然有效的代币授权（谁能动用我的代币），附带 USD 风险敞口
+    #[tool]
+    async fn list_approvals(
+        &self,
+        Parameters(args): Parameters<ApprovalsArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<ApprovalsResult>, ErrorData> {
+        let owner = self.resolve_address("address", &args.address).await?;
+        client.check_wallet(&owner)?;
+
+        let found = self
+            .balance
+            .get_approvals(owner, args.from_block)
+            .await
+            .map_err(internal_error)?;
+
+        let mut total = Decimal::ZERO;
+        let mut approvals = Vec::with_capacity(found.approvals.len());
+        for approval in found.approvals {
+            let allowance = match (approval.allowance, approval.decimals) {
+                (Some(raw), Some(decimals)) => ethers::utils::format_units(raw, u32::from(decimals)).ok(),
+                (Some(raw), None) => Some(raw.to_string()),
+                _ => None,
+            };
+
+            // 只有 ERC20 能估值：spender 最多能转走 min(allowance, 当前余额)
+            let usd_at_risk = match approval.kind {
+                ApprovalKind::Erc20 if approval.decimals.is_some() => {
+                    // 价格和余额都读授权核对时的那个块
+                    match self.valuation_usd_price(Some(approval.token), found.block).await {
+                        None => None,
+                        Some(price) => {
+                            let balance = self
+                                .balance
+                                .get_balance_at(owner, Some(approval.token), found.block)
+                                .await
+                                .map_err(internal_error)?;
+                            // 无限授权换算成 Decimal 会溢出，按余额算
+                            let limit = allowance.as_deref().and_then(|a| Decimal::from_str(a).ok());
+                            let exposed = limit.map_or(balance, |l| l.min(balance));
+                            Some(exposed * price)
+                        }
+                    }
+                }
+                _ => None,
+            };
+            total += usd_at_risk.unwrap_or_default();
+
+            approvals.push(ApprovalResult {
+                kind: approval.kind,
+                token: format!("{:?}", approval.token),
+                symbol: self.price.registry.by_address(approval.token).map(|t| t.symbol),
+                spender: format!("{:?}", approval.spender),
+                token_id: approval.token_id.map(|id| id.to_string()),
+                allowance,
+                unlimited: approval.is_unlimited(),
+                usd_at_risk: usd_at_risk.map(|v| v.round_dp(2).to_string()),
+                last_block: approval.last_block,
+            });
+        }
+
+        Ok(Json(ApprovalsResult {
+            address: format!("{:?}", owner),
+            approvals,
+            usd_at_risk: total.round_dp(2).to_string(),
+            from_block: found.from_block,
+            truncated: found.truncated,
+            next_from_block: found.next_from_block,
+            block: found.block.as_u64(),
+        }))
+    }
+
+    /// 生成撤销授权的未签名交易（不广播）
+    #[tool]
+    async fn revoke_approvals(
+        &self,
+        Parameters(args): Parameters<RevokeArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<RevokeResult>, ErrorData> {
+        let owner = self.resolve_address("address", &args.address).await?;
+        client.check_wallet(&owner)?;
+        if args.approvals.is_empty() {
+            return Err(ErrorData::invalid_params("approvals must not be empty", None));
+        }
+
+        let mut transactions = Vec::with_capacity(args.approvals.len());
+        for item in args.approvals {
+            let token = self.resolve_address("token", &item.token).await?;
+            let spender = self.resolve_address("spender", &item.spender).await?;
+            let token_id = match item.token_id.as_deref() {
+                None => None,
+                Some(id) => Some(U256::from_dec_str(id).map_err(|_| invalid_params("token_id", id))?),
+            };
+            let data = approvals::revoke_calldata(item.kind, spender, token_id)
+                .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+
+            let description = match (item.kind, token_id) {
+                (ApprovalKind::Erc721, Some(id)) => {
+                    format!("Clear approval of {:?} for token #{} of {:?}", spender, id, token)
+                }
+                (ApprovalKind::ApprovalForAll, _) => {
+                    format!("Remove operator {:?} from all tokens of {:?}", spender, token)
+                }
+                _ => format!("Set allowance of {:?} on {:?} to 0", spender, token),
+            };
+            transactions.push(RevokeTransaction {
+                from: format!("{:?}", owner),
+                to: format!("{:?}", token),
+                value: "0".to_string(),
+                data: data.to_string(),
+                description,
+            });
+        }
+
+        Ok(Json(RevokeResult { transactions }))
+    }
+
+    /// 用调用方提供的函数签名或 JSON ABI 读取任意合约（eth_call，不发交易）
+    #[tool]
+    async fn call_contract(
+        &self,
+        Parameters(args): Parameters<CallContractArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<CallContractResult>, ErrorData> {
+        let simulate = args.simulate.unwrap_or(false);
+        if simulate && !client.can_execute() {
+            return Err(ErrorData::invalid_params(
+                format!("Client '{}' is not allowed to simulate state-changing calls", client.id),
+                None,
+            ));
+        }
+
+        let function = match (&args.abi, args.function.as_deref()) {
+            (Some(abi), name) => contract::function_from_abi(abi, name),
+            (None, Some(signature)) => contract::parse_signature(signature),
+            (None, None) => {
+                return Err(ErrorData::invalid_params("Either function or abi is required", None));
+            }
+        }
+        .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+
+        let to = self.resolve_address("address", &args.address).await?;
+        let from = match args.from.as_deref() {
+            None => None,
+            Some(s) => Some(self.resolve_address("from", s).await?),
+        };
+        let value = match args.value.as_deref() {
+            None => None,
+            Some(v) => Some(U256::from_dec_str(v).map_err(|_| invalid_params("value", v))?),
+        };
+        if (from.is_some() || value.is_some()) && !simulate {
+            return Err(ErrorData::invalid_params("from / value are only used with simulate: true", None));
+        }
+
+        let call = ContractCall { to, from, value, block: args.block };
+        let output = self
+            .contract
+            .call(&function, &args.args.unwrap_or_default(), call, simulate)
+            .await
+            .map_err(|e| ErrorData::invalid_params(format!("{:#}", e), None))?;
+
+        Ok(Json(CallContractResult {
+            address: format!("{:?}", to),
+            function: contract::signature(&function),
+            calldata: output.calldata.to_string(),
+            outputs: output
+                .outputs
+                .into_iter()
+                .map(|(name, kind, value)| ContractOutput { name, kind, value })
+                .collect(),
+            raw: output.raw.to_string(),
+            block: output.block.as_u64(),
+            simulated: simulate,
+        }))
+    }
+
+    /// 查询交易及回执：状态、gas、手续费（ETH / USD），并解码 calldata 和事件
+    #[tool]
+    async fn get_transaction(
+        &self,
+        Parameters(args): Parameters<TransactionArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<TransactionResult>, ErrorData> {
+        let hash: H256 = args.hash.trim().parse().map_err(|_| invalid_params("hash", &args.hash))?;
+        let details = self
+            .transactions
+            .get_transaction(hash)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| ErrorData::invalid_params(format!("Transaction {:?} not found", hash), None))?;
+
+        let tx = &details.transaction;
+        // 限定钱包的调用方只能看发送方或接收方是自己钱包的交易
+        if !tx.to.is_some_and(|to| client.can_query_wallet(&to)) {
+            client.check_wallet(&tx.from)?;
+        }
+        let receipt = details.receipt.as_ref();
+        let fee_usd = match (details.fee, tx.block_number) {
+            (Some(fee), Some(block)) => {
+                self.usd_price(None, Some(block)).await.map(|price| (fee * price).round_dp(2).to_string())
+            }
+            _ => None,
+        };
+        let format_eth = |wei: U256, decimals: u32| {
+            ethers::utils::format_units(wei, decimals).unwrap_or_else(|_| wei.to_string())
+        };
+
+        Ok(Json(TransactionResult {
+            hash: format!("{:?}", tx.hash),
+            status: details.status,
+            block: tx.block_number.map(|b| b.as_u64()),
+            timestamp: details
+                .timestamp
+                .and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0))
+                .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            from: format!("{:?}", tx.from),
+            to: tx.to.map(|to| format!("{:?}", to)),
+            contract_address: receipt.and_then(|r| r.contract_address).map(|a| format!("{:?}", a)),
+            value: format_eth(tx.value, 18),
+            nonce: tx.nonce.to_string(),
+            gas_limit: tx.gas.to_string(),
+            gas_used: receipt.and_then(|r| r.gas_used).map(|g| g.to_string()),
+            effective_gas_price_gwei: receipt
+                .and_then(|r| r.effective_gas_price.or(tx.gas_price))
+                .map(|p| format_eth(p, 9)),
+            fee: details.fee.map(|f| f.to_string()),
+            fee_usd,
+            input: tx.input.to_string(),
+            call: details.call.map(Into::into),
+            logs: details
+                .logs
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }))
+    }
+
+    /// 按事件签名查询日志：可按合约、indexed 参数、块号或时间范围过滤，自动分段翻页，返回解码后的参数和块时间
+    #[tool]
+    async fn get_logs(
+        &self,
+        Parameters(args): Parameters<GetLogsArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<LogsResult>, ErrorData> {
+        let event = events::parse_event(&args.event).map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+        let filters = args.filters.unwrap_or_default();
+        let topics = events::topic_filters(&event, &filters)
+            .map_err(|e| ErrorData::invalid_params(format!("{:#}", e), None))?;
+        if client.is_wallet_scoped() {
+            // 限定钱包的调用方必须用自己的钱包过滤某个 address 类型的 indexed 参数
+            let indexed = event.inputs.iter().filter(|p| p.indexed);
+            let mut scoped = false;
+            for (param, values) in indexed.zip(&topics) {
+                let (ParamType::Address, Some(values)) = (&param.kind, values) else { continue };
+                for topic in values {
+                    client.check_wallet(&Address::from(*topic))?;
+                }
+                scoped = true;
+            }
+            if !scoped {
+                return Err(ErrorData::invalid_params(
+                    format!(
+                        "Client '{}' may only query logs filtered by its wallets on an indexed address parameter",
+                        client.id
+                    ),
+                    None,
+                ));
+            }
+        }
+        let limit = args.limit.unwrap_or(DEFAULT_LOG_LIMIT);
+        if limit == 0 || limit > MAX_LOG_LIMIT {
+            return Err(ErrorData::invalid_params(
+                format!("limit must be between 1 and {}", MAX_LOG_LIMIT),
+                None,
+            ));
+        }
+        if (args.from_block.is_some() && args.from_time.is_some())
+            || (args.to_block.is_some() && args.to_time.is_some())
+        {
+            return Err(ErrorData::invalid_params("Give either a block or a time for each end of the range", None));
+        }
+
+        let mut addresses = Vec::new();
+        for a in args.addresses.as_deref().unwrap_or_default() {
+            addresses.push(self.resolve_address("address", a).await?);
+        }
+
+        let parse_time = |what: &str, s: &str| {
+            chrono::DateTime::parse_from_rfc3339(s.trim())
+                .map(|t| t.timestamp().max(0) as u64)
+                .map_err(|_| invalid_params(what, s))
+        };
+        let latest = self
+            .cache
+            .latest_block(&*self.events.provider)
+            .await
+            .map_err(internal_error)?
+            .as_u64();
+        let from_block = match (args.from_block, args.from_time.as_deref()) {
+            (_, Some(t)) => {
+                let ts = parse_time("from_time", t)?;
+                self.events.first_block_at_or_after(ts, latest).await.map_err(internal_error)?
+            }
+            (block, None) => block.unwrap_or(0),
+        };
+        let to_block = match (args.to_block, args.to_time.as_deref()) {
+            (_, Some(t)) => {
+                let ts = parse_time("to_time", t)?;
+                // 时间戳晚于 to_time 的第一个块的前一个；创世块之前时范围为空
+                match self.events.first_block_at_or_after(ts + 1, latest).await.map_err(internal_error)? {
+                    0 => return Err(ErrorData::invalid_params(format!("to_time {} is before the first block", t), None)),
+                    n => n - 1,
+                }
+            }
+            (block, None) => block.unwrap_or(latest),
+        };
+        if from_block > to_block {
+            return Err(ErrorData::invalid_params(
+                format!("Empty block range {}..{}", from_block, to_block),
+                None,
+            ));
+        }
+
+        let found = self
+            .events
+            .get_logs(&event, &addresses, &filters, from_block, to_block, limit)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(Json(LogsResult {
+            event: events::signature(&event),
+            topic0: format!("{:?}", event.signature()),
+            from_block,
+            to_block,
+            logs: found
+                .logs
+                .into_iter()
+                .map(|l| EventLogResult {
+                    block: l.log.block_number.map(|b| b.as_u64()),
+                    timestamp: l
+                        .timestamp
+                        .and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0))
+                        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+                    transaction_hash: l.log.transaction_hash.map(|h| format!("{:?}", h)),
+                    log_index: l.log.log_index.map(|i| i.as_u64()),
+                    address: format!("{:?}", l.log.address),
+                    args: l
+                        .args
+                        .into_iter()
+                        .map(|(name, kind, value)| ContractOutput { name, kind, value })
+                        .collect(),
+                })
+                .collect(),
+            skipped: found.skipped,
+            truncated: found.next_block.is_some(),
+            next_from_block: found.next_block,
+        }))
+    }
+
+    /// 从本地索引查询钱包的 ERC20 / ETH 转账历史，按块倒序分页
+    #[tool]
+    async fn get_transfer_history(
+        &self,
+        Parameters(args): Parameters<TransferHistoryArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<TransferHistoryResult>, ErrorData> {
+        let Some(indexer) = self.indexer.as_ref() else {
+            return Err(ErrorData::invalid_request(
+                "Transfer indexer is not enabled (set MCP_INDEXER_DB and MCP_INDEXER_WALLETS)",
+                None,
+            ));
+        };
+        let wallet = self.resolve_address("address", &args.address).await?;
+        client.check_wallet(&wallet)?;
+        if !indexer.follows(&wallet) {
+            return Err(ErrorData::invalid_params(format!("Wallet {:?} is not indexed", wallet), None));
+        }
+
+        let token = match args.token.as_deref() {
+            None => None,
+            Some(t) if t.eq_ignore_ascii_case("ETH") => Some(None),
+            Some(t) => Some(Some(self.resolve_address("token", t).await?)),
+        };
+        let counterparty = match args.counterparty.as_deref() {
+            None => None,
+            Some(c) => Some(self.resolve_address("counterparty", c).await?),
+        };
+        let limit = args.limit.unwrap_or(DEFAULT_TRANSFER_PAGE);
+        if limit == 0 || limit > MAX_TRANSFER_PAGE {
+            return Err(ErrorData::invalid_params(
+                format!("limit must be between 1 and {}", MAX_TRANSFER_PAGE),
+                None,
+            ));
+        }
+        let after = match args.cursor.as_deref() {
+            None => None,
+            Some(c) => Some(c.parse::<TransferCursor>().map_err(|_| invalid_params("cursor", c))?),
+        };
+
+        // 多取一条，判断还有没有下一页
+        let store = indexer.store();
+        let mut transfers = store
+            .query(&TransferQuery {
+                wallet,
+                token,
+                direction: args.direction,
+                counterparty,
+                from_block: args.from_block,
+                to_block: args.to_block,
+                after,
+                limit: limit + 1,
+            })
+            .map_err(internal_error)?;
+        let next_cursor = if transfers.len() > limit {
+            transfers.truncate(limit);
+            transfers.last().map(|t| t.cursor().to_string())
+        } else {
+            None
+        };
+
+        let chain = self.cache.chain_id(&*self.balance.provider).await.map_err(internal_error)?;
+        let mut records = Vec::with_capacity(transfers.len());
+        for t in transfers {
+            let decimals = match t.token {
+                None => Some(18),
+                Some(token) => self.balance.decimals(chain, token).await.ok(),
+            };
+            records.push(TransferRecord {
+                block: t.block,
+                timestamp: chrono::DateTime::from_timestamp(t.timestamp as i64, 0)
+                    .map(|ts| ts.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+                    .unwrap_or_default(),
+                transaction_hash: format!("{:?}", t.transaction_hash),
+                token: t.token.map_or_else(|| "ETH".to_string(), |a| format!("{:?}", a)),
+                from: format!("{:?}", t.from),
+                to: format!("{:?}", t.to),
+                direction: if t.to == wallet { Direction::In } else { Direction::Out },
+                amount: decimals.and_then(|d| ethers::utils::format_units(t.amount, d as u32).ok()),
+                amount_raw: t.amount.to_string(),
+            });
+        }
+
+        Ok(Json(TransferHistoryResult {
+            address: format!("{:?}", wallet),
+            transfers: records,
+            next_cursor,
+            indexed_to_block: store.cursor().map_err(internal_error)?,
+        }))
     }
 
     #[tool]
     async fn get_price(
         &self,
         Parameters(args): Parameters<PriceArgs>,
-    ) -> Json<PriceResult> {
-        let price: Decimal = self.price.get_price(args.token.as_deref()).await.unwrap();
-        Json(PriceResult { price: price.to_string() })
+    ) -> Result<Json<PriceResult>, ErrorData> {
+        let source = args.source.as_deref().unwrap_or("chainlink");
+        if source != "chainlink" && args.quote.is_some() {
+            return Err(ErrorData::invalid_params("quote only applies to source \"chainlink\"", None));
+        }
+        if source != "aggregate" && args.max_deviation_bps.is_some() {
+            return Err(ErrorData::invalid_params("max_deviation_bps only applies to source \"aggregate\"", None));
+        }
+        match source {
+            "chainlink" if args.window_secs.is_some() => Err(ErrorData::invalid_params(
+                "window_secs only applies to source \"twap\" or \"aggregate\"",
+                None,
+            )),
+            "chainlink" => self.chainlink_price(args).await,
+            "twap" => self.twap_price(args).await,
+            "aggregate" => self.aggregate_price(args).await,
+            other => Err(invalid_params("source", other)),
+        }
     }
 
     #[tool]
     async fn swap_tokens(
         &self,
         Parameters(args): Parameters<SwapArgs>,
-    ) -> Json<SwapResult>  {
+    ) -> Result<Json<SwapResult>, ErrorData> {
 
-        let amount_dec = Decimal::from_str(&args.amount_in).unwrap();
+        let amount_dec = Decimal::from_str(&args.amount_in)
+            .map_err(|_| invalid_params("amount_in", &args.amount_in))?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
 
-        // let amount_in = Decimal::from_f64(0.001).unwrap();
-
-        // 调用 swap_tokens
-        let (estimated_output, gas) = self
+        // 调用 swap_tokens；报价失败时不再分析池子（拿 0 去比只会得出 100% 的冲击）
+        let quote = self
             .swap
-            .swap_tokens(&args.from_token, &args.to_token,amount_dec, args.slippage)
-            .await
-            .unwrap_or((Decimal::ZERO, Decimal::ZERO));
+            .swap_tokens_at(&args.from_token, &args.to_token,amount_dec, args.slippage, block.number)
+            .await;
+        let (estimated_output, gas, price_impact_bps, warnings) = match quote {
+            Ok((output, gas)) if !output.is_zero() => {
+                let (impact, warnings) = self
+                    .explain_pool(&args, amount_dec, output, block.number)
+                    .await;
+                (output, gas, impact, warnings)
+            }
+            Ok(_) => {
+                let warning = format!("No quote for {} -> {}", args.from_token, args.to_token);
+                (Decimal::ZERO, Decimal::ZERO, None, vec![warning])
+            }
+            Err(e) => (Decimal::ZERO, Decimal::ZERO, None, vec![format!("Quote failed: {:#}", e)]),
+        };
 
-        Json(SwapResult {
+        Ok(Json(SwapResult {
             estimated_output: estimated_output.to_string(),
             gas: gas.to_string(),
-        })
+            price_impact_bps,
+            warnings,
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 在进程内 EVM 里 fork 链上状态，按 approve → swap 实际执行一遍，返回每步的 gas、事件、revert 调用栈，
+    /// 以及钱包和收款方的前后余额（能发现报价之外的转账税）
+    #[tool]
+    async fn simulate_swap(
+        &self,
+        Parameters(args): Parameters<SimulateSwapArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<SimulationResult>, ErrorData> {
+        let amount_dec = Decimal::from_str(&args.amount_in)
+            .map_err(|_| invalid_params("amount_in", &args.amount_in))?;
+        let recipient = match args.recipient.as_deref() {
+            None => self.swap.config.wallet_address,
+            Some(s) => self.resolve_address("recipient", s).await?,
+        };
+        // 结果里有发送方钱包和收款方的前后余额，两者都要在客户端的钱包范围内
+        client.check_wallet(&self.swap.config.wallet_address)?;
+        client.check_wallet(&recipient)?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let result = self
+            .swap
+            .simulate_swap_at(&args.from_token, &args.to_token, amount_dec, args.slippage, recipient, block.number)
+            .await
+            .map_err(internal_error)?;
+        let format_output = |amount: U256| {
+            ethers::utils::format_units(amount, result.output_decimals).unwrap_or_else(|_| amount.to_string())
+        };
+
+        Ok(Json(SimulationResult {
+            success: result.simulation.steps.iter().all(|s| s.success),
+            quoted_output: format_output(result.quoted_output),
+            received_output: format_output(result.received_output),
+            shortfall_bps: result.shortfall_bps(),
+            steps: result.simulation.steps.into_iter().map(Into::into).collect(),
+            balances: result.simulation.balances.into_iter().map(Into::into).collect(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 通过 V2 factory 找到两个代币的 pair，返回储备、双向现价、LP 总量和 USD 计的 TVL
+    #[tool]
+    async fn get_pool(
+        &self,
+        Parameters(args): Parameters<PoolArgs>,
+    ) -> Result<Json<PoolResult>, ErrorData> {
+        let token_a = self.resolve_token("token_a", &args.token_a).await?;
+        let token_b = self.resolve_token("token_b", &args.token_b).await?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let pool = self
+            .pool
+            .pair_at(token_a, token_b, block.number)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| {
+                ErrorData::invalid_params(format!("No Uniswap V2 pair for {}/{}", args.token_a, args.token_b), None)
+            })?;
+
+        // 按多来源中位价估值：TVL 不跟着这个 pair 自己的现价走
+        let mut tokens = Vec::new();
+        for (address, decimals) in [(pool.token0, pool.decimals0), (pool.token1, pool.decimals1)] {
+            let usd_price = self.valuation_usd_price(Some(address), block.number).await;
+            let reserve = pool.reserve(address);
+            let value = reserve.zip(usd_price).map(|(r, p)| r * p);
+            let result = PoolTokenResult {
+                address: format!("{:?}", address),
+                symbol: self.token_symbol(address),
+                decimals,
+                reserve: reserve.map(|r| r.normalize().to_string()),
+                price_in_other: pool.spot_price(address).map(|p| p.round_dp(18).normalize().to_string()),
+                usd_price: usd_price.map(|p| p.normalize().to_string()),
+            };
+            tokens.push((result, value));
+        }
+        let tvl_usd = match (tokens[0].1, tokens[1].1) {
+            (Some(a), Some(b)) => Some(a + b),
+            // V2 池子两边价值相等
+            (Some(v), None) | (None, Some(v)) => Some(v * Decimal::TWO),
+            (None, None) => None,
+        };
+        let mut tokens = tokens.into_iter().map(|(result, _)| result);
+
+        Ok(Json(PoolResult {
+            pair: format!("{:?}", pool.pair),
+            token0: tokens.next().expect("two tokens"),
+            token1: tokens.next().expect("two tokens"),
+            total_supply: ethers::utils::format_ether(pool.total_supply),
+            tvl_usd: tvl_usd.map(|v| v.round_dp(2).to_string()),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 读 Uniswap V3 池子的 slot0、活跃流动性、费率和 tickSpacing，沿已初始化的 tick 算出把价格推动 ±1% / ±2% / ±5% 需要的数量
+    #[tool]
+    async fn get_v3_pool(
+        &self,
+        Parameters(args): Parameters<V3PoolArgs>,
+    ) -> Result<Json<V3PoolResult>, ErrorData> {
+        if let Some(fee) = args.fee.filter(|fee| !V3_FEE_TIERS.contains(fee)) {
+            return Err(invalid_params("fee", &fee.to_string()));
+        }
+        if self.pool.config.uniswap_v3_factory.is_none() {
+            return Err(ErrorData::invalid_params("UNISWAP_V3_FACTORY is not set", None));
+        }
+        let token_a = self.resolve_token("token_a", &args.token_a).await?;
+        let token_b = self.resolve_token("token_b", &args.token_b).await?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let pool = self
+            .pool
+            .v3_pool_at(token_a, token_b, args.fee, block.number)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| {
+                ErrorData::invalid_params(format!("No Uniswap V3 pool for {}/{}", args.token_a, args.token_b), None)
+            })?;
+
+        let depth = DEPTH_LEVELS_BPS
+            .iter()
+            .map(|bps| {
+                let level = pool.depth(*bps);
+                DepthResult {
+                    price_change_bps: level.price_change_bps,
+                    price: significant(level.price),
+                    token_in: format!("{:?}", level.token_in),
+                    amount_in: level.amount_in.and_then(significant),
+                    token_out: format!("{:?}", level.token_out),
+                    amount_out: level.amount_out.and_then(significant),
+                }
+            })
+            .collect();
+        let price = pool.price();
+
+        Ok(Json(V3PoolResult {
+            pool: format!("{:?}", pool.pool),
+            token0: format!("{:?}", pool.token0),
+            token0_symbol: self.token_symbol(pool.token0),
+            token1: format!("{:?}", pool.token1),
+            token1_symbol: self.token_symbol(pool.token1),
+            fee: pool.fee,
+            tick_spacing: pool.tick_spacing,
+            tick: pool.tick,
+            sqrt_price_x96: pool.sqrt_price_x96.to_string(),
+            liquidity: pool.liquidity.to_string(),
+            price0_in_1: significant(price),
+            price1_in_0: (price > 0.0).then(|| 1.0 / price).and_then(significant),
+            depth,
+            initialized_ticks: pool.ticks.len(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 在 fork 上小额买入代币再转账、卖出，测出买卖税和被拦截的转账（貔貅盘），并列出 owner 能调用的暂停 / 黑名单 / 增发等函数
+    #[tool]
+    async fn check_token_risk(
+        &self,
+        Parameters(args): Parameters<TokenRiskArgs>,
+    ) -> Result<Json<TokenRiskResult>, ErrorData> {
+        let amount_eth = args.amount_eth.as_deref().unwrap_or("0.01");
+        let amount_in: U256 = ethers::utils::parse_ether(amount_eth)
+            .map_err(|_| invalid_params("amount_eth", amount_eth))?;
+        if amount_in.is_zero() {
+            return Err(invalid_params("amount_eth", amount_eth));
+        }
+        let abi: Option<ethers::abi::Abi> = match args.abi {
+            None => None,
+            Some(abi) => Some(
+                serde_json::from_value(abi)
+                    .map_err(|e| ErrorData::invalid_params(format!("Invalid abi: {}", e), None))?,
+            ),
+        };
+        let token = self.resolve_token("token", &args.token).await?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let risk = self
+            .swap
+            .check_token_risk_at(token, amount_in, abi.as_ref(), block.number)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(Json(TokenRiskResult {
+            token: format!("{:?}", risk.token),
+            honeypot: risk.is_honeypot(),
+            warnings: risk.warnings(),
+            buy_tax_bps: risk.buy_tax_bps,
+            sell_tax_bps: risk.sell_tax_bps,
+            buy_error: risk.buy_error,
+            transfer_error: risk.transfer_error,
+            sell_error: risk.sell_error,
+            amount_in: ethers::utils::format_ether(risk.amount_in),
+            bought: risk.bought.to_string(),
+            sold_for: ethers::utils::format_ether(risk.sold_for),
+            owner: risk.owner.map(|o| format!("{:?}", o)),
+            owner_functions: risk.owner_functions.into_iter().map(Into::into).collect(),
+            steps: risk.steps.into_iter().map(Into::into).collect(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
     }
 
-    pub fn new(balance: Arc<BalanceModule>, price: Arc<PriceModule>, swap: Arc<SwapModule>) -> Self {
+    /// 读取任意 ERC20 的 name / symbol / decimals / totalSupply，可选注册进代币表
+    #[tool]
+    async fn get_token_info(
+        &self,
+        Parameters(args): Parameters<TokenInfoArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Result<Json<TokenInfoResult>, ErrorData> {
+        let register = args.register.unwrap_or(false);
+        if register && !client.can_register_tokens() {
+            return Err(ErrorData::invalid_params(
+                format!("Client '{}' is not allowed to register tokens", client.id),
+                None,
+            ));
+        }
+        let address = self.resolve_address("address", &args.address).await?;
+        let price_feed: Option<Address> = match args.price_feed.as_deref() {
+            None => None,
+            Some(s) => Some(self.resolve_address("price_feed", s).await?),
+        };
+
+        let info = self.tokens.token_info(address).await.map_err(internal_error)?;
+
+        let registered_as = if register {
+            let entry = self
+                .tokens
+                .register(&info, args.symbol, price_feed)
+                .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+            Some(entry.symbol)
+        } else {
+            None
+        };
+
+        Ok(Json(TokenInfoResult {
+            address: format!("{:?}", info.address),
+            ens_name: self.primary_name(info.address).await,
+            total_supply: info.total_supply_formatted(),
+            total_supply_raw: info.total_supply.map(|s| s.to_string()),
+            name: info.name,
+            symbol: info.symbol,
+            decimals: info.decimals,
+            warnings: info.warnings,
+            registered_as,
+        }))
+    }
+
+    /// 查询当前客户端的调用次数、RPC 用量和剩余预算
+    #[tool]
+    async fn get_usage(
+        &self,
+        Parameters(_args): Parameters<UsageArgs>,
+        Extension(client): Extension<ClientIdentity>,
+    ) -> Json<UsageReport> {
+        let mut report = self.usage.report(&client);
+        report.cache = Some(self.cache.stats());
+        Json(report)
+    }
+
+    pub fn new(
+        balance: Arc<BalanceModule>,
+        price: Arc<PriceModule>,
+        swap: Arc<SwapModule>,
+        tokens: Arc<TokenModule>,
+    ) -> Self {
+         // main 里各模块共用同一个缓存，这里只用来出 metrics
+         let cache = balance.cache.clone();
+         let ens = Arc::new(EnsModule::shared(balance.provider.clone(), cache.clone()));
+         let contract = Arc::new(ContractModule::shared(balance.provider.clone(), cache.clone()));
+         let transactions = Arc::new(TransactionModule::shared(balance.provider.clone(), cache.clone()));
+         let events = Arc::new(EventModule::shared(balance.provider.clone(), cache.clone()));
+         let pool = Arc::new(
+             PoolModule::shared(balance.provider.clone(), swap.config.clone())
+                 .with_cache(cache.clone())
+                 .with_registry(swap.registry.clone()),
+         );
          Self {
             balance,
             price,
             swap,
+            tokens,
+            ens,
+            contract,
+            transactions,
+            events,
+            pool,
+            indexer: None,
+            confidence: Confidence::default(),
+            usage: Arc::new(UsageTracker::new(RateLimits::default())),
+            cache,
             tool_router: Self::tool_router(),
         }
     }
+
+    /// 替换限流/用量统计（默认不限流）
+    pub fn with_usage(mut self, usage: UsageTracker) -> Self {
+        self.usage = Arc::new(usage);
+        self
+    }
+
+    /// 替换 ENS 模块（默认复用 balance 的 provider 和缓存，连官方 registry）
+    pub fn with_ens(mut self, ens: Arc<EnsModule>) -> Self {
+        self.ens = ens;
+        self
+    }
+
+    /// 替换通用合约调用模块（默认复用 balance 的 provider 和缓存）
+    pub fn with_contract(mut self, contract: Arc<ContractModule>) -> Self {
+        self.contract = contract;
+        self
+    }
+
+    /// 替换交易查询模块（默认复用 balance 的 provider 和缓存）
+    pub fn with_transactions(mut self, transactions: Arc<TransactionModule>) -> Self {
+        self.transactions = transactions;
+        self
+    }
+
+    /// 替换事件日志查询模块（默认复用 balance 的 provider 和缓存）
+    pub fn with_events(mut self, events: Arc<EventModule>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_pool(mut self, pool: Arc<PoolModule>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// 启用本地转账索引
+    pub fn with_indexer(mut self, indexer: Arc<Indexer>) -> Self {
+        self.indexer = Some(indexer);
+        self
+    }
+
+    /// 余额 / 价格 / 报价默认读取的块（默认 latest）
+    pub fn with_confidence(mut self, confidence: Confidence) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// 按参数（不填用默认值）选出这次读取固定使用的块
+    async fn pinned_block(&self, confidence: Option<&str>) -> Result<PinnedBlock, ErrorData> {
+        let confidence = match confidence {
+            None => self.confidence,
+            Some(s) => s.parse().map_err(|_| invalid_params("confidence", s))?,
+        };
+        self.cache
+            .pinned_block(&*self.balance.provider, confidence)
+            .await
+            .map_err(internal_error)
+    }
+
+    /// 读取完成后报告的块哈希：先确认这个块没有在读取期间被重组掉
+    async fn confirmed_hash(&self, block: PinnedBlock) -> Result<String, ErrorData> {
+        self.cache
+            .confirm_pinned(&*self.balance.provider, block)
+            .await
+            .map_err(internal_error)?;
+        Ok(format!("{:?}", block.hash))
+    }
+
+    /// 按 ENS 名处理的输入：已配置 / 注册的 symbol（哪怕带点，如 "USDC.e"）优先当代币
+    fn is_ens_input(&self, input: &str) -> bool {
+        self.known_token(input).is_none() && ens::is_ens_name(input)
+    }
+
+    /// 地址参数：0x 地址或 ENS 名
+    async fn resolve_address(&self, what: &str, input: &str) -> Result<Address, ErrorData> {
+        if !ens::is_ens_name(input) {
+            return input.trim().parse().map_err(|_| invalid_params(what, input));
+        }
+        self.ens
+            .resolve(input)
+            .await
+            .map_err(|e| ErrorData::invalid_params(format!("Invalid {}: {}", what, e), None))
+    }
+
+    /// `get_price` 的 Chainlink 来源
+    async fn chainlink_price(&self, args: PriceArgs) -> Result<Json<PriceResult>, ErrorData> {
+        if let Some(quote) = args.quote.as_deref() {
+            return self.cross_rate(args.token.as_deref(), quote, args.confidence.as_deref()).await;
+        }
+        // ENS 名先解析成 feed 地址，symbol 和 0x 地址交给 PriceModule
+        let token = match args.token {
+            Some(t) if self.is_ens_input(&t) => {
+                Some(format!("{:?}", self.resolve_address("token", &t).await?))
+            }
+            other => other,
+        };
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+        let price: Decimal = self
+            .price
+            .get_price_at(token.as_deref(), block.number)
+            .await
+            .map_err(internal_error)?;
+        // feed 的计价单位和更新时间；feed 没有 description() 时不影响价格
+        let rate = match self.price.feed_rate_at(token.as_deref(), block.number).await {
+            Ok(rate) => Some(rate),
+            Err(e) => {
+                debug!("Cannot describe the feed for {:?}: {}", token, e);
+                None
+            }
+        };
+        Ok(Json(PriceResult {
+            price: price.to_string(),
+            quote: rate.as_ref().map(|r| r.quote.clone()),
+            source: "chainlink".to_string(),
+            staleness_secs: rate.as_ref().map(|r| r.staleness_secs),
+            path: rate.map(|r| r.path.into_iter().map(Into::into).collect()).unwrap_or_default(),
+            window_secs: None,
+            pools: Vec::new(),
+            sources: Vec::new(),
+            warnings: Vec::new(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// `get_price` 带 `quote` 时：沿 Chainlink feed 换算，报告路径和最旧的更新
+    async fn cross_rate(&self, token: Option<&str>, quote: &str, confidence: Option<&str>) -> Result<Json<PriceResult>, ErrorData> {
+        let base = match token {
+            None => "ETH".to_string(),
+            Some(t) => self.currency("token", t).await?,
+        };
+        let quote = self.currency("quote", quote).await?;
+        let block = self.pinned_block(confidence).await?;
+
+        let rate = self
+            .price
+            .cross_rate_at(&base, &quote, block.number)
+            .await
+            .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+        Ok(Json(PriceResult {
+            price: rate.price.to_string(),
+            quote: Some(rate.quote),
+            source: "chainlink".to_string(),
+            path: rate.path.into_iter().map(Into::into).collect(),
+            staleness_secs: Some(rate.staleness_secs),
+            window_secs: None,
+            pools: Vec::new(),
+            sources: Vec::new(),
+            warnings: Vec::new(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// 换算图里的计价单位：symbol 原样用；地址（或 ENS 名）按配置 / 注册表里的 symbol，
+    /// 都没有时当作 feed 取它的 base
+    async fn currency(&self, what: &str, input: &str) -> Result<String, ErrorData> {
+        if !input.starts_with("0x") && !self.is_ens_input(input) {
+            return Ok(price::currency(input));
+        }
+        let address = self.resolve_address(what, input).await?;
+        if let Some(symbol) = self.token_symbol(address) {
+            return Ok(price::currency(&symbol));
+        }
+        match self.price.feed_pair(address).await {
+            Ok((base, _)) => Ok(base),
+            Err(_) => Err(invalid_params(what, input)),
+        }
+    }
+
+    /// `get_price` 的 V3 TWAP 来源：对 USDC 的时间加权平均价格，必要时经 WETH 换算
+    async fn twap_price(&self, args: PriceArgs) -> Result<Json<PriceResult>, ErrorData> {
+        let window_secs = self.twap_window(args.window_secs)?;
+        if self.pool.config.uniswap_v3_factory.is_none() {
+            return Err(ErrorData::invalid_params("UNISWAP_V3_FACTORY is not set", None));
+        }
+        let token = self.resolve_token("token", args.token.as_deref().unwrap_or("ETH")).await?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let legs = self
+            .pool
+            .usd_twap_at(token, window_secs, block.number)
+            .await
+            .map_err(internal_error)?;
+        Ok(Json(PriceResult {
+            price: pool::route_price(&legs).to_string(),
+            quote: Some("USD".to_string()),
+            source: "twap".to_string(),
+            path: Vec::new(),
+            staleness_secs: None,
+            window_secs: Some(window_secs),
+            pools: legs.iter().map(|leg| format!("{:?}", leg.pool)).collect(),
+            sources: Vec::new(),
+            warnings: Vec::new(),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// `get_price` 的多来源聚合：各来源的 USD 价格、中位数和偏离过多的来源
+    async fn aggregate_price(&self, args: PriceArgs) -> Result<Json<PriceResult>, ErrorData> {
+        let window_secs = self.twap_window(args.window_secs)?;
+        let max_deviation_bps = args
+            .max_deviation_bps
+            .or(self.price.config.max_price_deviation_bps)
+            .unwrap_or(DEFAULT_MAX_DEVIATION_BPS);
+        let input = args.token.as_deref().unwrap_or("ETH");
+        let token = self.resolve_token("token", input).await?;
+        let block = self.pinned_block(args.confidence.as_deref()).await?;
+
+        let aggregated = self.aggregated_usd_price(token, window_secs, max_deviation_bps, block.number).await;
+        let Some(median) = aggregated.median else {
+            let errors: Vec<String> = aggregated
+                .sources
+                .iter()
+                .map(|s| format!("{}: {}", s.source, s.error.as_deref().unwrap_or("no price")))
+                .collect();
+            return Err(ErrorData::internal_error(
+                format!("No price source could price {}: {}", input, errors.join("; ")),
+                None,
+            ));
+        };
+        let outliers: Vec<&str> = aggregated.outliers().iter().map(|s| s.source).collect();
+        let sources = aggregated
+            .sources
+            .iter()
+            .map(|s| SourcePriceResult {
+                source: s.source.to_string(),
+                price: s.price.map(|p| p.normalize().to_string()),
+                deviation_bps: s.price.and_then(|p| aggregated.deviation_bps(p)),
+                outlier: outliers.contains(&s.source),
+                error: s.error.clone(),
+            })
+            .collect();
+        let twap_used = aggregated.sources.iter().any(|s| s.source == "v3_twap");
+
+        Ok(Json(PriceResult {
+            price: median.normalize().to_string(),
+            quote: Some("USD".to_string()),
+            source: "aggregate".to_string(),
+            path: Vec::new(),
+            staleness_secs: None,
+            window_secs: twap_used.then_some(window_secs),
+            pools: Vec::new(),
+            sources,
+            warnings: aggregated.warnings(input),
+            block: block.number.as_u64(),
+            block_hash: self.confirmed_hash(block).await?,
+        }))
+    }
+
+    /// TWAP 窗口：参数、服务配置、默认值依次取
+    fn twap_window(&self, window_secs: Option<u32>) -> Result<u32, ErrorData> {
+        let window_secs = window_secs
+            .or(self.pool.config.twap_window_secs)
+            .unwrap_or(DEFAULT_TWAP_WINDOW_SECS);
+        if window_secs == 0 {
+            return Err(invalid_params("window_secs", "0"));
+        }
+        Ok(window_secs)
+    }
+
+    /// 代币在所有可用来源上的 USD 价格：Chainlink、V2 现价、V3 TWAP（配置了 V3 factory 时）、
+    /// HTTP 接口（配置了时）；某个来源出错只记在那一项上
+    ///
+    /// 同一块内按代币、窗口和阈值缓存，HTTP 接口也只问一次。
+    async fn aggregated_usd_price(
+        &self,
+        token: Address,
+        window_secs: u32,
+        max_deviation_bps: u64,
+        block: U64,
+    ) -> AggregatedPrice {
+        let query = || self.query_usd_price_sources(token, window_secs, max_deviation_bps, block);
+        let cached = async {
+            let chain = self.cache.chain_id(&*self.balance.provider).await?;
+            let key = format!("price.aggregate:{:?}:{}:{}", token, window_secs, max_deviation_bps);
+            self.cache.at_block(chain, key, block, || async { Ok(query().await) }).await
+        };
+        match cached.await {
+            Ok(price) => price,
+            // 只有查链 ID 会失败：不缓存，直接问
+            Err(e) => {
+                debug!("Cannot cache the aggregated price of {:?}: {}", token, e);
+                query().await
+            }
+        }
+    }
+
+    /// 按服务默认的 TWAP 窗口和偏离阈值聚合的 USD 价格
+    async fn default_aggregated_usd_price(&self, token: Address, block: U64) -> AggregatedPrice {
+        let max_deviation_bps = self.price.config.max_price_deviation_bps.unwrap_or(DEFAULT_MAX_DEVIATION_BPS);
+        let window_secs = self.pool.config.twap_window_secs.unwrap_or(DEFAULT_TWAP_WINDOW_SECS);
+        self.aggregated_usd_price(token, window_secs, max_deviation_bps, block).await
+    }
+
+    /// 余额价值、授权敞口和池子 TVL 用的 USD 价格：多来源中位价，单个来源被操纵也带不偏
+    ///
+    /// ETH（`token` 为 None）按 WETH 算；没配置 WETH 时只能用默认的 ETH feed。
+    async fn valuation_usd_price(&self, token: Option<Address>, block: U64) -> Option<Decimal> {
+        match token.or(self.swap.config.token_address("WETH")) {
+            Some(address) => self.default_aggregated_usd_price(address, block).await.median,
+            None => self.usd_price(None, Some(block)).await,
+        }
+    }
+
+    /// 逐个询问各价格来源，不走缓存
+    async fn query_usd_price_sources(
+        &self,
+        token: Address,
+        window_secs: u32,
+        max_deviation_bps: u64,
+        block: U64,
+    ) -> AggregatedPrice {
+        let mut sources = vec![
+            SourcePrice::new("chainlink", self.chainlink_usd_price(token, block).await),
+            SourcePrice::new("v2_spot", self.pool.usd_spot_at(token, block).await),
+        ];
+        if self.pool.config.uniswap_v3_factory.is_some() {
+            let twap = self.pool.usd_twap_at(token, window_secs, block).await;
+            sources.push(SourcePrice::new("v3_twap", twap.map(|legs| pool::route_price(&legs))));
+        }
+        if self.price.config.price_api.is_some() {
+            let symbol = self.token_symbol(token);
+            sources.push(SourcePrice::new("api", self.price.api_price(token, symbol.as_deref()).await));
+        }
+        AggregatedPrice::new(sources, max_deviation_bps)
+    }
+
+    /// 代币的 Chainlink USD 价格：WETH 用 ETH feed，其他代币用注册时登记的 feed（不按 USD 计价的换算成 USD）
+    async fn chainlink_usd_price(&self, token: Address, block: U64) -> anyhow::Result<Decimal> {
+        let feed = if Some(token) == self.swap.config.token_address("WETH") {
+            None
+        } else {
+            let feed = self.price.registry.by_address(token).and_then(|t| t.price_feed);
+            Some(format!("{:?}", feed.ok_or_else(|| anyhow::anyhow!("No Chainlink feed registered for {:?}", token))?))
+        };
+        self.price.usd_price_at(feed.as_deref(), block).await
+    }
+
+    /// 配置或注册表里的代币 symbol；"ETH" 按 WETH 算
+    fn known_token(&self, symbol: &str) -> Option<Address> {
+        let config = &self.swap.config;
+        let symbol = if symbol == "ETH" { "WETH" } else { symbol };
+        config.token_address(symbol).or_else(|| self.swap.registry.address(symbol))
+    }
+
+    /// 代币参数：symbol、0x 地址或 ENS 名
+    async fn resolve_token(&self, what: &str, input: &str) -> Result<Address, ErrorData> {
+        match self.known_token(input) {
+            Some(address) => Ok(address),
+            None => self.resolve_address(what, input).await,
+        }
+    }
+
+    /// 地址对应的 symbol：先查配置，再查注册表
+    fn token_symbol(&self, address: Address) -> Option<String> {
+        let configured = self.swap.config.token_addresses.iter().find(|(_, a)| **a == address);
+        match configured {
+            Some((symbol, _)) => Some(symbol.clone()),
+            None => self.swap.registry.by_address(address).map(|t| t.symbol),
+        }
+    }
+
+    /// swap 报价附带的池子说明：价格冲击，以及没有池子 / 池子太浅的提示；查池子出错时不影响报价
+    async fn explain_pool(
+        &self,
+        args: &SwapArgs,
+        amount_in: Decimal,
+        estimated_output: Decimal,
+        block: U64,
+    ) -> (Option<u64>, Vec<String>) {
+        let (from_token, to_token) = (args.from_token.as_str(), args.to_token.as_str());
+        let (Some(from), Some(to)) = (self.known_token(from_token), self.known_token(to_token)) else {
+            return (None, Vec::new());
+        };
+        let pool = match self.pool.pair_at(from, to, block).await {
+            Ok(Some(pool)) => pool,
+            Ok(None) => return (None, vec![format!("No Uniswap V2 pair for {}/{}", from_token, to_token)]),
+            Err(e) => {
+                debug!("Cannot read V2 pair for {}/{}: {}", from_token, to_token, e);
+                return (None, Vec::new());
+            }
+        };
+
+        let reserves = (pool.reserve(from).unwrap_or_default(), pool.reserve(to).unwrap_or_default());
+        let describe = || {
+            format!(
+                "{} {} / {} {} in reserve",
+                reserves.0.normalize(),
+                from_token,
+                reserves.1.normalize(),
+                to_token
+            )
+        };
+        if reserves.0.is_zero() || reserves.1.is_zero() {
+            return (None, vec![format!("Uniswap V2 pair {:?} has no liquidity ({})", pool.pair, describe())]);
+        }
+        // 按两边的多来源中位价算，pair 自己的现价可能正被操纵；有一边没有中位价时再退回现价
+        let from_price = self.default_aggregated_usd_price(from, block).await;
+        let to_price = self.default_aggregated_usd_price(to, block).await;
+        let mut warnings = from_price.warnings(from_token);
+        warnings.extend(to_price.warnings(to_token));
+        let (impact, reference) = match (from_price.median, to_price.median) {
+            (Some(from_usd), Some(to_usd)) => {
+                let fair_output = amount_in.checked_mul(from_usd).and_then(|v| v.checked_div(to_usd));
+                let impact = fair_output.filter(|fair| !fair.is_zero()).and_then(|fair| {
+                    let shortfall = (fair - estimated_output).max(Decimal::ZERO);
+                    u64::try_from((shortfall / fair * Decimal::from(10_000)).round()).ok()
+                });
+                (impact, "the median price across sources")
+            }
+            _ => (pool.price_impact_bps(from, amount_in, estimated_output), "the pair's spot price"),
+        };
+        if let Some(bps) = impact.filter(|bps| *bps >= THIN_POOL_IMPACT_BPS) {
+            warnings.push(format!(
+                "Pool is thin ({}): this trade gets {}.{:02}% less than {}",
+                describe(),
+                bps / 100,
+                bps % 100,
+                reference
+            ));
+        }
+        (impact, warnings)
+    }
+
+    /// 代币的 USD 价格：ETH 用默认 feed，ERC20 用注册表里登记的 feed；没有就是 None
+    ///
+    /// `block` 为 None 时读最新块。
+    async fn usd_price(&self, token: Option<Address>, block: Option<U64>) -> Option<Decimal> {
+        let feed = match token {
+            None => None,
+            Some(address) => Some(format!("{:?}", self.price.registry.by_address(address)?.price_feed?)),
+        };
+        let result = match block {
+            None => self.price.usd_price(feed.as_deref()).await,
+            Some(block) => self.price.usd_price_at(feed.as_deref(), block).await,
+        };
+        match result {
+            Ok(price) => Some(price),
+            Err(e) => {
+                debug!("No USD price for {:?}: {}", token, e);
+                None
+            }
+        }
+    }
+
+    /// 输出里附带的 ENS 主名称；查不到或出错都不影响主结果
+    async fn primary_name(&self, address: Address) -> Option<String> {
+        match self.ens.lookup(address).await {
+            Ok(name) => name,
+            Err(e) => {
+                debug!("ENS lookup for {:?} failed: {}", address, e);
+                None
+            }
+        }
+    }
 }
 
-#[tool_handler]
+// 不用 #[tool_handler]：list_tools / call_tool 需要按调用方身份过滤
 impl ServerHandler for TokenService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
@@ -126,4 +2398,47 @@ impl ServerHandler for TokenService {
             ..Default::default()
         }
     }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        let client = ClientIdentity::from_context(&context);
+        let tools = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .filter(|tool| client.can_use_tool(&tool.name))
+            .collect();
+        Ok(ListToolsResult::with_all_items(tools))
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let client = ClientIdentity::from_context(&context);
+        // 对调用方不可见的工具，按不存在处理
+        if !client.can_use_tool(&request.name) {
+            return Err(ErrorData::invalid_params("tool not found", None));
+        }
+
+        // get_usage 不计入限流，被限流的客户端也能查自己还剩多少
+        let tool = request.name.to_string();
+        if tool != "get_usage" {
+            self.usage.check(&client, &tool).map_err(rate_limited)?;
+        }
+
+        context.extensions.insert(client.clone());
+        let tcc = ToolCallContext::new(self, request, context);
+        let rpc_calls = RpcCounter::default();
+        let result = rpc_calls.scope(self.tool_router.call(tcc)).await;
+
+        if tool != "get_usage" {
+            self.usage.record(&client, &tool, rpc_calls.get());
+        }
+        result
+    }
 }
@@ -0,0 +1,578 @@
+// src/simulate.rs
+//! 进程内 EVM（revm）模拟：从 provider 按需 fork 某个块的状态，顺序执行多笔交易
+//!
+//! 和 eth_call 相比，能把 approve → swap 这样的多步流程放在同一份状态上跑，
+//! 并给出每步的 gas、事件、revert 调用栈，以及执行前后的余额变化。
+use anyhow::{anyhow, Result};
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, Bytes, Log, H256, I256, U256, U64};
+use ethers::utils::{hex, id, keccak256};
+use revm::db::CacheDB;
+use revm::interpreter::{CallInputs, CallOutcome};
+use revm::primitives::{
+    self as evm, AccountInfo, BlockEnv, Bytecode, EVMError, ExecutionResult, SpecId, TxEnv, TxKind,
+};
+use revm::{inspector_handle_register, Database, DatabaseRef, Evm, EvmContext, Inspector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+
+use crate::abi_library::{self, DecodedEvent};
+use crate::contract::revert_reason;
+use crate::rpc::{RpcCounter, RpcProvider};
+
+/// 每笔模拟交易的 gas 上限
+const STEP_GAS_LIMIT: u64 = 30_000_000;
+
+/// 读余额这类只读调用的 gas 上限
+const VIEW_GAS_LIMIT: u64 = 1_000_000;
+
+fn evm_address(address: Address) -> evm::Address {
+    evm::Address::from(address.0)
+}
+
+fn eth_address(address: evm::Address) -> Address {
+    Address::from_slice(address.as_slice())
+}
+
+fn evm_u256(value: U256) -> evm::U256 {
+    evm::U256::from_limbs(value.0)
+}
+
+fn eth_u256(value: evm::U256) -> U256 {
+    U256(value.into_limbs())
+}
+
+/// ERC20 `Transfer(address,address,uint256)` 的 topic0
+fn transfer_topic() -> H256 {
+    H256(keccak256("Transfer(address,address,uint256)"))
+}
+
+/// 预置状态里的一个账户；没列出的 storage 槽照常从 fork 读（离线时为 0）
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    #[serde(default)]
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: u64,
+    #[serde(default)]
+    pub code: Bytes,
+    #[serde(default)]
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// 预置状态：覆盖 fork 出来的账户，或者离线模拟时的全部状态
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// 离线模拟所在的块号 / 时间戳；fork 时以节点块头为准
+    #[serde(default)]
+    pub block: u64,
+    #[serde(default)]
+    pub timestamp: u64,
+    #[serde(default)]
+    pub accounts: BTreeMap<Address, AccountSnapshot>,
+}
+
+/// 从节点读状态用的 provider 和块
+struct Remote<M> {
+    provider: Arc<M>,
+    block: BlockId,
+    handle: Handle,
+    /// 发起模拟的工具调用的 RPC 计数器；模拟跑在 blocking 线程上，task-local 传不过去
+    counter: Option<RpcCounter>,
+}
+
+impl<M: Middleware> Remote<M> {
+    fn block_on<F: std::future::Future>(&self, fut: F) -> F::Output {
+        match &self.counter {
+            Some(counter) => self.handle.block_on(counter.scope(fut)),
+            None => self.handle.block_on(fut),
+        }
+    }
+}
+
+impl<M> Clone for Remote<M> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            block: self.block,
+            handle: self.handle.clone(),
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+/// 读过的远端状态；同一个块上不会变，执行前后两份状态共用
+#[derive(Default)]
+struct Fetched {
+    accounts: HashMap<evm::Address, AccountInfo>,
+    storage: HashMap<(evm::Address, evm::U256), evm::U256>,
+}
+
+/// revm 的只读数据源：按需从节点读账户和 storage；没有 provider 时所有账户都是空的
+///
+/// 只能在 blocking 线程里用（内部用 `Handle::block_on` 等 RPC）。
+pub struct ForkDb<M> {
+    remote: Option<Remote<M>>,
+    fetched: Arc<Mutex<Fetched>>,
+}
+
+impl<M> Clone for ForkDb<M> {
+    fn clone(&self) -> Self {
+        Self { remote: self.remote.clone(), fetched: self.fetched.clone() }
+    }
+}
+
+impl<M: Middleware> DatabaseRef for ForkDb<M> {
+    type Error = anyhow::Error;
+
+    fn basic_ref(&self, address: evm::Address) -> Result<Option<AccountInfo>> {
+        let Some(remote) = &self.remote else {
+            return Ok(None);
+        };
+        if let Some(info) = self.fetched.lock().unwrap().accounts.get(&address) {
+            return Ok(Some(info.clone()));
+        }
+        let who = eth_address(address);
+        let provider = &remote.provider;
+        let (balance, nonce, code) = remote.block_on(async {
+            tokio::join!(
+                provider.get_balance(who, Some(remote.block)),
+                provider.get_transaction_count(who, Some(remote.block)),
+                provider.get_code(who, Some(remote.block)),
+            )
+        });
+        let balance = balance.map_err(|e| anyhow!("Cannot fork account {:?}: {}", who, e))?;
+        let nonce = nonce.map_err(|e| anyhow!("Cannot fork account {:?}: {}", who, e))?;
+        let code = code.map_err(|e| anyhow!("Cannot fork account {:?}: {}", who, e))?;
+
+        let bytecode = Bytecode::new_raw(code.to_vec().into());
+        let info = AccountInfo::new(evm_u256(balance), nonce.as_u64(), bytecode.hash_slow(), bytecode);
+        self.fetched.lock().unwrap().accounts.insert(address, info.clone());
+        Ok(Some(info))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: evm::B256) -> Result<Bytecode> {
+        // 代码随账户一起读进来，CacheDB 不会再按哈希来要
+        Err(anyhow!("Code {} was not loaded with its account", code_hash))
+    }
+
+    fn storage_ref(&self, address: evm::Address, index: evm::U256) -> Result<evm::U256> {
+        let Some(remote) = &self.remote else {
+            return Ok(evm::U256::ZERO);
+        };
+        if let Some(value) = self.fetched.lock().unwrap().storage.get(&(address, index)) {
+            return Ok(*value);
+        }
+        let who = eth_address(address);
+        let slot = H256(index.to_be_bytes());
+        let value = remote
+            .block_on(remote.provider.get_storage_at(who, slot, Some(remote.block)))
+            .map_err(|e| anyhow!("Cannot fork storage {:?}[{:?}]: {}", who, slot, e))?;
+        let value = evm::U256::from_be_bytes(value.0);
+        self.fetched.lock().unwrap().storage.insert((address, index), value);
+        Ok(value)
+    }
+
+    fn block_hash_ref(&self, number: evm::U256) -> Result<evm::B256> {
+        let Some(remote) = &self.remote else {
+            return Ok(evm::keccak256(number.to_be_bytes::<32>()));
+        };
+        let number = u64::try_from(number).map_err(|_| anyhow!("Invalid block number {}", number))?;
+        let block = remote
+            .block_on(remote.provider.get_block(number))
+            .map_err(|e| anyhow!("Cannot read block {}: {}", number, e))?;
+        let hash = block.and_then(|b| b.hash).unwrap_or_default();
+        Ok(evm::B256::from(hash.0))
+    }
+}
+
+/// 一笔要模拟的交易
+#[derive(Clone, Debug)]
+pub struct SimStep {
+    /// 报告里显示的名字，如 "approve"
+    pub label: String,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+}
+
+/// 调用栈里的一帧
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallFrame {
+    /// 0 是交易本身的调用
+    pub depth: usize,
+    /// "CALL" / "STATICCALL" / "DELEGATECALL" …
+    pub kind: String,
+    pub from: Address,
+    pub to: Address,
+    /// calldata 的前 4 字节
+    pub selector: Option<String>,
+    pub success: bool,
+    /// 失败时的 Error(string) / Panic 原因，解不出时为失败类型
+    pub reason: Option<String>,
+}
+
+/// 一笔交易的执行结果
+#[derive(Clone, Debug)]
+pub struct StepReport {
+    pub label: String,
+    pub success: bool,
+    pub gas_used: u64,
+    /// 返回数据（revert 时是 revert 数据）
+    pub output: Bytes,
+    /// 交易成功时发出的事件，能认出的附带解码结果
+    pub logs: Vec<(Log, Option<DecodedEvent>)>,
+    pub revert_reason: Option<String>,
+    /// 失败时的调用栈，按调用顺序；成功时为空
+    pub trace: Vec<CallFrame>,
+}
+
+/// 执行前后某个地址的余额；`token` 为 None 表示 ETH
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceDelta {
+    pub holder: Address,
+    pub token: Option<Address>,
+    pub before: U256,
+    pub after: U256,
+    /// 按 Transfer 事件累计的 (转入, 转出)；ETH 没有事件，为 None
+    pub transfers: Option<(U256, U256)>,
+}
+
+impl BalanceDelta {
+    /// after - before
+    pub fn delta(&self) -> I256 {
+        I256::from_raw(self.after) - I256::from_raw(self.before)
+    }
+
+    /// 余额实际变化里 Transfer 事件解释不了的部分；不为 0 说明代币在转账时偷偷扣了钱（或增发）
+    pub fn unexplained(&self) -> Option<I256> {
+        let (received, sent) = self.transfers?;
+        Some(self.delta() - (I256::from_raw(received) - I256::from_raw(sent)))
+    }
+}
+
+/// 一次模拟的完整结果
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub block: U64,
+    pub steps: Vec<StepReport>,
+    /// 各步发送方和关注的地址（如 swap 的收款方）在 ETH 和所有发出过 Transfer 的代币上的前后余额
+    pub balances: Vec<BalanceDelta>,
+}
+
+impl Simulation {
+    /// `holder` 在 `token`（None 为 ETH）上的前后余额
+    pub fn balance(&self, holder: Address, token: Option<Address>) -> Option<&BalanceDelta> {
+        self.balances.iter().find(|b| b.holder == holder && b.token == token)
+    }
+}
+
+/// 记录调用栈的 inspector
+#[derive(Default)]
+struct Tracer {
+    frames: Vec<CallFrame>,
+    /// 还没结束的帧在 `frames` 里的下标
+    open: Vec<usize>,
+}
+
+impl<DB: Database> Inspector<DB> for Tracer {
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.open.push(self.frames.len());
+        self.frames.push(CallFrame {
+            depth: self.open.len() - 1,
+            kind: format!("{:?}", inputs.scheme).to_uppercase(),
+            from: eth_address(inputs.caller),
+            to: eth_address(inputs.target_address),
+            selector: inputs.input.get(..4).map(|s| format!("0x{}", hex::encode(s))),
+            success: true,
+            reason: None,
+        });
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        if let Some(i) = self.open.pop() {
+            let result = outcome.instruction_result();
+            let frame = &mut self.frames[i];
+            frame.success = result.is_ok();
+            if !frame.success {
+                frame.reason = Some(revert_reason(outcome.output()).unwrap_or_else(|| format!("{:?}", result)));
+            }
+        }
+        outcome
+    }
+}
+
+/// 模拟所在的块环境
+#[derive(Clone, Copy, Debug)]
+struct SimEnv {
+    block: U64,
+    timestamp: U256,
+    chain_id: u64,
+}
+
+impl SimEnv {
+    fn block_env(&self) -> BlockEnv {
+        BlockEnv {
+            number: evm::U256::from(self.block.as_u64()),
+            timestamp: evm_u256(self.timestamp),
+            // gas 价格按 0 算，余额变化里只有转账本身
+            basefee: evm::U256::ZERO,
+            ..Default::default()
+        }
+    }
+
+    fn tx_env(&self, from: Address, to: Address, value: U256, data: Vec<u8>, gas_limit: u64) -> TxEnv {
+        TxEnv {
+            caller: evm_address(from),
+            transact_to: TxKind::Call(evm_address(to)),
+            value: evm_u256(value),
+            data: data.into(),
+            gas_limit,
+            gas_price: evm::U256::ZERO,
+            // 不检查 nonce，模拟任意账户
+            nonce: None,
+            ..Default::default()
+        }
+    }
+}
+
+/// 模拟器：一份 fork（或离线预置）的状态，加上块环境
+pub struct Simulator<M = RpcProvider> {
+    db: CacheDB<ForkDb<M>>,
+    env: SimEnv,
+    /// 除了各步发送方，还要报告余额的地址
+    watched: Vec<Address>,
+}
+
+impl<M> Clone for Simulator<M> {
+    fn clone(&self) -> Self {
+        Self { db: self.db.clone(), env: self.env, watched: self.watched.clone() }
+    }
+}
+
+impl Simulator {
+    /// 不连节点，只用预置状态；没列出的账户都是空的
+    pub fn offline(snapshot: StateSnapshot) -> Self {
+        let db = ForkDb { remote: None, fetched: Arc::default() };
+        let env = SimEnv { block: snapshot.block.into(), timestamp: snapshot.timestamp.into(), chain_id: 1 };
+        Self { db: CacheDB::new(db), env, watched: Vec::new() }.with_state(snapshot)
+    }
+}
+
+impl<M: Middleware + 'static> Simulator<M> {
+    /// fork `block` 时的链上状态；账户和 storage 用到时才读
+    pub async fn fork(provider: Arc<M>, block: U64) -> Result<Self> {
+        let header = provider
+            .get_block(block)
+            .await
+            .map_err(|e| anyhow!("Cannot read block {}: {}", block, e))?
+            .ok_or_else(|| anyhow!("Block {} not found", block))?;
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .map_err(|e| anyhow!("Cannot read chain id: {}", e))?;
+        let remote = Remote {
+            provider,
+            block: block.into(),
+            handle: Handle::current(),
+            counter: RpcCounter::current(),
+        };
+        let env = SimEnv { block, timestamp: header.timestamp, chain_id: chain_id.as_u64() };
+        Ok(Self {
+            db: CacheDB::new(ForkDb { remote: Some(remote), fetched: Arc::default() }),
+            env,
+            watched: Vec::new(),
+        })
+    }
+
+    /// 用预置状态覆盖账户（余额、nonce、代码、指定的 storage 槽）
+    pub fn with_state(mut self, snapshot: StateSnapshot) -> Self {
+        for (address, account) in snapshot.accounts {
+            let address = evm_address(address);
+            let bytecode = Bytecode::new_raw(account.code.to_vec().into());
+            let info = AccountInfo::new(evm_u256(account.balance), account.nonce, bytecode.hash_slow(), bytecode);
+            self.db.insert_account_info(address, info);
+            let storage = &mut self.db.accounts.get_mut(&address).expect("account just inserted").storage;
+            for (slot, value) in account.storage {
+                storage.insert(evm_u256(slot), evm_u256(value));
+            }
+        }
+        self
+    }
+
+    /// 结果里也报告 `holder` 的前后余额（如 swap 的收款方）
+    pub fn watch(mut self, holder: Address) -> Self {
+        self.watched.push(holder);
+        self
+    }
+
+    /// 依次执行 `steps`，每步都在上一步提交后的状态上跑；某步失败不影响后面的步骤
+    pub async fn run(self, steps: Vec<SimStep>) -> Result<Simulation> {
+        Ok(self.advance(steps).await?.1)
+    }
+
+    /// 同 `run`，但把执行后的模拟器也还回来，下一批交易可以按这一批的结果构造，接着在它的状态上跑
+    pub async fn advance(self, steps: Vec<SimStep>) -> Result<(Self, Simulation)> {
+        tokio::task::spawn_blocking(move || {
+            let mut this = self;
+            let simulation = this.run_blocking(steps)?;
+            Ok((this, simulation))
+        })
+        .await?
+    }
+
+    fn run_blocking(&mut self, steps: Vec<SimStep>) -> Result<Simulation> {
+        let mut initial = self.db.clone();
+        let mut reports = Vec::new();
+        for step in &steps {
+            reports.push(self.execute(step)?);
+        }
+
+        // 前后余额：(发送方 + 关注的地址) × (ETH + 发出过 Transfer 的代币)
+        let mut holders: Vec<Address> = Vec::new();
+        for holder in steps.iter().map(|s| s.from).chain(self.watched.iter().copied()) {
+            if !holders.contains(&holder) {
+                holders.push(holder);
+            }
+        }
+        // (代币, from, to, 数量)
+        let events: Vec<(Address, Address, Address, U256)> = reports
+            .iter()
+            .flat_map(|r| &r.logs)
+            .filter(|(log, _)| log.topics.len() == 3 && log.topics[0] == transfer_topic() && log.data.len() == 32)
+            .map(|(log, _)| {
+                let from = Address::from(log.topics[1]);
+                let to = Address::from(log.topics[2]);
+                (log.address, from, to, U256::from_big_endian(&log.data))
+            })
+            .collect();
+        let mut tokens: Vec<Option<Address>> = vec![None];
+        for (token, ..) in &events {
+            if !tokens.contains(&Some(*token)) {
+                tokens.push(Some(*token));
+            }
+        }
+
+        let mut balances = Vec::new();
+        for &holder in &holders {
+            for &token in &tokens {
+                let old = read_balance(&self.env, &mut initial, token, holder)?;
+                let new = read_balance(&self.env, &mut self.db, token, holder)?;
+                let (Some(before), Some(after)) = (old, new) else {
+                    continue;
+                };
+                let transfers = token.map(|token| {
+                    let mut flows = (U256::zero(), U256::zero());
+                    for &(_, from, to, amount) in events.iter().filter(|e| e.0 == token) {
+                        if to == holder {
+                            flows.0 += amount;
+                        }
+                        if from == holder {
+                            flows.1 += amount;
+                        }
+                    }
+                    flows
+                });
+                balances.push(BalanceDelta { holder, token, before, after, transfers });
+            }
+        }
+
+        Ok(Simulation { block: self.env.block, steps: reports, balances })
+    }
+
+    fn execute(&mut self, step: &SimStep) -> Result<StepReport> {
+        let tx = self.env.tx_env(step.from, step.to, step.value, step.data.to_vec(), STEP_GAS_LIMIT);
+        let mut evm = Evm::builder()
+            .with_db(&mut self.db)
+            .with_external_context(Tracer::default())
+            .with_spec_id(SpecId::CANCUN)
+            .modify_cfg_env(|cfg| cfg.chain_id = self.env.chain_id)
+            .with_block_env(self.env.block_env())
+            .with_tx_env(tx)
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let result = evm.transact_commit();
+        let tracer = std::mem::take(&mut evm.context.external);
+
+        let mut report = StepReport {
+            label: step.label.clone(),
+            success: false,
+            gas_used: 0,
+            output: Bytes::new(),
+            logs: Vec::new(),
+            revert_reason: None,
+            trace: Vec::new(),
+        };
+        match result {
+            Ok(ExecutionResult::Success { gas_used, logs, output, .. }) => {
+                report.success = true;
+                report.gas_used = gas_used;
+                report.output = output.into_data().to_vec().into();
+                report.logs = logs
+                    .into_iter()
+                    .map(|log| {
+                        let log = Log {
+                            address: eth_address(log.address),
+                            topics: log.data.topics().iter().map(|t| H256(t.0)).collect(),
+                            data: log.data.data.to_vec().into(),
+                            ..Default::default()
+                        };
+                        let decoded = abi_library::decode_log(&log);
+                        (log, decoded)
+                    })
+                    .collect();
+            }
+            Ok(ExecutionResult::Revert { gas_used, output }) => {
+                report.gas_used = gas_used;
+                report.revert_reason = Some(revert_reason(&output).unwrap_or_else(|| "execution reverted".to_string()));
+                report.output = output.to_vec().into();
+                report.trace = tracer.frames;
+            }
+            Ok(ExecutionResult::Halt { reason, gas_used }) => {
+                report.gas_used = gas_used;
+                report.revert_reason = Some(format!("{:?}", reason));
+                report.trace = tracer.frames;
+            }
+            // 读状态失败：整个模拟不可信，直接报错
+            Err(EVMError::Database(e)) => return Err(e),
+            // 交易本身不合法（比如余额不够付 value），当作这一步失败
+            Err(e) => report.revert_reason = Some(e.to_string()),
+        }
+        Ok(report)
+    }
+}
+
+/// ETH 余额或 `balanceOf(holder)`；代币调用失败时为 None
+fn read_balance<M: Middleware>(
+    env: &SimEnv,
+    db: &mut CacheDB<ForkDb<M>>,
+    token: Option<Address>,
+    holder: Address,
+) -> Result<Option<U256>> {
+    let Some(token) = token else {
+        let info = db.basic(evm_address(holder))?;
+        return Ok(Some(info.map_or(U256::zero(), |i| eth_u256(i.balance))));
+    };
+    let mut data = id("balanceOf(address)").to_vec();
+    data.extend_from_slice(H256::from(holder).as_bytes());
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_spec_id(SpecId::CANCUN)
+        .modify_cfg_env(|cfg| cfg.chain_id = env.chain_id)
+        .with_block_env(env.block_env())
+        .with_tx_env(env.tx_env(Address::zero(), token, U256::zero(), data, VIEW_GAS_LIMIT))
+        .build();
+    match evm.transact() {
+        Ok(outcome) => Ok(outcome
+            .result
+            .output()
+            .filter(|out| outcome.result.is_success() && out.len() >= 32)
+            .map(|out| U256::from_big_endian(&out[..32]))),
+        Err(EVMError::Database(e)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
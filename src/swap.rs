@@ -1,16 +1,21 @@
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{anyhow, Result};
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
 use ethers::types::U256;
-use ethers::utils::{format_units, parse_units};
+use ethers::abi::{self, Abi, ParamType};
+use ethers::utils::{format_units, keccak256, parse_units};
 use rust_decimal::prelude::FromPrimitive;
 use rust_decimal::Decimal;
 use std::str::FromStr;
 use std::sync::Arc;
 use tracing::info;
 
+use crate::cache::RpcCache;
 use crate::config::AppConfig;
+use crate::registry::TokenRegistry;
+use crate::rpc::{metered, RpcProvider};
+use crate::risk::{self, TokenRisk};
+use crate::simulate::{AccountSnapshot, SimStep, Simulation, Simulator, StateSnapshot, StepReport};
 
 /// 处理滑点（交易保护用）
 fn apply_slippage_wei(amount: U256, slippage_bp: u32) -> U256 {
@@ -18,19 +23,134 @@ fn apply_slippage_wei(amount: U256, slippage_bp: u32) -> U256 {
     amount * U256::from(10000 - slippage_bp) / U256::from(10000)
 }
 
-pub struct SwapModule {
-    pub provider: Arc<Provider<Http>>,
+/// 实际到账比报价少的部分，基点；没有报价时为 None
+fn shortfall_bps(quoted: U256, received: U256) -> Option<u64> {
+    if quoted.is_zero() {
+        return None;
+    }
+    let shortfall = quoted.saturating_sub(received);
+    Some((shortfall * U256::from(10_000) / quoted).as_u64())
+}
+
+/// 模拟用的临时地址：由名字派生，和任何真实账户无关
+fn probe_address(name: &str) -> Address {
+    Address::from_slice(&keccak256(name)[12..])
+}
+
+/// 失败步骤的原因；成功时为 None
+fn failure(step: &StepReport) -> Option<String> {
+    (!step.success).then(|| step.revert_reason.clone().unwrap_or_else(|| "execution failed".to_string()))
+}
+
+/// `getAmountsOut` 步骤返回的最后一个数量；失败或解不开时为 0
+fn quoted_amount(step: &StepReport) -> U256 {
+    let kind = ParamType::Array(Box::new(ParamType::Uint(256)));
+    match abi::decode(&[kind], &step.output) {
+        Ok(tokens) if step.success => tokens
+            .into_iter()
+            .next()
+            .and_then(|t| t.into_array())
+            .and_then(|amounts| amounts.last().cloned()?.into_uint())
+            .unwrap_or_default(),
+        _ => U256::zero(),
+    }
+}
+
+/// 报价结果和构造好（未发送）的 swap 交易
+struct SwapPlan {
+    from_addr: Address,
+    to_addr: Address,
+    amount_in_wei: U256,
+    estimated_wei: U256,
+    tx: TypedTransaction,
+}
+
+/// `simulate_swap_at` 的结果：报价和实际到账，加上完整的模拟过程
+#[derive(Clone, Debug)]
+pub struct SwapSimulation {
+    pub amount_in: U256,
+    /// router `getAmountsOut` 的报价
+    pub quoted_output: U256,
+    /// 收款方余额实际增加的数量；比报价少说明输出代币转账时扣了税
+    pub received_output: U256,
+    pub output_decimals: u32,
+    pub simulation: Simulation,
+}
+
+impl SwapSimulation {
+    /// 实际到账比报价少的部分，基点；swap 失败时为 None
+    pub fn shortfall_bps(&self) -> Option<u64> {
+        if !self.simulation.steps.iter().all(|s| s.success) {
+            return None;
+        }
+        shortfall_bps(self.quoted_output, self.received_output)
+    }
+}
+
+/// Uniswap V2 报价模拟；provider 默认是计数的 HTTP provider，测试里可以换成任意 `Middleware`
+pub struct SwapModule<M = RpcProvider> {
+    pub provider: Arc<M>,
     pub config: AppConfig,
+    pub cache: Arc<RpcCache>,
+    pub registry: Arc<TokenRegistry>,
 }
 
 impl SwapModule {
     pub fn new(provider: Provider<Http>, config: AppConfig) -> Self {
+        Self::shared(Arc::new(metered(provider)), config)
+    }
+}
+
+impl<M: Middleware + 'static> SwapModule<M> {
+    /// 复用已经包好的 provider
+    pub fn shared(provider: Arc<M>, config: AppConfig) -> Self {
         Self {
-            provider: Arc::new(provider),
+            provider,
             config,
+            cache: Arc::new(RpcCache::default()),
+            registry: Arc::new(TokenRegistry::default()),
         }
     }
 
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 和 TokenModule / PriceModule 共用同一个注册表
+    pub fn with_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// symbol → 代币地址：先查配置，再查运行时注册表
+    fn token_address(&self, symbol: &str) -> Result<Address> {
+        self.config
+            .token_address(symbol)
+            .or_else(|| self.registry.address(symbol))
+            .ok_or_else(|| anyhow!("Unknown token: {}", symbol))
+    }
+
+    /// ERC20 decimals，永久缓存
+    async fn decimals(&self, token: Address) -> Result<u32> {
+        abigen!(
+            ERC20,
+            r#"[
+                function decimals() view returns (uint8)
+            ]"#
+        );
+
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let decimals: u8 = self
+            .cache
+            .immutable(chain, format!("erc20.decimals:{:?}", token), || async {
+                Ok(ERC20::new(token, self.provider.clone()).decimals().call().await?)
+            })
+            .await?;
+        Ok(decimals as u32)
+    }
+
     /// 模拟 V2 swap
     /// from_token / to_token: 传名称即可，比如 "ETH", "USDC", "BTC"
     /// 返回 (estimated_output, gas_estimate)
@@ -41,20 +161,269 @@ impl SwapModule {
         amount_in: Decimal,
         slippage: f64,
     ) -> Result<(Decimal, Decimal)> {
+        let block = self.cache.latest_block(&*self.provider).await?;
+        self.swap_tokens_at(from_token, to_token, amount_in, slippage, block).await
+    }
+
+    /// 同 `swap_tokens`，报价和 gas 估算都基于 `block` 的状态
+    pub async fn swap_tokens_at(
+        &self,
+        from_token: &str,
+        to_token: &str,
+        amount_in: Decimal,
+        slippage: f64,
+        block: U64,
+    ) -> Result<(Decimal, Decimal)> {
+        let wallet = self.config.wallet_address;
+        let Some(plan) = self.plan_swap(from_token, to_token, amount_in, slippage, wallet, block).await? else {
+            return Ok((Decimal::ZERO, Decimal::ZERO));
+        };
+
+        // -------------------------------
+        // 模拟调用 eth_call 获取输出（可选）
+        // -------------------------------
+        let _return_bytes = self.provider.call(&plan.tx, Some(block.into())).await?;
+
+        // -------------------------------
+        // 估算 gas
+        // -------------------------------
+        let gas = self.provider.estimate_gas(&plan.tx, Some(block.into())).await?;
+        let gas_dec = Decimal::from_u128(gas.as_u128()).unwrap();
+
+        let to_decimals: u32 = self.decimals(plan.to_addr).await?;
+        let est_dec = Decimal::from_str(&format_units(plan.estimated_wei, to_decimals)?)?;
+        Ok((est_dec, gas_dec))
+    }
+
+    /// 在 `block` 的 fork 上用进程内 EVM 跑一遍：卖 ERC20 时先 approve router，再 swap，输出打给 `recipient`
+    ///
+    /// 和 `swap_tokens_at` 不同，能看到每步的 gas、事件、revert 调用栈，以及钱包和收款方的前后余额；
+    /// 收款方实际到账和报价的差额能暴露转账扣税的代币。
+    pub async fn simulate_swap_at(
+        &self,
+        from_token: &str,
+        to_token: &str,
+        amount_in: Decimal,
+        slippage: f64,
+        recipient: Address,
+        block: U64,
+    ) -> Result<SwapSimulation> {
         abigen!(
-            UniswapV2Router,
+            ERC20Approve,
+            r#"[
+                function approve(address spender, uint256 amount) returns (bool)
+            ]"#
+        );
+
+        let plan = self
+            .plan_swap(from_token, to_token, amount_in, slippage, recipient, block)
+            .await?
+            .ok_or_else(|| anyhow!("No quote for {} -> {}", from_token, to_token))?;
+        let wallet = self.config.wallet_address;
+
+        let mut steps = Vec::new();
+        if from_token != "ETH" {
+            let token = ERC20Approve::new(plan.from_addr, self.provider.clone());
+            let approve = token.approve(self.config.uniswap_v2_router, plan.amount_in_wei);
+            steps.push(SimStep {
+                label: "approve".to_string(),
+                from: wallet,
+                to: plan.from_addr,
+                value: U256::zero(),
+                data: approve.calldata().unwrap_or_default(),
+            });
+        }
+        steps.push(SimStep {
+            label: "swap".to_string(),
+            from: wallet,
+            to: self.config.uniswap_v2_router,
+            value: plan.tx.value().copied().unwrap_or_default(),
+            data: plan.tx.data().cloned().unwrap_or_default(),
+        });
+
+        let simulation = Simulator::fork(self.provider.clone(), block)
+            .await?
+            .watch(recipient)
+            .run(steps)
+            .await?;
+        let output_token = (to_token != "ETH").then_some(plan.to_addr);
+        let received = simulation
+            .balance(recipient, output_token)
+            .filter(|b| b.after > b.before)
+            .map_or(U256::zero(), |b| b.after - b.before);
+        Ok(SwapSimulation {
+            amount_in: plan.amount_in_wei,
+            quoted_output: plan.estimated_wei,
+            received_output: received,
+            output_decimals: self.decimals(plan.to_addr).await?,
+            simulation,
+        })
+    }
+
+    /// 在 `block` 的 fork 上用 `amount_in` wei 的 ETH 小额买入 `token`，再分别试一次钱包间转账和全部卖回 ETH
+    ///
+    /// 买家是预置了 ETH 的临时地址；买卖都走 router 的 SupportingFeeOnTransferTokens 版本、最小输出为 0，
+    /// 这样扣税的代币也能成交，税率按报价和实际到账的差额算。另外按 ABI / 字节码列出 owner 能调用的危险函数。
+    pub async fn check_token_risk_at(
+        &self,
+        token: Address,
+        amount_in: U256,
+        abi: Option<&Abi>,
+        block: U64,
+    ) -> Result<TokenRisk> {
+        abigen!(
+            FeeOnTransferRouter,
             r#"[
                 function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts)
-                function swapExactTokensForTokens(uint256 amountIn,uint256 amountOutMin,address[] path,address to,uint256 deadline) external returns (uint256[] amounts)
-                function swapExactETHForTokens(uint256 amountOutMin,address[] path,address to,uint256 deadline) external payable returns (uint256[] amounts)
-                function swapExactTokensForETH(uint256 amountIn,uint256 amountOutMin,address[] path,address to,uint256 deadline) external returns (uint256[] amounts)
+                function swapExactETHForTokensSupportingFeeOnTransferTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) external payable
+                function swapExactTokensForETHSupportingFeeOnTransferTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external
+            ]"#
+        );
+        abigen!(
+            OwnableERC20,
+            r#"[
+                function transfer(address to, uint256 amount) returns (bool)
+                function approve(address spender, uint256 amount) returns (bool)
+                function owner() view returns (address)
             ]"#
         );
 
+        let weth = self.config.token_address("WETH").ok_or_else(|| anyhow!("WETH not set"))?;
+        let router_addr = self.config.uniswap_v2_router;
+        let router = FeeOnTransferRouter::new(router_addr, self.provider.clone());
+        let erc20 = OwnableERC20::new(token, self.provider.clone());
+        let trader = probe_address("eth-mcp-server/check_token_risk/trader");
+        let receiver = probe_address("eth-mcp-server/check_token_risk/receiver");
+
+        let code = self.provider.get_code(token, Some(block.into())).await?;
+        let owner = erc20.owner().block(block).call().await.ok();
+        let mut report = TokenRisk {
+            token,
+            trader,
+            amount_in,
+            buy_quote: U256::zero(),
+            bought: U256::zero(),
+            sell_quote: U256::zero(),
+            sold_for: U256::zero(),
+            buy_tax_bps: None,
+            sell_tax_bps: None,
+            buy_error: None,
+            transfer_error: None,
+            sell_error: None,
+            owner,
+            owner_functions: risk::owner_functions(&code, abi),
+            steps: Vec::new(),
+            code,
+        };
+        if report.code.is_empty() {
+            return Ok(report);
+        }
+
+        let header = self
+            .provider
+            .get_block(block)
+            .await?
+            .ok_or_else(|| anyhow!("Block {} not found", block))?;
+        let deadline = header.timestamp + 600;
+        let step = |label: &str, to: Address, value: U256, data: Option<Bytes>| SimStep {
+            label: label.to_string(),
+            from: trader,
+            to,
+            value,
+            data: data.unwrap_or_default(),
+        };
+
+        // 1. 买入：先在同一份状态上报价，再实际成交
+        let funded = StateSnapshot {
+            accounts: [(trader, AccountSnapshot { balance: amount_in, ..Default::default() })].into(),
+            ..Default::default()
+        };
+        let buy_path = vec![weth, token];
+        let quote = router.get_amounts_out(amount_in, buy_path.clone());
+        let buy = router.swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+            U256::zero(),
+            buy_path,
+            trader,
+            deadline,
+        );
+        let (simulator, bought) = Simulator::fork(self.provider.clone(), block)
+            .await?
+            .with_state(funded)
+            .advance(vec![
+                step("quote_buy", router_addr, U256::zero(), quote.calldata()),
+                step("buy", router_addr, amount_in, buy.calldata()),
+            ])
+            .await?;
+        report.buy_quote = quoted_amount(&bought.steps[0]);
+        report.bought = bought
+            .balance(trader, Some(token))
+            .filter(|b| b.after > b.before)
+            .map_or(U256::zero(), |b| b.after - b.before);
+        report.buy_error = failure(&bought.steps[1]);
+        if report.buy_error.is_none() {
+            report.buy_tax_bps = shortfall_bps(report.buy_quote, report.bought);
+        }
+        report.steps.extend(bought.steps);
+        if report.buy_error.is_some() || report.bought.is_zero() {
+            return Ok(report);
+        }
+
+        // 2. 买入后的状态上分别试：转给另一个地址；approve 后全部卖回 ETH
+        let transfer = erc20.transfer(receiver, report.bought);
+        let transferred = simulator
+            .clone()
+            .run(vec![step("transfer", token, U256::zero(), transfer.calldata())])
+            .await?;
+        report.transfer_error = failure(&transferred.steps[0]);
+        report.steps.extend(transferred.steps);
+
+        let sell_path = vec![token, weth];
+        let approve = erc20.approve(router_addr, report.bought);
+        let quote = router.get_amounts_out(report.bought, sell_path.clone());
+        let sell = router.swap_exact_tokens_for_eth_supporting_fee_on_transfer_tokens(
+            report.bought,
+            U256::zero(),
+            sell_path,
+            trader,
+            deadline,
+        );
+        let sold = simulator
+            .run(vec![
+                step("approve", token, U256::zero(), approve.calldata()),
+                step("quote_sell", router_addr, U256::zero(), quote.calldata()),
+                step("sell", router_addr, U256::zero(), sell.calldata()),
+            ])
+            .await?;
+        report.sell_quote = quoted_amount(&sold.steps[1]);
+        report.sold_for = sold
+            .balance(trader, None)
+            .filter(|b| b.after > b.before)
+            .map_or(U256::zero(), |b| b.after - b.before);
+        report.sell_error = failure(&sold.steps[0]).or_else(|| failure(&sold.steps[2]));
+        if report.sell_error.is_none() {
+            report.sell_tax_bps = shortfall_bps(report.sell_quote, report.sold_for);
+        }
+        report.steps.extend(sold.steps);
+        Ok(report)
+    }
+
+    /// 报价并构造 swap 交易；router 报不出价（没有池子等）时为 None
+    async fn plan_swap(
+        &self,
+        from_token: &str,
+        to_token: &str,
+        amount_in: Decimal,
+        slippage: f64,
+        recipient: Address,
+        block: U64,
+    ) -> Result<Option<SwapPlan>> {
         abigen!(
-            ERC20,
+            UniswapV2Router,
             r#"[
-                function decimals() view returns (uint8)
+                function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts)
+                function swapExactTokensForTokens(uint256 amountIn,uint256 amountOutMin,address[] path,address to,uint256 deadline) external returns (uint256[] amounts)
+                function swapExactETHForTokens(uint256 amountOutMin,address[] path,address to,uint256 deadline) external payable returns (uint256[] amounts)
+                function swapExactTokensForETH(uint256 amountIn,uint256 amountOutMin,address[] path,address to,uint256 deadline) external returns (uint256[] amounts)
             ]"#
         );
 
@@ -67,16 +436,12 @@ impl SwapModule {
         let from_addr = if from_token == "ETH" {
             weth_addr
         } else {
-            self.config
-                .token_address(from_token)
-                .expect("Invalid from_token")
+            self.token_address(from_token)?
         };
         let to_addr = if to_token == "ETH" {
             weth_addr
         } else {
-            self.config
-                .token_address(to_token)
-                .expect("Invalid to_token")
+            self.token_address(to_token)?
         };
 
         let is_eth_to_token = from_token == "ETH";
@@ -85,10 +450,7 @@ impl SwapModule {
         // -------------------------------
         // 获取 decimals
         // -------------------------------
-        let from_decimals: u32 = ERC20::new(from_addr, self.provider.clone())
-            .decimals()
-            .call()
-            .await? as u32;
+        let from_decimals: u32 = self.decimals(from_addr).await?;
 
         // -------------------------------
         // 构造 path + amount_in
@@ -102,12 +464,13 @@ impl SwapModule {
         info!("amount_in_wei: {:#?}", amount_in_wei);
         let amounts_out = match router
             .get_amounts_out(amount_in_wei, path.clone())
+            .block(block)
             .call()
             .await
         {
             Ok(res) => res,
             Err(e) => {
-                return Ok((Decimal::ZERO, Decimal::ZERO));
+                return Ok(None);
             }
         };
         // 用整数计算滑点
@@ -122,13 +485,19 @@ impl SwapModule {
         // -------------------------------
         // 构造交易（模拟，不发送）
         // -------------------------------
-        let deadline = U256::from((Utc::now().timestamp() + 600) as u64);
+        // deadline 取模拟所在块的时间 + 10 分钟，同一块上的模拟结果（和录制的请求）可重现
+        let header = self
+            .provider
+            .get_block(block)
+            .await?
+            .ok_or_else(|| anyhow!("Block {} not found", block))?;
+        let deadline = header.timestamp + 600;
         let tx: TypedTransaction = if is_eth_to_token {
             router
                 .swap_exact_eth_for_tokens(
                     min_u256,
                     path.clone(),
-                    self.config.wallet_address,
+                    recipient,
                     deadline,
                 )
                 .value(amount_in_wei)
@@ -139,7 +508,7 @@ impl SwapModule {
                     amount_in_wei,
                     min_u256,
                     path.clone(),
-                    self.config.wallet_address,
+                    recipient,
                     deadline,
                 )
                 .tx
@@ -149,28 +518,12 @@ impl SwapModule {
                     amount_in_wei,
                     min_u256,
                     path.clone(),
-                    self.config.wallet_address,
+                    recipient,
                     deadline,
                 )
                 .tx
         };
 
-        // -------------------------------
-        // 模拟调用 eth_call 获取输出（可选）
-        // -------------------------------
-        let _return_bytes = self.provider.call(&tx, None).await?;
-
-        // -------------------------------
-        // 估算 gas
-        // -------------------------------
-        let gas = self.provider.estimate_gas(&tx, None).await?;
-        let gas_dec = Decimal::from_u128(gas.as_u128()).unwrap();
-
-        let to_decimals: u32 = ERC20::new(to_addr, self.provider.clone())
-            .decimals()
-            .call()
-            .await? as u32;
-        let est_dec = Decimal::from_str(&format_units(estimated_wei, to_decimals)?)?;
-        Ok((est_dec, gas_dec))
+        Ok(Some(SwapPlan { from_addr, to_addr, amount_in_wei, estimated_wei, tx }))
     }
 }
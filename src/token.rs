@@ -0,0 +1,204 @@
+// src/token.rs
+use anyhow::{bail, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::format_units;
+use std::sync::Arc;
+
+use crate::cache::RpcCache;
+use crate::registry::{TokenEntry, TokenRegistry};
+use crate::rpc::{metered, view_call, RpcProvider};
+
+const NAME_SELECTOR: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+const SYMBOL_SELECTOR: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+const TOTAL_SUPPLY_SELECTOR: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+
+/// 链上读到的代币元数据；读不到的字段为 None，原因写在 warnings 里
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub address: Address,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub total_supply: Option<U256>,
+    pub warnings: Vec<String>,
+}
+
+impl TokenMetadata {
+    /// 按 decimals 格式化后的 totalSupply
+    pub fn total_supply_formatted(&self) -> Option<String> {
+        let supply = self.total_supply?;
+        let decimals = self.decimals?;
+        format_units(supply, decimals as u32).ok()
+    }
+}
+
+/// 解码 name()/symbol() 的返回值
+///
+/// 标准 ERC20 返回 `string`，MKR、SAI 等早期合约返回 `bytes32`（右侧补 0）。
+pub fn decode_string_or_bytes32(data: &[u8]) -> Option<String> {
+    if data.len() >= 64 {
+        if let Ok(tokens) = abi::decode(&[ParamType::String], data) {
+            if let Some(Token::String(s)) = tokens.into_iter().next() {
+                return Some(s).filter(|s| !s.is_empty());
+            }
+        }
+    }
+
+    if data.len() == 32 {
+        let end = data.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        return std::str::from_utf8(&data[..end])
+            .ok()
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty());
+    }
+
+    None
+}
+
+/// 解码 decimals()：有的合约返回 uint256 而不是 uint8
+pub fn decode_decimals(data: &[u8]) -> Option<u8> {
+    if data.len() < 32 {
+        return None;
+    }
+    let value = U256::from_big_endian(&data[..32]);
+    (value <= U256::from(u8::MAX)).then(|| value.as_u32() as u8)
+}
+
+pub struct TokenModule {
+    pub provider: Arc<RpcProvider>,
+    pub cache: Arc<RpcCache>,
+    pub registry: Arc<TokenRegistry>,
+}
+
+impl TokenModule {
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+
+    /// 复用已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, cache: Arc<RpcCache>) -> Self {
+        Self {
+            provider,
+            cache,
+            registry: Arc::new(TokenRegistry::default()),
+        }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 和 PriceModule / SwapModule 共用同一个注册表
+    pub fn with_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// 读取任意 ERC20 的 name / symbol / decimals / totalSupply
+    pub async fn token_info(&self, address: Address) -> Result<TokenMetadata> {
+        let chain = self.cache.chain_id(&*self.provider).await?;
+        let block = self.cache.latest_block(&*self.provider).await?;
+
+        let code = self.provider.get_code(address, Some(block.into())).await?;
+        if code.is_empty() {
+            bail!("{:?} is not a contract", address);
+        }
+
+        let mut warnings = Vec::new();
+
+        // name / symbol / decimals 不会变，永久缓存
+        let name: Option<String> = self
+            .cache
+            .immutable(chain, format!("token.name:{:?}", address), || async {
+                let data = self.call_raw(address, NAME_SELECTOR, None).await?;
+                Ok(data.as_deref().and_then(decode_string_or_bytes32))
+            })
+            .await?;
+        if name.is_none() {
+            warnings.push("name() is missing or not a string/bytes32".to_string());
+        }
+
+        let symbol: Option<String> = self
+            .cache
+            .immutable(chain, format!("token.symbol:{:?}", address), || async {
+                let data = self.call_raw(address, SYMBOL_SELECTOR, None).await?;
+                Ok(data.as_deref().and_then(decode_string_or_bytes32))
+            })
+            .await?;
+        if symbol.is_none() {
+            warnings.push("symbol() is missing or not a string/bytes32".to_string());
+        }
+
+        let decimals: Option<u8> = self
+            .cache
+            .immutable(chain, format!("token.decimals:{:?}", address), || async {
+                let data = self.call_raw(address, DECIMALS_SELECTOR, None).await?;
+                Ok(data.as_deref().and_then(decode_decimals))
+            })
+            .await?;
+        if decimals.is_none() {
+            warnings.push("decimals() is missing; amounts are shown in raw units".to_string());
+        }
+
+        let total_supply: Option<U256> = self
+            .cache
+            .at_block(chain, format!("token.totalSupply:{:?}", address), block, || async {
+                let data = self.call_raw(address, TOTAL_SUPPLY_SELECTOR, Some(block)).await?;
+                Ok(data.filter(|d| d.len() >= 32).map(|d| U256::from_big_endian(&d[..32])))
+            })
+            .await?;
+        if total_supply.is_none() {
+            warnings.push("totalSupply() is missing".to_string());
+        }
+
+        Ok(TokenMetadata {
+            address,
+            name,
+            symbol,
+            decimals,
+            total_supply,
+            warnings,
+        })
+    }
+
+    /// 把读到的代币写进注册表，之后 swap_tokens / get_price 可以按 symbol 使用
+    pub fn register(
+        &self,
+        info: &TokenMetadata,
+        symbol: Option<String>,
+        price_feed: Option<Address>,
+    ) -> Result<TokenEntry> {
+        let Some(symbol) = symbol.or_else(|| info.symbol.clone()) else {
+            bail!("Token {:?} has no symbol; pass one explicitly to register it", info.address);
+        };
+
+        let entry = TokenEntry {
+            symbol,
+            address: info.address,
+            decimals: info.decimals,
+            price_feed,
+        };
+        self.registry.register(entry.clone())?;
+        Ok(entry)
+    }
+
+    /// 无参数的 view 调用；合约 revert 时返回 None，网络错误照常返回 Err
+    async fn call_raw(
+        &self,
+        to: Address,
+        selector: [u8; 4],
+        block: Option<U64>,
+    ) -> Result<Option<Bytes>> {
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(to)
+            .data(selector.to_vec())
+            .into();
+
+        view_call(&self.provider, &tx, block).await
+    }
+}
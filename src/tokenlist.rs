@@ -0,0 +1,222 @@
+// src/tokenlist.rs
+use anyhow::{bail, Context, Result};
+use ethers::types::Address;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::registry::TokenEntry;
+
+/// Uniswap token list（https://tokenlists.org）
+///
+/// 字段和约束对应官方 tokenlist.schema.json；未知字段直接拒绝。
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct TokenList {
+    pub name: String,
+    pub timestamp: String,
+    pub version: TokenListVersion,
+    pub tokens: Vec<TokenListToken>,
+    #[serde(default)]
+    pub token_map: Option<serde_json::Value>,
+    #[serde(default)]
+    pub keywords: Option<Vec<String>>,
+    #[serde(default)]
+    pub tags: Option<serde_json::Value>,
+    #[serde(default, rename = "logoURI")]
+    pub logo_uri: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenListVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct TokenListToken {
+    pub chain_id: u64,
+    pub address: String,
+    pub decimals: u64,
+    pub name: String,
+    pub symbol: String,
+    #[serde(default, rename = "logoURI")]
+    pub logo_uri: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl TokenList {
+    /// 解析并按 schema 校验
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let list: TokenList = serde_json::from_str(raw)?;
+        let errors = list.validate();
+        if !errors.is_empty() {
+            bail!("Token list {:?} does not match the schema:\n  {}", list.name, errors.join("\n  "));
+        }
+        Ok(list)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read token list {}", path.display()))?;
+        Self::from_json(&raw).with_context(|| format!("Invalid token list {}", path.display()))
+    }
+
+    /// schema 里 serde 表达不了的约束（长度、格式、取值范围）
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !(1..=30).contains(&self.name.chars().count()) || !is_list_name(&self.name) {
+            errors.push(format!("name: {:?} must be 1-30 word characters or spaces", self.name));
+        }
+        if chrono::DateTime::parse_from_rfc3339(&self.timestamp).is_err() {
+            errors.push(format!("timestamp: {:?} is not an RFC 3339 date-time", self.timestamp));
+        }
+        if !(1..=10_000).contains(&self.tokens.len()) {
+            errors.push(format!("tokens: expected 1-10000 tokens, got {}", self.tokens.len()));
+        }
+        if let Some(keywords) = &self.keywords {
+            if keywords.len() > 20 {
+                errors.push("keywords: at most 20 keywords".to_string());
+            }
+        }
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            let at = |field: &str| format!("tokens[{}].{}", i, field);
+
+            if token.chain_id == 0 {
+                errors.push(format!("{}: must be >= 1", at("chainId")));
+            }
+            if !is_hex_address(&token.address) {
+                errors.push(format!("{}: {:?} is not a 0x-prefixed 20-byte address", at("address"), token.address));
+            }
+            if token.decimals > 255 {
+                errors.push(format!("{}: {} is out of range 0-255", at("decimals"), token.decimals));
+            }
+            if token.name.chars().count() > 60 {
+                errors.push(format!("{}: longer than 60 characters", at("name")));
+            }
+            let symbol_len = token.symbol.chars().count();
+            if !(1..=20).contains(&symbol_len) || token.symbol.chars().any(char::is_whitespace) {
+                errors.push(format!("{}: {:?} must be 1-20 characters without whitespace", at("symbol"), token.symbol));
+            }
+            if token.tags.as_ref().is_some_and(|tags| tags.len() > 10) {
+                errors.push(format!("{}: at most 10 tags", at("tags")));
+            }
+        }
+
+        errors
+    }
+}
+
+fn is_list_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ')
+}
+
+fn is_hex_address(s: &str) -> bool {
+    s.len() == 42 && s.starts_with("0x") && s[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 合并结果：进注册表的代币，以及合并时发现的问题
+#[derive(Debug, Default)]
+pub struct MergedTokens {
+    pub tokens: Vec<TokenEntry>,
+    pub issues: Vec<String>,
+}
+
+/// 合并多个列表中指定链的代币
+///
+/// - 前面的列表优先：同一个 symbol 指向不同地址时保留先出现的
+/// - 同一个地址以不同 symbol 出现时保留先出现的
+/// - `overrides`（配置里显式写的地址）优先于所有列表
+///
+/// 被丢弃的条目都记在 `issues` 里。
+pub fn merge_token_lists(
+    lists: &[TokenList],
+    chain_id: u64,
+    overrides: &HashMap<String, Address>,
+) -> MergedTokens {
+    let overrides: HashMap<String, Address> = overrides
+        .iter()
+        .map(|(symbol, address)| (symbol.to_uppercase(), *address))
+        .collect();
+
+    let mut merged = MergedTokens::default();
+    // symbol（大写）→ (地址, 来源列表)
+    let mut by_symbol: HashMap<String, (Address, &str)> = HashMap::new();
+    // 地址 → (symbol, 来源列表)
+    let mut by_address: HashMap<Address, (String, &str)> = HashMap::new();
+
+    for list in lists {
+        for token in list.tokens.iter().filter(|t| t.chain_id == chain_id) {
+            // validate 已经检查过格式
+            let Ok(address) = token.address.parse::<Address>() else { continue };
+            let key = token.symbol.to_uppercase();
+
+            if let Some(configured) = overrides.get(&key) {
+                if *configured != address {
+                    merged.issues.push(format!(
+                        "{}: {} {:?} is overridden by configured address {:?}",
+                        list.name, token.symbol, address, configured
+                    ));
+                    continue;
+                }
+            }
+
+            match (by_symbol.get(&key), by_address.get(&address)) {
+                // 多个列表里的同一个代币
+                (Some((existing, _)), _) if *existing == address => continue,
+                (Some((existing, source)), _) => {
+                    merged.issues.push(format!(
+                        "{}: symbol {} {:?} conflicts with {:?} from {}",
+                        list.name, token.symbol, address, existing, source
+                    ));
+                    continue;
+                }
+                (None, Some((symbol, source))) => {
+                    merged.issues.push(format!(
+                        "{}: address {:?} ({}) is already listed as {} by {}",
+                        list.name, address, token.symbol, symbol, source
+                    ));
+                    continue;
+                }
+                (None, None) => {}
+            }
+
+            by_symbol.insert(key, (address, &list.name));
+            by_address.insert(address, (token.symbol.clone(), &list.name));
+            merged.tokens.push(TokenEntry {
+                symbol: token.symbol.clone(),
+                address,
+                decimals: u8::try_from(token.decimals).ok(),
+                price_feed: None,
+            });
+        }
+    }
+
+    merged
+}
+
+/// 读取并合并多个列表文件
+pub fn load_token_lists(
+    paths: &[String],
+    chain_id: u64,
+    overrides: &HashMap<String, Address>,
+) -> Result<MergedTokens> {
+    let lists = paths
+        .iter()
+        .map(TokenList::load)
+        .collect::<Result<Vec<_>>>()?;
+    // 多半是 MCP_CHAIN_ID 配错了
+    if !lists.is_empty() && !lists.iter().flat_map(|l| &l.tokens).any(|t| t.chain_id == chain_id) {
+        bail!("No token in {:?} is on chain {}", paths, chain_id);
+    }
+    Ok(merge_token_lists(&lists, chain_id, overrides))
+}
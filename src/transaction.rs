@@ -0,0 +1,117 @@
+// src/transaction.rs
+use anyhow::Result;
+use ethers::prelude::*;
+use rmcp::schemars;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::abi_library::{self, DecodedCall, DecodedEvent};
+use crate::cache::RpcCache;
+use crate::rpc::{metered, RpcProvider};
+
+/// 交易执行状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    /// 还在 mempool 里
+    Pending,
+    Success,
+    Failed,
+}
+
+/// 一笔交易、它的回执和解码结果
+#[derive(Clone, Debug)]
+pub struct TransactionDetails {
+    pub transaction: Transaction,
+    pub receipt: Option<TransactionReceipt>,
+    pub status: TxStatus,
+    /// 已上链时：gas_used × effective_gas_price，单位 ETH
+    pub fee: Option<Decimal>,
+    /// 已上链时：所在块的时间戳（秒）
+    pub timestamp: Option<u64>,
+    pub call: Option<DecodedCall>,
+    /// 每条日志和它的解码结果（不认识的为 None）
+    pub logs: Vec<(Log, Option<DecodedEvent>)>,
+}
+
+pub struct TransactionModule {
+    pub provider: Arc<RpcProvider>,
+    pub cache: Arc<RpcCache>,
+}
+
+impl TransactionModule {
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::shared(Arc::new(metered(provider)), Arc::new(RpcCache::default()))
+    }
+
+    /// 复用其他模块已经包好的 provider
+    pub fn shared(provider: Arc<RpcProvider>, cache: Arc<RpcCache>) -> Self {
+        Self { provider, cache }
+    }
+
+    /// 和其他模块共用同一个缓存
+    pub fn with_cache(mut self, cache: Arc<RpcCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 查交易和回执，用内置 ABI 库解码 calldata 和日志；节点不认识这笔交易时为 None
+    ///
+    /// 交易本身每次都查（pending 会变成已上链）；回执和块时间戳按所在块的哈希缓存，重组后换了块自然不命中。
+    pub async fn get_transaction(&self, hash: H256) -> Result<Option<TransactionDetails>> {
+        let Some(transaction) = self.provider.get_transaction(hash).await? else {
+            return Ok(None);
+        };
+        let (receipt, timestamp) = match transaction.block_hash {
+            Some(block_hash) => {
+                let chain = self.cache.chain_id(&*self.provider).await?;
+                let receipt = self.receipt(chain, hash, block_hash).await?;
+                let timestamp = self.block_timestamp(chain, block_hash).await?;
+                (receipt, timestamp)
+            }
+            None => (None, None),
+        };
+
+        let status = match receipt.as_ref() {
+            None => TxStatus::Pending,
+            Some(r) if r.status == Some(U64::one()) => TxStatus::Success,
+            Some(_) => TxStatus::Failed,
+        };
+
+        let fee = receipt.as_ref().and_then(|r| {
+            let price = r.effective_gas_price.or(transaction.gas_price)?;
+            let wei = r.gas_used? * price;
+            ethers::utils::format_units(wei, 18).ok()?.parse::<Decimal>().ok()
+        });
+
+        let call = abi_library::decode_calldata(&transaction.input);
+        let logs = receipt
+            .as_ref()
+            .map(|r| r.logs.iter().map(|log| (log.clone(), abi_library::decode_log(log))).collect())
+            .unwrap_or_default();
+
+        Ok(Some(TransactionDetails { transaction, receipt, status, fee, timestamp, call, logs }))
+    }
+
+    /// 块 `block_hash` 里这笔交易的回执；节点还没索引到时为 None（下次重新查）
+    async fn receipt(&self, chain: u64, hash: H256, block_hash: H256) -> Result<Option<TransactionReceipt>> {
+        self.cache
+            .immutable_once_found(chain, format!("tx.receipt:{:?}:{:?}", hash, block_hash), || async {
+                Ok(self
+                    .provider
+                    .get_transaction_receipt(hash)
+                    .await?
+                    .filter(|r| r.block_hash == Some(block_hash)))
+            })
+            .await
+    }
+
+    async fn block_timestamp(&self, chain: u64, block_hash: H256) -> Result<Option<u64>> {
+        self.cache
+            .immutable_once_found(chain, format!("block.timestamp:{:?}", block_hash), || async {
+                Ok(self.provider.get_block(block_hash).await?.map(|b| b.timestamp.as_u64()))
+            })
+            .await
+    }
+}
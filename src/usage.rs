@@ -0,0 +1,219 @@
+// src/usage.rs
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::ClientIdentity;
+use crate::cache::CacheStats;
+use crate::config::RateLimits;
+
+/// 令牌桶：容量 `capacity`，每秒回填 `refill_per_sec`
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, period: Duration, now: Instant) -> Self {
+        let capacity = capacity as f64;
+        Self {
+            capacity,
+            refill_per_sec: capacity / period.as_secs_f64(),
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// 距离桶里至少有 1 个令牌还要等多久
+    fn retry_after(&self) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            None
+        } else {
+            // 限额为 0 时永远等不到
+            let secs = (1.0 - self.tokens) / self.refill_per_sec;
+            Some(Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX))
+        }
+    }
+
+    /// 扣除令牌，允许透支（RPC 次数要等调用结束才知道）
+    fn charge(&mut self, n: f64) {
+        self.tokens -= n;
+    }
+}
+
+/// 被限流时返回给调用方
+#[derive(Clone, Debug)]
+pub struct RateLimited {
+    pub reason: String,
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, retry after {}s", self.reason, self.retry_after.as_secs().max(1))
+    }
+}
+
+#[derive(Default)]
+struct ClientUsage {
+    /// tool → (调用次数, RPC 次数)
+    tools: HashMap<String, (u64, u64)>,
+    rejected: u64,
+    calls: Option<TokenBucket>,
+    tool_calls: HashMap<String, TokenBucket>,
+    rpc_budget: Option<TokenBucket>,
+}
+
+/// 每个客户端的限流和 RPC 用量统计
+pub struct UsageTracker {
+    default_limits: RateLimits,
+    clients: Mutex<HashMap<String, ClientUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new(default_limits: RateLimits) -> Self {
+        Self {
+            default_limits,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn limits_for<'a>(&'a self, client: &'a ClientIdentity) -> &'a RateLimits {
+        client.limits.as_ref().unwrap_or(&self.default_limits)
+    }
+
+    /// 调用工具前检查限流；全部通过才扣令牌
+    pub fn check(&self, client: &ClientIdentity, tool: &str) -> Result<(), RateLimited> {
+        let limits = self.limits_for(client);
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        let usage = clients.entry(client.id.clone()).or_default();
+
+        let minute = Duration::from_secs(60);
+        if let Some(n) = limits.calls_per_minute {
+            usage.calls.get_or_insert_with(|| TokenBucket::new(n, minute, now));
+        }
+        if let Some(&n) = limits.tools.get(tool) {
+            usage
+                .tool_calls
+                .entry(tool.to_string())
+                .or_insert_with(|| TokenBucket::new(n, minute, now));
+        }
+        if let Some(n) = limits.rpc_calls_per_hour {
+            usage
+                .rpc_budget
+                .get_or_insert_with(|| TokenBucket::new(n, Duration::from_secs(3600), now));
+        }
+
+        // RPC 预算只检查不扣，调用结束后按实际次数扣
+        let mut rejection = usage.rpc_budget.as_mut().and_then(|budget| {
+            budget.refill(now);
+            budget.retry_after().map(|retry_after| RateLimited {
+                reason: "RPC budget exhausted".to_string(),
+                retry_after,
+            })
+        });
+
+        let mut tool_bucket = usage.tool_calls.get_mut(tool);
+        let mut call_bucket = usage.calls.as_mut();
+        for (bucket, reason) in [
+            (tool_bucket.as_deref_mut(), format!("Rate limit exceeded for {}", tool)),
+            (call_bucket.as_deref_mut(), "Rate limit exceeded".to_string()),
+        ] {
+            let Some(bucket) = bucket else { continue };
+            bucket.refill(now);
+            if rejection.is_none() {
+                rejection = bucket
+                    .retry_after()
+                    .map(|retry_after| RateLimited { reason, retry_after });
+            }
+        }
+
+        if let Some(rejected) = rejection {
+            usage.rejected += 1;
+            return Err(rejected);
+        }
+
+        for bucket in [tool_bucket, call_bucket].into_iter().flatten() {
+            bucket.charge(1.0);
+        }
+        Ok(())
+    }
+
+    /// 调用结束后记账
+    pub fn record(&self, client: &ClientIdentity, tool: &str, rpc_calls: u64) {
+        let mut clients = self.clients.lock().unwrap();
+        let usage = clients.entry(client.id.clone()).or_default();
+
+        let entry = usage.tools.entry(tool.to_string()).or_default();
+        entry.0 += 1;
+        entry.1 += rpc_calls;
+
+        if let Some(budget) = usage.rpc_budget.as_mut() {
+            budget.refill(Instant::now());
+            budget.charge(rpc_calls as f64);
+        }
+    }
+
+    /// 当前用量
+    pub fn report(&self, client: &ClientIdentity) -> UsageReport {
+        let limits = self.limits_for(client);
+        let mut clients = self.clients.lock().unwrap();
+        let usage = clients.entry(client.id.clone()).or_default();
+
+        let tools: BTreeMap<_, _> = usage
+            .tools
+            .iter()
+            .map(|(tool, &(calls, rpc_calls))| (tool.clone(), ToolUsage { calls, rpc_calls }))
+            .collect();
+
+        let rpc_budget_remaining = usage.rpc_budget.as_mut().map(|budget| {
+            budget.refill(Instant::now());
+            budget.tokens.max(0.0) as u64
+        });
+
+        UsageReport {
+            client: client.id.clone(),
+            total_calls: tools.values().map(|t| t.calls).sum(),
+            total_rpc_calls: tools.values().map(|t| t.rpc_calls).sum(),
+            rejected_calls: usage.rejected,
+            tools,
+            calls_per_minute: limits.calls_per_minute,
+            rpc_calls_per_hour: limits.rpc_calls_per_hour,
+            rpc_budget_remaining: rpc_budget_remaining
+                .or(limits.rpc_calls_per_hour.map(u64::from)),
+            cache: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema, Debug, PartialEq, Eq)]
+pub struct ToolUsage {
+    pub calls: u64,
+    pub rpc_calls: u64,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema, Debug)]
+pub struct UsageReport {
+    pub client: String,
+    pub tools: BTreeMap<String, ToolUsage>,
+    pub total_calls: u64,
+    pub total_rpc_calls: u64,
+    pub rejected_calls: u64,
+    pub calls_per_minute: Option<u32>,
+    pub rpc_calls_per_hour: Option<u32>,
+    pub rpc_budget_remaining: Option<u64>,
+    /// 共享 RPC 缓存的命中统计（所有客户端一起算，同 HTTP 模式的 /metrics）
+    pub cache: Option<CacheStats>,
+}
@@ -0,0 +1,306 @@
+// tests/approvals_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{
+    addr, address_topic, call, call_data, connect, hex, offline_service, reverted, service_for, topic, FakeNode, RpcReply,
+};
+use eth_mcp_server::approvals::{ApprovalKind, DEFAULT_APPROVAL_LOOKBACK, MAX_APPROVALS};
+use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::registry::TokenEntry;
+use ethers::abi::{self, Token};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, H256, U256};
+use serde_json::{json, Value};
+
+const OWNER: &str = "0x00000000000000000000000000000000000000a1";
+const OTHER: &str = "0x00000000000000000000000000000000000000b2";
+/// 6 位小数，有 price feed
+const USDC: &str = "0x00000000000000000000000000000000000000c1";
+/// 18 位小数，没有价格
+const DAI: &str = "0x00000000000000000000000000000000000000c2";
+const NFT: &str = "0x00000000000000000000000000000000000000c3";
+/// USDC/USD feed，8 位小数，1 USD
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+
+/// 无限授权
+const ROUTER: &str = "0x00000000000000000000000000000000000000d1";
+/// 授权后又撤销了
+const REVOKED: &str = "0x00000000000000000000000000000000000000d2";
+/// 有限授权 100 USDC
+const VAULT: &str = "0x00000000000000000000000000000000000000d3";
+/// 单个 NFT 授权：#7 仍有效，#8 已经转手
+const MARKET: &str = "0x00000000000000000000000000000000000000d4";
+/// setApprovalForAll(true)
+const OPERATOR: &str = "0x00000000000000000000000000000000000000d5";
+/// setApprovalForAll(true) 之后又 false
+const OLD_OPERATOR: &str = "0x00000000000000000000000000000000000000d6";
+
+/// OWNER 发出的授权事件，按时间顺序
+fn approval_logs() -> Vec<Value> {
+    let approval = topic("Approval(address,address,uint256)");
+    let for_all = topic("ApprovalForAll(address,address,bool)");
+    let amount = |v: U256| abi::encode(&[Token::Uint(v)]);
+    let flag = |b: bool| abi::encode(&[Token::Bool(b)]);
+
+    let logs: Vec<(&str, Vec<H256>, Vec<u8>)> = vec![
+        (USDC, vec![approval, address_topic(OWNER), address_topic(ROUTER)], amount(U256::MAX)),
+        (USDC, vec![approval, address_topic(OWNER), address_topic(REVOKED)], amount(U256::exp10(6))),
+        (USDC, vec![approval, address_topic(OWNER), address_topic(REVOKED)], amount(U256::zero())),
+        (USDC, vec![approval, address_topic(OWNER), address_topic(VAULT)], amount(U256::from(100_000_000))),
+        (DAI, vec![approval, address_topic(OWNER), address_topic(ROUTER)], amount(U256::exp10(20))),
+        (NFT, vec![approval, address_topic(OWNER), address_topic(MARKET), H256::from_low_u64_be(7)], vec![]),
+        (NFT, vec![approval, address_topic(OWNER), address_topic(MARKET), H256::from_low_u64_be(8)], vec![]),
+        // tokenId 9 授权后又 approve(0x0, 9) 撤销
+        (NFT, vec![approval, address_topic(OWNER), address_topic(MARKET), H256::from_low_u64_be(9)], vec![]),
+        (NFT, vec![approval, address_topic(OWNER), H256::zero(), H256::from_low_u64_be(9)], vec![]),
+        (NFT, vec![for_all, address_topic(OWNER), address_topic(OPERATOR)], flag(true)),
+        (NFT, vec![for_all, address_topic(OWNER), address_topic(OLD_OPERATOR)], flag(true)),
+        (NFT, vec![for_all, address_topic(OWNER), address_topic(OLD_OPERATOR)], flag(false)),
+    ];
+    logs.into_iter()
+        .enumerate()
+        .map(|(i, (address, topics, data))| {
+            json!({
+                "address": address,
+                "topics": topics.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>(),
+                "data": hex(&data),
+                "blockNumber": format!("{:#x}", 10 + i),
+                "transactionHash": format!("{:?}", H256::zero()),
+                "transactionIndex": "0x0",
+                "blockHash": format!("{:?}", H256::zero()),
+                "logIndex": "0x0",
+                "removed": false
+            })
+        })
+        .collect()
+}
+
+/// 当前链上状态
+fn contract_call(to: Address, data: &[u8]) -> Option<Vec<u8>> {
+    let selector = ethers::utils::hex::encode(&data[..4]);
+    let arg = |i: usize| &data[4 + 32 * i..36 + 32 * i];
+    let address = |i: usize| Address::from_slice(&arg(i)[12..]);
+    let uint = |v: U256| abi::encode(&[Token::Uint(v)]);
+
+    match (selector.as_str(), to) {
+        // decimals()
+        ("313ce567", to) if to == addr(USDC) => Some(uint(6.into())),
+        ("313ce567", to) if to == addr(DAI) => Some(uint(18.into())),
+        ("313ce567", to) if to == addr(FEED) => Some(uint(8.into())),
+        // balanceOf(owner)：250 USDC
+        ("70a08231", to) if to == addr(USDC) => Some(uint(U256::from(250_000_000))),
+        // allowance(owner, spender)
+        ("dd62ed3e", to) if to == addr(USDC) => Some(uint(match address(1) {
+            s if s == addr(ROUTER) => U256::MAX,
+            s if s == addr(VAULT) => U256::from(100_000_000),
+            _ => U256::zero(),
+        })),
+        ("dd62ed3e", to) if to == addr(DAI) => Some(uint(U256::exp10(20))),
+        // getApproved(tokenId) / ownerOf(tokenId)
+        ("081812fc", to) if to == addr(NFT) => {
+            let approved = if U256::from_big_endian(arg(0)) == 9.into() { Address::zero() } else { addr(MARKET) };
+            Some(abi::encode(&[Token::Address(approved)]))
+        }
+        ("6352211e", to) if to == addr(NFT) => {
+            let owner = if [7, 9].map(U256::from).contains(&U256::from_big_endian(arg(0))) { OWNER } else { OTHER };
+            Some(abi::encode(&[Token::Address(addr(owner))]))
+        }
+        // isApprovedForAll(owner, operator)
+        ("e985e9c5", to) if to == addr(NFT) => Some(abi::encode(&[Token::Bool(address(1) == addr(OPERATOR))])),
+        // description()
+        ("7284e416", to) if to == addr(FEED) => Some(abi::encode(&[Token::String("USDC / USD".to_string())])),
+        // latestRoundData()
+        ("feaf968c", to) if to == addr(FEED) => Some(abi::encode(&[
+            Token::Uint(1.into()),
+            Token::Int(U256::exp10(8)),
+            Token::Uint(0.into()),
+            Token::Uint(0.into()),
+            Token::Uint(1.into()),
+        ])),
+        _ => None,
+    }
+}
+
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getLogs" => {
+            // 不限合约，按 owner 过滤
+            assert!(params[0].get("address").is_none());
+            assert_eq!(params[0]["topics"][1], json!(format!("{:?}", address_topic(OWNER))));
+            Ok(Value::Array(approval_logs()))
+        }
+        "eth_call" => {
+            let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+            let data = ethers::utils::hex::decode(&call_data(params)[2..]).unwrap();
+            match contract_call(to, &data) {
+                Some(out) => Ok(hex(&out)),
+                None => reverted(),
+            }
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_only_live_approvals_are_listed() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let balance = BalanceModule::new(Provider::<Http>::try_from(url.as_str())?);
+
+    let found = balance.get_approvals(addr(OWNER), None).await?;
+    let summary: Vec<_> = found
+        .approvals
+        .iter()
+        .map(|a| (a.kind, a.token, a.spender, a.token_id.map(|id| id.as_u64())))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (ApprovalKind::Erc20, addr(USDC), addr(ROUTER), None),
+            (ApprovalKind::Erc20, addr(USDC), addr(VAULT), None),
+            (ApprovalKind::Erc20, addr(DAI), addr(ROUTER), None),
+            (ApprovalKind::Erc721, addr(NFT), addr(MARKET), Some(7)),
+            (ApprovalKind::ApprovalForAll, addr(NFT), addr(OPERATOR), None),
+        ]
+    );
+    assert!(found.approvals[0].is_unlimited());
+    assert!(!found.approvals[1].is_unlimited());
+    assert_eq!(found.approvals[1].decimals, Some(6));
+    assert_eq!(found.approvals[1].last_block, 13);
+    assert!(!found.truncated);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_approvals_values_exposure_in_usd() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let service = service_for(&url, AppConfig::default());
+    service.price.registry.register(TokenEntry {
+        symbol: "USDC".to_string(),
+        address: addr(USDC),
+        decimals: Some(6),
+        price_feed: Some(addr(FEED)),
+    })?;
+    let client = connect(service).await?;
+
+    let result = call(&client, "list_approvals", json!({ "address": OWNER })).await?;
+    let approvals = result["approvals"].as_array().unwrap();
+    assert_eq!(approvals.len(), 5);
+
+    // 无限授权：能转走全部 250 USDC
+    assert_eq!(approvals[0]["symbol"], "USDC");
+    assert_eq!(approvals[0]["unlimited"], true);
+    assert_eq!(approvals[0]["usd_at_risk"], "250.00");
+    // 有限授权：min(100, 250)
+    assert_eq!(approvals[1]["allowance"], "100.000000");
+    assert_eq!(approvals[1]["usd_at_risk"], "100.00");
+    // 没有价格
+    assert_eq!(approvals[2]["allowance"], "100.000000000000000000");
+    assert_eq!(approvals[2]["usd_at_risk"], Value::Null);
+    assert_eq!(approvals[3]["kind"], "erc721");
+    assert_eq!(approvals[3]["token_id"], "7");
+    assert_eq!(approvals[4]["kind"], "approval_for_all");
+    assert_eq!(approvals[4]["unlimited"], true);
+    assert_eq!(result["usd_at_risk"], "350.00");
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_approval_scan_is_bounded_and_paged() -> Result<()> {
+    const HEAD: u64 = 5_000_000;
+    let approval = topic("Approval(address,address,uint256)");
+    let (node, url) = FakeNode::start(move |method, params| match method {
+        "eth_blockNumber" => Ok(json!(format!("{:#x}", HEAD))),
+        "eth_getLogs" => {
+            let block = |v: &Value| u64::from_str_radix(v.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+            let (from, to) = (block(&params[0]["fromBlock"]), block(&params[0]["toBlock"]));
+            // 默认只往回看 DEFAULT_APPROVAL_LOOKBACK 块
+            assert!(from >= HEAD - DEFAULT_APPROVAL_LOOKBACK, "scan starts at {}", from);
+            // 最近 600 块每块一条已经撤销的 USDC 授权
+            Ok(Value::Array(
+                (HEAD - 599..=HEAD)
+                    .filter(|n| (from..=to).contains(n))
+                    .map(|n| {
+                        json!({
+                            "address": USDC,
+                            "topics": [format!("{:?}", approval), format!("{:?}", address_topic(OWNER)), format!("{:?}", H256::from_low_u64_be(n))],
+                            "data": hex(&abi::encode(&[Token::Uint(U256::zero())])),
+                            "blockNumber": format!("{:#x}", n),
+                            "transactionHash": format!("{:?}", H256::zero()),
+                            "transactionIndex": "0x0",
+                            "blockHash": format!("{:?}", H256::zero()),
+                            "logIndex": "0x0",
+                            "removed": false
+                        })
+                    })
+                    .collect(),
+            ))
+        }
+        other => handle(other, params),
+    })
+    .await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(&client, "list_approvals", json!({ "address": OWNER })).await?;
+    assert_eq!(result["from_block"], HEAD - DEFAULT_APPROVAL_LOOKBACK);
+    assert_eq!(result["truncated"], true);
+    assert_eq!(result["next_from_block"], HEAD - 99);
+    assert_eq!(node.count("eth_call"), MAX_APPROVALS as u64);
+
+    let result = call(&client, "list_approvals", json!({ "address": OWNER, "from_block": HEAD - 99 })).await?;
+    assert_eq!(result["truncated"], false);
+    assert_eq!(result["next_from_block"], Value::Null);
+    assert_eq!(node.count("eth_call"), MAX_APPROVALS as u64 + 100);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_revoke_approvals_builds_calldata() -> Result<()> {
+    let client = connect(offline_service()).await?;
+
+    let result = call(
+        &client,
+        "revoke_approvals",
+        json!({
+            "address": OWNER,
+            "approvals": [
+                { "kind": "erc20", "token": USDC, "spender": ROUTER },
+                { "kind": "erc721", "token": NFT, "spender": MARKET, "token_id": "7" },
+                { "kind": "approval_for_all", "token": NFT, "spender": OPERATOR },
+            ]
+        }),
+    )
+    .await?;
+    let txs = result["transactions"].as_array().unwrap();
+    let word = |hex: &str| format!("{:0>64}", hex);
+
+    // approve(ROUTER, 0)
+    assert_eq!(txs[0]["to"], USDC);
+    assert_eq!(txs[0]["from"], OWNER);
+    assert_eq!(txs[0]["data"], format!("0x095ea7b3{}{}", word("d1"), word("0")));
+    // approve(address(0), 7)
+    assert_eq!(txs[1]["data"], format!("0x095ea7b3{}{}", word("0"), word("7")));
+    // setApprovalForAll(OPERATOR, false)
+    assert_eq!(txs[2]["to"], NFT);
+    assert_eq!(txs[2]["data"], format!("0xa22cb465{}{}", word("d5"), word("0")));
+
+    // 单个 NFT 授权必须指定 tokenId
+    let err = call(
+        &client,
+        "revoke_approvals",
+        json!({ "address": OWNER, "approvals": [{ "kind": "erc721", "token": NFT, "spender": MARKET }] }),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("token_id"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// tests/auth_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{offline_service, serve_http, service_for, FakeNode, RpcReply};
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::auth::{AuthConfig, ClientIdentity};
+use eth_mcp_server::config::ClientConfig;
+use eth_mcp_server::http::{HttpServer, MCP_PATH, METRICS_PATH};
+use eth_mcp_server::service::TokenService;
+use ethers::types::Address;
+use rmcp::model::CallToolRequestParam;
+use rmcp::service::RunningService;
+use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
+use rmcp::transport::StreamableHttpClientTransport;
+use rmcp::{RoleClient, ServiceExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+const ALLOWED_WALLET: &str = "0x00000000000000000000000000000000000000aa";
+const OTHER_WALLET: &str = "0x00000000000000000000000000000000000000bb";
+const ALLOWED_TX: &str = "0x00000000000000000000000000000000000000000000000000000000000000a1";
+const OTHER_TX: &str = "0x00000000000000000000000000000000000000000000000000000000000000b1";
+
+fn clients() -> Vec<ClientConfig> {
+    vec![
+        ClientConfig {
+            id: "admin".into(),
+            token: "admin-token".into(),
+            tools: None,
+            wallets: None,
+            allow_execution: true,
+            allow_registry: false,
+            limits: None,
+        },
+        ClientConfig {
+            id: "treasury".into(),
+            token: "treasury-token".into(),
+            tools: Some(vec!["get_balance".into(), "swap_tokens".into()]),
+            wallets: Some(vec![ALLOWED_WALLET.parse().unwrap()]),
+            allow_execution: false,
+            allow_registry: false,
+            limits: None,
+        },
+        ClientConfig {
+            id: "desk".into(),
+            token: "desk-token".into(),
+            tools: None,
+            wallets: Some(vec![ALLOWED_WALLET.parse().unwrap()]),
+            allow_execution: true,
+            allow_registry: false,
+            limits: None,
+        },
+        ClientConfig {
+            id: "auditor".into(),
+            token: "auditor-token".into(),
+            tools: Some(vec!["get_transaction".into(), "get_logs".into()]),
+            wallets: Some(vec![ALLOWED_WALLET.parse().unwrap()]),
+            allow_execution: false,
+            allow_registry: false,
+            limits: None,
+        },
+    ]
+}
+
+async fn start_server() -> Result<(String, CancellationToken)> {
+    start_server_with(offline_service()).await
+}
+
+async fn start_server_with(service: TokenService) -> Result<(String, CancellationToken)> {
+    let server = HttpServer::new(service, None).with_auth(AuthConfig::new(&clients())?);
+    let (url, shutdown, _) = serve_http(Arc::new(server)).await?;
+    Ok((url, shutdown))
+}
+
+async fn connect(url: &str, token: Option<&str>) -> Result<RunningService<RoleClient, ()>> {
+    let mut config = StreamableHttpClientTransportConfig::with_uri(url);
+    if let Some(token) = token {
+        config = config.auth_header(token);
+    }
+    Ok(().serve(StreamableHttpClientTransport::from_config(config)).await?)
+}
+
+async fn tool_names(client: &RunningService<RoleClient, ()>) -> Result<Vec<String>> {
+    let tools = client.list_all_tools().await?;
+    Ok(tools.iter().map(|t| t.name.to_string()).collect())
+}
+
+#[tokio::test]
+async fn test_missing_or_unknown_token_is_rejected() -> Result<()> {
+    let (url, shutdown) = start_server().await?;
+
+    assert!(connect(&url, None).await.is_err());
+    assert!(connect(&url, Some("not-a-real-token")).await.is_err());
+
+    shutdown.cancel();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metrics_need_no_client_token() -> Result<()> {
+    let (url, shutdown) = start_server().await?;
+    let http = reqwest::Client::new();
+
+    let metrics = http.get(url.replace(MCP_PATH, METRICS_PATH)).send().await?;
+    assert_eq!(metrics.status(), 200);
+    assert!(metrics.text().await?.contains("eth_mcp_cache_hits_total"));
+    // MCP endpoint 照样要 token
+    let mcp = http.post(&url).json(&json!({})).send().await?;
+    assert_eq!(mcp.status(), 401);
+
+    shutdown.cancel();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tools_are_scoped_per_client() -> Result<()> {
+    let (url, shutdown) = start_server().await?;
+
+    let admin = connect(&url, Some("admin-token")).await?;
+    let mut names = tool_names(&admin).await?;
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "call_contract",
+            "check_token_risk",
+            "get_balance",
+            "get_balances",
+            "get_logs",
+            "get_nft_holdings",
+            "get_pool",
+            "get_price",
+            "get_token_info",
+            "get_transaction",
+            "get_transfer_history",
+            "get_usage",
+            "get_v3_pool",
+            "list_approvals",
+            "revoke_approvals",
+            "simulate_swap",
+            "swap_tokens",
+        ]
+    );
+
+    // swap_tokens 在白名单里，但 treasury 没开 allow_execution
+    let treasury = connect(&url, Some("treasury-token")).await?;
+    assert_eq!(tool_names(&treasury).await?, vec!["get_balance"]);
+
+    let hidden = treasury
+        .call_tool(CallToolRequestParam {
+            name: "get_price".into(),
+            arguments: serde_json::json!({ "token": null }).as_object().cloned(),
+        })
+        .await;
+    assert!(hidden.is_err());
+
+    admin.cancel().await?;
+    treasury.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wallet_scope_is_enforced() -> Result<()> {
+    let (url, shutdown) = start_server().await?;
+
+    let treasury = connect(&url, Some("treasury-token")).await?;
+    let err = treasury
+        .call_tool(CallToolRequestParam {
+            name: "get_balance".into(),
+            arguments: serde_json::json!({ "address": OTHER_WALLET }).as_object().cloned(),
+        })
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not allowed to query wallet"));
+
+    treasury.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_registry_writes_need_permission() -> Result<()> {
+    let (url, shutdown) = start_server().await?;
+
+    // admin 能用所有工具，但没开 allow_registry
+    let admin = connect(&url, Some("admin-token")).await?;
+    let args = json!({ "address": OTHER_WALLET, "register": true, "symbol": "USDC", "price_feed": ALLOWED_WALLET });
+    let err = tool_error(&admin, "get_token_info", args).await;
+    assert!(err.contains("Client 'admin' is not allowed to register tokens"), "{}", err);
+
+    admin.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}
+
+/// 待打包的转账：`ALLOWED_TX` 由允许的钱包发出，`OTHER_TX` 与它无关
+fn history_node(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_getTransactionByHash" => {
+            let hash = params[0].as_str().unwrap();
+            let from = if hash == ALLOWED_TX { ALLOWED_WALLET } else { OTHER_WALLET };
+            Ok(json!({
+                "hash": hash,
+                "nonce": "0x0",
+                "blockHash": null,
+                "blockNumber": null,
+                "transactionIndex": null,
+                "from": from,
+                "to": "0x00000000000000000000000000000000000000cc",
+                "value": "0x0",
+                "gasPrice": "0x1",
+                "gas": "0x5208",
+                "input": "0x",
+                "v": "0x1",
+                "r": "0x1",
+                "s": "0x1",
+                "type": "0x0",
+                "chainId": "0x1"
+            }))
+        }
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getLogs" => Ok(json!([])),
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+async fn tool_error(client: &RunningService<RoleClient, ()>, tool: &str, args: Value) -> String {
+    client
+        .call_tool(CallToolRequestParam { name: tool.to_string().into(), arguments: args.as_object().cloned() })
+        .await
+        .unwrap_err()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_wallet_scope_applies_to_history_tools() -> Result<()> {
+    let (_node, node_url) = FakeNode::start(history_node).await?;
+    let (url, shutdown) = start_server_with(service_for(&node_url, AppConfig::default())).await?;
+
+    let auditor = connect(&url, Some("auditor-token")).await?;
+    let tx = common::call(&auditor, "get_transaction", json!({ "hash": ALLOWED_TX })).await?;
+    assert_eq!(tx["status"], "pending");
+    let err = tool_error(&auditor, "get_transaction", json!({ "hash": OTHER_TX })).await;
+    assert!(err.contains("not allowed to query wallet"), "{}", err);
+
+    let transfers = |filters: Value| {
+        json!({
+            "event": "Transfer(address indexed from, address indexed to, uint256 value)",
+            "filters": filters,
+            "from_block": 1,
+        })
+    };
+    let err = tool_error(&auditor, "get_logs", transfers(json!({}))).await;
+    assert!(err.contains("filtered by its wallets"), "{}", err);
+    for filters in [json!({ "to": OTHER_WALLET }), json!({ "to": [ALLOWED_WALLET, OTHER_WALLET] })] {
+        let err = tool_error(&auditor, "get_logs", transfers(filters)).await;
+        assert!(err.contains("not allowed to query wallet"), "{}", err);
+    }
+    let logs = common::call(&auditor, "get_logs", transfers(json!({ "to": ALLOWED_WALLET }))).await?;
+    assert_eq!(logs["logs"], json!([]));
+
+    auditor.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wallet_scope_applies_to_simulate_swap() -> Result<()> {
+    let service = |wallet: &str| {
+        let config = AppConfig { wallet_address: wallet.parse().unwrap(), ..Default::default() };
+        service_for("http://127.0.0.1:1", config)
+    };
+    let simulate = |recipient: Option<&str>| {
+        let mut args = json!({ "from_token": "ETH", "to_token": "USDC", "amount_in": "1", "slippage": 0.5 });
+        if let Some(recipient) = recipient {
+            args["recipient"] = json!(recipient);
+        }
+        args
+    };
+
+    // 服务端钱包不在 desk 的范围内：不论收款方是谁都不能模拟
+    let (url, shutdown) = start_server_with(service(OTHER_WALLET)).await?;
+    let desk = connect(&url, Some("desk-token")).await?;
+    for recipient in [None, Some(ALLOWED_WALLET)] {
+        let err = tool_error(&desk, "simulate_swap", simulate(recipient)).await;
+        assert!(err.contains(&format!("not allowed to query wallet {}", OTHER_WALLET)), "{}", err);
+    }
+    desk.cancel().await?;
+    shutdown.cancel();
+
+    // 服务端钱包在范围内，收款方不在
+    let (url, shutdown) = start_server_with(service(ALLOWED_WALLET)).await?;
+    let desk = connect(&url, Some("desk-token")).await?;
+    let err = tool_error(&desk, "simulate_swap", simulate(Some(OTHER_WALLET))).await;
+    assert!(err.contains(&format!("not allowed to query wallet {}", OTHER_WALLET)), "{}", err);
+    // 两者都在范围内时通过检查，之后才去连（不存在的）节点
+    let err = tool_error(&desk, "simulate_swap", simulate(Some(ALLOWED_WALLET))).await;
+    assert!(!err.contains("not allowed"), "{}", err);
+    desk.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}
+
+#[test]
+fn test_auth_config_validation() {
+    let mut dup_token = clients();
+    dup_token[1].token = "admin-token".into();
+    assert!(AuthConfig::new(&dup_token).is_err());
+
+    let mut dup_id = clients();
+    dup_id[1].id = "admin".into();
+    assert!(AuthConfig::new(&dup_id).is_err());
+
+    assert!(!AuthConfig::new(&[]).unwrap().is_enabled());
+}
+
+#[test]
+fn test_client_identity_permissions() {
+    let auth = AuthConfig::new(&clients()).unwrap();
+    let treasury = auth.authenticate("treasury-token").unwrap();
+    let allowed: Address = ALLOWED_WALLET.parse().unwrap();
+    let other: Address = OTHER_WALLET.parse().unwrap();
+
+    assert!(treasury.can_query_wallet(&allowed));
+    assert!(!treasury.can_query_wallet(&other));
+    assert!(!treasury.can_use_tool("swap_tokens"));
+    assert!(!treasury.can_use_tool("revoke_approvals"));
+    assert!(!treasury.can_execute());
+    assert!(ClientIdentity::local().can_use_tool("swap_tokens"));
+
+    // token 不会出现在 Debug 输出里
+    assert!(!format!("{:?}", clients()).contains("admin-token"));
+}
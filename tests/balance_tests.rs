@@ -1,53 +1,78 @@
 // tests/balance_tests.rs
-use ethers::prelude::*;
-use rust_decimal::Decimal;
-use std::{env, sync::Arc};
+mod common;
+
 use anyhow::Result;
+use common::addr;
+use common::mock::MockRpc;
 use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::cache::RpcCache;
+use ethers::abi::Token;
+use ethers::types::U256;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::sync::Arc;
 
-#[tokio::test]
-async fn test_eth_balance() -> Result<()> {
-    dotenv::dotenv().ok();
-    let _ = tracing_subscriber::fmt::try_init();
-
-    let rpc_url = env::var("INFURA_URL").expect("INFURA_URL not set");
-
-    let provider = Arc::new(Provider::<Http>::connect(rpc_url.as_str()).await);
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
+const OTHER: &str = "0x00000000000000000000000000000000000000a2";
+/// 18 位小数
+const UNI: &str = "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984";
 
-    let balance_module = BalanceModule::new((*provider).clone());
+async fn module(mock: &MockRpc) -> BalanceModule {
+    BalanceModule::shared(mock.provider().await, Arc::new(RpcCache::default()))
+}
 
-    let wallet: Address = env::var("WALLET_ADDRESS")?.parse()?;
+#[tokio::test]
+async fn test_eth_balance() -> Result<()> {
+    let mock = MockRpc::new();
+    mock.on("eth_getBalance", json!("0x14d1120d7b160000")); // 1.5 ETH
+    let balance = module(&mock).await;
 
-    let balance: Decimal = balance_module.get_balance(wallet, None).await?;
-    println!("ETH Balance: {}", balance);
+    let eth = balance.get_balance(addr(WALLET), None).await?;
+    assert_eq!(eth, Decimal::new(15, 1));
 
+    // 读取固定在最新块上
+    let (_, params) = mock.requests().into_iter().find(|(m, _)| m == "eth_getBalance").unwrap();
+    assert_eq!(params, json!([WALLET, "0x64"]));
     Ok(())
 }
 
 #[tokio::test]
 async fn test_erc20_balance() -> Result<()> {
-    dotenv::dotenv().ok();
-    let _ = tracing_subscriber::fmt::try_init();
+    let mock = MockRpc::new();
+    mock.on_call(
+        addr(UNI),
+        "balanceOf(address)",
+        &[Token::Address(addr(WALLET))],
+        &[Token::Uint(U256::exp10(18) * 25 / 10)],
+    )
+    .on_call(addr(UNI), "balanceOf(address)", &[Token::Address(addr(OTHER))], &[Token::Uint(U256::zero())])
+    .on_selector(addr(UNI), "decimals()", &[Token::Uint(18.into())]);
+    let balance = module(&mock).await;
 
-    let rpc_url = env::var("INFURA_URL").expect("INFURA_URL not set");
-    let provider = Arc::new(Provider::<Http>::connect(rpc_url.as_str()).await);
-    let balance_module = BalanceModule::new((*provider).clone());
-
-    let wallet: Address = env::var("WALLET_ADDRESS")?.parse()?;
+    assert_eq!(balance.get_balance(addr(WALLET), Some(addr(UNI))).await?, Decimal::new(25, 1));
+    assert_eq!(balance.get_balance(addr(OTHER), Some(addr(UNI))).await?, Decimal::ZERO);
+    // decimals 只查一次；同一块内重复查询走缓存
+    balance.get_balance(addr(WALLET), Some(addr(UNI))).await?;
+    assert_eq!(mock.count("eth_call"), 3);
+    Ok(())
+}
 
-    let uni_contract: Address = env::var("UNI")?.parse()?;
+#[tokio::test]
+async fn test_balance_errors() -> Result<()> {
+    let mock = MockRpc::new();
+    mock.fail("eth_getBalance", -32000, "header not found")
+        .revert(addr(UNI), "balanceOf(address)");
+    let balance = module(&mock).await;
 
+    let err = balance.get_balance(addr(WALLET), None).await.unwrap_err();
+    assert!(err.to_string().contains("header not found"));
 
-    let balance: Decimal = balance_module.get_balance(wallet, Some(uni_contract)).await?;
-    println!("UNI Balance: {}", balance);
+    let err = balance.get_balance(addr(WALLET), Some(addr(UNI))).await.unwrap_err();
+    assert!(err.to_string().contains("reverted"));
 
+    // 没有 Multicall3 时逐个查询，失败的格子单独报错
+    let cells = balance.get_balances(&[addr(WALLET)], &[None, Some(addr(UNI))]).await?;
+    assert_eq!(cells.len(), 2);
+    assert!(cells.iter().all(|c| c.balance.is_err()));
     Ok(())
 }
-
-// 1️⃣ Burned ETH (BETH)
-// Contract: 0x716bC7e331c9Da551e5Eb6A099c300db4c08E994
-// Description: Represents ETH permanently removed from circulating supply (burn mechanism tracking).
-//
-// 2️⃣ Uniswap (UNI)
-// Official ERC-20 Contract: 0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984
-// Description: Governance token of the Uniswap Protocol.
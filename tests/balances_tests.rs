@@ -0,0 +1,203 @@
+// tests/balances_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{
+    addr, aggregate3, block_header, call, call_data, connect, hex, reverted, service_for, FakeNode, RpcReply, AGGREGATE3,
+};
+use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::config::AppConfig;
+use ethers::abi::{self, Token};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const WALLET_A: &str = "0x00000000000000000000000000000000000000a1";
+const WALLET_B: &str = "0x00000000000000000000000000000000000000b2";
+/// 6 位小数
+const USDC: &str = "0x00000000000000000000000000000000000000c1";
+/// balanceOf 总是 revert
+const BROKEN: &str = "0x00000000000000000000000000000000000000c2";
+/// decimals() revert
+const NO_DECIMALS: &str = "0x00000000000000000000000000000000000000c3";
+/// ETH/USD feed，8 位小数，2000 USD
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+
+/// 本地链：USDC / ETH 余额、Chainlink feed，可选部署 Multicall3
+struct Chain {
+    multicall: bool,
+    eth: HashMap<Address, U256>,
+    usdc: HashMap<Address, U256>,
+}
+
+impl Chain {
+    fn new(multicall: bool) -> Self {
+        Self {
+            multicall,
+            eth: HashMap::from([
+                (addr(WALLET_A), U256::exp10(18)),
+                (addr(WALLET_B), U256::exp10(18) / 2),
+            ]),
+            usdc: HashMap::from([
+                (addr(WALLET_A), U256::from(1_500_000)),
+                (addr(WALLET_B), U256::from(2_000_000)),
+            ]),
+        }
+    }
+
+    /// 单个合约调用；Err 表示 revert
+    fn contract_call(&self, to: Address, data: &[u8]) -> Result<Vec<u8>, ()> {
+        let selector = ethers::utils::hex::encode(&data[..4]);
+        let arg = || Address::from_slice(&data[16..36]);
+        let uint = |v: U256| abi::encode(&[Token::Uint(v)]);
+
+        match (selector.as_str(), to) {
+            ("313ce567", to) if to == addr(USDC) => Ok(uint(6.into())),
+            ("313ce567", to) if to == addr(BROKEN) => Ok(uint(18.into())),
+            ("313ce567", to) if to == addr(FEED) => Ok(uint(8.into())),
+            ("313ce567", _) => Err(()),
+            ("70a08231", to) if to == addr(USDC) => Ok(uint(self.usdc.get(&arg()).copied().unwrap_or_default())),
+            ("70a08231", _) => Err(()),
+            // getEthBalance(address)
+            ("4d2301cc", to) if to == MULTICALL_ADDRESS => Ok(uint(self.eth.get(&arg()).copied().unwrap_or_default())),
+            // latestRoundData()
+            ("feaf968c", to) if to == addr(FEED) => Ok(abi::encode(&[
+                Token::Uint(1.into()),
+                Token::Int(U256::from(2000) * U256::exp10(8)),
+                Token::Uint(0.into()),
+                Token::Uint(0.into()),
+                Token::Uint(1.into()),
+            ])),
+            (selector, to) => panic!("unexpected call {} to {:?}", selector, to),
+        }
+    }
+
+    fn handle(&self, method: &str, params: &Value) -> RpcReply {
+        match method {
+            "eth_chainId" => Ok(json!("0x1")),
+            "eth_blockNumber" => Ok(json!("0x64")),
+            "eth_getBlockByNumber" => Ok(block_header(params, 0x64)),
+            "eth_getBalance" => {
+                let wallet: Address = params[0].as_str().unwrap().parse().unwrap();
+                Ok(json!(format!("{:#x}", self.eth.get(&wallet).copied().unwrap_or_default())))
+            }
+            "eth_call" => {
+                let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+                let data = ethers::utils::hex::decode(&call_data(params)[2..]).unwrap();
+                if to == MULTICALL_ADDRESS && data[..4] == AGGREGATE3 {
+                    // 没部署 Multicall3 的链上，调用空地址返回空数据
+                    let batch = aggregate3(&data, |to, data| self.contract_call(to, data).ok());
+                    return Ok(if self.multicall { hex(&batch) } else { json!("0x") });
+                }
+                match self.contract_call(to, &data) {
+                    Ok(out) => Ok(hex(&out)),
+                    Err(()) => reverted(),
+                }
+            }
+            other => panic!("unexpected method {}", other),
+        }
+    }
+}
+
+fn config_with_eth_feed() -> AppConfig {
+    AppConfig {
+        token_addresses: HashMap::from([("ETH".to_string(), addr(FEED))]),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_get_balances_matrix_with_subtotals() -> Result<()> {
+    let chain = Chain::new(true);
+    let (node, url) = FakeNode::start(move |m, p| chain.handle(m, p)).await?;
+    let client = connect(service_for(&url, config_with_eth_feed())).await?;
+
+    let result = call(
+        &client,
+        "get_balances",
+        json!({
+            "addresses": [WALLET_A, WALLET_B, "not-a-wallet"],
+            "tokens": ["ETH", USDC, BROKEN, NO_DECIMALS]
+        }),
+    )
+    .await?;
+
+    let rows = result["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 12);
+    assert_eq!(rows[0]["address"], WALLET_A);
+    assert_eq!(rows[0]["token"], "ETH");
+    assert_eq!(rows[0]["balance"], "1.000000000000000000");
+    assert_eq!(rows[1]["balance"], "1.500000");
+    // 单格失败只体现在那一格
+    assert!(rows[2]["error"].as_str().unwrap().contains("reverted"));
+    assert!(rows[3]["error"].as_str().unwrap().contains("decimals"));
+    assert!(rows[8]["error"].as_str().unwrap().contains("not-a-wallet"));
+
+    let tokens = result["tokens"].as_array().unwrap();
+    assert_eq!(tokens[0]["total"], "1.500000000000000000");
+    assert_eq!(tokens[0]["usd_value"], "3000.00");
+    assert_eq!(tokens[1]["total"], "3.500000");
+    assert_eq!(tokens[1]["usd_price"], Value::Null);
+    assert_eq!(tokens[2]["errors"], 3);
+
+    let wallets = result["wallets"].as_array().unwrap();
+    assert_eq!(wallets[0]["usd_value"], "2000.00");
+    assert_eq!(wallets[0]["unpriced_tokens"], json!([USDC]));
+    assert_eq!(wallets[0]["errors"], 2);
+    assert_eq!(wallets[2]["errors"], 4);
+    assert_eq!(result["usd_total"], "3000.00");
+
+    // 6 个余额打包成一个 aggregate3；另外 3 次 decimals、2 次 feed
+    assert_eq!(node.count("eth_call"), 6);
+    assert_eq!(node.count("eth_getBalance"), 0);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_falls_back_without_multicall() -> Result<()> {
+    let chain = Chain::new(false);
+    let (node, url) = FakeNode::start(move |m, p| chain.handle(m, p)).await?;
+    let balance = BalanceModule::new(Provider::<Http>::try_from(url.as_str())?);
+
+    let cells = balance
+        .get_balances(&[addr(WALLET_A), addr(WALLET_B)], &[None, Some(addr(USDC)), Some(addr(BROKEN))])
+        .await?;
+
+    let balances: Vec<_> = cells.iter().map(|c| c.balance.clone().ok()).collect();
+    assert_eq!(
+        balances,
+        vec![
+            Some(Decimal::ONE),
+            Some("1.5".parse()?),
+            None,
+            Some("0.5".parse()?),
+            Some(Decimal::TWO),
+            None,
+        ]
+    );
+    assert_eq!(cells[2].wallet, addr(WALLET_A));
+    assert_eq!(cells[2].token, Some(addr(BROKEN)));
+    assert_eq!(node.count("eth_getBalance"), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batches_are_split() -> Result<()> {
+    let chain = Chain::new(true);
+    let (node, url) = FakeNode::start(move |m, p| chain.handle(m, p)).await?;
+    let balance = BalanceModule::new(Provider::<Http>::try_from(url.as_str())?);
+
+    let wallets: Vec<Address> = (1000..1400u64).map(Address::from_low_u64_be).collect();
+    let cells = balance.get_balances(&wallets, &[None]).await?;
+
+    assert_eq!(cells.len(), 400);
+    assert!(cells.iter().all(|c| c.balance == Ok(Decimal::ZERO)));
+    // 300 + 100
+    assert_eq!(node.count("eth_call"), 2);
+    Ok(())
+}
@@ -0,0 +1,151 @@
+// tests/cache_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{block_header, call_data, word, FakeNode};
+use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::cache::RpcCache;
+use eth_mcp_server::config::Confidence;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const WALLET: &str = "0x00000000000000000000000000000000000000aa";
+const TOKEN: &str = "0x00000000000000000000000000000000000000cc";
+
+/// 假节点：decimals = 6，balanceOf = 2.5，块号可以手动推进
+async fn start_fake_node() -> Result<(Arc<FakeNode>, Arc<AtomicU64>, String)> {
+    let block = Arc::new(AtomicU64::new(100));
+    let (node, url) = FakeNode::start({
+        let block = block.clone();
+        move |method, params| match method {
+            "eth_chainId" => Ok(json!("0x1")),
+            "eth_blockNumber" => Ok(json!(format!("0x{:x}", block.load(Ordering::SeqCst)))),
+            "eth_getBlockByNumber" => Ok(block_header(params, block.load(Ordering::SeqCst))),
+            // 1 ETH
+            "eth_getBalance" => Ok(json!("0xde0b6b3a7640000")),
+            "eth_call" => match &call_data(params)[..10] {
+                "0x313ce567" => Ok(word(6)),         // decimals()
+                "0x70a08231" => Ok(word(2_500_000)), // balanceOf()
+                other => panic!("unexpected selector {}", other),
+            },
+            other => panic!("unexpected method {}", other),
+        }
+    })
+    .await?;
+    Ok((node, block, url))
+}
+
+#[tokio::test]
+async fn test_eth_balance_cached_within_block() -> Result<()> {
+    let (node, _block, url) = start_fake_node().await?;
+    let cache = Arc::new(RpcCache::new(Duration::from_secs(60)));
+    let balance = BalanceModule::new(Provider::<Http>::try_from(url.as_str())?).with_cache(cache.clone());
+    let wallet: Address = WALLET.parse()?;
+
+    for _ in 0..3 {
+        assert_eq!(balance.get_balance(wallet, None).await?, Decimal::ONE);
+    }
+
+    assert_eq!(node.count("eth_getBalance"), 1);
+    assert_eq!(node.count("eth_blockNumber"), 1);
+    assert_eq!(node.count("eth_chainId"), 1);
+
+    let stats = cache.stats();
+    assert_eq!((stats.block_hits, stats.block_misses), (2, 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_block_invalidates_but_decimals_stay_cached() -> Result<()> {
+    let (node, block, url) = start_fake_node().await?;
+    // 每次都重新查块号
+    let cache = Arc::new(RpcCache::new(Duration::ZERO));
+    let balance = BalanceModule::new(Provider::<Http>::try_from(url.as_str())?).with_cache(cache.clone());
+    let wallet: Address = WALLET.parse()?;
+    let token: Address = TOKEN.parse()?;
+
+    let expected: Decimal = "2.5".parse()?;
+    assert_eq!(balance.get_balance(wallet, Some(token)).await?, expected);
+    assert_eq!(balance.get_balance(wallet, Some(token)).await?, expected);
+    // 同一块：balanceOf + decimals 各一次
+    assert_eq!(node.count("eth_call"), 2);
+
+    block.fetch_add(1, Ordering::SeqCst);
+    assert_eq!(balance.get_balance(wallet, Some(token)).await?, expected);
+    // 新块：只重新查 balanceOf
+    assert_eq!(node.count("eth_call"), 3);
+
+    let stats = cache.stats();
+    assert_eq!((stats.immutable_hits, stats.immutable_misses), (2, 1));
+    assert_eq!((stats.block_hits, stats.block_misses), (1, 2));
+    // 旧块的条目已被清掉
+    assert_eq!(stats.block_entries, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pinned_older_blocks_stay_cached_across_new_blocks() -> Result<()> {
+    let (node, block, url) = start_fake_node().await?;
+    let cache = Arc::new(RpcCache::new(Duration::ZERO));
+    let provider = Provider::<Http>::try_from(url.as_str())?;
+    let balance = BalanceModule::new(provider.clone()).with_cache(cache.clone());
+    let wallet: Address = WALLET.parse()?;
+
+    let pinned = cache.pinned_block(&provider, Confidence::Confirmations(12)).await?;
+    assert_eq!(pinned.number.as_u64(), 88);
+    balance.get_balance_at(wallet, None, pinned.number).await?;
+
+    // 出了新块，第 88 块的余额仍按它的哈希命中缓存
+    block.fetch_add(1, Ordering::SeqCst);
+    cache.latest_block(&provider).await?;
+    balance.get_balance_at(wallet, None, pinned.number).await?;
+    assert_eq!(node.count("eth_getBalance"), 1);
+
+    // 没固定过的旧块不缓存
+    balance.get_balance_at(wallet, None, 50.into()).await?;
+    balance.get_balance_at(wallet, None, 50.into()).await?;
+    assert_eq!(node.count("eth_getBalance"), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_modules_can_share_cache() -> Result<()> {
+    let (node, _block, url) = start_fake_node().await?;
+    let cache = Arc::new(RpcCache::new(Duration::from_secs(60)));
+    let provider = Provider::<Http>::try_from(url.as_str())?;
+    let a = BalanceModule::new(provider.clone()).with_cache(cache.clone());
+    let b = BalanceModule::new(provider).with_cache(cache.clone());
+
+    a.get_balance(WALLET.parse()?, Some(TOKEN.parse()?)).await?;
+    b.get_balance(WALLET.parse()?, Some(TOKEN.parse()?)).await?;
+
+    assert_eq!(node.count("eth_call"), 2);
+    assert_eq!(node.count("eth_chainId"), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_immutable_values_are_typed() -> Result<()> {
+    let cache = RpcCache::default();
+    let v: U256 = cache.immutable(1, "x".into(), || async { Ok(U256::from(7)) }).await?;
+    assert_eq!(v, U256::from(7));
+
+    let v: U256 = cache
+        .immutable(1, "x".into(), || async { panic!("should be cached") })
+        .await?;
+    assert_eq!(v, U256::from(7));
+
+    // 不同链互不影响
+    let v: U256 = cache.immutable(5, "x".into(), || async { Ok(U256::from(9)) }).await?;
+    assert_eq!(v, U256::from(9));
+
+    let metrics = cache.stats().to_prometheus();
+    assert!(metrics.contains("eth_mcp_cache_hits_total{kind=\"immutable\"} 1"));
+    assert!(metrics.contains("eth_mcp_cache_misses_total{kind=\"immutable\"} 2"));
+    Ok(())
+}
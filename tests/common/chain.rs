@@ -0,0 +1,153 @@
+// tests/common/chain.rs
+//! revm 上的本地开发链：部署字节码、以任意账户发交易改状态，再经 FakeNode 以 JSON-RPC 对外提供
+
+use super::{block_header, FakeNode, RpcReply};
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, Bytes, U256};
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{self as evm, AccountInfo, BlockEnv, Bytecode, ExecutionResult, SpecId, TxEnv, TxKind};
+use revm::Evm;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const GAS_LIMIT: u64 = 10_000_000;
+
+pub struct LocalChain {
+    db: CacheDB<EmptyDB>,
+    block: u64,
+}
+
+impl Default for LocalChain {
+    fn default() -> Self {
+        Self { db: CacheDB::new(EmptyDB::default()), block: 0x64 }
+    }
+}
+
+fn evm_address(address: Address) -> evm::Address {
+    evm::Address::from_slice(address.as_bytes())
+}
+
+fn evm_u256(value: U256) -> evm::U256 {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    evm::U256::from_be_bytes(word)
+}
+
+impl LocalChain {
+    /// 把 `code` 放到 `address`
+    pub fn deploy(&mut self, address: Address, code: Bytes) -> &mut Self {
+        let bytecode = Bytecode::new_raw(code.to_vec().into());
+        let info = AccountInfo::new(evm::U256::ZERO, 1, bytecode.hash_slow(), bytecode);
+        self.db.insert_account_info(evm_address(address), info);
+        self
+    }
+
+    /// 设置账户的 ETH 余额
+    pub fn fund(&mut self, address: Address, wei: U256) -> &mut Self {
+        let address = evm_address(address);
+        let mut info = self.db.accounts.get(&address).map(|a| a.info.clone()).unwrap_or_default();
+        info.balance = evm_u256(wei);
+        self.db.insert_account_info(address, info);
+        self
+    }
+
+    /// 直接写 storage（相当于构造函数里的初始化）
+    pub fn store(&mut self, address: Address, slot: U256, value: U256) -> &mut Self {
+        self.db
+            .insert_account_storage(evm_address(address), evm_u256(slot), evm_u256(value))
+            .expect("EmptyDB never fails");
+        self
+    }
+
+    /// 以 `from` 的身份发一笔交易并提交；revert 时报错，状态不变
+    pub fn send(&mut self, from: Address, to: Address, data: Vec<u8>) -> Result<Bytes> {
+        let block = self.block_env();
+        let mut evm = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(SpecId::CANCUN)
+            .with_block_env(block)
+            .with_tx_env(tx_env(from, to, data))
+            .build();
+        let result = evm.transact_commit().map_err(|e| anyhow!("{:?}", e))?;
+        output(result).map_err(|data| anyhow!("transaction reverted: 0x{}", ethers::utils::hex::encode(data)))
+    }
+
+    /// eth_call：在当前状态上执行，不提交；revert 时返回 revert 数据
+    pub fn call(&mut self, to: Address, data: Vec<u8>) -> std::result::Result<Bytes, Bytes> {
+        let block = self.block_env();
+        let mut evm = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(SpecId::CANCUN)
+            .with_block_env(block)
+            .with_tx_env(tx_env(Address::zero(), to, data))
+            .build();
+        match evm.transact() {
+            Ok(outcome) => output(outcome.result),
+            Err(e) => panic!("eth_call failed: {:?}", e),
+        }
+    }
+
+    fn block_env(&self) -> BlockEnv {
+        BlockEnv { number: evm::U256::from(self.block), ..Default::default() }
+    }
+
+    /// 节点的 JSON-RPC 方法；没实现的方法直接 panic，测试里一眼能看出来
+    pub fn handle(&mut self, method: &str, params: &Value) -> RpcReply {
+        match method {
+            "eth_chainId" => Ok(json!("0x1")),
+            "eth_blockNumber" => Ok(json!(format!("{:#x}", self.block))),
+            "eth_getBlockByNumber" => Ok(block_header(params, self.block)),
+            "eth_getBalance" | "eth_getCode" => {
+                let address: Address = params[0].as_str().unwrap().parse().unwrap();
+                let account = self.db.accounts.get(&evm_address(address));
+                Ok(match method {
+                    "eth_getBalance" => json!(format!("{:#x}", account.map_or(evm::U256::ZERO, |a| a.info.balance))),
+                    _ => {
+                        let code = account.and_then(|a| a.info.code.clone()).map(|c| c.original_bytes());
+                        json!(format!("0x{}", ethers::utils::hex::encode(code.unwrap_or_default())))
+                    }
+                })
+            }
+            "eth_call" => {
+                let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+                let data = super::call_data(params);
+                let data = ethers::utils::hex::decode(data.trim_start_matches("0x")).unwrap();
+                match self.call(to, data) {
+                    Ok(out) => Ok(json!(out)),
+                    Err(revert) => Err(json!({ "code": 3, "message": "execution reverted", "data": revert })),
+                }
+            }
+            other => panic!("unexpected method {}", other),
+        }
+    }
+
+    /// 作为 FakeNode 的响应函数
+    pub fn into_handler(self) -> impl Fn(&str, &Value) -> RpcReply + Send + Sync + 'static {
+        let chain = Mutex::new(self);
+        move |method, params| chain.lock().unwrap().handle(method, params)
+    }
+
+    pub async fn serve(self) -> Result<(Arc<FakeNode>, String)> {
+        FakeNode::start(self.into_handler()).await
+    }
+}
+
+fn tx_env(from: Address, to: Address, data: Vec<u8>) -> TxEnv {
+    TxEnv {
+        caller: evm_address(from),
+        transact_to: TxKind::Call(evm_address(to)),
+        data: data.into(),
+        gas_limit: GAS_LIMIT,
+        gas_price: evm::U256::ZERO,
+        nonce: None,
+        ..Default::default()
+    }
+}
+
+fn output(result: ExecutionResult) -> std::result::Result<Bytes, Bytes> {
+    match result {
+        ExecutionResult::Success { output, .. } => Ok(output.into_data().to_vec().into()),
+        ExecutionResult::Revert { output, .. } => Err(output.to_vec().into()),
+        ExecutionResult::Halt { .. } => Err(Bytes::new()),
+    }
+}
@@ -0,0 +1,511 @@
+// tests/common/evm.rs
+//! 手写字节码的最小 ERC20、Uniswap V2 风格 router 和 ENS registry / resolver，给进程内 EVM 用（沙箱里没有 solc）
+
+use ethers::types::{Address, Bytes, H256, U256};
+use ethers::utils::{id, keccak256};
+use std::collections::HashMap;
+
+pub mod op {
+    pub const STOP: u8 = 0x00;
+    pub const ADD: u8 = 0x01;
+    pub const MUL: u8 = 0x02;
+    pub const SUB: u8 = 0x03;
+    pub const DIV: u8 = 0x04;
+    pub const LT: u8 = 0x10;
+    pub const GT: u8 = 0x11;
+    pub const EQ: u8 = 0x14;
+    pub const ISZERO: u8 = 0x15;
+    pub const AND: u8 = 0x16;
+    pub const SHR: u8 = 0x1c;
+    pub const SHA3: u8 = 0x20;
+    pub const ADDRESS: u8 = 0x30;
+    pub const CALLER: u8 = 0x33;
+    pub const CALLVALUE: u8 = 0x34;
+    pub const CALLDATALOAD: u8 = 0x35;
+    pub const RETURNDATASIZE: u8 = 0x3d;
+    pub const RETURNDATACOPY: u8 = 0x3e;
+    pub const POP: u8 = 0x50;
+    pub const MLOAD: u8 = 0x51;
+    pub const MSTORE: u8 = 0x52;
+    pub const SLOAD: u8 = 0x54;
+    pub const SSTORE: u8 = 0x55;
+    pub const JUMP: u8 = 0x56;
+    pub const JUMPI: u8 = 0x57;
+    pub const GAS: u8 = 0x5a;
+    pub const JUMPDEST: u8 = 0x5b;
+    pub const PUSH1: u8 = 0x60;
+    pub const PUSH2: u8 = 0x61;
+    pub const DUP1: u8 = 0x80;
+    pub const DUP2: u8 = 0x81;
+    pub const DUP3: u8 = 0x82;
+    pub const DUP4: u8 = 0x83;
+    pub const SWAP1: u8 = 0x90;
+    pub const LOG3: u8 = 0xa3;
+    pub const CALL: u8 = 0xf1;
+    pub const RETURN: u8 = 0xf3;
+    pub const STATICCALL: u8 = 0xfa;
+    pub const REVERT: u8 = 0xfd;
+}
+
+use op::*;
+
+/// 带标签的小汇编器：跳转目标用 PUSH2 占位，`build` 时回填
+#[derive(Default)]
+pub struct Asm {
+    code: Vec<u8>,
+    labels: HashMap<&'static str, usize>,
+    fixups: Vec<(usize, &'static str)>,
+}
+
+impl Asm {
+    pub fn op(&mut self, op: u8) -> &mut Self {
+        self.code.push(op);
+        self
+    }
+
+    pub fn ops(&mut self, ops: &[u8]) -> &mut Self {
+        self.code.extend_from_slice(ops);
+        self
+    }
+
+    /// 用最短的 PUSHn 压入 `value`
+    pub fn push(&mut self, value: impl Into<U256>) -> &mut Self {
+        let mut word = [0u8; 32];
+        value.into().to_big_endian(&mut word);
+        let skip = word.iter().take_while(|b| **b == 0).count().min(31);
+        self.code.push(PUSH1 + (31 - skip) as u8);
+        self.code.extend_from_slice(&word[skip..]);
+        self
+    }
+
+    /// 压入函数 selector 左对齐的 32 字节（直接 MSTORE 成 calldata 开头）
+    pub fn push_selector(&mut self, signature: &str) -> &mut Self {
+        let mut word = [0u8; 32];
+        word[..4].copy_from_slice(&id(signature));
+        self.push(U256::from_big_endian(&word))
+    }
+
+    pub fn label(&mut self, name: &'static str) -> &mut Self {
+        self.labels.insert(name, self.code.len());
+        self.op(JUMPDEST)
+    }
+
+    pub fn push_label(&mut self, name: &'static str) -> &mut Self {
+        self.code.push(PUSH2);
+        self.fixups.push((self.code.len(), name));
+        self.code.extend_from_slice(&[0, 0]);
+        self
+    }
+
+    pub fn jump(&mut self, name: &'static str) -> &mut Self {
+        self.push_label(name).op(JUMP)
+    }
+
+    pub fn jumpi(&mut self, name: &'static str) -> &mut Self {
+        self.push_label(name).op(JUMPI)
+    }
+
+    /// 第 i 个 32 字节参数
+    pub fn arg(&mut self, i: u64) -> &mut Self {
+        self.push(4 + 32 * i).op(CALLDATALOAD)
+    }
+
+    pub fn mload(&mut self, offset: u64) -> &mut Self {
+        self.push(offset).op(MLOAD)
+    }
+
+    /// 栈顶存到 memory[offset]
+    pub fn mstore(&mut self, offset: u64) -> &mut Self {
+        self.push(offset).op(MSTORE)
+    }
+
+    /// selector 等于 `signature` 时跳到 `label`（栈顶是 selector，保留）
+    pub fn dispatch(&mut self, signature: &str, label: &'static str) -> &mut Self {
+        self.op(DUP1).push(u32::from_be_bytes(id(signature))).op(EQ).jumpi(label)
+    }
+
+    /// 返回栈顶的一个字
+    pub fn return_word(&mut self) -> &mut Self {
+        self.mstore(0).push(32).push(0).op(RETURN)
+    }
+
+    /// `revert Error(message)`；message 不超过 32 字节
+    pub fn revert_with(&mut self, message: &str) -> &mut Self {
+        assert!(message.len() <= 32);
+        let mut text = [0u8; 32];
+        text[..message.len()].copy_from_slice(message.as_bytes());
+        self.push_selector("Error(string)")
+            .mstore(0)
+            .push(0x20)
+            .mstore(4)
+            .push(message.len() as u64)
+            .mstore(36)
+            .push(U256::from_big_endian(&text))
+            .mstore(68)
+            .push(100)
+            .push(0)
+            .op(REVERT)
+    }
+
+    /// 把调用失败的 revert 数据原样抛出去
+    pub fn bubble_revert(&mut self) -> &mut Self {
+        self.op(RETURNDATASIZE).push(0).push(0).op(RETURNDATACOPY).op(RETURNDATASIZE).push(0).op(REVERT)
+    }
+
+    pub fn build(&self) -> Bytes {
+        let mut code = self.code.clone();
+        for (at, name) in &self.fixups {
+            let target = self.labels[name] as u16;
+            code[*at..*at + 2].copy_from_slice(&target.to_be_bytes());
+        }
+        code.into()
+    }
+}
+
+/// ERC20 `Transfer` / `Approval` 的 topic0
+pub fn event_topic(signature: &str) -> U256 {
+    U256::from_big_endian(&keccak256(signature))
+}
+
+/// 最小 ERC20 的余额槽：`balanceOf[holder]` 存在 slot = holder
+pub fn balance_slot(holder: Address) -> U256 {
+    U256::from_big_endian(H256::from(holder).as_bytes())
+}
+
+/// `allowance[owner][spender]` 存在 slot = keccak256(owner ‖ spender)
+pub fn allowance_slot(owner: Address, spender: Address) -> U256 {
+    let mut key = H256::from(owner).as_bytes().to_vec();
+    key.extend_from_slice(H256::from(spender).as_bytes());
+    U256::from_big_endian(&keccak256(key))
+}
+
+/// 栈上 [from, to, amount] → 记账并发 Transfer；余额不够跳到 "insufficient_balance"
+///
+/// `tax_per_mille` 不为 0 时收款方只到账 amount * (1000 - tax) / 1000，差额直接销毁，但 Transfer 事件照报全额。
+fn emit_move(asm: &mut Asm, tax_per_mille: u64) {
+    asm.op(DUP3).op(SLOAD) // from to amount balFrom
+        .ops(&[DUP2, DUP2, LT]) // balFrom < amount
+        .jumpi("insufficient_balance")
+        .ops(&[DUP2, SWAP1, SUB]) // from to amount balFrom-amount
+        .ops(&[DUP4, SSTORE]) // from to amount
+        .ops(&[DUP2, SLOAD, DUP2]) // from to amount balTo amount
+        .push(1000 - tax_per_mille).op(MUL).push(1000).op(SWAP1).op(DIV) // from to amount balTo net
+        .op(ADD) // from to amount balTo+net
+        .ops(&[DUP3, SSTORE]) // from to amount
+        .mstore(0) // from to
+        .op(SWAP1) // to from
+        .push(event_topic("Transfer(address,address,uint256)"))
+        .push(32)
+        .push(0)
+        .op(LOG3);
+}
+
+/// 18 位小数的最小 ERC20：balanceOf / transfer / transferFrom / approve / allowance / decimals
+pub fn erc20() -> Bytes {
+    token(0, false)
+}
+
+/// 转账时偷偷扣 `tax_per_mille`‰ 的 ERC20（事件里看不出来）
+pub fn taxed_erc20(tax_per_mille: u64) -> Bytes {
+    token(tax_per_mille, false)
+}
+
+/// 貔貅盘：`transfer` 正常（能从 router 买到），`transferFrom` 一律 revert（卖不回去）；
+/// 另外带 owner 专用的 `blacklist(address)` / `mint(address,uint256)`
+pub fn honeypot_erc20() -> Bytes {
+    token(0, true)
+}
+
+fn token(tax_per_mille: u64, honeypot: bool) -> Bytes {
+    let mut asm = Asm::default();
+    asm.push(0).op(CALLDATALOAD).push(0xe0).op(SHR)
+        .dispatch("balanceOf(address)", "balance_of")
+        .dispatch("transfer(address,uint256)", "transfer")
+        .dispatch("transferFrom(address,address,uint256)", "transfer_from")
+        .dispatch("approve(address,uint256)", "approve")
+        .dispatch("allowance(address,address)", "allowance")
+        .dispatch("decimals()", "decimals");
+    if honeypot {
+        asm.dispatch("blacklist(address)", "only_owner").dispatch("mint(address,uint256)", "only_owner");
+    }
+    asm.push(0).push(0).op(REVERT);
+
+    asm.label("balance_of").arg(0).op(SLOAD).return_word();
+    asm.label("decimals").push(18).return_word();
+    asm.label("allowance")
+        .arg(0).mstore(0).arg(1).mstore(32)
+        .push(64).push(0).op(SHA3).op(SLOAD).return_word();
+
+    asm.label("approve")
+        .arg(1)
+        .op(CALLER).mstore(0).arg(0).mstore(32)
+        .push(64).push(0).op(SHA3).op(SSTORE)
+        .arg(1).mstore(0)
+        .arg(0).op(CALLER)
+        .push(event_topic("Approval(address,address,uint256)"))
+        .push(32).push(0).op(LOG3)
+        .push(1).return_word();
+
+    asm.label("transfer").op(CALLER).arg(0).arg(1);
+    emit_move(&mut asm, tax_per_mille);
+    asm.push(1).return_word();
+
+    if honeypot {
+        asm.label("transfer_from").revert_with("TRANSFER_BLOCKED");
+        asm.label("only_owner").revert_with("Ownable: caller is not the owner");
+        asm.label("insufficient_balance").revert_with("insufficient balance");
+        return asm.build();
+    }
+    asm.label("transfer_from")
+        .arg(0).mstore(0).op(CALLER).mstore(32)
+        .push(64).push(0).op(SHA3) // slot
+        .ops(&[DUP1, SLOAD]) // slot allowed
+        .arg(2) // slot allowed amount
+        .ops(&[DUP2, DUP2, GT]) // amount > allowed
+        .jumpi("insufficient_allowance")
+        .ops(&[SWAP1, SUB, SWAP1, SSTORE])
+        .arg(0).arg(1).arg(2);
+    emit_move(&mut asm, tax_per_mille);
+    asm.push(1).return_word();
+
+    asm.label("insufficient_balance").revert_with("insufficient balance");
+    asm.label("insufficient_allowance").revert_with("insufficient allowance");
+    asm.build()
+}
+
+// router 的 memory 布局
+const AMOUNT_IN: u64 = 0x200;
+const AMOUNT_OUT: u64 = 0x220;
+const TOKEN_IN: u64 = 0x240;
+const TOKEN_OUT: u64 = 0x260;
+const RECIPIENT: u64 = 0x280;
+const BALANCE_BEFORE: u64 = 0x2a0;
+const CALLDATA: u64 = 0x100;
+
+/// memory[AMOUNT_OUT] = memory[AMOUNT_IN] * rate / 1000
+fn emit_quote(asm: &mut Asm, rate_per_mille: u64) {
+    asm.mload(AMOUNT_IN).push(rate_per_mille).op(MUL).push(1000).op(SWAP1).op(DIV).mstore(AMOUNT_OUT);
+}
+
+/// 返回 uint256[] [amountIn, amountOut]
+fn emit_return_amounts(asm: &mut Asm) {
+    asm.push(0x20).mstore(0)
+        .push(2).mstore(0x20)
+        .mload(AMOUNT_IN).mstore(0x40)
+        .mload(AMOUNT_OUT).mstore(0x60)
+        .push(0x80).push(0).op(RETURN);
+}
+
+/// CALL memory[token] 上 memory[CALLDATA..CALLDATA+size] 的调用，失败时原样 revert
+fn emit_call(asm: &mut Asm, token: u64, size: u64) {
+    asm.push(0x20).push(0).push(size).push(CALLDATA).push(0).mload(token).op(GAS).op(CALL)
+        .op(ISZERO).jumpi("bubble");
+}
+
+/// `transfer(memory[RECIPIENT], memory[AMOUNT_OUT])` on memory[TOKEN_OUT]
+fn emit_pay_out(asm: &mut Asm) {
+    asm.push_selector("transfer(address,uint256)").mstore(CALLDATA)
+        .mload(RECIPIENT).mstore(CALLDATA + 4)
+        .mload(AMOUNT_OUT).mstore(CALLDATA + 36);
+    emit_call(asm, TOKEN_OUT, 68);
+}
+
+/// memory[0] = memory[token].balanceOf(router)
+fn emit_own_balance(asm: &mut Asm, token: u64) {
+    asm.push_selector("balanceOf(address)").mstore(CALLDATA)
+        .op(ADDRESS).mstore(CALLDATA + 4);
+    emit_call(asm, token, 36);
+}
+
+/// 固定汇率（每 1000 个输入换 `rate_per_mille` 个输出）的 V2 风格 router，输出代币 / ETH 要预先存在 router 上
+///
+/// 支持 getAmountsOut、swapExactTokensForTokens、swapExactETHForTokens，以及
+/// swapExact{ETHForTokens,TokensForETH}SupportingFeeOnTransferTokens；只看 path 的头尾两个代币。
+/// 卖代币换 ETH 时按 router 实际收到的数量计价，和真实的 SupportingFeeOnTransferTokens 一样。
+pub fn router(rate_per_mille: u64) -> Bytes {
+    let mut asm = Asm::default();
+    asm.push(0).op(CALLDATALOAD).push(0xe0).op(SHR)
+        .dispatch("getAmountsOut(uint256,address[])", "get_amounts_out")
+        .dispatch("swapExactTokensForTokens(uint256,uint256,address[],address,uint256)", "tokens_for_tokens")
+        .dispatch("swapExactETHForTokens(uint256,address[],address,uint256)", "eth_for_tokens")
+        .dispatch("swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)", "eth_for_tokens")
+        .dispatch("swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)", "tokens_for_eth")
+        .push(0).push(0).op(REVERT);
+
+    asm.label("get_amounts_out").arg(0).mstore(AMOUNT_IN);
+    emit_quote(&mut asm, rate_per_mille);
+    emit_return_amounts(&mut asm);
+
+    // (amountIn, amountOutMin, path, to, deadline)
+    asm.label("tokens_for_tokens")
+        .arg(0).mstore(AMOUNT_IN)
+        .arg(2).push(36).op(ADD).op(CALLDATALOAD).mstore(TOKEN_IN)
+        .arg(2).push(68).op(ADD).op(CALLDATALOAD).mstore(TOKEN_OUT)
+        .arg(3).mstore(RECIPIENT);
+    emit_quote(&mut asm, rate_per_mille);
+    asm.arg(1).mload(AMOUNT_OUT).op(LT).jumpi("insufficient_output")
+        .push_selector("transferFrom(address,address,uint256)").mstore(CALLDATA)
+        .op(CALLER).mstore(CALLDATA + 4)
+        .op(ADDRESS).mstore(CALLDATA + 36)
+        .mload(AMOUNT_IN).mstore(CALLDATA + 68);
+    emit_call(&mut asm, TOKEN_IN, 100);
+    emit_pay_out(&mut asm);
+    emit_return_amounts(&mut asm);
+
+    // (amountOutMin, path, to, deadline) payable
+    asm.label("eth_for_tokens")
+        .op(CALLVALUE).mstore(AMOUNT_IN)
+        .arg(1).push(68).op(ADD).op(CALLDATALOAD).mstore(TOKEN_OUT)
+        .arg(2).mstore(RECIPIENT);
+    emit_quote(&mut asm, rate_per_mille);
+    asm.arg(0).mload(AMOUNT_OUT).op(LT).jumpi("insufficient_output");
+    emit_pay_out(&mut asm);
+    emit_return_amounts(&mut asm);
+
+    // (amountIn, amountOutMin, path, to, deadline)，不返回
+    asm.label("tokens_for_eth")
+        .arg(2).push(36).op(ADD).op(CALLDATALOAD).mstore(TOKEN_IN)
+        .arg(3).mstore(RECIPIENT);
+    emit_own_balance(&mut asm, TOKEN_IN);
+    asm.mload(0).mstore(BALANCE_BEFORE)
+        .push_selector("transferFrom(address,address,uint256)").mstore(CALLDATA)
+        .op(CALLER).mstore(CALLDATA + 4)
+        .op(ADDRESS).mstore(CALLDATA + 36)
+        .arg(0).mstore(CALLDATA + 68);
+    emit_call(&mut asm, TOKEN_IN, 100);
+    emit_own_balance(&mut asm, TOKEN_IN);
+    asm.mload(BALANCE_BEFORE).mload(0).op(SUB).mstore(AMOUNT_IN);
+    emit_quote(&mut asm, rate_per_mille);
+    asm.arg(1).mload(AMOUNT_OUT).op(LT).jumpi("insufficient_output")
+        .push(0).push(0).push(0).push(0).mload(AMOUNT_OUT).mload(RECIPIENT).op(GAS).op(CALL)
+        .op(ISZERO).jumpi("bubble")
+        .op(STOP);
+
+    asm.label("insufficient_output").revert_with("INSUFFICIENT_OUTPUT_AMOUNT");
+    asm.label("bubble").bubble_revert();
+    asm.op(STOP);
+    asm.build()
+}
+
+/// ENSRegistry 的 `records[node]` 起始槽：keccak256(node ‖ 0)，owner 在这里，resolver 在下一个槽
+pub fn ens_record_slot(node: H256) -> U256 {
+    let mut key = node.as_bytes().to_vec();
+    key.extend_from_slice(&[0u8; 32]);
+    U256::from_big_endian(&keccak256(key))
+}
+
+/// 栈顶的 node → 它的 record 槽
+fn emit_record_slot(asm: &mut Asm) {
+    asm.mstore(0).push(0).mstore(32).push(64).push(0).op(SHA3);
+}
+
+/// ENSRegistry 的核心部分：owner / resolver / setSubnodeOwner / setResolver，存储布局和官方合约一致
+///
+/// 只有 node 的 owner 能改它的子节点和 resolver；根节点的 owner 部署时写进 `ens_record_slot(0)`。
+pub fn ens_registry() -> Bytes {
+    let mut asm = Asm::default();
+    asm.push(0).op(CALLDATALOAD).push(0xe0).op(SHR)
+        .dispatch("owner(bytes32)", "owner")
+        .dispatch("resolver(bytes32)", "resolver")
+        .dispatch("setSubnodeOwner(bytes32,bytes32,address)", "set_subnode_owner")
+        .dispatch("setResolver(bytes32,address)", "set_resolver")
+        .push(0).push(0).op(REVERT);
+
+    asm.label("owner").arg(0);
+    emit_record_slot(&mut asm);
+    asm.op(SLOAD).return_word();
+
+    asm.label("resolver").arg(0);
+    emit_record_slot(&mut asm);
+    asm.push(1).op(ADD).op(SLOAD).return_word();
+
+    // 子程序：调用方压入返回地址后跳进来
+    asm.label("only_owner").arg(0);
+    emit_record_slot(&mut asm);
+    asm.op(SLOAD).op(CALLER).op(EQ).op(ISZERO).jumpi("unauthorised")
+        .op(JUMP);
+
+    // subnode = keccak256(node ‖ label)，返回 subnode
+    asm.label("set_subnode_owner").push_label("set_subnode_owner_authorised").jump("only_owner");
+    asm.label("set_subnode_owner_authorised")
+        .op(POP)
+        .arg(0).mstore(0).arg(1).mstore(32).push(64).push(0).op(SHA3) // subnode
+        .op(DUP1);
+    emit_record_slot(&mut asm);
+    asm.arg(2).op(SWAP1).op(SSTORE) // subnode
+        .return_word();
+
+    asm.label("set_resolver").push_label("set_resolver_authorised").jump("only_owner");
+    asm.label("set_resolver_authorised").op(POP).arg(1).arg(0);
+    emit_record_slot(&mut asm);
+    asm.push(1).op(ADD).op(SSTORE).op(STOP);
+
+    asm.label("unauthorised").revert_with("ENS: not the node owner");
+    asm.build()
+}
+
+/// 栈顶的 node → resolver 里第 `kind` 类记录的槽：keccak256(node ‖ kind)
+fn emit_resolver_slot(asm: &mut Asm, kind: u64) {
+    asm.mstore(0).push(kind).mstore(32).push(64).push(0).op(SHA3);
+}
+
+/// PublicResolver 的 addr / name 两类记录；写记录要求调用方是 `registry` 里这个 node 的 owner
+///
+/// name 只支持不超过 31 字节的字符串，按 Solidity 短字符串的格式存在一个槽里。
+pub fn public_resolver(registry: Address) -> Bytes {
+    const ADDR: u64 = 1;
+    const NAME: u64 = 2;
+    let mut asm = Asm::default();
+    asm.push(0).op(CALLDATALOAD).push(0xe0).op(SHR)
+        .dispatch("addr(bytes32)", "addr")
+        .dispatch("name(bytes32)", "name")
+        .dispatch("setAddr(bytes32,address)", "set_addr")
+        .dispatch("setName(bytes32,string)", "set_name")
+        .push(0).push(0).op(REVERT);
+
+    asm.label("addr").arg(0);
+    emit_resolver_slot(&mut asm, ADDR);
+    asm.op(SLOAD).return_word();
+
+    // 返回 (0x20, len, data)
+    asm.label("name").arg(0);
+    emit_resolver_slot(&mut asm, NAME);
+    asm.op(SLOAD) // v
+        .op(DUP1).push(0xff).op(AND).push(2).op(SWAP1).op(DIV).mstore(0x20) // v
+        .op(DUP1).push(0xff).op(AND).op(SWAP1).op(SUB).mstore(0x40)
+        .push(0x20).mstore(0)
+        .push(0x60).push(0).op(RETURN);
+
+    // registry.owner(node) == msg.sender，否则 revert
+    asm.label("authorise")
+        .push_selector("owner(bytes32)").mstore(CALLDATA)
+        .arg(0).mstore(CALLDATA + 4)
+        .push(0x20).push(0).push(36).push(CALLDATA)
+        .push(U256::from_big_endian(H256::from(registry).as_bytes()))
+        .op(GAS).op(STATICCALL)
+        .op(ISZERO).jumpi("bubble")
+        .mload(0).op(CALLER).op(EQ).op(ISZERO).jumpi("unauthorised")
+        .op(JUMP);
+
+    asm.label("set_addr").push_label("set_addr_authorised").jump("authorise");
+    asm.label("set_addr_authorised").arg(1).arg(0);
+    emit_resolver_slot(&mut asm, ADDR);
+    asm.op(SSTORE).op(STOP);
+
+    // (node, string)：字符串的长度在 4 + offset，内容紧随其后
+    asm.label("set_name").push_label("set_name_authorised").jump("authorise");
+    asm.label("set_name_authorised")
+        .arg(1).push(4).op(ADD).op(CALLDATALOAD) // len
+        .push(31).op(DUP2).op(GT).jumpi("too_long")
+        .push(2).op(MUL)
+        .arg(1).push(36).op(ADD).op(CALLDATALOAD)
+        .op(ADD) // value
+        .arg(0);
+    emit_resolver_slot(&mut asm, NAME);
+    asm.op(SSTORE).op(STOP);
+
+    asm.label("unauthorised").revert_with("ENS: not the node owner");
+    asm.label("too_long").revert_with("name longer than 31 bytes");
+    asm.label("bubble").bubble_revert();
+    asm.build()
+}
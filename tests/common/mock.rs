@@ -0,0 +1,202 @@
+// tests/common/mock.rs
+//! 脚本化的 FakeNode 响应：按方法、按 (合约, selector, 参数) 预先写好响应
+
+use super::{aggregate3, encode, selector, FakeNode, RpcReply, AGGREGATE3};
+use eth_mcp_server::rpc::{metered, RpcProvider};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::abi::{self, Token};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, Bytes, U256};
+use ethers::utils::hex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type Reply = RpcReply;
+
+#[derive(Default)]
+struct Script {
+    methods: HashMap<String, Reply>,
+    /// (合约, 完整 calldata) → 返回值
+    calls: HashMap<(Address, String), Reply>,
+    /// (合约, selector) → 返回值，不看参数
+    selectors: HashMap<(Address, String), Reply>,
+    /// eth_getBalance / eth_getCode / eth_getTransactionCount / eth_getStorageAt 用的账户状态
+    accounts: HashMap<Address, Account>,
+    /// 部署了 Multicall3：aggregate3 拆开按脚本逐个回答
+    multicall: bool,
+    requests: Vec<(String, Value)>,
+}
+
+impl Script {
+    /// 脚本里 `to` 上这段 calldata 的响应：先看完整 calldata，再看 selector
+    fn call(&self, to: Address, data: &str) -> Option<&Reply> {
+        let exact = self.calls.get(&(to, data.to_string()));
+        exact.or_else(|| data.get(..10).and_then(|s| self.selectors.get(&(to, s.to_string()))))
+    }
+}
+
+#[derive(Clone, Default)]
+struct Account {
+    balance: U256,
+    code: Bytes,
+    storage: HashMap<U256, U256>,
+}
+
+const STATE_METHODS: [&str; 4] = ["eth_getBalance", "eth_getCode", "eth_getTransactionCount", "eth_getStorageAt"];
+
+/// 按方法、按 (合约, selector, 参数) 预先写好响应的节点脚本，经 [`FakeNode`] 对外提供
+///
+/// 没写响应的请求返回 -32601，便于测试 RPC 出错的路径。
+#[derive(Clone, Default)]
+pub struct MockRpc {
+    script: Arc<Mutex<Script>>,
+}
+
+fn rpc_error(code: i64, message: &str) -> Value {
+    json!({ "code": code, "message": message })
+}
+
+impl MockRpc {
+    /// 默认链 ID 1、最新块 100
+    pub fn new() -> Self {
+        let mock = Self::default();
+        mock.on("eth_chainId", json!("0x1"));
+        mock.on("eth_blockNumber", json!("0x64"));
+        mock
+    }
+
+    /// 作为 FakeNode 的响应函数
+    pub fn handler(&self) -> impl Fn(&str, &Value) -> RpcReply + Send + Sync + 'static {
+        let mock = self.clone();
+        move |method, params| mock.reply(method, params)
+    }
+
+    /// 起一个按脚本应答的 FakeNode，返回连到它的 provider
+    pub async fn provider(&self) -> Arc<RpcProvider> {
+        let (_node, url) = FakeNode::start(self.handler()).await.expect("start fake node");
+        Arc::new(metered(Provider::<Http>::try_from(url.as_str()).unwrap()))
+    }
+
+    /// 某个方法不论参数都返回 `result`
+    pub fn on(&self, method: &str, result: Value) -> &Self {
+        self.script.lock().unwrap().methods.insert(method.to_string(), Ok(result));
+        self
+    }
+
+    /// 某个方法返回 JSON-RPC 错误
+    pub fn fail(&self, method: &str, code: i64, message: &str) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .methods
+            .insert(method.to_string(), Err(rpc_error(code, message)));
+        self
+    }
+
+    /// `to.signature(args)` 的 eth_call 返回 `output`；参数必须完全一致
+    pub fn on_call(&self, to: Address, signature: &str, args: &[Token], output: &[Token]) -> &Self {
+        let data = format!("{}{}", selector(signature), hex::encode(abi::encode(args)));
+        self.script.lock().unwrap().calls.insert((to, data), Ok(encode(output)));
+        self
+    }
+
+    /// `to.signature(..)` 的 eth_call 不论参数都返回 `output`
+    pub fn on_selector(&self, to: Address, signature: &str, output: &[Token]) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .selectors
+            .insert((to, selector(signature)), Ok(encode(output)));
+        self
+    }
+
+    /// `to.signature(..)` 的 eth_call revert
+    pub fn revert(&self, to: Address, signature: &str) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .selectors
+            .insert((to, selector(signature)), Err(rpc_error(3, "execution reverted")));
+        self
+    }
+
+    /// 在 Multicall3 地址上应答 aggregate3，子调用按脚本回答（没写的子调用算失败）
+    pub fn multicall(&self) -> &Self {
+        self.script.lock().unwrap().multicall = true;
+        self
+    }
+
+    /// 账户的余额和代码；没写的账户按空账户回答状态查询
+    pub fn account(&self, address: Address, balance: U256, code: Bytes) -> &Self {
+        let mut script = self.script.lock().unwrap();
+        let account = script.accounts.entry(address).or_default();
+        account.balance = balance;
+        account.code = code;
+        self
+    }
+
+    /// 账户的一个 storage 槽
+    pub fn storage(&self, address: Address, slot: U256, value: U256) -> &Self {
+        self.script.lock().unwrap().accounts.entry(address).or_default().storage.insert(slot, value);
+        self
+    }
+
+    /// 某个方法被调用的次数
+    pub fn count(&self, method: &str) -> usize {
+        self.script.lock().unwrap().requests.iter().filter(|(m, _)| m == method).count()
+    }
+
+    /// 按顺序记录的 (方法, 参数)
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.script.lock().unwrap().requests.clone()
+    }
+
+    fn reply(&self, method: &str, params: &Value) -> Reply {
+        let mut script = self.script.lock().unwrap();
+        script.requests.push((method.to_string(), params.clone()));
+
+        if method == "eth_call" || method == "eth_estimateGas" {
+            let to: Option<Address> = params[0]["to"].as_str().and_then(|s| s.parse().ok());
+            let data = params[0]["data"].as_str().or(params[0]["input"].as_str()).unwrap_or("0x");
+            if method == "eth_call" && script.multicall && to == Some(MULTICALL_ADDRESS) {
+                let data = hex::decode(data.trim_start_matches("0x")).unwrap();
+                if data.starts_with(&AGGREGATE3) {
+                    let out = aggregate3(&data, |to, calldata| {
+                        let reply = script.call(to, &format!("0x{}", hex::encode(calldata)))?;
+                        let out = reply.as_ref().ok()?.as_str()?;
+                        Some(hex::decode(out.trim_start_matches("0x")).unwrap())
+                    });
+                    return Ok(json!(format!("0x{}", hex::encode(out))));
+                }
+            }
+            if let Some(to) = to {
+                if let Some(reply) = script.call(to, data) {
+                    // 估算 gas 只关心会不会 revert，成功时交给脚本里的 eth_estimateGas
+                    if method == "eth_call" || reply.is_err() {
+                        return reply.clone();
+                    }
+                }
+            }
+        }
+        if !script.methods.contains_key(method) && STATE_METHODS.contains(&method) {
+            let address: Address = params[0].as_str().and_then(|s| s.parse().ok()).unwrap_or_default();
+            let account = script.accounts.get(&address).cloned().unwrap_or_default();
+            return Ok(match method {
+                "eth_getBalance" => json!(format!("{:#x}", account.balance)),
+                "eth_getCode" => json!(account.code),
+                "eth_getTransactionCount" => json!("0x0"),
+                _ => {
+                    let slot = params[1].as_str().and_then(|s| U256::from_str_radix(s.trim_start_matches("0x"), 16).ok()).unwrap_or_default();
+                    let value = account.storage.get(&slot).copied().unwrap_or_default();
+                    json!(format!("0x{:064x}", value))
+                }
+            });
+        }
+        script
+            .methods
+            .get(method)
+            .cloned()
+            .unwrap_or_else(|| Err(rpc_error(-32601, &format!("no scripted response for {}", method))))
+    }
+}
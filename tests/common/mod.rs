@@ -0,0 +1,256 @@
+// tests/common/mod.rs
+#![allow(dead_code)]
+
+pub mod chain;
+pub mod evm;
+pub mod mock;
+
+use anyhow::Result;
+use axum::{extract::State, routing::post, Json};
+use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::cache::RpcCache;
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::http::{HttpServer, MCP_PATH};
+use eth_mcp_server::price::PriceModule;
+use eth_mcp_server::registry::TokenRegistry;
+use eth_mcp_server::rpc::{metered, RpcProvider};
+use eth_mcp_server::service::TokenService;
+use eth_mcp_server::swap::SwapModule;
+use eth_mcp_server::token::TokenModule;
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, H256};
+use rmcp::model::CallToolRequestParam;
+use rmcp::service::{RoleClient, RunningService};
+use rmcp::ServiceExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// 节点返回：Ok(result) 或 Err(JSON-RPC error 对象)
+pub type RpcReply = std::result::Result<Value, Value>;
+
+type Handler = Box<dyn Fn(&str, &Value) -> RpcReply + Send + Sync>;
+
+/// 本地假节点：按方法计数，响应由测试提供
+pub struct FakeNode {
+    calls: Mutex<HashMap<String, u64>>,
+    handler: Handler,
+}
+
+impl FakeNode {
+    pub async fn start(
+        handler: impl Fn(&str, &Value) -> RpcReply + Send + Sync + 'static,
+    ) -> Result<(Arc<FakeNode>, String)> {
+        let node = Arc::new(FakeNode {
+            calls: Mutex::new(HashMap::new()),
+            handler: Box::new(handler),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let app = axum::Router::new()
+            .route(
+                "/",
+                post(|State(node): State<Arc<FakeNode>>, Json(req): Json<Value>| async move {
+                    let method = req["method"].as_str().unwrap_or_default();
+                    *node.calls.lock().unwrap().entry(method.to_string()).or_default() += 1;
+                    Json(match (node.handler)(method, &req["params"]) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": req["id"], "result": result }),
+                        Err(error) => json!({ "jsonrpc": "2.0", "id": req["id"], "error": error }),
+                    })
+                }),
+            )
+            .with_state(node.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok((node, url))
+    }
+
+    pub fn count(&self, method: &str) -> u64 {
+        self.calls.lock().unwrap().get(method).copied().unwrap_or(0)
+    }
+}
+
+/// 本地 HTTP 接口：任何 GET 都返回 `body`，记下请求的路径和查询串
+pub async fn serve_json(body: Value) -> Result<(Arc<Mutex<Vec<String>>>, String)> {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let seen = requests.clone();
+    let app = axum::Router::new().fallback(move |uri: axum::http::Uri| {
+        seen.lock().unwrap().push(uri.to_string());
+        let body = body.clone();
+        async move { Json(body) }
+    });
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok((requests, url))
+}
+
+/// 在随机端口上跑 MCP HTTP 服务，返回 MCP 地址、关闭令牌和服务任务
+pub async fn serve_http(server: Arc<HttpServer>) -> Result<(String, CancellationToken, JoinHandle<Result<()>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}{}", listener.local_addr()?, MCP_PATH);
+    let shutdown = CancellationToken::new();
+    let handle = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { server.serve(listener, shutdown).await }
+    });
+    Ok((url, shutdown, handle))
+}
+
+/// 测试里的地址常量
+pub fn addr(s: &str) -> Address {
+    s.parse().unwrap()
+}
+
+/// eth_call 的 calldata
+pub fn call_data(params: &Value) -> &str {
+    params[0]["data"]
+        .as_str()
+        .or(params[0]["input"].as_str())
+        .unwrap_or("")
+}
+
+/// 32 字节的 uint 返回值
+pub fn word(n: u64) -> Value {
+    json!(format!("0x{:064x}", n))
+}
+
+/// 0x 前缀的十六进制 JSON 字符串
+pub fn hex(data: &[u8]) -> Value {
+    json!(format!("0x{}", ethers::utils::hex::encode(data)))
+}
+
+/// 函数签名的 4 字节 selector（0x 前缀）
+pub fn selector(signature: &str) -> String {
+    format!("0x{}", ethers::utils::hex::encode(ethers::utils::id(signature)))
+}
+
+/// ABI 编码后的返回值
+pub fn encode(tokens: &[Token]) -> Value {
+    hex(&abi::encode(tokens))
+}
+
+/// 事件签名的 topic0
+pub fn topic(signature: &str) -> H256 {
+    H256::from(ethers::utils::keccak256(signature))
+}
+
+/// indexed address 参数的 topic
+pub fn address_topic(a: &str) -> H256 {
+    H256::from(addr(a))
+}
+
+/// eth_getBlockByNumber 的最小响应：块哈希由块号决定，`safe` / `finalized` 分别落后 `latest` 32 / 64 块
+pub fn block_header(params: &Value, latest: u64) -> Value {
+    let number = match params[0].as_str().unwrap_or("latest") {
+        "latest" | "pending" => latest,
+        "safe" => latest.saturating_sub(32),
+        "finalized" => latest.saturating_sub(64),
+        hex => u64::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap(),
+    };
+    json!({
+        "number": format!("{:#x}", number),
+        "hash": format!("0x{:064x}", number),
+        "timestamp": format!("{:#x}", 1_700_000_000 + 12 * number),
+    })
+}
+
+/// Multicall3.aggregate3((address,bool,bytes)[])：逐个交给 `call`，None 表示该子调用 revert
+pub fn aggregate3(data: &[u8], call: impl Fn(Address, &[u8]) -> Option<Vec<u8>>) -> Vec<u8> {
+    let call3 = ParamType::Tuple(vec![ParamType::Address, ParamType::Bool, ParamType::Bytes]);
+    let Token::Array(calls) = abi::decode(&[ParamType::Array(Box::new(call3))], &data[4..])
+        .unwrap()
+        .remove(0)
+    else {
+        unreachable!()
+    };
+
+    let results = calls
+        .into_iter()
+        .map(|c| {
+            let Token::Tuple(fields) = c else { unreachable!() };
+            let (to, data) = (fields[0].clone().into_address().unwrap(), fields[2].clone().into_bytes().unwrap());
+            match call(to, &data) {
+                Some(out) => Token::Tuple(vec![Token::Bool(true), Token::Bytes(out)]),
+                None => Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+            }
+        })
+        .collect();
+    abi::encode(&[Token::Array(results)])
+}
+
+/// aggregate3 的 selector
+pub const AGGREGATE3: [u8; 4] = [0x82, 0xad, 0x56, 0xcb];
+
+/// 合约 revert
+pub fn reverted() -> RpcReply {
+    Err(json!({ "code": 3, "message": "execution reverted" }))
+}
+
+/// 用同一个 provider / 缓存 / 注册表组装 TokenService
+pub fn service_for(url: &str, config: AppConfig) -> TokenService {
+    let provider = Provider::<Http>::try_from(url).unwrap();
+    service_on(Arc::new(metered(provider)), config)
+}
+
+/// 同 `service_for`，provider 由调用方给（如录制 / 回放 fixture）
+pub fn service_on(provider: Arc<RpcProvider>, config: AppConfig) -> TokenService {
+    service_with_cache(provider, config, RpcCache::default())
+}
+
+/// 同 `service_on`，缓存由调用方给（如 `RpcCache::frozen()`）
+pub fn service_with_cache(provider: Arc<RpcProvider>, config: AppConfig, cache: RpcCache) -> TokenService {
+    let cache = Arc::new(cache);
+    let registry = Arc::new(TokenRegistry::default());
+
+    TokenService::new(
+        Arc::new(BalanceModule::shared(provider.clone(), cache.clone())),
+        Arc::new(
+            PriceModule::shared(provider.clone(), config.clone())
+                .with_cache(cache.clone())
+                .with_registry(registry.clone()),
+        ),
+        Arc::new(
+            SwapModule::shared(provider.clone(), config)
+                .with_cache(cache.clone())
+                .with_registry(registry.clone()),
+        ),
+        Arc::new(TokenModule::shared(provider, cache).with_registry(registry)),
+    )
+}
+
+/// 内存里的双工通道，直接以 MCP 客户端身份连上 service
+pub async fn connect(service: TokenService) -> Result<RunningService<RoleClient, ()>> {
+    let (server, client) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Ok(running) = service.serve(server).await {
+            let _ = running.waiting().await;
+        }
+    });
+    Ok(().serve(client).await?)
+}
+
+/// 调用工具，返回 structured content
+pub async fn call(
+    client: &RunningService<RoleClient, ()>,
+    tool: &str,
+    args: Value,
+) -> Result<Value> {
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: tool.to_string().into(),
+            arguments: args.as_object().cloned(),
+        })
+        .await?;
+    result
+        .structured_content
+        .ok_or_else(|| anyhow::anyhow!("{} returned no structured content", tool))
+}
+
+/// 不需要真实节点：list_tools 等协议层请求不会触发 RPC
+pub fn offline_service() -> TokenService {
+    service_for("http://127.0.0.1:1", AppConfig::default())
+}
@@ -0,0 +1,165 @@
+// tests/confidence_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{block_header, call, call_data, connect, service_for, word, FakeNode, RpcReply};
+use eth_mcp_server::config::{AppConfig, Confidence};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::types::Address;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const LATEST: u64 = 1000;
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
+/// ETH/USD feed，8 位小数
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+
+fn block_number(tag: &Value) -> u64 {
+    u64::from_str_radix(tag.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// 余额和价格都随块变化：第 n 块余额 n/1000 ETH，价格 n USD，这样能看出读的是哪个块
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!(format!("{:#x}", LATEST))),
+        "eth_getBlockByNumber" => Ok(block_header(params, LATEST)),
+        "eth_getBalance" => Ok(json!(format!("{:#x}", block_number(&params[1]) as u128 * 10u128.pow(15)))),
+        "eth_call" => {
+            let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+            let data = call_data(params);
+            if to == MULTICALL_ADDRESS {
+                // 没有 Multicall3，退回逐个查询
+                return Ok(json!("0x"));
+            }
+            if to != FEED.parse().unwrap() {
+                // ENS 反查：没有 resolver
+                return Ok(word(0));
+            }
+            match &data[..10] {
+                // latestRoundData()
+                "0xfeaf968c" => {
+                    let answer = block_number(&params[1]) * 100_000_000;
+                    let words = [1, answer, 0, 0, 1].map(|n| format!("{:064x}", n));
+                    Ok(json!(format!("0x{}", words.concat())))
+                }
+                // decimals()
+                "0x313ce567" => Ok(word(8)),
+                other => panic!("unexpected feed call {}", other),
+            }
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+fn config() -> AppConfig {
+    AppConfig {
+        token_addresses: HashMap::from([("ETH".to_string(), FEED.parse().unwrap())]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_parse_confidence() {
+    assert_eq!("latest".parse::<Confidence>().unwrap(), Confidence::Latest);
+    assert_eq!("0".parse::<Confidence>().unwrap(), Confidence::Latest);
+    assert_eq!("12".parse::<Confidence>().unwrap(), Confidence::Confirmations(12));
+    assert_eq!("safe".parse::<Confidence>().unwrap(), Confidence::Safe);
+    assert_eq!(" finalized ".parse::<Confidence>().unwrap(), Confidence::Finalized);
+    assert!("soon".parse::<Confidence>().is_err());
+    assert!("-1".parse::<Confidence>().is_err());
+}
+
+#[tokio::test]
+async fn test_reads_are_pinned_to_requested_block() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let latest = call(&client, "get_balance", json!({ "address": WALLET })).await?;
+    assert_eq!(latest["balance"], "1.000000000000000000");
+    assert_eq!(latest["block"], LATEST);
+    assert_eq!(latest["block_hash"], format!("0x{:064x}", LATEST));
+
+    let confirmed = call(&client, "get_balance", json!({ "address": WALLET, "confidence": "12" })).await?;
+    assert_eq!(confirmed["balance"], "0.988000000000000000");
+    assert_eq!(confirmed["block"], 988);
+    assert_eq!(confirmed["block_hash"], format!("0x{:064x}", 988));
+
+    // safe / finalized 用节点的块标签，块号以节点返回为准
+    let safe = call(&client, "get_price", json!({ "confidence": "safe" })).await?;
+    assert_eq!(safe["price"], "968.00000000");
+    assert_eq!(safe["block"], 968);
+
+    let finalized = call(&client, "get_price", json!({ "confidence": "finalized" })).await?;
+    assert_eq!(finalized["price"], "936.00000000");
+    assert_eq!(finalized["block_hash"], format!("0x{:064x}", 936));
+
+    // 同一个最新块内，固定块的块头只查一次；每次读完只多一次确认块哈希的请求（finalized 不用确认）
+    assert_eq!(node.count("eth_getBlockByNumber"), 7);
+    call(&client, "get_balance", json!({ "address": WALLET, "confidence": "12" })).await?;
+    assert_eq!(node.count("eth_getBlockByNumber"), 8);
+
+    let err = call(&client, "get_price", json!({ "confidence": "soon" })).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid confidence"));
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_default_confidence_applies_to_balances_and_prices() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let service = service_for(&url, config()).with_confidence(Confidence::Finalized);
+    let client = connect(service).await?;
+
+    let result = call(&client, "get_balances", json!({ "addresses": [WALLET] })).await?;
+    assert_eq!(result["block"], 936);
+    assert_eq!(result["block_hash"], format!("0x{:064x}", 936));
+    assert_eq!(result["rows"][0]["balance"], "0.936000000000000000");
+    // USD 价格读自同一个块
+    assert_eq!(result["tokens"][0]["usd_price"], "936.00000000");
+    assert_eq!(result["usd_total"], "876.10");
+
+    // 单次调用可以覆盖默认值
+    let latest = call(&client, "get_balance", json!({ "address": WALLET, "confidence": "latest" })).await?;
+    assert_eq!(latest["block"], LATEST);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reorg_during_read_is_reported() -> Result<()> {
+    // 最新块在第二次查块头时被换掉：哈希变了，余额也变了
+    let headers = Arc::new(AtomicU64::new(0));
+    let (node, url) = FakeNode::start({
+        let headers = headers.clone();
+        move |method: &str, params: &Value| match method {
+            "eth_getBlockByNumber" => {
+                let mut header = block_header(params, LATEST);
+                if headers.fetch_add(1, Ordering::SeqCst) >= 1 {
+                    header["hash"] = json!(format!("0x{:064x}", 0xbeef));
+                }
+                Ok(header)
+            }
+            "eth_getBalance" if headers.load(Ordering::SeqCst) >= 2 => Ok(json!("0x0")),
+            _ => handle(method, params),
+        }
+    })
+    .await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let err = call(&client, "get_balance", json!({ "address": WALLET })).await.unwrap_err();
+    assert!(err.to_string().contains("reorganized"), "{}", err);
+
+    // 缓存里旧块的块头和余额已清掉，重试读的是新块
+    let retried = call(&client, "get_balance", json!({ "address": WALLET })).await?;
+    assert_eq!(retried["block_hash"], format!("0x{:064x}", 0xbeef));
+    assert_eq!(retried["balance"], "0.000000000000000000");
+    assert_eq!(node.count("eth_getBalance"), 2);
+
+    client.cancel().await?;
+    Ok(())
+}
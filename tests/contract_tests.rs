@@ -0,0 +1,174 @@
+// tests/contract_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{call, call_data, connect, encode, selector, service_for, word, FakeNode};
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::contract::{self, detokenize, tokenize};
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::U256;
+use serde_json::{json, Value};
+
+const PAIR: &str = "0x00000000000000000000000000000000000000c1";
+const HOLDER: &str = "0x00000000000000000000000000000000000000a1";
+
+fn handle(method: &str, params: &Value) -> common::RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_call" => {
+            let data = call_data(params);
+            if data.starts_with(&selector("getReserves()")) {
+                Ok(encode(&[
+                    Token::Uint(U256::from_dec_str("1234567890123456789012345").unwrap()),
+                    Token::Uint(42.into()),
+                    Token::Uint(1_700_000_000u64.into()),
+                ]))
+            } else if data.starts_with(&selector("balanceOf(address)")) {
+                assert!(data.ends_with(&HOLDER[2..]));
+                Ok(word(7))
+            } else if data.starts_with(&selector("deposit()")) {
+                // 模拟时带上了 from / value
+                assert_eq!(params[0]["from"], HOLDER);
+                assert_eq!(params[0]["value"], "0x3e8");
+                Ok(json!("0x"))
+            } else if data.starts_with(&selector("withdraw(uint256)")) {
+                let reason = abi::encode(&[Token::String("paused".to_string())]);
+                Err(json!({
+                    "code": 3,
+                    "message": "execution reverted",
+                    "data": format!("0x08c379a0{}", ethers::utils::hex::encode(reason)),
+                }))
+            } else {
+                panic!("unexpected call {}", data)
+            }
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_call_view_function_by_signature_or_abi() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(
+        &client,
+        "call_contract",
+        json!({
+            "address": PAIR,
+            "function": "function getReserves() view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)"
+        }),
+    )
+    .await?;
+    assert_eq!(result["function"], "getReserves()");
+    // 大整数不丢精度
+    assert_eq!(
+        result["outputs"],
+        json!([
+            { "name": "reserve0", "type": "uint112", "value": "1234567890123456789012345" },
+            { "name": "reserve1", "type": "uint112", "value": "42" },
+            { "name": "blockTimestampLast", "type": "uint32", "value": "1700000000" },
+        ])
+    );
+    assert_eq!(result["block"], 100);
+    assert_eq!(result["simulated"], false);
+
+    // 同一块内重复读取走缓存
+    call(&client, "call_contract", json!({ "address": PAIR, "function": "getReserves() view returns (uint112, uint112, uint32)" })).await?;
+    assert_eq!(node.count("eth_call"), 1);
+
+    let abi = json!([
+        { "type": "function", "name": "balanceOf", "stateMutability": "view",
+          "inputs": [{ "name": "owner", "type": "address" }],
+          "outputs": [{ "name": "", "type": "uint256" }] },
+        { "type": "function", "name": "transfer", "stateMutability": "nonpayable",
+          "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+          "outputs": [{ "name": "", "type": "bool" }] },
+    ]);
+    let result = call(
+        &client,
+        "call_contract",
+        json!({ "address": PAIR, "abi": abi, "function": "balanceOf", "args": [HOLDER] }),
+    )
+    .await?;
+    assert_eq!(result["outputs"][0]["value"], "7");
+
+    // 参数个数不对
+    let err = call(&client, "call_contract", json!({ "address": PAIR, "abi": abi, "function": "balanceOf" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("takes 1 arguments"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_state_changing_functions_need_simulate() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let err = call(&client, "call_contract", json!({ "address": PAIR, "function": "deposit() payable" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("simulate: true"), "{}", err);
+    assert_eq!(node.count("eth_call"), 0);
+
+    let result = call(
+        &client,
+        "call_contract",
+        json!({ "address": PAIR, "function": "deposit() payable", "simulate": true, "from": HOLDER, "value": "1000" }),
+    )
+    .await?;
+    assert_eq!(result["simulated"], true);
+    assert_eq!(result["outputs"], json!([]));
+
+    // revert 原因从 Error(string) 里解出来
+    let err = call(
+        &client,
+        "call_contract",
+        json!({ "address": PAIR, "function": "withdraw(uint256)", "args": ["5"], "simulate": true }),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("withdraw reverted: paused"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[test]
+fn test_json_arguments_round_trip() {
+    let f = contract::parse_signature(
+        "swap(int24 tick, bytes32 salt, (address,uint256[]) order, bytes data) returns (int256)",
+    )
+    .unwrap();
+    let args = [
+        json!("-887272"),
+        json!(format!("0x{}", "ab".repeat(32))),
+        json!([HOLDER, ["1", "0xff", 3]]),
+        json!("0xdeadbeef"),
+    ];
+    let tokens: Vec<Token> = f
+        .inputs
+        .iter()
+        .zip(&args)
+        .map(|(p, v)| tokenize(&p.kind, v).unwrap())
+        .collect();
+    let decoded: Vec<Value> = f.inputs.iter().zip(&tokens).map(|(p, t)| detokenize(&p.kind, t)).collect();
+    assert_eq!(decoded[0], "-887272");
+    assert_eq!(decoded[2], json!([HOLDER, ["1", "255", "3"]]));
+    assert_eq!(decoded[3], "0xdeadbeef");
+    assert!(!contract::is_read_only(&f));
+
+    // 超出位宽、长度不对、类型不对都报错
+    assert!(tokenize(&ParamType::Int(24), &json!("8388608")).is_err());
+    assert!(tokenize(&ParamType::Uint(8), &json!(256)).is_err());
+    assert!(tokenize(&ParamType::FixedBytes(32), &json!("0x01")).is_err());
+    assert!(tokenize(&ParamType::Address, &json!(1)).is_err());
+    assert_eq!(
+        detokenize(&ParamType::Int(256), &tokenize(&ParamType::Int(256), &json!(-1)).unwrap()),
+        "-1"
+    );
+}
@@ -0,0 +1,227 @@
+// tests/ens_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::chain::LocalChain;
+use common::{addr, call, call_data, connect, evm, reverted, service_for, word, FakeNode};
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::ens::{is_ens_name, normalize};
+use ethers::abi::{self, Token};
+use ethers::providers::ens::{namehash, reverse_address, ENS_ADDRESS};
+use ethers::types::{H256, U256};
+use ethers::utils::{id, keccak256};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+const RESOLVER: &str = "0x0000000000000000000000000000000000000e25";
+/// 部署 registry 的账户，拥有根节点和 .eth / addr.reverse
+const DEPLOYER: &str = "0x0000000000000000000000000000000000000d01";
+const VITALIK: &str = "0xd8da6bf26964af9d7eed9e10e63a1f5c3d1f3a69";
+const IMPOSTOR: &str = "0x00000000000000000000000000000000000000bb";
+
+fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data
+}
+
+/// 本地链上的 ENS：registry 部署在官方地址，所有名字共用一个 PublicResolver；记录都由各自的 owner 发交易写入
+struct EnsChain {
+    chain: LocalChain,
+}
+
+impl EnsChain {
+    fn new() -> Result<Self> {
+        let mut chain = LocalChain::default();
+        chain
+            .deploy(ENS_ADDRESS, evm::ens_registry())
+            .store(ENS_ADDRESS, evm::ens_record_slot(H256::zero()), addr(DEPLOYER).as_bytes().into())
+            .deploy(addr(RESOLVER), evm::public_resolver(ENS_ADDRESS));
+        let mut ens = Self { chain };
+        ens.subnode(DEPLOYER, "", "eth", DEPLOYER)?;
+        ens.subnode(DEPLOYER, "", "reverse", DEPLOYER)?;
+        ens.subnode(DEPLOYER, "reverse", "addr", DEPLOYER)?;
+        Ok(ens)
+    }
+
+    /// `parent` 的 owner 把 `label.parent` 分给 `owner`
+    fn subnode(&mut self, from: &str, parent: &str, label: &str, owner: &str) -> Result<()> {
+        let data = calldata(
+            "setSubnodeOwner(bytes32,bytes32,address)",
+            &[
+                Token::FixedBytes(namehash(parent).as_bytes().to_vec()),
+                Token::FixedBytes(keccak256(label).to_vec()),
+                Token::Address(addr(owner)),
+            ],
+        );
+        self.chain.send(addr(from), ENS_ADDRESS, data)?;
+        Ok(())
+    }
+
+    /// `owner` 给自己的名字设 resolver 和一条记录
+    fn set_record(&mut self, owner: &str, name: &str, signature: &str, value: Token) -> Result<()> {
+        let node = Token::FixedBytes(namehash(name).as_bytes().to_vec());
+        let data = calldata("setResolver(bytes32,address)", &[node.clone(), Token::Address(addr(RESOLVER))]);
+        self.chain.send(addr(owner), ENS_ADDRESS, data)?;
+        self.chain.send(addr(owner), addr(RESOLVER), calldata(signature, &[node, value]))?;
+        Ok(())
+    }
+
+    /// 在 .eth 下注册 `label`，正向解析到 `owner`
+    fn register(mut self, label: &str, owner: &str) -> Result<Self> {
+        self.subnode(DEPLOYER, "eth", label, owner)?;
+        self.set_record(owner, &format!("{}.eth", label), "setAddr(bytes32,address)", Token::Address(addr(owner)))?;
+        Ok(self)
+    }
+
+    /// `address` 声明自己的反向记录
+    fn reverse(mut self, address: &str, name: &str) -> Result<Self> {
+        let label = format!("{:x}", addr(address));
+        self.subnode(DEPLOYER, "addr.reverse", &label, address)?;
+        self.set_record(address, &reverse_address(addr(address)), "setName(bytes32,string)", Token::String(name.to_string()))?;
+        Ok(self)
+    }
+}
+
+fn ens_chain() -> Result<LocalChain> {
+    let mut ens = EnsChain::new()?
+        .register("vitalik", VITALIK)?
+        .reverse(VITALIK, "vitalik.eth")?
+        // 指向同一地址的别名，但不是主名称
+        .register("alias", VITALIK)?
+        // 反向记录谎称自己是 vitalik.eth
+        .reverse(IMPOSTOR, "vitalik.eth")?;
+    ens.chain.fund(addr(VITALIK), U256::exp10(18));
+    Ok(ens.chain)
+}
+
+async fn start_chain() -> Result<String> {
+    let (_node, url) = ens_chain()?.serve().await?;
+    Ok(url)
+}
+
+#[test]
+fn test_only_the_owner_can_change_records() -> Result<()> {
+    let mut ens = EnsChain::new()?.register("vitalik", VITALIK)?;
+    // 冒充者既不能改 vitalik.eth 的解析，也不能抢注它
+    let hijack = ens.set_record(IMPOSTOR, "vitalik.eth", "setAddr(bytes32,address)", Token::Address(addr(IMPOSTOR)));
+    assert!(hijack.is_err());
+    assert!(ens.subnode(IMPOSTOR, "eth", "vitalik", IMPOSTOR).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_normalize() -> Result<()> {
+    assert_eq!(normalize("Vitalik.ETH")?, "vitalik.eth");
+    // 全角字符折叠成 ASCII
+    assert_eq!(normalize("ｖitalik.eth")?, "vitalik.eth");
+    assert!(normalize("vitalik..eth").is_err());
+    assert!(normalize("vita lik.eth").is_err());
+    assert!(normalize("vitalik!.eth").is_err());
+    // ENSIP-15：只认 "." 分隔，不解 Punycode，"_" 只能打头，不许混用易混淆的字母表
+    assert!(normalize("vitalik\u{3002}eth").is_err());
+    assert!(normalize("xn--vitalik-9ja.eth").is_err());
+    assert_eq!(normalize("_dmarc.vitalik.eth")?, "_dmarc.vitalik.eth");
+    assert!(normalize("vita_lik.eth").is_err());
+    assert!(normalize("\u{0430}pple.eth").is_err());
+    assert_eq!(normalize("\u{0430}\u{0431}\u{0432}.eth")?, "\u{0430}\u{0431}\u{0432}.eth");
+    assert_eq!(normalize("\u{2764}\u{FE0F}.eth")?, "\u{2764}.eth");
+
+    assert!(is_ens_name("vitalik.eth"));
+    assert!(!is_ens_name(VITALIK));
+    assert!(!is_ens_name("USDC"));
+    // 带点的代币 symbol
+    assert!(!is_ens_name("USDC.e"));
+    assert!(!is_ens_name("BTC.b"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_balance_accepts_ens_name() -> Result<()> {
+    let url = start_chain().await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(&client, "get_balance", json!({ "address": "Vitalik.eth" })).await?;
+    assert_eq!(result["address"], VITALIK);
+    assert_eq!(result["ens_name"], "vitalik.eth");
+    assert_eq!(result["balance"], "1.000000000000000000");
+
+    // 0x 地址输入也会带上主名称
+    let result = call(&client, "get_balance", json!({ "address": VITALIK })).await?;
+    assert_eq!(result["ens_name"], "vitalik.eth");
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reverse_verification() -> Result<()> {
+    let url = start_chain().await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    // 不要求校验时，别名照常解析
+    let result = call(&client, "get_balance", json!({ "address": "alias.eth" })).await?;
+    assert_eq!(result["address"], VITALIK);
+
+    let err = call(&client, "get_balance", json!({ "address": "alias.eth", "verify_ens": true }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("primary name is vitalik.eth"), "{}", err);
+
+    call(&client, "get_balance", json!({ "address": "vitalik.eth", "verify_ens": true })).await?;
+
+    // 反向记录和正向解析对不上，不显示名字
+    let result = call(&client, "get_balance", json!({ "address": IMPOSTOR })).await?;
+    assert_eq!(result["ens_name"], Value::Null);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unknown_name_is_invalid_params() -> Result<()> {
+    let url = start_chain().await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let err = call(&client, "get_balance", json!({ "address": "nobody.eth" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("nobody.eth has no resolver"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_configured_dotted_symbol_skips_ens() -> Result<()> {
+    let chain = ens_chain()?.into_handler();
+    let (_node, url) = FakeNode::start(move |method, params| {
+        if method != "eth_call" || params[0]["to"] != FEED {
+            return chain(method, params);
+        }
+        match &call_data(params)[..10] {
+            // latestRoundData()
+            "0xfeaf968c" => Ok(json!(format!(
+                "0x{}",
+                [1, 100_000_000, 0, 1_700_000_000, 1].map(|n: u64| format!("{:064x}", n)).concat()
+            ))),
+            // decimals()
+            "0x313ce567" => Ok(word(8)),
+            _ => reverted(),
+        }
+    })
+    .await?;
+    // 最后一段像 TLD，但它是配置好的 symbol
+    let config = AppConfig {
+        token_addresses: HashMap::from([("USDC.ARB".to_string(), FEED.parse()?)]),
+        ..Default::default()
+    };
+    let client = connect(service_for(&url, config)).await?;
+
+    let result = call(&client, "get_price", json!({ "token": "USDC.ARB" })).await?;
+    assert_eq!(result["price"], "1.00000000");
+
+    client.cancel().await?;
+    Ok(())
+}
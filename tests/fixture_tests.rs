@@ -0,0 +1,222 @@
+// tests/fixture_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{block_header, call, call_data, connect, service_on, service_with_cache, word, FakeNode, RpcReply};
+use eth_mcp_server::cache::RpcCache;
+use eth_mcp_server::config::{AppConfig, RpcFixture};
+use eth_mcp_server::fixture::{self, Replay};
+use eth_mcp_server::rpc::{self, Metered, RpcProvider, RpcTransport};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::Address;
+use ethers::utils::hex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
+const OTHER: &str = "0x00000000000000000000000000000000000000a2";
+/// ETH/USD feed，8 位小数，2000 USD
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+/// balanceOf 总是 revert
+const BROKEN: &str = "0x00000000000000000000000000000000000000c2";
+
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getBlockByNumber" => Ok(block_header(params, 0x64)),
+        "eth_getBalance" => Ok(json!("0xde0b6b3a7640000")),
+        "eth_call" => {
+            let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+            if to == BROKEN.parse().unwrap() {
+                return Err(json!({ "code": 3, "message": "execution reverted", "data": "0x" }));
+            }
+            if to != FEED.parse().unwrap() {
+                // ENS 反查：没有 resolver
+                return Ok(word(0));
+            }
+            match &call_data(params)[..10] {
+                "0xfeaf968c" => {
+                    let words = [1, 2000 * 100_000_000u64, 0, 0, 1].map(|n| format!("{:064x}", n));
+                    Ok(json!(format!("0x{}", words.concat())))
+                }
+                "0x313ce567" => Ok(word(8)),
+                // description() → "ETH / USD"
+                "0x7284e416" => Ok(json!(format!("0x{}{}{:0<64}", word_hex(0x20), word_hex(9), hex::encode("ETH / USD")))),
+                other => panic!("unexpected feed call {}", other),
+            }
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+fn word_hex(n: u64) -> String {
+    format!("{:064x}", n)
+}
+
+fn config() -> AppConfig {
+    AppConfig {
+        token_addresses: HashMap::from([("ETH".to_string(), FEED.parse().unwrap())]),
+        ..Default::default()
+    }
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("eth-mcp-{}-{}.jsonl", name, std::process::id()))
+}
+
+fn replay_provider(replay: &Replay) -> Arc<RpcProvider> {
+    Arc::new(Provider::new(Metered::new(RpcTransport::Replay(replay.clone()))))
+}
+
+/// 录制时跑的工具调用；结果（含报错）用于和回放比对
+async fn run_flow(provider: Arc<RpcProvider>) -> Result<Vec<String>> {
+    let client = connect(service_on(provider, config())).await?;
+    let mut outputs = Vec::new();
+    for (tool, args) in [
+        ("get_balance", json!({ "address": WALLET })),
+        ("get_price", json!({})),
+        ("get_balance", json!({ "address": WALLET, "token": BROKEN })),
+    ] {
+        let output = match call(&client, tool, args).await {
+            Ok(result) => result.to_string(),
+            Err(e) => format!("error: {}", e),
+        };
+        outputs.push(output);
+    }
+    client.cancel().await?;
+    Ok(outputs)
+}
+
+#[tokio::test]
+async fn test_record_then_replay() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let path = fixture_path("record");
+    let recorded = {
+        let fixture = RpcFixture::Record(path.display().to_string());
+        let provider = rpc::connect(Provider::<Http>::try_from(url.as_str())?, Some(&fixture), None)?;
+        run_flow(Arc::new(provider)).await?
+    };
+    assert!(recorded[0].contains("1.000000000000000000"));
+    assert!(recorded[1].contains(r#""quote":"USD""#), "{}", recorded[1]);
+    assert!(recorded[2].starts_with("error:"));
+
+    // 每个请求一行，revert 录成 JSON-RPC 错误
+    let exchanges = fixture::load(&path)?;
+    let node_calls: u64 = ["eth_chainId", "eth_blockNumber", "eth_getBlockByNumber", "eth_getBalance", "eth_call"]
+        .iter()
+        .map(|m| node.count(m))
+        .sum();
+    assert_eq!(exchanges.len() as u64, node_calls);
+    let reverted = exchanges.iter().find(|e| e.error.is_some()).unwrap();
+    assert_eq!(reverted.method, "eth_call");
+    assert_eq!(reverted.error.as_ref().unwrap()["message"], "execution reverted");
+
+    // 回放不连节点，结果逐字节一致，录制全部用完
+    let replay = Replay::load(&path)?;
+    let replayed = run_flow(replay_provider(&replay)).await?;
+    assert_eq!(replayed, recorded);
+    assert!(replay.remaining().is_empty());
+    assert_eq!(node.count("eth_chainId"), 1);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_does_not_depend_on_call_timing() -> Result<()> {
+    // 每次查块号都出了一个新块：按时间轮询的话，录制和回放的节奏不同，请求里的块号就对不上
+    let block = Arc::new(AtomicU64::new(0x64));
+    let (node, url) = FakeNode::start({
+        let block = block.clone();
+        move |method, params| match method {
+            "eth_blockNumber" => Ok(json!(format!("{:#x}", block.fetch_add(1, Ordering::SeqCst)))),
+            _ => handle(method, params),
+        }
+    })
+    .await?;
+    let flow = |provider: Arc<RpcProvider>, pause: Duration| async move {
+        let client = connect(service_with_cache(provider, config(), RpcCache::frozen())).await?;
+        let mut outputs = Vec::new();
+        for _ in 0..2 {
+            outputs.push(call(&client, "get_balance", json!({ "address": WALLET })).await?.to_string());
+            tokio::time::sleep(pause).await;
+        }
+        client.cancel().await?;
+        anyhow::Ok(outputs)
+    };
+
+    // 录制时两次调用之间隔得比默认轮询间隔（1 秒）还久
+    let path = fixture_path("timing");
+    let recorded = {
+        let fixture = RpcFixture::Record(path.display().to_string());
+        let provider = rpc::connect(Provider::<Http>::try_from(url.as_str())?, Some(&fixture), None)?;
+        flow(Arc::new(provider), Duration::from_millis(1100)).await?
+    };
+    assert_eq!(node.count("eth_blockNumber"), 1);
+
+    // 回放时不停顿，请求序列仍然和录制一致
+    let replay = Replay::load(&path)?;
+    let replayed = flow(replay_provider(&replay), Duration::ZERO).await?;
+    assert_eq!(replayed, recorded);
+    assert!(replay.remaining().is_empty());
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_rejects_unexpected_request() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let path = fixture_path("mismatch");
+    {
+        let fixture = RpcFixture::Record(path.display().to_string());
+        let provider = rpc::connect(Provider::<Http>::try_from(url.as_str())?, Some(&fixture), None)?;
+        let client = connect(service_on(Arc::new(provider), config())).await?;
+        call(&client, "get_balance", json!({ "address": WALLET })).await?;
+        client.cancel().await?;
+    }
+
+    // 换一个钱包：eth_getBalance 的参数和录制对不上
+    let replay = Replay::load(&path)?;
+    let client = connect(service_on(replay_provider(&replay), config())).await?;
+    let err = call(&client, "get_balance", json!({ "address": OTHER })).await.unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("Unexpected RPC request eth_getBalance"), "{}", message);
+    assert!(message.contains(OTHER), "{}", message);
+    assert!(message.contains(&format!("next recorded request: eth_getBalance [\"{}\"", WALLET)), "{}", message);
+    // 没用上的录制（余额和读完后的块头确认）留给调用方检查
+    assert_eq!(replay.remaining().len(), 3);
+    client.cancel().await?;
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_follows_recorded_order_unless_unordered() -> Result<()> {
+    let exchange = |method: &str, result: Value| fixture::Exchange {
+        method: method.to_string(),
+        params: Value::Null,
+        result: Some(result),
+        error: None,
+    };
+    // 录制里先查块号、后查链 ID
+    let recorded = vec![exchange("eth_blockNumber", json!("0x64")), exchange("eth_chainId", json!("0x1"))];
+
+    let strict = Replay::new(recorded.clone());
+    let err = replay_provider(&strict).get_chainid().await.unwrap_err();
+    assert!(err.to_string().contains("Unexpected RPC request eth_chainId"), "{}", err);
+    assert!(err.to_string().contains("next recorded request: eth_blockNumber"), "{}", err);
+
+    let unordered = Replay::new(recorded).unordered();
+    let provider = replay_provider(&unordered);
+    assert_eq!(provider.get_chainid().await?, 1.into());
+    assert_eq!(provider.get_block_number().await?, 100.into());
+    assert!(unordered.remaining().is_empty());
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// tests/http_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{offline_service, serve_http};
+use eth_mcp_server::config::TransportMode;
+use eth_mcp_server::http::HttpServer;
+use rmcp::transport::StreamableHttpClientTransport;
+use rmcp::ServiceExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+async fn start_server() -> Result<(Arc<HttpServer>, String, CancellationToken, tokio::task::JoinHandle<Result<()>>)> {
+    let server = Arc::new(HttpServer::new(offline_service(), None));
+    let (url, shutdown, handle) = serve_http(server.clone()).await?;
+    Ok((server, url, shutdown, handle))
+}
+
+#[tokio::test]
+async fn test_list_tools_over_http() -> Result<()> {
+    let (_server, url, shutdown, handle) = start_server().await?;
+
+    let client = ().serve(StreamableHttpClientTransport::from_uri(url)).await?;
+    let tools = client.list_all_tools().await?;
+    let names: Vec<_> = tools.iter().map(|t| t.name.to_string()).collect();
+
+    assert!(names.contains(&"get_balance".to_string()));
+    assert!(names.contains(&"get_price".to_string()));
+    assert!(names.contains(&"swap_tokens".to_string()));
+
+    client.cancel().await?;
+    shutdown.cancel();
+    handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_each_client_gets_its_own_session() -> Result<()> {
+    let (server, url, shutdown, handle) = start_server().await?;
+
+    let client_a = ().serve(StreamableHttpClientTransport::from_uri(url.clone())).await?;
+    let client_b = ().serve(StreamableHttpClientTransport::from_uri(url)).await?;
+    assert_eq!(server.session_count().await, 2);
+
+    // 一个会话断开不影响另一个
+    client_a.cancel().await?;
+    assert!(!client_b.list_all_tools().await?.is_empty());
+
+    client_b.cancel().await?;
+    shutdown.cancel();
+    handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_with_open_session() -> Result<()> {
+    let (server, url, shutdown, handle) = start_server().await?;
+
+    // 客户端不主动断开，服务端也必须能退出
+    let _client = ().serve(StreamableHttpClientTransport::from_uri(url)).await?;
+    assert_eq!(server.session_count().await, 1);
+
+    shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(5), handle).await???;
+    assert_eq!(server.session_count().await, 0);
+    Ok(())
+}
+
+#[test]
+fn test_transport_mode_from_args() -> Result<()> {
+    let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(
+        TransportMode::from_args(args(&["--transport", "http", "--bind", "0.0.0.0:9000"]))?,
+        TransportMode::Http { bind: "0.0.0.0:9000".parse()? }
+    );
+    assert!(TransportMode::from_args(args(&["--transport", "carrier-pigeon"])).is_err());
+    assert!(TransportMode::from_args(args(&["--transport", "http", "--bind", "nope"])).is_err());
+    assert!(TransportMode::from_args(args(&["--verbose"])).is_err());
+    Ok(())
+}
@@ -0,0 +1,442 @@
+// tests/indexer_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{address_topic, call, call_data, connect, offline_service, service_for, topic, word, FakeNode, RpcReply};
+use eth_mcp_server::config::{AppConfig, IndexerConfig, NativeTransfers};
+use eth_mcp_server::indexer::Indexer;
+use ethers::providers::{Http, Provider};
+use ethers::types::{H256, U256};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
+const ALICE: &str = "0x00000000000000000000000000000000000000b2";
+const BOB: &str = "0x00000000000000000000000000000000000000b3";
+const VAULT: &str = "0x00000000000000000000000000000000000000c4";
+/// 6 位小数的 ERC20
+const TOKEN: &str = "0x00000000000000000000000000000000000000d5";
+const NFT: &str = "0x00000000000000000000000000000000000000d6";
+
+const ETHER: u64 = 1_000_000_000_000_000_000;
+
+/// 一个 ERC20 / ERC721 Transfer 事件
+#[derive(Clone)]
+struct FakeLog {
+    token: &'static str,
+    from: &'static str,
+    to: &'static str,
+    amount: u64,
+    /// ERC721：amount 是 indexed tokenId
+    nft: bool,
+}
+
+/// 一笔交易，附带它的 trace（trace address, from, to, wei, 是否出错）
+#[derive(Clone)]
+struct FakeTx {
+    from: &'static str,
+    to: &'static str,
+    value: u64,
+    success: bool,
+    logs: Vec<FakeLog>,
+    traces: Vec<(Vec<usize>, &'static str, &'static str, u64, bool)>,
+}
+
+impl FakeTx {
+    fn call(from: &'static str, to: &'static str, value: u64) -> Self {
+        Self {
+            from,
+            to,
+            value,
+            success: true,
+            logs: vec![],
+            traces: vec![(vec![], from, to, value, false)],
+        }
+    }
+
+    fn erc20(from: &'static str, to: &'static str, amount: u64) -> Self {
+        Self {
+            logs: vec![FakeLog { token: TOKEN, from, to, amount, nft: false }],
+            ..Self::call(from, TOKEN, 0)
+        }
+    }
+}
+
+/// 可以重组的假链：每个块带一个分叉编号，编号参与块哈希和交易哈希
+struct Chain {
+    forks: Vec<u64>,
+    blocks: Vec<Vec<FakeTx>>,
+}
+
+impl Chain {
+    fn new(height: usize) -> Self {
+        Self { forks: vec![0; height + 1], blocks: vec![vec![]; height + 1] }
+    }
+
+    /// 把 `from` 之后的块换成另一条分叉
+    fn reorg(&mut self, from: usize, blocks: Vec<Vec<FakeTx>>) {
+        self.forks.truncate(from);
+        self.blocks.truncate(from);
+        self.forks.extend(std::iter::repeat_n(1, blocks.len()));
+        self.blocks.extend(blocks);
+    }
+
+    fn block_hash(&self, number: u64) -> String {
+        format!("{:?}", H256::from_low_u64_be((self.forks[number as usize] << 32) | number))
+    }
+
+    fn tx_hash(&self, number: u64, index: usize) -> String {
+        let fork = self.forks[number as usize];
+        format!("{:?}", H256::from_low_u64_be((1 << 60) | (fork << 32) | (number << 8) | index as u64))
+    }
+
+    /// (块号, 交易序号, 交易)
+    fn txs(&self) -> impl Iterator<Item = (u64, usize, &FakeTx)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(n, txs)| txs.iter().enumerate().map(move |(i, tx)| (n as u64, i, tx)))
+    }
+
+    fn transaction(&self, number: u64, index: usize, tx: &FakeTx) -> Value {
+        json!({
+            "hash": self.tx_hash(number, index),
+            "nonce": "0x0",
+            "blockHash": self.block_hash(number),
+            "blockNumber": format!("{:#x}", number),
+            "transactionIndex": format!("{:#x}", index),
+            "from": tx.from,
+            "to": tx.to,
+            "value": format!("{:#x}", tx.value),
+            "gasPrice": "0x1",
+            "gas": "0x5208",
+            "input": "0x",
+            "v": "0x1",
+            "r": "0x1",
+            "s": "0x1",
+            "type": "0x0",
+            "chainId": "0x1"
+        })
+    }
+}
+
+fn number(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+fn matches(filter: &Value, value: &Value) -> bool {
+    match filter {
+        Value::Null => true,
+        Value::Array(values) => values.contains(value),
+        one => one == value,
+    }
+}
+
+fn handler(chain: Arc<Mutex<Chain>>) -> impl Fn(&str, &Value) -> RpcReply + Send + Sync + 'static {
+    move |method, params| {
+        let chain = chain.lock().unwrap();
+        let head = chain.blocks.len() as u64 - 1;
+        match method {
+            "eth_chainId" => Ok(json!("0x1")),
+            "eth_blockNumber" => Ok(json!(format!("{:#x}", head))),
+            "eth_getBlockByNumber" => {
+                let n = number(&params[0]);
+                let full = params[1].as_bool().unwrap_or(false);
+                let txs: Vec<Value> = chain.blocks[n as usize]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, tx)| match full {
+                        true => chain.transaction(n, i, tx),
+                        false => json!(chain.tx_hash(n, i)),
+                    })
+                    .collect();
+                Ok(json!({
+                    "number": format!("{:#x}", n),
+                    "hash": chain.block_hash(n),
+                    "timestamp": format!("{:#x}", 1_700_000_000 + 12 * n),
+                    "transactions": txs,
+                }))
+            }
+            "eth_getTransactionReceipt" => {
+                let (n, i, tx) = chain.txs().find(|(n, i, _)| json!(chain.tx_hash(*n, *i)) == params[0]).unwrap();
+                Ok(json!({
+                    "transactionHash": chain.tx_hash(n, i),
+                    "transactionIndex": format!("{:#x}", i),
+                    "blockHash": chain.block_hash(n),
+                    "blockNumber": format!("{:#x}", n),
+                    "from": tx.from,
+                    "to": tx.to,
+                    "cumulativeGasUsed": "0x5208",
+                    "gasUsed": "0x5208",
+                    "contractAddress": null,
+                    "logs": [],
+                    "status": if tx.success { "0x1" } else { "0x0" },
+                    "logsBloom": format!("0x{}", "00".repeat(256)),
+                    "type": "0x0"
+                }))
+            }
+            "eth_getLogs" => {
+                let filter = &params[0];
+                let (from, to) = (number(&filter["fromBlock"]), number(&filter["toBlock"]));
+                let topics = filter["topics"].as_array().cloned().unwrap_or_default();
+                let mut logs = Vec::new();
+                let mut index = 0;
+                for (n, i, tx) in chain.txs() {
+                    for log in &tx.logs {
+                        let mut log_topics = vec![
+                            json!(topic("Transfer(address,address,uint256)")),
+                            json!(address_topic(log.from)),
+                            json!(address_topic(log.to)),
+                        ];
+                        let mut data = format!("0x{:064x}", log.amount);
+                        if log.nft {
+                            log_topics.push(json!(format!("0x{:064x}", log.amount)));
+                            data = "0x".to_string();
+                        }
+                        let json = json!({
+                            "address": log.token,
+                            "topics": log_topics,
+                            "data": data,
+                            "blockNumber": format!("{:#x}", n),
+                            "blockHash": chain.block_hash(n),
+                            "transactionHash": chain.tx_hash(n, i),
+                            "transactionIndex": format!("{:#x}", i),
+                            "logIndex": format!("{:#x}", index),
+                            "removed": false
+                        });
+                        index += 1;
+                        if (from..=to).contains(&n)
+                            && matches(&filter["address"], &json["address"])
+                            && topics.iter().enumerate().all(|(t, f)| matches(f, &json["topics"][t]))
+                        {
+                            logs.push(json);
+                        }
+                    }
+                }
+                Ok(json!(logs))
+            }
+            "trace_filter" => {
+                let filter = &params[0];
+                let (from, to) = (number(&filter["fromBlock"]), number(&filter["toBlock"]));
+                let mut traces = Vec::new();
+                for (n, i, tx) in chain.txs() {
+                    for (address, caller, callee, value, failed) in &tx.traces {
+                        let json = json!({
+                            "action": {
+                                "from": caller,
+                                "to": callee,
+                                "value": format!("{:#x}", value),
+                                "gas": "0x0",
+                                "input": "0x",
+                                "callType": "call"
+                            },
+                            "result": { "gasUsed": "0x0", "output": "0x" },
+                            "error": if *failed { json!("Reverted") } else { Value::Null },
+                            "traceAddress": address,
+                            "subtraces": 0,
+                            "transactionPosition": i,
+                            "transactionHash": chain.tx_hash(n, i),
+                            "blockNumber": n,
+                            "blockHash": chain.block_hash(n),
+                            "type": "call"
+                        });
+                        let listed = |key: &str, value: &Value| {
+                            filter[key].as_array().is_none_or(|list| list.contains(value))
+                        };
+                        if (from..=to).contains(&n)
+                            && listed("fromAddress", &json["action"]["from"])
+                            && listed("toAddress", &json["action"]["to"])
+                        {
+                            traces.push(json);
+                        }
+                    }
+                }
+                Ok(json!(traces))
+            }
+            // TOKEN.decimals()
+            "eth_call" => {
+                assert_eq!(&call_data(params)[..10], "0x313ce567");
+                Ok(word(6))
+            }
+            other => panic!("unexpected method {}", other),
+        }
+    }
+}
+
+fn indexer_for(url: &str, config: IndexerConfig) -> Arc<Indexer> {
+    Arc::new(Indexer::new(Provider::<Http>::try_from(url).unwrap(), config).unwrap())
+}
+
+fn values(result: &Value, key: &str) -> Vec<String> {
+    result["transfers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t[key].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_indexer_traces_and_history_pagination() -> Result<()> {
+    let mut chain = Chain::new(20);
+    chain.blocks[3] = vec![FakeTx::erc20(ALICE, WALLET, 5_000_000)];
+    chain.blocks[5] = vec![
+        FakeTx::call(BOB, VAULT, 0),
+        FakeTx {
+            logs: vec![
+                FakeLog { token: TOKEN, from: WALLET, to: BOB, amount: 1_500_000, nft: false },
+                // ERC721 Transfer 签名相同，不算 ERC20
+                FakeLog { token: NFT, from: WALLET, to: BOB, amount: 7, nft: true },
+            ],
+            ..FakeTx::call(WALLET, TOKEN, 0)
+        },
+    ];
+    chain.blocks[8] = vec![FakeTx::call(ALICE, WALLET, ETHER)];
+    chain.blocks[9] = vec![FakeTx {
+        traces: vec![
+            (vec![], BOB, VAULT, 0, false),
+            // 合约内部转给钱包
+            (vec![0], VAULT, WALLET, ETHER / 2, false),
+            // revert 的调用不算
+            (vec![1], VAULT, WALLET, ETHER, true),
+        ],
+        ..FakeTx::call(BOB, VAULT, 0)
+    }];
+    chain.blocks[12] = vec![FakeTx::call(WALLET, BOB, 2 * ETHER)];
+    let (_node, url) = FakeNode::start(handler(Arc::new(Mutex::new(chain)))).await?;
+
+    let mut config = IndexerConfig::new(":memory:", vec![WALLET.parse()?]);
+    config.native = NativeTransfers::Traces;
+    let indexer = indexer_for(&url, config);
+    let report = indexer.sync().await?;
+    assert_eq!(report.transfers, 5);
+    assert_eq!(report.indexed_to, Some(20));
+    assert_eq!(report.rolled_back_to, None);
+    // 已经同步到最新块，再同步没有新数据
+    assert_eq!(indexer.sync().await?.transfers, 0);
+
+    let client = connect(service_for(&url, AppConfig::default()).with_indexer(indexer)).await?;
+    let all = call(&client, "get_transfer_history", json!({ "address": WALLET })).await?;
+    assert_eq!(all["indexed_to_block"], 20);
+    assert_eq!(all["next_cursor"], Value::Null);
+    let blocks: Vec<u64> = all["transfers"].as_array().unwrap().iter().map(|t| t["block"].as_u64().unwrap()).collect();
+    assert_eq!(blocks, [12, 9, 8, 5, 3]);
+    assert_eq!(
+        all["transfers"][0],
+        json!({
+            "block": 12,
+            "timestamp": "2023-11-14T22:15:44Z",
+            "transaction_hash": format!("{:?}", H256::from_low_u64_be((1 << 60) | (12 << 8))),
+            "token": "ETH",
+            "from": WALLET,
+            "to": BOB,
+            "direction": "out",
+            "amount": "2.000000000000000000",
+            "amount_raw": (U256::from(2) * U256::from(ETHER)).to_string(),
+        })
+    );
+    assert_eq!(values(&all, "amount")[1], "0.500000000000000000");
+    assert_eq!(values(&all, "amount")[3..], ["1.500000", "5.000000"]);
+
+    // 分页：每页 2 条
+    let mut pages = Vec::new();
+    let mut cursor = Value::Null;
+    loop {
+        let page = call(
+            &client,
+            "get_transfer_history",
+            json!({ "address": WALLET, "limit": 2, "cursor": cursor }),
+        )
+        .await?;
+        pages.push(values(&page, "amount_raw"));
+        cursor = page["next_cursor"].clone();
+        if cursor.is_null() {
+            break;
+        }
+    }
+    assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+    assert_eq!(pages.concat(), values(&all, "amount_raw"));
+
+    // 过滤
+    let eth = call(&client, "get_transfer_history", json!({ "address": WALLET, "token": "eth" })).await?;
+    assert_eq!(values(&eth, "token"), ["ETH", "ETH", "ETH"]);
+    let token = call(&client, "get_transfer_history", json!({ "address": WALLET, "token": TOKEN })).await?;
+    assert_eq!(values(&token, "amount"), ["1.500000", "5.000000"]);
+    let out = call(&client, "get_transfer_history", json!({ "address": WALLET, "direction": "out" })).await?;
+    assert_eq!(values(&out, "to"), [BOB, BOB]);
+    let alice = call(&client, "get_transfer_history", json!({ "address": WALLET, "counterparty": ALICE })).await?;
+    assert_eq!(values(&alice, "direction"), ["in", "in"]);
+    let range = call(
+        &client,
+        "get_transfer_history",
+        json!({ "address": WALLET, "from_block": 5, "to_block": 9 }),
+    )
+    .await?;
+    assert_eq!(range["transfers"].as_array().unwrap().len(), 3);
+
+    let err = call(&client, "get_transfer_history", json!({ "address": BOB })).await.unwrap_err();
+    assert!(err.to_string().contains("not indexed"), "{}", err);
+    let err = call(&client, "get_transfer_history", json!({ "address": WALLET, "cursor": "x" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid cursor"), "{}", err);
+    client.cancel().await?;
+
+    // 没配置索引时工具不可用
+    let client = connect(offline_service()).await?;
+    let err = call(&client, "get_transfer_history", json!({ "address": WALLET })).await.unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "{}", err);
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_indexer_block_scan_rolls_back_reorgs() -> Result<()> {
+    let mut chain = Chain::new(10);
+    chain.blocks[7] = vec![
+        FakeTx::call(ALICE, WALLET, ETHER),
+        // 不带 ETH 的调用和失败的交易都不算
+        FakeTx::call(WALLET, VAULT, 0),
+        FakeTx { success: false, ..FakeTx::call(WALLET, BOB, ETHER) },
+    ];
+    chain.blocks[9] = vec![FakeTx::erc20(ALICE, WALLET, 1_000_000)];
+    let chain = Arc::new(Mutex::new(chain));
+    let (_node, url) = FakeNode::start(handler(chain.clone())).await?;
+
+    let db = std::env::temp_dir().join(format!("indexer-test-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&db);
+    let mut config = IndexerConfig::new(db.to_string_lossy(), vec![WALLET.parse()?]);
+    config.native = NativeTransfers::Blocks;
+    config.start_block = 5;
+    let indexer = indexer_for(&url, config.clone());
+    let report = indexer.sync().await?;
+    assert_eq!(report.transfers, 2);
+    assert_eq!(report.indexed_to, Some(10));
+
+    // 块 8 起被替换：块 9 的转账换了金额，新链更长
+    chain.lock().unwrap().reorg(
+        8,
+        vec![vec![], vec![FakeTx::erc20(ALICE, WALLET, 3_000_000)], vec![], vec![FakeTx::call(WALLET, BOB, ETHER)]],
+    );
+    let report = indexer.sync().await?;
+    // 记录过哈希的块里，7 是还在链上的最近一块
+    assert_eq!(report.rolled_back_to, Some(7));
+    assert_eq!(report.transfers, 2);
+    assert_eq!(report.indexed_to, Some(11));
+
+    let client = connect(service_for(&url, AppConfig::default()).with_indexer(indexer)).await?;
+    let history = call(&client, "get_transfer_history", json!({ "address": WALLET })).await?;
+    assert_eq!(values(&history, "amount"), ["1.000000000000000000", "3.000000", "1.000000000000000000"]);
+    assert_eq!(values(&history, "direction"), ["out", "in", "in"]);
+    client.cancel().await?;
+
+    // 数据在文件里，重新打开还在；换了钱包就重建
+    let reopened = indexer_for(&url, config.clone());
+    assert_eq!(reopened.store().cursor()?, Some(11));
+    config.wallets.push(BOB.parse()?);
+    let rebuilt = indexer_for(&url, config);
+    assert_eq!(rebuilt.store().cursor()?, None);
+
+    let _ = std::fs::remove_file(&db);
+    Ok(())
+}
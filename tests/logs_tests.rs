@@ -0,0 +1,294 @@
+// tests/logs_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{addr, call, connect, service_for, FakeNode, RpcReply};
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::rpc;
+use ethers::abi::{self, Token};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Filter, H256, U256};
+use ethers::utils::keccak256;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const TOKEN: &str = "0x00000000000000000000000000000000000000c1";
+const ALICE: &str = "0x00000000000000000000000000000000000000a1";
+const BOB: &str = "0x00000000000000000000000000000000000000b2";
+
+/// 最新块
+const LATEST: u64 = 10_000;
+/// 块 n 的时间戳：GENESIS_TIME + 12n
+const GENESIS_TIME: u64 = 1_600_000_000;
+/// 节点拒绝超过这个块数的 eth_getLogs
+const MAX_RANGE: u64 = 2_000;
+
+fn transfer_topic() -> H256 {
+    H256::from(keccak256("Transfer(address,address,uint256)"))
+}
+
+/// (块号, from, to, 金额)
+const TRANSFERS: [(u64, &str, &str, u64); 5] = [
+    (100, ALICE, BOB, 1),
+    (2_500, BOB, ALICE, 2),
+    (2_500, ALICE, BOB, 3),
+    (7_000, ALICE, BOB, 4),
+    (9_999, BOB, ALICE, 5),
+];
+
+fn transfer_logs() -> Vec<Value> {
+    TRANSFERS
+        .iter()
+        .enumerate()
+        .map(|(i, (block, from, to, amount))| {
+            json!({
+                "address": TOKEN,
+                "topics": [
+                    format!("{:?}", transfer_topic()),
+                    format!("{:?}", H256::from(addr(from))),
+                    format!("{:?}", H256::from(addr(to))),
+                ],
+                "data": format!("0x{}", ethers::utils::hex::encode(abi::encode(&[Token::Uint(U256::from(*amount))]))),
+                "blockNumber": format!("{:#x}", block),
+                "blockHash": format!("{:?}", H256::from_low_u64_be(*block)),
+                "transactionHash": format!("{:?}", H256::from_low_u64_be(1_000 + i as u64)),
+                "transactionIndex": "0x0",
+                "logIndex": format!("{:#x}", i),
+                "removed": false
+            })
+        })
+        .collect()
+}
+
+fn block_number(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// topic 过滤条件：null、单个值或数组
+fn topic_matches(filter: &Value, topic: &Value) -> bool {
+    match filter {
+        Value::Null => true,
+        Value::Array(values) => values.contains(topic),
+        value => value == topic,
+    }
+}
+
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!(format!("{:#x}", LATEST))),
+        "eth_getBlockByNumber" => {
+            let n = block_number(&params[0]);
+            Ok(json!({
+                "number": format!("{:#x}", n),
+                "hash": format!("{:?}", H256::from_low_u64_be(n)),
+                "timestamp": format!("{:#x}", GENESIS_TIME + 12 * n),
+            }))
+        }
+        "eth_getLogs" => {
+            let filter = &params[0];
+            let (from, to) = (block_number(&filter["fromBlock"]), block_number(&filter["toBlock"]));
+            if to - from + 1 > MAX_RANGE {
+                return Err(json!({ "code": -32005, "message": "block range too large" }));
+            }
+            let topics = filter["topics"].as_array().cloned().unwrap_or_default();
+            let logs: Vec<Value> = transfer_logs()
+                .into_iter()
+                .filter(|log| {
+                    let block = block_number(&log["blockNumber"]);
+                    (from..=to).contains(&block)
+                        && topics
+                            .iter()
+                            .enumerate()
+                            .all(|(i, t)| topic_matches(t, &log["topics"][i]))
+                })
+                .collect();
+            Ok(json!(logs))
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+const TRANSFER: &str = "Transfer(address indexed from, address indexed to, uint256 value)";
+
+#[tokio::test]
+async fn test_get_logs_pages_through_range_limits() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(&client, "get_logs", json!({ "event": TRANSFER, "addresses": [TOKEN] })).await?;
+    assert_eq!(result["event"], "Transfer(address,address,uint256)");
+    assert_eq!(result["topic0"], format!("{:?}", transfer_topic()));
+    assert_eq!(result["from_block"], 0);
+    assert_eq!(result["to_block"], LATEST);
+    assert_eq!(result["truncated"], false);
+
+    let logs = result["logs"].as_array().unwrap();
+    assert_eq!(logs.len(), 5);
+    assert_eq!(logs[0]["block"], 100);
+    assert_eq!(logs[0]["timestamp"], "2020-09-13T12:46:40Z");
+    assert_eq!(logs[0]["args"][0], json!({ "name": "from", "type": "address", "value": ALICE }));
+    assert_eq!(logs[0]["args"][2], json!({ "name": "value", "type": "uint256", "value": "1" }));
+    assert_eq!(logs[4]["args"][2]["value"], "5");
+    // 节点拒绝大范围查询后分段缩小
+    assert!(node.count("eth_getLogs") > LATEST / MAX_RANGE);
+    // 同一块的时间戳只查一次
+    assert_eq!(node.count("eth_getBlockByNumber"), 4);
+
+    // 条数上限：同一块的日志不拆开，next_from_block 接着查
+    let first = call(&client, "get_logs", json!({ "event": TRANSFER, "limit": 2 })).await?;
+    let logs = first["logs"].as_array().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(first["truncated"], true);
+    assert_eq!(first["next_from_block"], 2_500);
+    let rest = call(
+        &client,
+        "get_logs",
+        json!({ "event": TRANSFER, "from_block": 2_500, "limit": 2 }),
+    )
+    .await?;
+    let values: Vec<&Value> = rest["logs"].as_array().unwrap().iter().map(|l| &l["args"][2]["value"]).collect();
+    assert_eq!(values, ["2", "3"]);
+    assert_eq!(rest["next_from_block"], 4_453);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_logs_indexed_filters() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(
+        &client,
+        "get_logs",
+        json!({ "event": "event Transfer(address indexed from, address indexed to, uint256 value)", "filters": { "to": BOB } }),
+    )
+    .await?;
+    let values: Vec<&Value> = result["logs"].as_array().unwrap().iter().map(|l| &l["args"][2]["value"]).collect();
+    assert_eq!(values, ["1", "3", "4"]);
+
+    // 数组表示任一匹配
+    let result = call(
+        &client,
+        "get_logs",
+        json!({ "event": TRANSFER, "filters": { "from": [ALICE, BOB], "to": ALICE } }),
+    )
+    .await?;
+    assert_eq!(result["logs"].as_array().unwrap().len(), 2);
+
+    // 非 indexed 参数不能过滤
+    let err = call(&client, "get_logs", json!({ "event": TRANSFER, "filters": { "value": "1" } }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not an indexed parameter"), "{}", err);
+
+    let err = call(&client, "get_logs", json!({ "event": TRANSFER, "filters": { "to": "bob" } }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid filter to"), "{}", err);
+
+    let err = call(&client, "get_logs", json!({ "event": "Transfer(address indexed" })).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid event signature"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_logs_time_range() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    // 块 2500 的时间戳是 GENESIS_TIME + 30000，块 7000 是 + 84000
+    let from = chrono::DateTime::from_timestamp((GENESIS_TIME + 30_000) as i64, 0).unwrap();
+    let to = chrono::DateTime::from_timestamp((GENESIS_TIME + 84_000 - 1) as i64, 0).unwrap();
+    let result = call(
+        &client,
+        "get_logs",
+        json!({ "event": TRANSFER, "from_time": from.to_rfc3339(), "to_time": to.to_rfc3339() }),
+    )
+    .await?;
+    assert_eq!(result["from_block"], 2_500);
+    // to_time 落在块 6999 和 7000 之间
+    assert_eq!(result["to_block"], 6_999);
+    let values: Vec<&Value> = result["logs"].as_array().unwrap().iter().map(|l| &l["args"][2]["value"]).collect();
+    assert_eq!(values, ["2", "3"]);
+
+    let err = call(
+        &client,
+        "get_logs",
+        json!({ "event": TRANSFER, "from_block": 1, "from_time": from.to_rfc3339() }),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("either a block or a time"), "{}", err);
+
+    let err = call(&client, "get_logs", json!({ "event": TRANSFER, "from_time": "yesterday" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid from_time"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+/// 节点在 SPARSE_FROM 之前最多接受 MAX_RANGE 块，之后不限；记下每次请求的范围
+fn dense_then_sparse(requests: Arc<Mutex<Vec<(u64, u64)>>>) -> impl Fn(&str, &Value) -> RpcReply {
+    const SPARSE_FROM: u64 = 5_000;
+    move |method, params| match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_getLogs" => {
+            let filter = &params[0];
+            let (from, to) = (block_number(&filter["fromBlock"]), block_number(&filter["toBlock"]));
+            requests.lock().unwrap().push((from, to));
+            if from < SPARSE_FROM && to - from + 1 > MAX_RANGE {
+                return Err(json!({ "code": -32602, "message": "query returned more than 10000 results" }));
+            }
+            Ok(json!([]))
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_scan_logs_respects_max_range_and_grows_back() -> Result<()> {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let (_node, url) = FakeNode::start(dense_then_sparse(requests.clone())).await?;
+    let provider = rpc::connect(Provider::<Http>::try_from(url.as_str())?, None, Some(8_000))?;
+
+    let scan = rpc::scan_logs(&provider, Filter::new(), 0, LATEST, None).await?;
+    assert!(scan.logs.is_empty());
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            // 配置的上限，不是默认的 500,000
+            (0, 7_999),
+            (0, 3_999),
+            (0, 1_999),
+            (2_000, 3_999),
+            (4_000, 5_999),
+            (6_000, 7_999),
+            // 连续成功后放大
+            (8_000, LATEST),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scan_logs_does_not_shrink_on_other_errors() -> Result<()> {
+    let (node, url) = FakeNode::start(|method: &str, _: &Value| match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_getLogs" => Err(json!({ "code": -32000, "message": "header not found" })),
+        other => panic!("unexpected method {}", other),
+    })
+    .await?;
+    let provider = rpc::connect(Provider::<Http>::try_from(url.as_str())?, None, None)?;
+
+    let err = rpc::scan_logs(&provider, Filter::new(), 0, LATEST, None).await.unwrap_err();
+    assert!(err.to_string().contains("header not found"), "{}", err);
+    assert_eq!(node.count("eth_getLogs"), 1);
+    Ok(())
+}
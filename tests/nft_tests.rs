@@ -0,0 +1,317 @@
+// tests/nft_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{
+    addr, address_topic, aggregate3, call, call_data, connect, hex, reverted, service_for, topic, FakeNode, RpcReply,
+    AGGREGATE3,
+};
+use eth_mcp_server::balance::BalanceModule;
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::nft::{HoldingsSource, NftStandard, DEFAULT_NFT_LOG_LIMIT};
+use ethers::abi::{self, Token};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, H256, U256};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const OWNER: &str = "0x00000000000000000000000000000000000000a1";
+const OTHER: &str = "0x00000000000000000000000000000000000000b2";
+/// ERC721 + Enumerable + Metadata
+const ENUMERABLE: &str = "0x0000000000000000000000000000000000000071";
+/// 只有 ERC721，需要扫日志
+const PLAIN: &str = "0x0000000000000000000000000000000000000072";
+const MULTI: &str = "0x0000000000000000000000000000000000000073";
+/// 只有 ERC721，OWNER 持有 700 个
+const MANY: &str = "0x0000000000000000000000000000000000000075";
+/// 普通 ERC20，supportsInterface 会 revert
+const FUNGIBLE: &str = "0x0000000000000000000000000000000000000074";
+
+fn uint_topic(n: u64) -> H256 {
+    H256::from_low_u64_be(n)
+}
+
+struct FakeLog {
+    address: Address,
+    topics: Vec<H256>,
+    data: Vec<u8>,
+}
+
+/// 本地链上部署的三个 NFT 合约
+struct Chain {
+    head: u64,
+    /// 单次 eth_getLogs 允许的最大块数
+    max_log_range: u64,
+    logs: Vec<FakeLog>,
+}
+
+impl Chain {
+    fn new(head: u64, max_log_range: u64) -> Self {
+        let transfer = topic("Transfer(address,address,uint256)");
+        let single = topic("TransferSingle(address,address,address,uint256,uint256)");
+        let batch = topic("TransferBatch(address,address,address,uint256[],uint256[])");
+        let zero = H256::zero();
+
+        let logs = vec![
+            // PLAIN：铸造 1、2 给 OWNER，之后 2 转给 OTHER
+            FakeLog { address: addr(PLAIN), topics: vec![transfer, zero, address_topic(OWNER), uint_topic(1)], data: vec![] },
+            FakeLog { address: addr(PLAIN), topics: vec![transfer, zero, address_topic(OWNER), uint_topic(2)], data: vec![] },
+            FakeLog { address: addr(PLAIN), topics: vec![transfer, address_topic(OWNER), address_topic(OTHER), uint_topic(2)], data: vec![] },
+            // MULTI：单笔转入 id 5 × 3，批量转入 6、8
+            FakeLog {
+                address: addr(MULTI),
+                topics: vec![single, address_topic(OTHER), zero, address_topic(OWNER)],
+                data: abi::encode(&[Token::Uint(5.into()), Token::Uint(3.into())]),
+            },
+            FakeLog {
+                address: addr(MULTI),
+                topics: vec![batch, address_topic(OTHER), zero, address_topic(OWNER)],
+                data: abi::encode(&[
+                    Token::Array(vec![Token::Uint(6.into()), Token::Uint(8.into())]),
+                    Token::Array(vec![Token::Uint(1.into()), Token::Uint(1.into())]),
+                ]),
+            },
+        ];
+        let minted = (1..=700).map(|id| FakeLog {
+            address: addr(MANY),
+            topics: vec![transfer, zero, address_topic(OWNER), uint_topic(id)],
+            data: vec![],
+        });
+        Self { head, max_log_range, logs: logs.into_iter().chain(minted).collect() }
+    }
+
+    fn contract_call(&self, to: Address, data: &[u8]) -> Option<Vec<u8>> {
+        let selector = ethers::utils::hex::encode(&data[..4]);
+        let word = |i: usize| U256::from_big_endian(&data[4 + 32 * i..36 + 32 * i]);
+        let uint = |v: u64| abi::encode(&[Token::Uint(v.into())]);
+        let boolean = |b: bool| abi::encode(&[Token::Bool(b)]);
+        let string = |s: String| abi::encode(&[Token::String(s)]);
+
+        let contract = match to {
+            to if to == addr(ENUMERABLE) => "enumerable",
+            to if to == addr(PLAIN) => "plain",
+            to if to == addr(MULTI) => "multi",
+            to if to == addr(MANY) => "many",
+            _ => "fungible",
+        };
+
+        match (contract, selector.as_str()) {
+            // supportsInterface(bytes4)
+            ("fungible", "01ffc9a7") => None,
+            (_, "01ffc9a7") => {
+                let interface = ethers::utils::hex::encode(&data[4..8]);
+                let supported: &[&str] = match contract {
+                    "enumerable" => &["80ac58cd", "780e9d63", "5b5e139f"],
+                    "plain" | "many" => &["80ac58cd"],
+                    _ => &["d9b67a26"],
+                };
+                Some(boolean(supported.contains(&interface.as_str())))
+            }
+            // balanceOf(address) / tokenOfOwnerByIndex / tokenURI
+            ("enumerable", "70a08231") => Some(uint(2)),
+            ("enumerable", "2f745c59") => Some(uint([7, 9][word(1).as_usize()])),
+            ("enumerable", "c87b56dd") => Some(string(format!("ipfs://meta/{}", word(0)))),
+            // ownerOf(uint256)
+            ("plain", "6352211e") => match word(0).as_u64() {
+                1 => Some(abi::encode(&[Token::Address(addr(OWNER))])),
+                2 => Some(abi::encode(&[Token::Address(addr(OTHER))])),
+                _ => None,
+            },
+            ("many", "6352211e") => Some(abi::encode(&[Token::Address(addr(OWNER))])),
+            // balanceOfBatch(address[],uint256[])
+            ("multi", "4e1273f4") => {
+                let tokens = abi::decode(
+                    &[
+                        abi::ParamType::Array(Box::new(abi::ParamType::Address)),
+                        abi::ParamType::Array(Box::new(abi::ParamType::Uint(256))),
+                    ],
+                    &data[4..],
+                )
+                .unwrap();
+                let ids = tokens[1].clone().into_array().unwrap();
+                let balances = HashMap::from([(5u64, 3u64), (6, 0), (8, 1)]);
+                Some(abi::encode(&[Token::Array(
+                    ids.into_iter()
+                        .map(|id| Token::Uint(balances[&id.into_uint().unwrap().as_u64()].into()))
+                        .collect(),
+                )]))
+            }
+            // uri(uint256)
+            ("multi", "0e89341c") => Some(string("https://meta/{id}.json".to_string())),
+            (contract, selector) => panic!("unexpected call {} on {}", selector, contract),
+        }
+    }
+
+    fn get_logs(&self, filter: &Value) -> RpcReply {
+        let block = |v: &Value| u64::from_str_radix(v.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+        let (from, to) = (block(&filter["fromBlock"]), block(&filter["toBlock"]));
+        if to - from + 1 > self.max_log_range {
+            return Err(json!({ "code": -32005, "message": "query exceeds max block range" }));
+        }
+
+        let address: Address = filter["address"].as_str().unwrap().parse().unwrap();
+        let matches = |log: &FakeLog| {
+            log.address == address
+                && filter["topics"].as_array().unwrap().iter().enumerate().all(|(i, t)| match t {
+                    Value::Null => true,
+                    Value::String(s) => log.topics.get(i) == Some(&s.parse().unwrap()),
+                    Value::Array(any) => any.iter().any(|s| log.topics.get(i) == Some(&s.as_str().unwrap().parse().unwrap())),
+                    _ => false,
+                })
+        };
+        // 所有日志都在第 10 块
+        let in_range = from <= 10 && 10 <= to;
+        Ok(Value::Array(
+            self.logs
+                .iter()
+                .filter(|log| in_range && matches(log))
+                .map(|log| {
+                    json!({
+                        "address": format!("{:?}", log.address),
+                        "topics": log.topics.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>(),
+                        "data": hex(&log.data),
+                        "blockNumber": "0xa",
+                        "transactionHash": format!("{:?}", H256::zero()),
+                        "transactionIndex": "0x0",
+                        "blockHash": format!("{:?}", H256::zero()),
+                        "logIndex": "0x0",
+                        "removed": false
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    fn handle(&self, method: &str, params: &Value) -> RpcReply {
+        match method {
+            "eth_chainId" => Ok(json!("0x1")),
+            "eth_blockNumber" => Ok(json!(format!("{:#x}", self.head))),
+            "eth_getLogs" => self.get_logs(&params[0]),
+            "eth_call" => {
+                let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+                let data = ethers::utils::hex::decode(&call_data(params)[2..]).unwrap();
+                if to == MULTICALL_ADDRESS && data[..4] == AGGREGATE3 {
+                    return Ok(hex(&aggregate3(&data, |to, data| self.contract_call(to, data))));
+                }
+                match self.contract_call(to, &data) {
+                    Some(out) => Ok(hex(&out)),
+                    None => reverted(),
+                }
+            }
+            other => panic!("unexpected method {}", other),
+        }
+    }
+}
+
+async fn module(chain: Chain) -> Result<(std::sync::Arc<FakeNode>, BalanceModule)> {
+    let (node, url) = FakeNode::start(move |m, p| chain.handle(m, p)).await?;
+    Ok((node, BalanceModule::new(Provider::<Http>::try_from(url.as_str())?)))
+}
+
+#[tokio::test]
+async fn test_enumerable_erc721() -> Result<()> {
+    let (node, balance) = module(Chain::new(100, u64::MAX)).await?;
+
+    let result = balance.get_nft_holdings(addr(OWNER), addr(ENUMERABLE), None, DEFAULT_NFT_LOG_LIMIT).await?;
+    assert_eq!(result.interfaces.standard, NftStandard::Erc721);
+    assert_eq!(result.source, HoldingsSource::Enumerable);
+    let ids: Vec<_> = result.holdings.iter().map(|h| h.token_id.as_u64()).collect();
+    assert_eq!(ids, vec![7, 9]);
+    assert_eq!(result.holdings[1].token_uri.as_deref(), Some("ipfs://meta/9"));
+    assert_eq!(node.count("eth_getLogs"), 0);
+    // 3 次 ERC165 探测、balanceOf，tokenOfOwnerByIndex 和 tokenURI 各一个 Multicall 批次
+    assert_eq!(node.count("eth_call"), 6);
+
+    // 接口探测结果永久缓存
+    let calls = node.count("eth_call");
+    balance.nft_interfaces(addr(ENUMERABLE)).await?;
+    assert_eq!(node.count("eth_call"), calls);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_erc721_without_enumeration_scans_transfers() -> Result<()> {
+    // 节点一次最多查 200k 块，扫描范围要自动缩小
+    let (node, balance) = module(Chain::new(300_000, 200_000)).await?;
+
+    let result = balance.get_nft_holdings(addr(OWNER), addr(PLAIN), None, DEFAULT_NFT_LOG_LIMIT).await?;
+    assert_eq!(result.source, HoldingsSource::TransferLogs);
+    // tokenId 2 已经转走
+    let ids: Vec<_> = result.holdings.iter().map(|h| h.token_id.as_u64()).collect();
+    assert_eq!(ids, vec![1]);
+    assert_eq!(result.holdings[0].token_uri, None);
+    // 500k、250k 失败，之后 3 段 125k
+    assert_eq!(node.count("eth_getLogs"), 5);
+
+    assert_eq!(balance.owner_of(addr(PLAIN), 2.into()).await?, Some(addr(OTHER)));
+    assert_eq!(balance.owner_of(addr(PLAIN), 3.into()).await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_transfer_scan_is_bounded_and_pages() -> Result<()> {
+    // 日志都在第 10 块；不给 from_block 时只往回看 2,000,000 块
+    let (node, balance) = module(Chain::new(3_000_000, u64::MAX)).await?;
+    let result = balance.get_nft_holdings(addr(OWNER), addr(PLAIN), None, DEFAULT_NFT_LOG_LIMIT).await?;
+    assert_eq!(result.from_block, Some(1_000_000));
+    assert!(result.holdings.is_empty());
+    // 1,000,000..=3,000,000 按默认 500k 一段
+    assert_eq!(node.count("eth_getLogs"), 5);
+
+    // 超过 limit：同一块的两条转入不拆开，下次从第 11 块接着扫
+    let result = balance.get_nft_holdings(addr(OWNER), addr(PLAIN), Some(0), 1).await?;
+    let ids: Vec<_> = result.holdings.iter().map(|h| h.token_id.as_u64()).collect();
+    assert_eq!(ids, vec![1]);
+    assert!(result.truncated);
+    assert_eq!(result.next_from_block, Some(11));
+    let rest = balance.get_nft_holdings(addr(OWNER), addr(PLAIN), Some(11), 1).await?;
+    assert!(rest.holdings.is_empty());
+    assert_eq!((rest.truncated, rest.next_from_block), (false, None));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_erc721_ownership_checks_are_batched_and_stop_early() -> Result<()> {
+    let (node, balance) = module(Chain::new(100, u64::MAX)).await?;
+
+    let result = balance.get_nft_holdings(addr(OWNER), addr(MANY), None, DEFAULT_NFT_LOG_LIMIT).await?;
+    assert_eq!(result.holdings.len(), 500);
+    assert!(result.truncated);
+    // 3 次 ERC165 探测，加 2 个 ownerOf 批次：第二批凑够 501 个就停，第三批（第 601~700 个）不再查
+    assert_eq!(node.count("eth_call"), 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_erc1155_holdings_over_mcp() -> Result<()> {
+    let chain = Chain::new(100, u64::MAX);
+    let (_node, url) = FakeNode::start(move |m, p| chain.handle(m, p)).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(&client, "get_nft_holdings", json!({ "address": OWNER, "collection": MULTI })).await?;
+    assert_eq!(result["standard"], "erc1155");
+    assert_eq!(result["source"], "transfer_logs");
+    assert_eq!(
+        result["holdings"],
+        json!([
+            { "token_id": "5", "amount": "3", "token_uri": format!("https://meta/{:064x}.json", 5) },
+            { "token_id": "8", "amount": "1", "token_uri": format!("https://meta/{:064x}.json", 8) },
+        ])
+    );
+
+    assert_eq!(result["from_block"], 0);
+    assert_eq!(result["next_from_block"], Value::Null);
+    let err = call(&client, "get_nft_holdings", json!({ "address": OWNER, "collection": MULTI, "limit": 0 }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("limit must be between 1 and 10000"), "{}", err);
+
+    let err = call(&client, "get_nft_holdings", json!({ "address": OWNER, "collection": FUNGIBLE }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("neither ERC721 nor ERC1155"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
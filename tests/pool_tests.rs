@@ -0,0 +1,453 @@
+// tests/pool_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::mock::MockRpc;
+use common::{
+    addr, block_header, call, call_data, connect, encode, selector, serve_json, service_for, word, FakeNode, RpcReply,
+};
+use eth_mcp_server::config::{AppConfig, PriceApiConfig};
+use eth_mcp_server::pool::{PoolModule, DEPTH_LEVELS_BPS};
+use rust_decimal::Decimal;
+use ethers::abi::{self, Token};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::types::{Address, U256};
+use ethers::utils::hex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+const FACTORY: &str = "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f";
+/// WETH/USDC pair：token0 = USDC（地址更小）
+const PAIR: &str = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
+const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+/// 6 位小数
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+/// 没有 pair
+const UNI: &str = "0x1f9840a85d5af5bf1d1768f5bd5779f6d3ea6c6c";
+/// ETH/USD feed，8 位小数，2000 USD
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+const V3_FACTORY: &str = "0x1f98431c8ad98523631ae4a59f267346ea31f984";
+/// 0.3% 池子：tick 0，活跃流动性 1e18，±120 两个已初始化 tick 外只剩一半
+const V3_POOL: &str = "0x00000000000000000000000000000000000000c3";
+/// 0.05% 池子，流动性小，不指定费率时不选
+const V3_POOL_500: &str = "0x00000000000000000000000000000000000000c5";
+/// 只有对 WETH 的 V3 池子，18 位小数，地址比 WETH 小（token0）
+const BETH: &str = "0x00000000000000000000000000000000000000be";
+const BETH_WETH_POOL: &str = "0x00000000000000000000000000000000000000c7";
+const WETH_USDC_POOL: &str = "0x00000000000000000000000000000000000000c8";
+/// V3 测试里的两个 18 位小数代币
+const TOKEN0: &str = "0x00000000000000000000000000000000000000a0";
+const TOKEN1: &str = "0x00000000000000000000000000000000000000a1";
+
+/// 10 WETH / 20000 USDC 的池子；1 ETH 按恒定乘积（扣 0.3%）换 1813.221787 USDC
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getBlockByNumber" => Ok(block_header(params, 0x64)),
+        "eth_estimateGas" => Ok(json!("0x1d4c0")),
+        "eth_call" => {
+            let to = params[0]["to"].as_str().unwrap().to_lowercase();
+            let data = call_data(params);
+            let args = hex::decode(&data[10..]).unwrap();
+            let call = |signature: &str| data.starts_with(&selector(signature));
+            match to.as_str() {
+                ROUTER if call("factory()") => Ok(encode(&[Token::Address(addr(FACTORY))])),
+                ROUTER if call("getAmountsOut(uint256,address[])") => {
+                    let amounts = vec![Token::Uint(U256::exp10(18)), Token::Uint(1_813_221_787u64.into())];
+                    Ok(encode(&[Token::Array(amounts)]))
+                }
+                ROUTER => Ok(encode(&[Token::Array(vec![])])),
+                FACTORY => {
+                    let tokens = abi::decode(&[abi::ParamType::Address, abi::ParamType::Address], &args).unwrap();
+                    let pair: Vec<Address> = tokens.into_iter().filter_map(Token::into_address).collect();
+                    let found = pair.contains(&addr(WETH)) && pair.contains(&addr(USDC));
+                    Ok(encode(&[Token::Address(if found { addr(PAIR) } else { Address::zero() })]))
+                }
+                PAIR if call("token0()") => Ok(encode(&[Token::Address(addr(USDC))])),
+                PAIR if call("token1()") => Ok(encode(&[Token::Address(addr(WETH))])),
+                PAIR if call("getReserves()") => Ok(encode(&[
+                    Token::Uint(U256::from(20_000_000_000u64)),
+                    Token::Uint(U256::exp10(19)),
+                    Token::Uint(1_700_000_000u64.into()),
+                ])),
+                PAIR if call("totalSupply()") => Ok(encode(&[Token::Uint(U256::from_dec_str("447213595499957939282").unwrap())])),
+                WETH if call("decimals()") => Ok(word(18)),
+                USDC if call("decimals()") => Ok(word(6)),
+                FEED if call("decimals()") => Ok(word(8)),
+                FEED => {
+                    let words = [1, 2000 * 100_000_000u64, 0, 0, 1].map(|n| format!("{:064x}", n));
+                    Ok(json!(format!("0x{}", words.concat())))
+                }
+                other => panic!("unexpected call to {} {}", other, data),
+            }
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+fn config() -> AppConfig {
+    AppConfig {
+        token_addresses: HashMap::from([
+            ("ETH".to_string(), addr(FEED)),
+            ("WETH".to_string(), addr(WETH)),
+            ("USDC".to_string(), addr(USDC)),
+        ]),
+        uniswap_v2_router: addr(ROUTER),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_get_pool_reports_reserves_prices_and_tvl() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let pool = call(&client, "get_pool", json!({ "token_a": "ETH", "token_b": USDC })).await?;
+    assert_eq!(pool["pair"], PAIR);
+    assert_eq!(pool["token0"]["symbol"], "USDC");
+    assert_eq!(pool["token0"]["reserve"], "20000");
+    assert_eq!(pool["token0"]["price_in_other"], "0.0005");
+    // 估值按多来源中位价：USDC 只有 V2 一个来源（当作 1 USD），WETH 是 Chainlink 和 V2 现价的中位数
+    assert_eq!(pool["token0"]["usd_price"], "1");
+    assert_eq!(pool["token1"]["symbol"], "WETH");
+    assert_eq!(pool["token1"]["reserve"], "10");
+    assert_eq!(pool["token1"]["price_in_other"], "2000");
+    assert_eq!(pool["token1"]["usd_price"], "2000");
+    assert_eq!(pool["total_supply"], "447.213595499957939282");
+    // 20000 × 1 + 10 × 2000
+    assert_eq!(pool["tvl_usd"], "40000.00");
+    assert_eq!(pool["block"], 100);
+
+    // 反过来查是同一个 pair
+    let reversed = call(&client, "get_pool", json!({ "token_a": "USDC", "token_b": "WETH" })).await?;
+    assert_eq!(reversed["pair"], PAIR);
+    assert_eq!(reversed["token0"]["address"], USDC);
+
+    let err = call(&client, "get_pool", json!({ "token_a": "USDC", "token_b": UNI })).await.unwrap_err();
+    assert!(err.to_string().contains(&format!("No Uniswap V2 pair for USDC/{}", UNI)), "{}", err);
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_swap_quote_explains_thin_pool() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let (requests, api) = serve_json(json!({ WETH: { "usd": 2000 }, USDC: { "usd": 1 } })).await?;
+    let config = AppConfig {
+        price_api: Some(PriceApiConfig {
+            url: format!("{}/token_price?contract_addresses={{address}}", api),
+            field: "{address}.usd".to_string(),
+        }),
+        ..config()
+    };
+    let client = connect(service_for(&url, config)).await?;
+    let args = json!({ "from_token": "ETH", "to_token": "USDC", "amount_in": "1", "slippage": 0.5 });
+
+    // 按两边的中位价：能换 2000 USDC，实际少了 9.34%
+    let swap = call(&client, "swap_tokens", args.clone()).await?;
+    assert_eq!(swap["estimated_output"], "1813.221787");
+    assert_eq!(swap["price_impact_bps"], 934);
+    assert_eq!(
+        swap["warnings"],
+        json!(["Pool is thin (10 ETH / 20000 USDC in reserve): this trade gets 9.34% less than the median price across sources"])
+    );
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // 同一块内再报价，两边的中位价都走缓存，不再问外部接口
+    let again = call(&client, "swap_tokens", args).await?;
+    assert_eq!(again["warnings"], swap["warnings"]);
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_swap_quote_skips_pool_analysis() -> Result<()> {
+    let pair_reads = Arc::new(AtomicU64::new(0));
+    let (_node, url) = FakeNode::start({
+        let pair_reads = pair_reads.clone();
+        move |method: &str, params: &Value| {
+            if params[0]["to"].as_str().is_some_and(|to| to.eq_ignore_ascii_case(PAIR)) {
+                pair_reads.fetch_add(1, Ordering::SeqCst);
+            }
+            match method {
+                "eth_estimateGas" => Err(json!({ "code": 3, "message": "execution reverted: EXPIRED" })),
+                _ => handle(method, params),
+            }
+        }
+    })
+    .await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let swap = call(&client, "swap_tokens", json!({ "from_token": "ETH", "to_token": "USDC", "amount_in": "1", "slippage": 0.5 })).await?;
+    assert_eq!(swap["estimated_output"], "0");
+    assert_eq!(swap["price_impact_bps"], Value::Null);
+    let warnings = swap["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].as_str().unwrap().starts_with("Quote failed: "), "{}", warnings[0]);
+    assert!(warnings[0].as_str().unwrap().contains("EXPIRED"), "{}", warnings[0]);
+    // 没有再去读池子
+    assert_eq!(pair_reads.load(Ordering::SeqCst), 0);
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_configured_factory_skips_router_lookup() -> Result<()> {
+    let mock = MockRpc::new();
+    mock.on_call(
+        addr(FACTORY),
+        "getPair(address,address)",
+        &[Token::Address(addr(UNI)), Token::Address(addr(USDC))],
+        &[Token::Address(Address::zero())],
+    );
+    let config = AppConfig { uniswap_v2_factory: Some(addr(FACTORY)), ..config() };
+    let pools = PoolModule::shared(mock.provider().await, config);
+
+    // 参数按地址排序后再问 factory；没有 pair 时为 None
+    assert_eq!(pools.pair_at(addr(USDC), addr(UNI), 100.into()).await?, None);
+    let routed = mock.requests().into_iter().filter(|(_, p)| p[0]["to"] == ROUTER).count();
+    assert_eq!(routed, 0);
+    assert!(pools.pair_at(addr(UNI), addr(UNI), 100.into()).await.is_err());
+    Ok(())
+}
+
+/// 有符号整数参数（二进制补码）
+fn int(n: i64) -> Token {
+    Token::Int(if n < 0 { U256::MAX - U256::from(n.unsigned_abs() - 1) } else { U256::from(n) })
+}
+
+fn v3_mock() -> MockRpc {
+    let (pool, unit) = (addr(V3_POOL), U256::exp10(18));
+    let mock = MockRpc::new();
+    let (a, b) = (Token::Address(addr(TOKEN0)), Token::Address(addr(TOKEN1)));
+    for (fee, found) in [(100, Address::zero()), (500, addr(V3_POOL_500)), (3000, pool), (10_000, Address::zero())] {
+        let args = [a.clone(), b.clone(), Token::Uint(fee.into())];
+        mock.on_call(addr(V3_FACTORY), "getPool(address,address,uint24)", &args, &[Token::Address(found)]);
+    }
+    mock.on_selector(addr(V3_POOL_500), "liquidity()", &[Token::Uint(U256::exp10(15))])
+        .on_selector(pool, "liquidity()", &[Token::Uint(unit)])
+        .on_selector(pool, "token0()", &[a])
+        .on_selector(pool, "token1()", &[b])
+        .on_selector(pool, "fee()", &[Token::Uint(3000.into())])
+        .on_selector(pool, "tickSpacing()", &[int(60)])
+        .on_selector(
+            pool,
+            "slot0()",
+            &[Token::Uint(U256::one() << 96), int(0), int(0), int(1), int(1), int(0), Token::Bool(true)],
+        )
+        .on_selector(addr(TOKEN0), "decimals()", &[Token::Uint(18.into())])
+        .on_selector(addr(TOKEN1), "decimals()", &[Token::Uint(18.into())])
+        // 压缩后的 tick ±2：字 0 的第 2 位、字 -1 的第 254 位
+        .on_call(pool, "tickBitmap(int16)", &[int(0)], &[Token::Uint(U256::one() << 2)])
+        .on_call(pool, "tickBitmap(int16)", &[int(-1)], &[Token::Uint(U256::one() << 254)]);
+    let half = unit / 2;
+    for (tick, net) in [(120, -1), (-120, 1)] {
+        let net = if net < 0 { U256::MAX - half + 1 } else { half };
+        let (zero, init) = (Token::Uint(U256::zero()), Token::Bool(true));
+        let outputs = [Token::Uint(half), Token::Int(net), zero.clone(), zero.clone(), zero.clone(), zero.clone(), zero, init];
+        mock.on_call(pool, "ticks(int24)", &[int(tick)], &outputs);
+    }
+    mock
+}
+
+fn close(actual: Option<f64>, expected: f64) -> bool {
+    actual.is_some_and(|a| ((a - expected) / expected).abs() < 1e-9)
+}
+
+#[tokio::test]
+async fn test_v3_depth_walks_initialized_ticks() -> Result<()> {
+    let mock = v3_mock();
+    mock.multicall();
+    let config = AppConfig { uniswap_v3_factory: Some(addr(V3_FACTORY)), ..config() };
+    let pools = PoolModule::shared(mock.provider().await, config);
+
+    // 不指定费率：3000 的池子流动性最大
+    let pool = pools.v3_pool_at(addr(TOKEN0), addr(TOKEN1), None, 100.into()).await?.unwrap();
+    // 两个 tickBitmap 字、两个 tick 各一次 Multicall3
+    let batched = mock
+        .requests()
+        .iter()
+        .filter(|(m, p)| m == "eth_call" && p[0]["to"] == format!("{:?}", MULTICALL_ADDRESS))
+        .count();
+    assert_eq!(batched, 2);
+    let by_selector = |signature: &str| {
+        mock.requests().iter().filter(|(_, p)| common::call_data(p).starts_with(&selector(signature))).count()
+    };
+    assert_eq!((by_selector("tickBitmap(int16)"), by_selector("ticks(int24)")), (0, 0));
+    assert_eq!((pool.pool, pool.fee, pool.tick_spacing, pool.tick), (addr(V3_POOL), 3000, 60, 0));
+    let half = 500_000_000_000_000_000;
+    assert_eq!(pool.ticks.iter().map(|(t, n)| (*t, *n)).collect::<Vec<_>>(), vec![(-120, half), (120, -half)]);
+    assert_eq!(pool.price(), 1.0);
+
+    let pool = pools.v3_pool_at(addr(TOKEN0), addr(TOKEN1), Some(3000), 100.into()).await?.unwrap();
+    let levels: Vec<_> = DEPTH_LEVELS_BPS.iter().map(|bps| pool.depth(*bps)).collect();
+    let gross = |x: f64| x / 0.997;
+    let edge = 1.0001f64.powi(60);
+
+    // +1%：还在 tick 120 以内，全程 L = 1；token1 换 token0
+    let up = &levels[3];
+    assert_eq!((up.price_change_bps, up.token_in, up.token_out), (100, addr(TOKEN1), addr(TOKEN0)));
+    assert!(close(up.amount_in, gross(1.01f64.sqrt() - 1.0)), "{:?}", up);
+    assert!(close(up.amount_out, 1.0 - 1.0 / 1.01f64.sqrt()), "{:?}", up);
+    assert!((up.price - 1.01).abs() < 1e-12);
+
+    // +2%：跨过 tick 120 后 L 减半
+    assert!(close(levels[4].amount_in, gross((edge - 1.0) + 0.5 * (1.02f64.sqrt() - edge))), "{:?}", levels[4]);
+
+    // -1% / -5%：token0 换 token1，-5% 跨过 tick -120
+    let down = &levels[2];
+    assert_eq!((down.price_change_bps, down.token_in, down.token_out), (-100, addr(TOKEN0), addr(TOKEN1)));
+    assert!(close(down.amount_in, gross(1.0 / 0.99f64.sqrt() - 1.0)), "{:?}", down);
+    assert!(close(down.amount_out, 1.0 - 0.99f64.sqrt()), "{:?}", down);
+    let low = 1.0 / edge;
+    let expected = gross((1.0 / low - 1.0) + 0.5 * (1.0 / 0.95f64.sqrt() - 1.0 / low));
+    assert!(close(levels[0].amount_in, expected), "{:?}", levels[0]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_v3_pool_needs_factory() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let err = call(&client, "get_v3_pool", json!({ "token_a": "ETH", "token_b": "USDC" })).await.unwrap_err();
+    assert!(err.to_string().contains("UNISWAP_V3_FACTORY is not set"), "{}", err);
+    let err = call(&client, "get_v3_pool", json!({ "token_a": "ETH", "token_b": "USDC", "fee": 2500 })).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid fee: 2500"), "{}", err);
+    client.cancel().await?;
+    Ok(())
+}
+
+/// BETH 没有 USDC 池子，经 WETH 换算；`observe` 返回 30 分钟前和现在的 tick 累计值
+fn twap_mock() -> MockRpc {
+    let mock = MockRpc::new();
+    let pools = [
+        (BETH, USDC, None),
+        (BETH, WETH, Some((500, BETH_WETH_POOL))),
+        (WETH, USDC, Some((3000, WETH_USDC_POOL))),
+    ];
+    for (base, quote, found) in pools {
+        for fee in [100u32, 500, 3000, 10_000] {
+            let pool = found.filter(|(f, _)| *f == fee).map(|(_, p)| addr(p)).unwrap_or_default();
+            let args = [Token::Address(addr(base)), Token::Address(addr(quote)), Token::Uint(fee.into())];
+            mock.on_call(addr(V3_FACTORY), "getPool(address,address,uint24)", &args, &[Token::Address(pool)]);
+        }
+    }
+    // BETH/WETH 平均 tick -500.0006 → -501；WETH/USDC 平均 tick 200311
+    for (pool, cumulative) in [(BETH_WETH_POOL, -500 * 1800 - 1), (WETH_USDC_POOL, 200_311 * 1800)] {
+        let cumulatives = Token::Array(vec![int(1_000_000), int(1_000_000 + cumulative)]);
+        let liquidity = Token::Array(vec![Token::Uint(U256::zero()); 2]);
+        mock.on_selector(addr(pool), "observe(uint32[])", &[cumulatives, liquidity])
+            .on_selector(addr(pool), "liquidity()", &[Token::Uint(U256::exp10(18))]);
+    }
+    for (token, decimals) in [(BETH, 18u64), (WETH, 18), (USDC, 6)] {
+        mock.on_selector(addr(token), "decimals()", &[Token::Uint(decimals.into())]);
+    }
+    mock
+}
+
+fn approx(actual: Decimal, expected: f64) -> bool {
+    let actual: f64 = actual.to_string().parse().unwrap();
+    ((actual - expected) / expected).abs() < 1e-7
+}
+
+#[tokio::test]
+async fn test_usd_twap_routes_through_weth() -> Result<()> {
+    let mock = twap_mock();
+    let config = AppConfig { uniswap_v3_factory: Some(addr(V3_FACTORY)), ..config() };
+    let pools = PoolModule::shared(mock.provider().await, config);
+
+    // WETH 地址比 USDC 大，是 token1：价格 = 10^12 / 1.0001^tick
+    let weth_usd = 1e12 / 1.0001f64.powi(200_311);
+    let legs = pools.usd_twap_at(addr(WETH), 1800, 100.into()).await?;
+    assert_eq!(legs.len(), 1);
+    assert_eq!((legs[0].pool, legs[0].mean_tick, legs[0].window_secs), (addr(WETH_USDC_POOL), 200_311, 1800));
+    assert!(approx(legs[0].price, weth_usd), "{:?}", legs[0]);
+    assert!((2000.0..2001.0).contains(&weth_usd));
+
+    // BETH：先对 USDC 找不到池子，再经 WETH；负数的平均 tick 向负无穷取整
+    let legs = pools.usd_twap_at(addr(BETH), 1800, 100.into()).await?;
+    let route: Vec<_> = legs.iter().map(|leg| (leg.pool, leg.base, leg.quote, leg.mean_tick)).collect();
+    assert_eq!(
+        route,
+        vec![(addr(BETH_WETH_POOL), addr(BETH), addr(WETH), -501), (addr(WETH_USDC_POOL), addr(WETH), addr(USDC), 200_311)]
+    );
+    assert!(approx(legs[0].price, 1.0001f64.powi(-501)), "{:?}", legs[0]);
+
+    // USDC 本身就是 1 USD
+    assert!(pools.usd_twap_at(addr(USDC), 1800, 100.into()).await?.is_empty());
+    assert!(pools.twap_at(addr(WETH), addr(USDC), 0, 100.into()).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_price_source_arguments() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, config())).await?;
+
+    let chainlink = call(&client, "get_price", json!({})).await?;
+    assert_eq!(chainlink["price"], "2000.00000000");
+    assert_eq!(chainlink["source"], "chainlink");
+    assert_eq!(chainlink["window_secs"], Value::Null);
+
+    let err = call(&client, "get_price", json!({ "window_secs": 600 })).await.unwrap_err();
+    assert!(err.to_string().contains("window_secs only applies to source \"twap\" or \"aggregate\""), "{}", err);
+    let err = call(&client, "get_price", json!({ "source": "twap", "quote": "ETH" })).await.unwrap_err();
+    assert!(err.to_string().contains("quote only applies to source \"chainlink\""), "{}", err);
+    let err = call(&client, "get_price", json!({ "source": "dex" })).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid source: dex"), "{}", err);
+    let err = call(&client, "get_price", json!({ "source": "twap", "token": "BETH" })).await.unwrap_err();
+    assert!(err.to_string().contains("UNISWAP_V3_FACTORY is not set"), "{}", err);
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_aggregate_price_flags_deviating_source() -> Result<()> {
+    let (_node, url) = FakeNode::start(handle).await?;
+    // 外部接口说 ETH 值 2150，比 Chainlink 和 V2 池子高 7.5%
+    let (_requests, api) = serve_json(json!({ WETH: { "usd": 2150 }, USDC: { "usd": 1 } })).await?;
+    let config = AppConfig {
+        price_api: Some(PriceApiConfig {
+            url: format!("{}/token_price?contract_addresses={{address}}", api),
+            field: "{address}.usd".to_string(),
+        }),
+        ..config()
+    };
+    let client = connect(service_for(&url, config)).await?;
+
+    let price = call(&client, "get_price", json!({ "source": "aggregate" })).await?;
+    assert_eq!(price["source"], "aggregate");
+    assert_eq!(price["price"], "2000");
+    // 没配 V3 factory，不算 TWAP
+    assert_eq!(price["window_secs"], Value::Null);
+    let sources: Vec<_> = price["sources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| (s["source"].as_str().unwrap(), s["price"].as_str().unwrap(), s["deviation_bps"].as_u64().unwrap(), s["outlier"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(sources, vec![("chainlink", "2000", 0, false), ("v2_spot", "2000", 0, false), ("api", "2150", 750, true)]);
+    assert_eq!(price["warnings"], json!(["Price sources disagree on ETH: api says 2150 USD, 7.50% off the median 2000 USD"]));
+
+    // 阈值放宽到 10% 就不算异常
+    let relaxed = call(&client, "get_price", json!({ "source": "aggregate", "max_deviation_bps": 1000 })).await?;
+    assert_eq!(relaxed["warnings"], json!([]));
+    let err = call(&client, "get_price", json!({ "max_deviation_bps": 1000 })).await.unwrap_err();
+    assert!(err.to_string().contains("max_deviation_bps only applies"), "{}", err);
+
+    // swap 报价按中位价算冲击，提示里也带上来源分歧；中位价和 V2 现价一致
+    let args = json!({ "from_token": "ETH", "to_token": "USDC", "amount_in": "1", "slippage": 0.5 });
+    let swap = call(&client, "swap_tokens", args).await?;
+    assert_eq!(swap["price_impact_bps"], 934);
+    let warnings = swap["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+    assert_eq!(warnings[0], "Price sources disagree on ETH: api says 2150 USD, 7.50% off the median 2000 USD");
+    client.cancel().await?;
+    Ok(())
+}
@@ -1,50 +1,293 @@
 // tests/price_tests.rs
-use ethers::prelude::*;
+mod common;
+
+use anyhow::Result;
+use common::mock::MockRpc;
+use common::{addr, serve_json};
+use eth_mcp_server::config::{AppConfig, PriceApiConfig};
+use eth_mcp_server::price::{self, AggregatedPrice, PriceModule, RateHop, SourcePrice};
+use eth_mcp_server::registry::TokenEntry;
+use ethers::abi::Token;
+use ethers::types::U256;
 use rust_decimal::Decimal;
-use std::env;
-use std::sync::Arc;
-use eth_mcp_server::config::AppConfig;
-use eth_mcp_server::price::PriceModule;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// ETH/USD feed，8 位小数
+const ETH_FEED: &str = "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419";
+/// BTC/USD feed，8 位小数
+const BTC_FEED: &str = "0xf4030086522a5beea4988f8ca5b36dbc97bee88c";
+const UNI: &str = "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984";
+/// UNI/ETH feed，18 位小数
+const UNI_ETH_FEED: &str = "0xd6aa3d25116d8da79ea0246c4826eb951872e02e";
+/// 配置里的普通代币，没有 description()
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const DAI: &str = "0x6b175474e89094c44da98b954eedeac495271d0f";
+
+/// latestRoundData() 的返回值：(roundId, answer, startedAt, updatedAt, answeredInRound)
+fn round(answer: u64) -> Vec<Token> {
+    vec![
+        Token::Uint(1.into()),
+        Token::Int(U256::from(answer)),
+        Token::Uint(1_700_000_000u64.into()),
+        Token::Uint(1_700_000_000u64.into()),
+        Token::Uint(1.into()),
+    ]
+}
+
+fn mock_feeds() -> MockRpc {
+    let mock = MockRpc::new();
+    for (feed, answer) in [(ETH_FEED, 200_012_345_678), (BTC_FEED, 6_500_000_000_000)] {
+        mock.on_selector(addr(feed), "latestRoundData()", &round(answer))
+            .on_selector(addr(feed), "decimals()", &[Token::Uint(8.into())]);
+    }
+    mock
+}
+
+async fn module(mock: &MockRpc) -> PriceModule {
+    let config = AppConfig {
+        token_addresses: HashMap::from([
+            ("ETH".to_string(), addr(ETH_FEED)),
+            ("BTC".to_string(), addr(BTC_FEED)),
+        ]),
+        ..Default::default()
+    };
+    PriceModule::shared(mock.provider().await, config)
+}
 
 #[tokio::test]
-async fn test_price_module() {
-    // 初始化环境变量和日志
-    dotenv::dotenv().ok();
-    let _ = tracing_subscriber::fmt::try_init();
+async fn test_price_module() -> Result<()> {
+    let mock = mock_feeds();
+    let price = module(&mock).await;
 
-    // 初始化配置
-    let config = AppConfig::load();
+    assert_eq!(price.get_price(None).await?, Decimal::new(200_012_345_678, 8));
+    assert_eq!(price.get_price(Some("BTC")).await?, Decimal::new(65_000, 0));
+    // 直接传 feed 地址
+    assert_eq!(price.get_price(Some(BTC_FEED)).await?, Decimal::new(65_000, 0));
 
-    // RPC 连接
-    let rpc_url = env::var("INFURA_URL").expect("INFURA_URL not set");
-    let provider = Provider::<Http>::connect(rpc_url.as_str()).await;
+    // 运行时注册、带 feed 的代币
+    price.registry.register(TokenEntry {
+        symbol: "UNI".into(),
+        address: addr(UNI),
+        decimals: Some(18),
+        price_feed: Some(addr(BTC_FEED)),
+    })?;
+    assert_eq!(price.get_price(Some("UNI")).await?, Decimal::new(65_000, 0));
+    Ok(())
+}
 
-    // PriceModule 实例
-    let price_module = Arc::new(PriceModule::new(provider.clone(), config));
+#[tokio::test]
+async fn test_price_at_block() -> Result<()> {
+    let mock = mock_feeds();
+    let price = module(&mock).await;
 
+    price.get_price_at(None, 42.into()).await?;
+    let (_, params) = mock.requests().into_iter().find(|(m, _)| m == "eth_call").unwrap();
+    assert_eq!(params[1], "0x2a");
+    // 指定块时不需要查最新块号
+    assert_eq!(mock.count("eth_blockNumber"), 0);
+    Ok(())
+}
 
-    // 查询 ETH/USD
-    let eth_price: Decimal = price_module.get_price(None).await.unwrap();
-    println!("ETH/USD price: {}", eth_price);
+#[tokio::test]
+async fn test_price_errors() -> Result<()> {
+    let mock = mock_feeds();
+    mock.revert(addr(BTC_FEED), "latestRoundData()");
+    let price = module(&mock).await;
 
-    // 查询 DAI/USD
-    // let dai_price: Decimal = price_module.get_price(Some("DAI")).await.unwrap();
-    // println!("DAI/USD price: {}", dai_price);
+    let err = price.get_price(Some("DOGE")).await.unwrap_err();
+    assert!(err.to_string().contains("Unknown token or feed address: DOGE"));
 
-    // 查询 UNI/ETH
-    // let uni_price: Decimal = price_module.get_price(Some("UNI")).await.unwrap();
-    // println!("UNI/ETH price: {}", uni_price);
+    price.registry.register(TokenEntry {
+        symbol: "UNI".into(),
+        address: addr(UNI),
+        decimals: Some(18),
+        price_feed: None,
+    })?;
+    let err = price.get_price(Some("UNI")).await.unwrap_err();
+    assert!(err.to_string().contains("No price feed registered for token UNI"));
+
+    assert!(price.get_price(Some("0xnot-an-address")).await.is_err());
+
+    let err = price.get_price(Some("BTC")).await.unwrap_err();
+    assert!(err.to_string().contains("reverted"));
+    Ok(())
+}
+
+#[test]
+fn test_median_and_outliers() {
+    let d = |n: i64| Decimal::new(n, 0);
+    assert_eq!(price::median(vec![]), None);
+    assert_eq!(price::median(vec![d(3), d(1), d(2)]), Some(d(2)));
+    assert_eq!(price::median(vec![d(4), d(1), d(2), d(3)]), Some(Decimal::new(25, 1)));
+
+    let aggregated = AggregatedPrice::new(
+        vec![
+            SourcePrice::new("chainlink", Ok(d(2000))),
+            SourcePrice::new("v2_spot", Ok(d(2010))),
+            SourcePrice::new("v3_twap", Err(anyhow::anyhow!("UNISWAP_V3_FACTORY is not set"))),
+            SourcePrice::new("api", Ok(d(2150))),
+        ],
+        200,
+    );
+    // 失败的来源不参与中位数
+    assert_eq!(aggregated.median, Some(d(2010)));
+    assert_eq!(aggregated.sources[2].error.as_deref(), Some("UNISWAP_V3_FACTORY is not set"));
+    assert_eq!(aggregated.deviation_bps(d(2000)), Some(50));
+    assert_eq!(aggregated.outliers(), vec![&aggregated.sources[3]]);
+    assert_eq!(
+        aggregated.warnings("ETH"),
+        vec!["Price sources disagree on ETH: api says 2150 USD, 6.97% off the median 2010 USD"]
+    );
+}
+
+#[tokio::test]
+async fn test_api_price_fills_template_and_reads_field() -> Result<()> {
+    let (requests, url) = serve_json(json!({ "data": [{ "usd": "1.0012" }], "count": 1 })).await?;
+    let mock = MockRpc::new();
+    let config = AppConfig {
+        price_api: Some(PriceApiConfig {
+            url: format!("{}/simple/{{symbol}}?contract={{address}}", url),
+            field: "data.0.usd".to_string(),
+        }),
+        ..Default::default()
+    };
+    let price = PriceModule::shared(mock.provider().await, config.clone());
+
+    assert_eq!(price.api_price(addr(UNI), Some("UNI")).await?, Decimal::new(10012, 4));
+    assert_eq!(*requests.lock().unwrap(), vec![format!("/simple/UNI?contract={}", UNI)]);
+    // 模板要 symbol 却没有
+    assert!(price.api_price(addr(UNI), None).await.is_err());
+
+    // 字段不是数字
+    let config = AppConfig {
+        price_api: Some(PriceApiConfig { url: format!("{}/price", url), field: "data".to_string() }),
+        ..config
+    };
+    let err = PriceModule::shared(mock.provider().await, config).api_price(addr(UNI), None).await.unwrap_err();
+    assert!(err.to_string().contains("No number at \"data\""), "{}", err);
+    Ok(())
+}
 
-    // 查询 USDC/USD
-    // let usdc_price: Decimal = price_module.get_price(Some("USDC")).await.unwrap();
-    // println!("USDC/USD price: {}", usdc_price);
+/// ETH/USD、BTC/USD 在配置里，UNI/ETH 注册在 UNI 上；块时间 1_700_000_600
+async fn mock_graph() -> (MockRpc, PriceModule) {
+    let mock = mock_feeds();
+    mock.on("eth_getBlockByNumber", json!({ "number": "0x64", "hash": format!("0x{:064x}", 100), "timestamp": "0x6553f358" }))
+        .on_selector(addr(ETH_FEED), "description()", &[Token::String("ETH / USD".to_string())])
+        .on_selector(addr(BTC_FEED), "description()", &[Token::String("BTC / USD".to_string())])
+        .on_selector(addr(UNI_ETH_FEED), "description()", &[Token::String("UNI / ETH".to_string())])
+        .on_selector(addr(UNI_ETH_FEED), "decimals()", &[Token::Uint(18.into())]);
+    // 0.004 ETH，比其他 feed 早 300 秒更新
+    let mut uni = round(0);
+    uni[1] = Token::Int(U256::exp10(15) * 4);
+    uni[3] = Token::Uint(1_699_999_700u64.into());
+    mock.on_selector(addr(UNI_ETH_FEED), "latestRoundData()", &uni);
 
-    // 查询 BTC/USD
-    let btc_price: Decimal = price_module.get_price(Some("BTC")).await.unwrap();
-    println!("BTC/USD price: {}", btc_price);
+    let mut price = module(&mock).await;
+    price.config.token_addresses.insert("USDC".to_string(), addr(USDC));
+    price.registry.register(TokenEntry {
+        symbol: "UNI".to_string(),
+        address: addr(UNI),
+        decimals: Some(18),
+        price_feed: Some(addr(UNI_ETH_FEED)),
+    }).unwrap();
+    (mock, price)
+}
+
+fn hop(feed: &str, from: &str, to: &str, inverted: bool, rate: Decimal, updated_at: u64) -> RateHop {
+    RateHop { feed: addr(feed), from: from.to_string(), to: to.to_string(), inverted, rate, updated_at }
+}
+
+#[tokio::test]
+async fn test_cross_rate_takes_shortest_feed_path() -> Result<()> {
+    let (_mock, price) = mock_graph().await;
+    let eth_usd = Decimal::new(200_012_345_678, 8);
+    let uni_eth = Decimal::new(4, 3);
+
+    // UNI/ETH × ETH/USD，最旧的更新来自 UNI/ETH
+    let rate = price.cross_rate_at("UNI", "USD", 100.into()).await?;
+    assert_eq!((rate.base.as_str(), rate.quote.as_str()), ("UNI", "USD"));
+    assert_eq!(rate.price, uni_eth * eth_usd);
+    assert_eq!(
+        rate.path,
+        vec![
+            hop(UNI_ETH_FEED, "UNI", "ETH", false, uni_eth, 1_699_999_700),
+            hop(ETH_FEED, "ETH", "USD", false, eth_usd, 1_700_000_000),
+        ]
+    );
+    assert_eq!((rate.updated_at, rate.staleness_secs), (1_699_999_700, 900));
+
+    // 逆着 BTC/USD 走；WETH 当作 ETH
+    let rate = price.cross_rate_at("weth", "BTC", 100.into()).await?;
+    assert_eq!(rate.price, (eth_usd / Decimal::new(65_000, 0)).round_dp(18).normalize());
+    let route: Vec<_> = rate.path.iter().map(|h| (h.from.as_str(), h.to.as_str(), h.inverted)).collect();
+    assert_eq!(route, vec![("ETH", "USD", false), ("USD", "BTC", true)]);
+    assert_eq!(rate.staleness_secs, 600);
+
+    let rate = price.cross_rate_at("UNI", "BTC", 100.into()).await?;
+    assert_eq!(rate.path.len(), 3);
+    let rate = price.cross_rate_at("ETH", "UNI", 100.into()).await?;
+    assert_eq!(rate.price, Decimal::new(250, 0));
+    assert_eq!(rate.path, vec![hop(UNI_ETH_FEED, "ETH", "UNI", true, Decimal::new(250, 0), 1_699_999_700)]);
+
+    let same = price.cross_rate_at("USD", "usd", 100.into()).await?;
+    assert_eq!((same.price, same.path.len(), same.staleness_secs), (Decimal::ONE, 0, 0));
+
+    let err = price.cross_rate_at("DOGE", "USD", 100.into()).await.unwrap_err();
+    assert_eq!(err.to_string(), "No price feed path from DOGE to USD");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_feed_rate_reports_denomination() -> Result<()> {
+    let (mock, price) = mock_graph().await;
+    let rate = price.feed_rate_at(Some("BTC"), 100.into()).await?;
+    assert_eq!((rate.base.as_str(), rate.quote.as_str(), rate.price), ("BTC", "USD", Decimal::new(65_000, 0)));
+    assert_eq!(rate.staleness_secs, 600);
+
+    // description() 不会变，第二次不再请求
+    price.feed_rate_at(Some("BTC"), 100.into()).await?;
+    let described = mock.requests().iter().filter(|(m, p)| m == "eth_call" && p[0]["data"] == "0x7284e416").count();
+    assert_eq!(described, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_feed_graph_skips_non_feeds_once() -> Result<()> {
+    let (mock, price) = mock_graph().await;
+    let described = |to: &str| {
+        mock.requests()
+            .iter()
+            .filter(|(m, p)| m == "eth_call" && p[0]["data"] == "0x7284e416" && p[0]["to"] == to)
+            .count()
+    };
+
+    // 配置里的 USDC 不是 feed 键，不去问
+    price.cross_rate_at("UNI", "USD", 100.into()).await?;
+    assert_eq!(described(USDC), 0);
+
+    // 登记成 feed 的其实是个代币合约，description() 被拒；这个结果也缓存
+    price.registry.register(TokenEntry {
+        symbol: "DAI".to_string(),
+        address: addr(DAI),
+        decimals: Some(18),
+        price_feed: Some(addr(USDC)),
+    })?;
+    price.cross_rate_at("UNI", "USD", 100.into()).await?;
+    price.cross_rate_at("BTC", "UNI", 100.into()).await?;
+    assert_eq!(described(USDC), 1);
+    assert_eq!(described(ETH_FEED), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_usd_price_converts_eth_denominated_feed() -> Result<()> {
+    let (_mock, price) = mock_graph().await;
+    let eth_usd = Decimal::new(200_012_345_678, 8);
 
-    // 简单断言
-    assert!(eth_price > Decimal::new(0, 0));
-    // assert!(dai_price > Decimal::new(0, 0));
-    assert!(btc_price > Decimal::new(0, 0));
+    // UNI 登记的是 UNI/ETH feed：0.004 ETH 换成 USD
+    assert_eq!(price.usd_price_at(Some("UNI"), 100.into()).await?, Decimal::new(4, 3) * eth_usd);
+    assert_eq!(price.usd_price_at(Some("BTC"), 100.into()).await?, Decimal::new(65_000, 0));
+    assert_eq!(price.usd_price_at(None, 100.into()).await?, eth_usd);
+    Ok(())
 }
@@ -0,0 +1,158 @@
+// tests/risk_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::addr;
+use common::evm::{balance_slot, erc20, honeypot_erc20, router, taxed_erc20};
+use common::mock::MockRpc;
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::risk::{self, OwnerFunction, TokenRisk};
+use eth_mcp_server::swap::SwapModule;
+use ethers::abi::{Abi, Token};
+use ethers::types::{Bytes, U256};
+use serde_json::json;
+use std::collections::HashMap;
+
+const TOKEN: &str = "0x000000000000000000000000000000000000000a";
+/// 没有代码，router 模拟里用不到
+const WETH: &str = "0x00000000000000000000000000000000000000ee";
+const ROUTER: &str = "0x00000000000000000000000000000000000000f1";
+const OWNER: &str = "0x00000000000000000000000000000000000000b0";
+/// router 固定汇率：1 ETH = 2 TOKEN，反过来 1 TOKEN = 2 ETH
+const RATE_PER_MILLE: u64 = 2000;
+
+/// router 预存 1000 TOKEN 和 10 ETH，用 0.01 ETH 试买
+async fn check(code: Bytes, owner: Option<&str>) -> Result<TokenRisk> {
+    let unit = U256::exp10(18);
+    let mock = MockRpc::new();
+    mock.on("eth_getBlockByNumber", json!({ "number": "0x64", "hash": format!("0x{:064x}", 100), "timestamp": "0x6553f100" }))
+        .account(addr(TOKEN), 0.into(), code)
+        .account(addr(ROUTER), unit * 10, router(RATE_PER_MILLE))
+        .storage(addr(TOKEN), balance_slot(addr(ROUTER)), unit * 1000);
+    if let Some(owner) = owner {
+        mock.on_selector(addr(TOKEN), "owner()", &[Token::Address(addr(owner))]);
+    }
+
+    let config = AppConfig {
+        token_addresses: HashMap::from([("WETH".to_string(), addr(WETH))]),
+        uniswap_v2_router: addr(ROUTER),
+        ..Default::default()
+    };
+    let swap = SwapModule::shared(mock.provider().await, config);
+    swap.check_token_risk_at(addr(TOKEN), unit / 100, None, 100.into()).await
+}
+
+fn labels(risk: &TokenRisk) -> Vec<(&str, bool)> {
+    risk.steps.iter().map(|s| (s.label.as_str(), s.success)).collect()
+}
+
+#[tokio::test]
+async fn test_standard_token_round_trips_without_tax() -> Result<()> {
+    let risk = check(erc20(), None).await?;
+
+    assert_eq!(
+        labels(&risk),
+        vec![
+            ("quote_buy", true),
+            ("buy", true),
+            ("transfer", true),
+            ("approve", true),
+            ("quote_sell", true),
+            ("sell", true),
+        ]
+    );
+    let milli = U256::exp10(15);
+    assert_eq!((risk.buy_quote, risk.bought), (milli * 20, milli * 20));
+    assert_eq!((risk.sell_quote, risk.sold_for), (milli * 40, milli * 40));
+    assert_eq!((risk.buy_tax_bps, risk.sell_tax_bps), (Some(0), Some(0)));
+    assert!(!risk.is_honeypot());
+    assert_eq!(risk.owner, None);
+    assert!(risk.owner_functions.is_empty());
+    assert!(risk.warnings().is_empty(), "{:?}", risk.warnings());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_transfer_tax_shows_up_on_both_sides() -> Result<()> {
+    let risk = check(taxed_erc20(100), None).await?;
+
+    // 买入：router 转出时被扣 10%；卖出：router 收到的少了 10%，换回的 ETH 跟着少
+    let milli = U256::exp10(15);
+    assert_eq!((risk.buy_quote, risk.bought), (milli * 20, milli * 18));
+    assert_eq!((risk.sell_quote, risk.sold_for), (milli * 36, milli * 324 / 10));
+    assert_eq!((risk.buy_tax_bps, risk.sell_tax_bps), (Some(1000), Some(1000)));
+    assert!(!risk.is_honeypot());
+    assert_eq!(risk.warnings(), vec!["Buy tax is 10.00%", "Sell tax is 10.00%"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_honeypot_buys_but_cannot_sell() -> Result<()> {
+    let risk = check(honeypot_erc20(), Some(OWNER)).await?;
+
+    assert_eq!(risk.buy_tax_bps, Some(0));
+    assert_eq!(risk.transfer_error, None);
+    assert_eq!(risk.sell_error.as_deref(), Some("TRANSFER_BLOCKED"));
+    assert_eq!((risk.sell_tax_bps, risk.sold_for), (None, U256::zero()));
+    assert!(risk.is_honeypot());
+
+    // 卖出失败的调用栈：router → 代币 transferFrom
+    let sell = risk.steps.iter().find(|s| s.label == "sell").unwrap();
+    assert!(!sell.success);
+    assert!(sell.trace.iter().any(|f| f.to == addr(TOKEN) && f.reason.as_deref() == Some("TRANSFER_BLOCKED")));
+
+    let signatures: Vec<_> = risk.owner_functions.iter().map(|f| (f.signature.as_str(), f.category, f.source)).collect();
+    assert_eq!(
+        signatures,
+        vec![("blacklist(address)", "blacklist", "bytecode"), ("mint(address,uint256)", "mint", "bytecode")]
+    );
+    assert_eq!(risk.owner, Some(addr(OWNER)));
+    assert_eq!(
+        risk.warnings(),
+        vec![
+            "Honeypot: tokens can be bought but not sold (TRANSFER_BLOCKED)",
+            "Owner-controlled functions: blacklist, mint",
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missing_contract_is_reported_without_simulating() -> Result<()> {
+    let risk = check(Bytes::new(), None).await?;
+    assert!(risk.steps.is_empty());
+    assert_eq!(risk.warnings(), vec!["No contract code at the token address"]);
+    Ok(())
+}
+
+#[test]
+fn test_owner_functions_from_verified_abi_and_bytecode() -> Result<()> {
+    let abi: Abi = serde_json::from_value(json!([
+        { "type": "function", "name": "balanceOf", "stateMutability": "view",
+          "inputs": [{ "name": "owner", "type": "address" }], "outputs": [{ "name": "", "type": "uint256" }] },
+        { "type": "function", "name": "pauseTrading", "stateMutability": "nonpayable", "inputs": [], "outputs": [] },
+        { "type": "function", "name": "setSellTax", "stateMutability": "nonpayable",
+          "inputs": [{ "name": "bps", "type": "uint256" }], "outputs": [] },
+        { "type": "function", "name": "mintingFinished", "stateMutability": "view", "inputs": [],
+          "outputs": [{ "name": "", "type": "bool" }] },
+        { "type": "function", "name": "transfer", "stateMutability": "nonpayable",
+          "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+          "outputs": [{ "name": "", "type": "bool" }] }
+    ]))?;
+
+    let found = risk::owner_functions(&honeypot_erc20(), Some(&abi));
+    let function = |signature: &str, category, source| OwnerFunction { signature: signature.to_string(), category, source };
+    assert_eq!(
+        found,
+        vec![
+            // ABI 按名字认（view 函数不算），字节码按已知 selector 补上 ABI 里没有的
+            function("pauseTrading()", "pause", "abi"),
+            function("setSellTax(uint256)", "fee", "abi"),
+            function("blacklist(address)", "blacklist", "bytecode"),
+            function("mint(address,uint256)", "mint", "bytecode"),
+        ]
+    );
+    // 标准 ERC20 的字节码里没有
+    assert!(risk::owner_functions(&erc20(), None).is_empty());
+    Ok(())
+}
@@ -0,0 +1,282 @@
+// tests/simulate_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::addr;
+use common::evm::{allowance_slot, balance_slot, erc20, router, taxed_erc20};
+use common::mock::MockRpc;
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::simulate::{AccountSnapshot, BalanceDelta, SimStep, Simulator, StateSnapshot};
+use eth_mcp_server::swap::SwapModule;
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::{Bytes, I256, U256};
+use ethers::utils::id;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
+const TOKEN_A: &str = "0x000000000000000000000000000000000000000a";
+const TOKEN_B: &str = "0x000000000000000000000000000000000000000b";
+const ROUTER: &str = "0x00000000000000000000000000000000000000f1";
+/// 转账扣 10% 的代币
+const TAXED: &str = "0x000000000000000000000000000000000000000c";
+const OTHER: &str = "0x00000000000000000000000000000000000000a2";
+const UNIT: u64 = 1_000_000_000_000_000_000;
+/// router 固定汇率：1 A = 2 B
+const RATE_PER_MILLE: u64 = 2000;
+
+fn calldata(signature: &str, args: &[Token]) -> Bytes {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data.into()
+}
+
+fn step(label: &str, to: &str, value: u64, data: Bytes) -> SimStep {
+    SimStep { label: label.to_string(), from: addr(WALLET), to: addr(to), value: value.into(), data }
+}
+
+fn approve(amount: u64) -> SimStep {
+    let data = calldata("approve(address,uint256)", &[Token::Address(addr(ROUTER)), Token::Uint(amount.into())]);
+    step("approve", TOKEN_A, 0, data)
+}
+
+fn path(tokens: &[&str]) -> Token {
+    Token::Array(tokens.iter().map(|t| Token::Address(addr(t))).collect())
+}
+
+fn swap(amount_in: u64, min_out: u64) -> SimStep {
+    let data = calldata(
+        "swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+        &[
+            Token::Uint(amount_in.into()),
+            Token::Uint(min_out.into()),
+            path(&[TOKEN_A, TOKEN_B]),
+            Token::Address(addr(WALLET)),
+            Token::Uint(u64::MAX.into()),
+        ],
+    );
+    step("swap", ROUTER, 0, data)
+}
+
+fn account(code: Bytes, storage: &[(U256, u64)]) -> AccountSnapshot {
+    AccountSnapshot {
+        code,
+        storage: storage.iter().map(|(slot, value)| (*slot, U256::from(*value))).collect(),
+        ..Default::default()
+    }
+}
+
+/// 钱包有 1000 A 和 1 ETH，router 有 10000 B
+fn snapshot() -> StateSnapshot {
+    let wallet = AccountSnapshot { balance: U256::exp10(18), ..Default::default() };
+    StateSnapshot {
+        block: 100,
+        timestamp: 1_700_000_000,
+        accounts: BTreeMap::from([
+            (addr(WALLET), wallet),
+            (addr(TOKEN_A), account(erc20(), &[(balance_slot(addr(WALLET)), 1000)])),
+            (addr(TOKEN_B), account(erc20(), &[(balance_slot(addr(ROUTER)), 10_000)])),
+            (addr(ROUTER), account(router(RATE_PER_MILLE), &[])),
+        ]),
+    }
+}
+
+/// 钱包的前后余额；代币的 Transfer 转入 / 转出由 `transfers` 给出
+fn balance(token: Option<&str>, before: U256, after: U256, transfers: Option<(u64, u64)>) -> BalanceDelta {
+    BalanceDelta {
+        holder: addr(WALLET),
+        token: token.map(addr),
+        before,
+        after,
+        transfers: transfers.map(|(received, sent)| (received.into(), sent.into())),
+    }
+}
+
+#[tokio::test]
+async fn test_approve_then_swap_reports_steps_and_deltas() -> Result<()> {
+    let eth_swap = calldata(
+        "swapExactETHForTokens(uint256,address[],address,uint256)",
+        &[Token::Uint(10.into()), path(&[TOKEN_A, TOKEN_B]), Token::Address(addr(WALLET)), Token::Uint(u64::MAX.into())],
+    );
+    let steps = vec![approve(100), swap(100, 190), step("swap_eth", ROUTER, 5, eth_swap)];
+    let simulation = Simulator::offline(snapshot()).run(steps).await?;
+
+    assert_eq!(simulation.block.as_u64(), 100);
+    let [approved, swapped, eth_swapped] = &simulation.steps[..] else { panic!("expected 3 steps") };
+    assert!(approved.success && swapped.success && eth_swapped.success);
+    assert!(approved.gas_used > 21_000 && swapped.gas_used > approved.gas_used);
+    assert!(swapped.trace.is_empty() && swapped.revert_reason.is_none());
+
+    // 事件按发出顺序，ERC20 事件能解码
+    let (log, decoded) = &approved.logs[0];
+    assert_eq!(log.address, addr(TOKEN_A));
+    assert_eq!(decoded.as_ref().unwrap().event, "Approval(address,address,uint256)");
+    let transfers: Vec<_> = swapped
+        .logs
+        .iter()
+        .map(|(log, decoded)| (log.address, decoded.as_ref().unwrap().args[2].2.clone()))
+        .collect();
+    assert_eq!(transfers, vec![(addr(TOKEN_A), json!("100")), (addr(TOKEN_B), json!("200"))]);
+
+    // 返回值是 router 的 amounts
+    let amounts = abi::decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], &swapped.output)?;
+    assert_eq!(amounts, vec![Token::Array(vec![Token::Uint(100.into()), Token::Uint(200.into())])]);
+
+    // ETH 只少了 swap 付出的 5 wei（gas 价格按 0 算）；代币余额变化和 Transfer 事件对得上
+    let eth = U256::exp10(18);
+    assert_eq!(
+        simulation.balances,
+        vec![
+            balance(None, eth, eth - 5, None),
+            balance(Some(TOKEN_A), 1000.into(), 900.into(), Some((0, 100))),
+            balance(Some(TOKEN_B), 0.into(), 210.into(), Some((210, 0))),
+        ]
+    );
+    assert!(simulation.balances[1..].iter().all(|b| b.unexplained() == Some(0.into())));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_steps_report_revert_trace() -> Result<()> {
+    // 没有 approve 就 swap；然后 approve 了但要求的最少输出太高
+    let steps = vec![swap(100, 190), approve(100), swap(100, 300)];
+    let simulation = Simulator::offline(snapshot()).run(steps).await?;
+
+    let unapproved = &simulation.steps[0];
+    assert!(!unapproved.success);
+    assert!(unapproved.gas_used > 0);
+    assert!(unapproved.logs.is_empty());
+    assert_eq!(unapproved.revert_reason.as_deref(), Some("insufficient allowance"));
+    // 调用栈：router 调 transferFrom 失败，router 把原因原样抛出
+    let frames: Vec<_> = unapproved
+        .trace
+        .iter()
+        .map(|f| (f.depth, f.from, f.to, f.selector.clone().unwrap(), f.success, f.reason.clone().unwrap()))
+        .collect();
+    assert_eq!(
+        frames,
+        vec![
+            (0, addr(WALLET), addr(ROUTER), "0x38ed1739".to_string(), false, "insufficient allowance".to_string()),
+            (1, addr(ROUTER), addr(TOKEN_A), "0x23b872dd".to_string(), false, "insufficient allowance".to_string()),
+        ]
+    );
+
+    // 失败的步骤不影响后面的步骤，但也不改状态
+    assert!(simulation.steps[1].success);
+    let too_greedy = &simulation.steps[2];
+    assert_eq!(too_greedy.revert_reason.as_deref(), Some("INSUFFICIENT_OUTPUT_AMOUNT"));
+    assert_eq!(too_greedy.trace.len(), 1);
+    // 没有代币转账成功，只剩没动过的 ETH
+    let eth = U256::exp10(18);
+    assert_eq!(simulation.balances, vec![balance(None, eth, eth, None)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_simulate_swap_reports_recipient_and_hidden_tax() -> Result<()> {
+    let unit = U256::exp10(18);
+    let mock = MockRpc::new();
+    mock.on("eth_getBlockByNumber", json!({ "number": "0x64", "hash": format!("0x{:064x}", 100), "timestamp": "0x6553f100" }))
+        .on_selector(addr(TOKEN_A), "decimals()", &[Token::Uint(18.into())])
+        .on_selector(addr(TAXED), "decimals()", &[Token::Uint(18.into())])
+        .on_selector(
+            addr(ROUTER),
+            "getAmountsOut(uint256,address[])",
+            &[Token::Array(vec![Token::Uint(unit), Token::Uint(unit * 2)])],
+        )
+        .account(addr(TOKEN_A), 0.into(), erc20())
+        .account(addr(TAXED), 0.into(), taxed_erc20(100))
+        .account(addr(ROUTER), 0.into(), router(RATE_PER_MILLE))
+        .storage(addr(TOKEN_A), balance_slot(addr(WALLET)), unit * 5)
+        .storage(addr(TAXED), balance_slot(addr(ROUTER)), unit * 10);
+
+    let config = AppConfig {
+        token_addresses: HashMap::from([
+            ("WETH".to_string(), addr(TAXED)),
+            ("A".to_string(), addr(TOKEN_A)),
+            ("TAX".to_string(), addr(TAXED)),
+        ]),
+        uniswap_v2_router: addr(ROUTER),
+        wallet_address: addr(WALLET),
+        ..Default::default()
+    };
+    let swap = SwapModule::shared(mock.provider().await, config);
+    let result = swap.simulate_swap_at("A", "TAX", Decimal::ONE, 0.5, addr(OTHER), 100.into()).await?;
+
+    // 报价 2 TAX，收款方只到账 1.8：10% 的转账税
+    assert_eq!(result.quoted_output, unit * 2);
+    assert_eq!(result.received_output, unit * 18 / 10);
+    assert_eq!(result.shortfall_bps(), Some(1000));
+
+    let simulation = &result.simulation;
+    let wallet_a = simulation.balance(addr(WALLET), Some(addr(TOKEN_A))).unwrap();
+    assert_eq!((wallet_a.before, wallet_a.after), (unit * 5, unit * 4));
+    // 收款方的 Transfer 事件报的是全额，余额变化对不上
+    let received = simulation.balance(addr(OTHER), Some(addr(TAXED))).unwrap();
+    assert_eq!(received.transfers, Some((unit * 2, 0.into())));
+    assert_eq!(received.unexplained(), Some(-I256::from_raw(unit / 5)));
+    // 发送方和收款方 × (ETH, A, TAX) 都在报告里，包括没变的
+    assert_eq!(simulation.balances.len(), 6);
+    assert_eq!(simulation.balance(addr(OTHER), None).unwrap().delta(), 0.into());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_simulate_swap_forks_state_from_provider() -> Result<()> {
+    let unit = U256::exp10(18);
+    let mock = MockRpc::new();
+    mock.on("eth_getBlockByNumber", json!({ "number": "0x64", "hash": format!("0x{:064x}", 100), "timestamp": "0x6553f100" }))
+        .on_selector(addr(TOKEN_A), "decimals()", &[Token::Uint(18.into())])
+        .on_selector(addr(TOKEN_B), "decimals()", &[Token::Uint(18.into())])
+        .on_selector(
+            addr(ROUTER),
+            "getAmountsOut(uint256,address[])",
+            &[Token::Array(vec![Token::Uint(unit), Token::Uint(unit * 2)])],
+        )
+        .account(addr(TOKEN_A), 0.into(), erc20())
+        .account(addr(TOKEN_B), 0.into(), erc20())
+        .account(addr(ROUTER), 0.into(), router(RATE_PER_MILLE))
+        .storage(addr(TOKEN_A), balance_slot(addr(WALLET)), unit * 5)
+        .storage(addr(TOKEN_B), balance_slot(addr(ROUTER)), unit * 10);
+
+    let config = AppConfig {
+        token_addresses: HashMap::from([
+            ("WETH".to_string(), addr(TOKEN_B)),
+            ("A".to_string(), addr(TOKEN_A)),
+            ("B".to_string(), addr(TOKEN_B)),
+        ]),
+        uniswap_v2_router: addr(ROUTER),
+        wallet_address: addr(WALLET),
+        ..Default::default()
+    };
+    let swap = SwapModule::shared(mock.provider().await, config);
+    let simulation = swap.simulate_swap_at("A", "B", Decimal::ONE, 0.5, addr(WALLET), 100.into()).await?.simulation;
+
+    let labels: Vec<_> = simulation.steps.iter().map(|s| (s.label.as_str(), s.success)).collect();
+    assert_eq!(labels, vec![("approve", true), ("swap", true)]);
+    assert_eq!(
+        simulation.balances,
+        vec![
+            balance(None, 0.into(), 0.into(), None),
+            balance(Some(TOKEN_A), unit * 5, unit * 4, Some((0, UNIT))),
+            balance(Some(TOKEN_B), 0.into(), unit * 2, Some((2 * UNIT, 0))),
+        ]
+    );
+
+    // 状态都读自报价所在的块，每个槽只读一次（执行前后两份状态共用）
+    let state_reads: Vec<_> = mock
+        .requests()
+        .into_iter()
+        .filter(|(m, _)| m == "eth_getCode" || m == "eth_getStorageAt")
+        .collect();
+    assert!(state_reads.iter().all(|(_, params)| params.as_array().unwrap().last().unwrap() == "0x64"));
+    let reads_of = |slot: U256| {
+        let slot_of = |p: &serde_json::Value| U256::from_str_radix(p[1].as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+        state_reads.iter().filter(|(m, p)| m == "eth_getStorageAt" && slot_of(p) == slot).count()
+    };
+    assert_eq!(reads_of(allowance_slot(addr(WALLET), addr(ROUTER))), 1);
+    // 钱包在 A、B 上的余额槽各一次
+    assert_eq!(reads_of(balance_slot(addr(WALLET))), 2);
+    Ok(())
+}
@@ -1,48 +1,117 @@
 // tests/swap_tests.rs
-use ethers::prelude::*;
-use std::sync::Arc;
-use std::env;
-use rust_decimal::Decimal;
+mod common;
+
 use anyhow::Result;
-use rust_decimal::prelude::FromPrimitive;
+use common::addr;
+use common::mock::MockRpc;
 use eth_mcp_server::config::AppConfig;
 use eth_mcp_server::swap::SwapModule;
+use ethers::abi::Token;
+use ethers::types::U256;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::collections::HashMap;
 
-#[tokio::test]
-async fn test_simulate_swap_eth_to_weth() -> Result<()> {
-    // 加载环境变量 & 初始化日志
-    dotenv::dotenv().ok();
-    let _ = tracing_subscriber::fmt::try_init();
+const ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+/// 6 位小数
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const WALLET: &str = "0x00000000000000000000000000000000000000a1";
 
-    // 初始化配置
-    let config = AppConfig::load();
+fn uint(n: u64) -> Token {
+    Token::Uint(n.into())
+}
 
-    // 获取 INFURA RPC URL
-    let rpc_url = env::var("INFURA_URL").expect("INFURA_URL not set");
+const GET_AMOUNTS_OUT: &str = "getAmountsOut(uint256,address[])";
+const SWAP_ETH_FOR_TOKENS: &str = "swapExactETHForTokens(uint256,address[],address,uint256)";
+const SWAP_TOKENS_FOR_ETH: &str = "swapExactTokensForETH(uint256,uint256,address[],address,uint256)";
 
-    // 连接 Provider
-    let provider = Arc::new(Provider::<Http>::connect(rpc_url.as_str()).await);
+/// 0.001 ETH → 1.85 USDC；1 USDC → 0.0005 ETH
+fn mock_router() -> MockRpc {
+    let mock = MockRpc::new();
+    let amounts = |a: U256, b: U256| [Token::Array(vec![Token::Uint(a), Token::Uint(b)])];
+    let eth_in = U256::exp10(15);
+    mock.on_call(
+        addr(ROUTER),
+        GET_AMOUNTS_OUT,
+        &[Token::Uint(eth_in), Token::Array(vec![Token::Address(addr(WETH)), Token::Address(addr(USDC))])],
+        &amounts(eth_in, 1_850_000.into()),
+    )
+    .on_call(
+        addr(ROUTER),
+        GET_AMOUNTS_OUT,
+        &[uint(1_000_000), Token::Array(vec![Token::Address(addr(USDC)), Token::Address(addr(WETH))])],
+        &amounts(1_000_000.into(), U256::exp10(14) * 5),
+    )
+    .on_selector(addr(WETH), "decimals()", &[uint(18)])
+    .on_selector(addr(USDC), "decimals()", &[uint(6)])
+    .on_selector(addr(ROUTER), SWAP_ETH_FOR_TOKENS, &[Token::Array(vec![])])
+    .on_selector(addr(ROUTER), SWAP_TOKENS_FOR_ETH, &[Token::Array(vec![])])
+    .on("eth_getBlockByNumber", json!({ "number": "0x64", "hash": format!("0x{:064x}", 100), "timestamp": "0x6553f100" }))
+    .on("eth_estimateGas", json!("0x1d4c0"));
+    mock
+}
 
-    let swap_module = SwapModule::new((*provider).clone(),config);
+async fn module(mock: &MockRpc) -> SwapModule {
+    let config = AppConfig {
+        token_addresses: HashMap::from([("WETH".to_string(), addr(WETH)), ("USDC".to_string(), addr(USDC))]),
+        uniswap_v2_router: addr(ROUTER),
+        wallet_address: addr(WALLET),
+        ..Default::default()
+    };
+    SwapModule::shared(mock.provider().await, config)
+}
 
-    let from_token = "ETH";      // 原生 ETH
-    let to_token = "USDC";       // 目标 ERC20 token
+#[tokio::test]
+async fn test_simulate_swap_eth_to_usdc() -> Result<()> {
+    let mock = mock_router();
+    let swap = module(&mock).await;
 
-    let amount_in = Decimal::from_f64(0.001).unwrap();
-    let slippage = 0.5; // 0.5%
+    let (estimated_output, gas) = swap.swap_tokens("ETH", "USDC", Decimal::new(1, 3), 0.5).await?;
+    assert_eq!(estimated_output, Decimal::new(185, 2));
+    assert_eq!(gas, Decimal::from(120_000));
 
-    // 调用模拟 swap
-    let (estimated_output, gas_estimate) = swap_module
-        .swap_tokens(from_token, to_token, amount_in, slippage)
-        .await?;
+    // 报价、模拟调用和 gas 估算都在同一个块上
+    let blocks: Vec<_> = mock
+        .requests()
+        .into_iter()
+        .filter(|(m, params)| m == "eth_estimateGas" || (m == "eth_call" && params[0]["to"] == ROUTER))
+        .map(|(_, params)| params[1].clone())
+        .collect();
+    assert_eq!(blocks, vec![json!("0x64"); 3]);
 
+    // 模拟的是 swapExactETHForTokens，value 为输入金额
+    let (_, params) = mock.requests().into_iter().find(|(m, _)| m == "eth_estimateGas").unwrap();
+    assert_eq!(params[0]["value"], "0x38d7ea4c68000");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_simulate_swap_token_to_eth() -> Result<()> {
+    let mock = mock_router();
+    let swap = module(&mock).await;
+
+    let (estimated_output, _) = swap.swap_tokens_at("USDC", "ETH", Decimal::ONE, 1.0, 7.into()).await?;
+    assert_eq!(estimated_output, Decimal::new(5, 4));
+    assert_eq!(mock.count("eth_blockNumber"), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_swap_errors() -> Result<()> {
+    let mock = mock_router();
+    let swap = module(&mock).await;
 
-    println!("Estimated output: {}", estimated_output);
-    println!("Estimated gas: {}", gas_estimate);
+    let err = swap.swap_tokens("ETH", "DOGE", Decimal::ONE, 0.5).await.unwrap_err();
+    assert!(err.to_string().contains("Unknown token: DOGE"));
 
-    // 简单断言
-    assert!(estimated_output > Decimal::ZERO, "Estimated output should be > 0");
-    assert!(gas_estimate > Decimal::ZERO, "Gas estimate should be > 0");
+    // 没有流动性的路径（getAmountsOut 没有响应）报 0，而不是报错
+    let (out, gas) = swap.swap_tokens("ETH", "USDC", Decimal::ONE, 0.5).await?;
+    assert_eq!((out, gas), (Decimal::ZERO, Decimal::ZERO));
 
+    // 模拟交易 revert
+    mock.revert(addr(ROUTER), SWAP_ETH_FOR_TOKENS);
+    let err = swap.swap_tokens("ETH", "USDC", Decimal::new(1, 3), 0.5).await.unwrap_err();
+    assert!(err.to_string().contains("execution reverted"));
     Ok(())
 }
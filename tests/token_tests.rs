@@ -0,0 +1,148 @@
+// tests/token_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{call, call_data, connect, hex, reverted, service_for, word, FakeNode};
+use eth_mcp_server::config::AppConfig;
+use eth_mcp_server::registry::{TokenEntry, TokenRegistry};
+use eth_mcp_server::token::{decode_decimals, decode_string_or_bytes32};
+use ethers::abi::{self, Token};
+use ethers::types::{Address, U256};
+use serde_json::{json, Value};
+
+const TOKEN: &str = "0x00000000000000000000000000000000000000cc";
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+
+fn bytes32(s: &str) -> Vec<u8> {
+    let mut word = s.as_bytes().to_vec();
+    word.resize(32, 0);
+    word
+}
+
+/// 类 MKR 的代币：name 是 string，symbol 是 bytes32，decimals() 会 revert
+async fn start_fake_node() -> Result<(std::sync::Arc<FakeNode>, String)> {
+    FakeNode::start(|method, params| match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getCode" => Ok(json!("0x6080604052")),
+        "eth_call" => match &call_data(params)[..10] {
+            "0x06fdde03" => Ok(hex(&abi::encode(&[Token::String("Maker".into())]))),
+            "0x95d89b41" => Ok(hex(&bytes32("MKR"))),
+            "0x313ce567" => reverted(),
+            "0x18160ddd" => Ok(word(1_000_000)),
+            // ENS registry.resolver()：没有反向记录
+            "0x0178b8bf" => Ok(word(0)),
+            other => panic!("unexpected selector {}", other),
+        },
+        other => panic!("unexpected method {}", other),
+    })
+    .await
+}
+
+#[test]
+fn test_decode_string_or_bytes32() {
+    let encoded = abi::encode(&[Token::String("Wrapped Ether".into())]);
+    assert_eq!(decode_string_or_bytes32(&encoded).as_deref(), Some("Wrapped Ether"));
+    assert_eq!(decode_string_or_bytes32(&bytes32("MKR")).as_deref(), Some("MKR"));
+
+    assert_eq!(decode_string_or_bytes32(&[0u8; 32]), None);
+    assert_eq!(decode_string_or_bytes32(&[]), None);
+    // 非 UTF-8 的 bytes32
+    assert_eq!(decode_string_or_bytes32(&[0xffu8; 32]), None);
+}
+
+#[test]
+fn test_decode_decimals() {
+    assert_eq!(decode_decimals(&abi::encode(&[Token::Uint(U256::from(18))])), Some(18));
+    assert_eq!(decode_decimals(&abi::encode(&[Token::Uint(U256::from(256))])), None);
+    assert_eq!(decode_decimals(&[0u8; 4]), None);
+}
+
+#[test]
+fn test_registry_rejects_conflicting_symbol() -> Result<()> {
+    let registry = TokenRegistry::default();
+    let entry = |address: &str| -> Result<TokenEntry> {
+        Ok(TokenEntry {
+            symbol: "mkr".into(),
+            address: address.parse()?,
+            decimals: Some(18),
+            price_feed: None,
+        })
+    };
+
+    registry.register(entry(TOKEN)?)?;
+    // 同地址重复注册没问题
+    registry.register(entry(TOKEN)?)?;
+    assert!(registry.register(entry(FEED)?).is_err());
+
+    // symbol 不区分大小写
+    assert_eq!(registry.address("MKR"), Some(TOKEN.parse()?));
+    assert_eq!(registry.len(), 1);
+
+    // 同一个地址不能再换个 symbol 注册
+    let alias = TokenEntry { symbol: "X".into(), ..entry(TOKEN)? };
+    let err = registry.register(alias).unwrap_err();
+    assert!(err.to_string().contains("is already registered as MKR"), "{}", err);
+    assert_eq!(registry.by_address(TOKEN.parse()?).unwrap().symbol, "mkr");
+
+    // 不能给已注册的代币换 price feed；不带 feed 的重复注册不影响原来的
+    let feed = |address: &str| -> Result<TokenEntry> { Ok(TokenEntry { price_feed: Some(address.parse()?), ..entry(TOKEN)? }) };
+    let registry = TokenRegistry::default();
+    registry.register(feed(FEED)?)?;
+    registry.register(entry(TOKEN)?)?;
+    registry.register(feed(FEED)?)?;
+    assert!(registry.register(feed(TOKEN)?).is_err());
+    assert_eq!(registry.price_feed("MKR"), Some(FEED.parse()?));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_token_info_handles_bytes32_and_missing_decimals() -> Result<()> {
+    let (node, url) = start_fake_node().await?;
+    let service = service_for(&url, AppConfig::default());
+    let client = connect(service).await?;
+
+    let info = call(&client, "get_token_info", json!({ "address": TOKEN })).await?;
+
+    assert_eq!(info["name"], "Maker");
+    assert_eq!(info["symbol"], "MKR");
+    assert_eq!(info["decimals"], Value::Null);
+    assert_eq!(info["total_supply_raw"], "1000000");
+    assert_eq!(info["total_supply"], Value::Null);
+    assert_eq!(info["warnings"].as_array().unwrap().len(), 1);
+    assert_eq!(info["registered_as"], Value::Null);
+
+    // 元数据永久缓存，totalSupply 同一块内命中，第二次不再 eth_call
+    call(&client, "get_token_info", json!({ "address": TOKEN })).await?;
+    // 4 个元数据 + 1 次 ENS 反查
+    assert_eq!(node.count("eth_call"), 5);
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_registered_token_resolves_by_symbol() -> Result<()> {
+    let (_node, url) = start_fake_node().await?;
+    let service = service_for(&url, AppConfig::default());
+    let client = connect(service.clone()).await?;
+
+    let info = call(
+        &client,
+        "get_token_info",
+        json!({ "address": TOKEN, "register": true, "price_feed": FEED }),
+    )
+    .await?;
+    assert_eq!(info["registered_as"], "MKR");
+
+    let entry = service.tokens.registry.get("mkr").unwrap();
+    assert_eq!(entry.address, TOKEN.parse::<Address>()?);
+    assert_eq!(entry.price_feed, Some(FEED.parse()?));
+    assert_eq!(entry.decimals, None);
+
+    // 地址格式不对
+    assert!(call(&client, "get_token_info", json!({ "address": "not-an-address" })).await.is_err());
+
+    client.cancel().await?;
+    Ok(())
+}
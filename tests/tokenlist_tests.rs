@@ -0,0 +1,134 @@
+// tests/tokenlist_tests.rs
+use anyhow::Result;
+use eth_mcp_server::registry::TokenRegistry;
+use eth_mcp_server::tokenlist::{load_token_lists, merge_token_lists, TokenList};
+use ethers::types::Address;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const DAI: &str = "0x6b175474e89094c44da98b954eedeac495271d0f";
+const FAKE: &str = "0x00000000000000000000000000000000000000cc";
+
+fn token(chain_id: u64, address: &str, symbol: &str, decimals: u64) -> Value {
+    json!({
+        "chainId": chain_id,
+        "address": address,
+        "symbol": symbol,
+        "name": format!("{} token", symbol),
+        "decimals": decimals,
+        "logoURI": "ipfs://example"
+    })
+}
+
+fn list(name: &str, tokens: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "timestamp": "2024-01-01T00:00:00.000Z",
+        "version": { "major": 1, "minor": 0, "patch": 0 },
+        "tokens": tokens
+    })
+}
+
+fn parse(value: &Value) -> Result<TokenList> {
+    TokenList::from_json(&value.to_string())
+}
+
+#[test]
+fn test_valid_list_filters_by_chain() -> Result<()> {
+    let l = parse(&list(
+        "Default",
+        vec![token(1, USDC, "USDC", 6), token(10, FAKE, "USDC", 6), token(1, DAI, "DAI", 18)],
+    ))?;
+
+    let merged = merge_token_lists(&[l], 1, &HashMap::new());
+    assert!(merged.issues.is_empty());
+    let symbols: Vec<_> = merged.tokens.iter().map(|t| t.symbol.as_str()).collect();
+    assert_eq!(symbols, vec!["USDC", "DAI"]);
+    assert_eq!(merged.tokens[0].decimals, Some(6));
+    Ok(())
+}
+
+#[test]
+fn test_schema_violations_are_reported() {
+    let mut bad_token = token(1, "0x1234", "BAD SYMBOL", 300);
+    bad_token["chainId"] = json!(0);
+    let err = parse(&list("Bad!", vec![bad_token])).unwrap_err().to_string();
+
+    for field in ["name", "tokens[0].chainId", "tokens[0].address", "tokens[0].decimals", "tokens[0].symbol"] {
+        assert!(err.contains(field), "missing {} in {}", field, err);
+    }
+
+    // 未知字段、缺字段、空列表
+    let mut extra = list("Extra", vec![token(1, USDC, "USDC", 6)]);
+    extra["tokens"][0]["price"] = json!(1);
+    assert!(parse(&extra).is_err());
+
+    let mut missing = list("Missing", vec![token(1, USDC, "USDC", 6)]);
+    missing.as_object_mut().unwrap().remove("version");
+    assert!(parse(&missing).is_err());
+
+    assert!(parse(&list("Empty", vec![])).is_err());
+
+    let mut timestamp = list("Timestamp", vec![token(1, USDC, "USDC", 6)]);
+    timestamp["timestamp"] = json!("yesterday");
+    assert!(parse(&timestamp).is_err());
+}
+
+#[test]
+fn test_conflicts_and_duplicates_are_reported() -> Result<()> {
+    let first = parse(&list("First", vec![token(1, USDC, "USDC", 6), token(1, DAI, "DAI", 18)]))?;
+    let second = parse(&list(
+        "Second",
+        vec![
+            // 同一个代币，静默去重
+            token(1, USDC, "usdc", 6),
+            // symbol 冲突
+            token(1, FAKE, "DAI", 18),
+            // 同一地址换了个 symbol
+            token(1, DAI, "DAI2", 18),
+        ],
+    ))?;
+
+    let merged = merge_token_lists(&[first, second], 1, &HashMap::new());
+    assert_eq!(merged.tokens.len(), 2);
+    assert_eq!(merged.issues.len(), 2);
+    assert!(merged.issues[0].contains("symbol DAI"));
+    assert!(merged.issues[1].contains("already listed as DAI"));
+    Ok(())
+}
+
+#[test]
+fn test_configured_addresses_override_lists() -> Result<()> {
+    let l = parse(&list("Default", vec![token(1, USDC, "USDC", 6), token(1, DAI, "DAI", 18)]))?;
+    let overrides = HashMap::from([("dai".to_string(), FAKE.parse::<Address>()?)]);
+
+    let merged = merge_token_lists(&[l], 1, &overrides);
+    assert_eq!(merged.tokens.len(), 1);
+    assert_eq!(merged.tokens[0].symbol, "USDC");
+    assert!(merged.issues[0].contains("overridden"));
+    Ok(())
+}
+
+#[test]
+fn test_load_files_into_registry() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("tokenlist-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("list.json");
+    std::fs::write(&path, list("Default", vec![token(1, USDC, "USDC", 6)]).to_string())?;
+    let paths = vec![path.display().to_string()];
+
+    let merged = load_token_lists(&paths, 1, &HashMap::new())?;
+    let registry = TokenRegistry::default();
+    for t in merged.tokens {
+        registry.register(t)?;
+    }
+    assert_eq!(registry.address("usdc"), Some(USDC.parse()?));
+
+    // 链 ID 配错时直接报错，而不是悄悄加载 0 个代币
+    assert!(load_token_lists(&paths, 137, &HashMap::new()).is_err());
+    assert!(load_token_lists(&["/nonexistent.json".to_string()], 1, &HashMap::new()).is_err());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
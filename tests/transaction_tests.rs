@@ -0,0 +1,270 @@
+// tests/transaction_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{addr, address_topic, call, call_data, connect, hex, service_for, topic, FakeNode, RpcReply};
+use eth_mcp_server::config::AppConfig;
+use ethers::abi::{self, HumanReadableParser, Token};
+use ethers::types::{H256, I256, U256};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const SENDER: &str = "0x00000000000000000000000000000000000000a1";
+const ROUTER: &str = "0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45";
+const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const POOL: &str = "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640";
+const NFT: &str = "0x00000000000000000000000000000000000000c3";
+/// ETH/USD feed，8 位小数，2000 USD
+const FEED: &str = "0x00000000000000000000000000000000000000fe";
+
+const SWAP_TX: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+const PENDING_TX: &str = "0x2222222222222222222222222222222222222222222222222222222222222222";
+
+/// SwapRouter02.multicall(deadline, [exactInputSingle(USDC → WETH), unwrapWETH9])
+fn swap_calldata() -> Vec<u8> {
+    let parse = |sig: &str| HumanReadableParser::parse_function(sig).unwrap();
+    let swap = parse("function exactInputSingle((address,address,uint24,address,uint256,uint256,uint160) params)")
+        .encode_input(&[Token::Tuple(vec![
+            Token::Address(addr(USDC)),
+            Token::Address(addr(WETH)),
+            Token::Uint(500.into()),
+            Token::Address(addr(ROUTER)),
+            Token::Uint(2_000_000_000u64.into()),
+            Token::Uint(U256::exp10(18) / 2),
+            Token::Uint(0.into()),
+        ])])
+        .unwrap();
+    let unwrap = parse("function unwrapWETH9(uint256 amountMinimum, address recipient)")
+        .encode_input(&[Token::Uint(U256::exp10(18) / 2), Token::Address(addr(SENDER))])
+        .unwrap();
+    parse("function multicall(uint256 deadline, bytes[] data)")
+        .encode_input(&[
+            Token::Uint(1_700_000_000u64.into()),
+            Token::Array(vec![Token::Bytes(swap), Token::Bytes(unwrap)]),
+        ])
+        .unwrap()
+}
+
+fn log(index: u64, address: &str, topics: Vec<H256>, data: Vec<u8>) -> Value {
+    json!({
+        "address": address,
+        "topics": topics,
+        "data": hex(&data),
+        "blockNumber": "0x64",
+        "transactionHash": SWAP_TX,
+        "transactionIndex": "0x0",
+        "blockHash": format!("{:?}", H256::repeat_byte(0xbb)),
+        "logIndex": format!("{:#x}", index),
+        "removed": false
+    })
+}
+
+fn transaction(hash: &str, block: Option<u64>) -> Value {
+    json!({
+        "hash": hash,
+        "nonce": "0x7",
+        "blockHash": block.map(|_| format!("{:?}", H256::repeat_byte(0xbb))),
+        "blockNumber": block.map(|b| format!("{:#x}", b)),
+        "transactionIndex": block.map(|_| "0x0"),
+        "from": SENDER,
+        "to": ROUTER,
+        "value": "0x0",
+        "gasPrice": "0x4a817c800",
+        "gas": "0x493e0",
+        "input": hex(&swap_calldata()),
+        "v": "0x1",
+        "r": "0x1",
+        "s": "0x1",
+        "type": "0x0",
+        "chainId": "0x1"
+    })
+}
+
+fn receipt() -> Value {
+    let amount = |v: U256| abi::encode(&[Token::Uint(v)]);
+    let logs = vec![
+        // ERC20 Transfer：SENDER → POOL 1000 USDC
+        log(
+            0,
+            USDC,
+            vec![topic("Transfer(address,address,uint256)"), address_topic(SENDER), address_topic(POOL)],
+            amount(U256::from(1_000_000_000u64)),
+        ),
+        // Uniswap V3 Swap
+        log(
+            1,
+            POOL,
+            vec![
+                topic("Swap(address,address,int256,int256,uint160,uint128,int24)"),
+                address_topic(ROUTER),
+                address_topic(ROUTER),
+            ],
+            abi::encode(&[
+                Token::Int(U256::from(1_000_000_000u64)),
+                Token::Int(I256::from(-500_000_000_000_000_000i64).into_raw()),
+                Token::Uint(U256::from(2).pow(96.into())),
+                Token::Uint(U256::exp10(18)),
+                Token::Int(I256::from(-200_000).into_raw()),
+            ]),
+        ),
+        // WETH Withdrawal
+        log(
+            2,
+            WETH,
+            vec![topic("Withdrawal(address,uint256)"), address_topic(ROUTER)],
+            amount(U256::exp10(18) / 2),
+        ),
+        // ERC721 Transfer（tokenId 也是 indexed）不在库里，只给原始数据
+        log(
+            3,
+            NFT,
+            vec![
+                topic("Transfer(address,address,uint256)"),
+                address_topic(SENDER),
+                address_topic(ROUTER),
+                H256::from_low_u64_be(9),
+            ],
+            vec![],
+        ),
+    ];
+    json!({
+        "transactionHash": SWAP_TX,
+        "transactionIndex": "0x0",
+        "blockHash": format!("{:?}", H256::repeat_byte(0xbb)),
+        "blockNumber": "0x64",
+        "from": SENDER,
+        "to": ROUTER,
+        "cumulativeGasUsed": "0x249f0",
+        "gasUsed": "0x249f0",
+        "contractAddress": null,
+        "logs": logs,
+        "status": "0x1",
+        "logsBloom": format!("0x{}", "00".repeat(256)),
+        "effectiveGasPrice": "0x4a817c800",
+        "type": "0x0"
+    })
+}
+
+fn handle(method: &str, params: &Value) -> RpcReply {
+    match method {
+        "eth_chainId" => Ok(json!("0x1")),
+        // 交易在第 100 块，链头已经到了 0x70
+        "eth_blockNumber" => Ok(json!("0x70")),
+        "eth_getBlockByHash" => {
+            assert_eq!(params[0], format!("{:?}", H256::repeat_byte(0xbb)));
+            Ok(json!({ "number": "0x64", "hash": params[0], "timestamp": format!("{:#x}", 1_700_000_000u64) }))
+        }
+        "eth_getTransactionByHash" => Ok(match params[0].as_str().unwrap() {
+            SWAP_TX => transaction(SWAP_TX, Some(100)),
+            PENDING_TX => transaction(PENDING_TX, None),
+            _ => Value::Null,
+        }),
+        "eth_getTransactionReceipt" => {
+            assert_eq!(params[0], SWAP_TX, "pending transactions have no receipt");
+            Ok(receipt())
+        }
+        "eth_call" => {
+            // ETH/USD feed：decimals() / latestRoundData()；交易所在块 2000 USD，之后涨到 3000
+            assert_eq!(params[0]["to"], FEED);
+            let usd = if params[1] == "0x64" { 2000 } else { 3000 };
+            let out = match &call_data(params)[..10] {
+                "0x313ce567" => abi::encode(&[Token::Uint(8.into())]),
+                _ => abi::encode(&[
+                    Token::Uint(1.into()),
+                    Token::Int(U256::from(usd) * U256::exp10(8)),
+                    Token::Uint(0.into()),
+                    Token::Uint(0.into()),
+                    Token::Uint(1.into()),
+                ]),
+            };
+            Ok(hex(&out))
+        }
+        other => panic!("unexpected method {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_get_transaction_decodes_calldata_and_logs() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let config = AppConfig {
+        token_addresses: HashMap::from([("ETH".to_string(), addr(FEED))]),
+        ..Default::default()
+    };
+    let client = connect(service_for(&url, config)).await?;
+
+    let result = call(&client, "get_transaction", json!({ "hash": SWAP_TX })).await?;
+    assert_eq!(result["status"], "success");
+    assert_eq!(result["block"], 100);
+    assert_eq!(result["timestamp"], "2023-11-14T22:13:20Z");
+    assert_eq!(result["gas_used"], "150000");
+    assert_eq!(result["effective_gas_price_gwei"], "20.000000000");
+    // 150000 × 20 gwei = 0.003 ETH，按交易所在块的 2000 USD 算，不是现在的 3000
+    assert_eq!(result["fee"], "0.003000000000000000");
+    assert_eq!(result["fee_usd"], "6.00");
+
+    // 回执和块时间按块哈希缓存
+    call(&client, "get_transaction", json!({ "hash": SWAP_TX })).await?;
+    assert_eq!(node.count("eth_getTransactionByHash"), 2);
+    assert_eq!(node.count("eth_getTransactionReceipt"), 1);
+    assert_eq!(node.count("eth_getBlockByHash"), 1);
+
+    let call_ = &result["call"];
+    assert_eq!(call_["abi"], "UniswapV3SwapRouter02");
+    assert_eq!(call_["function"], "multicall(uint256,bytes[])");
+    assert_eq!(call_["args"][0], json!({ "name": "deadline", "type": "uint256", "value": "1700000000" }));
+    // multicall 里的子调用
+    let inner = call_["calls"].as_array().unwrap();
+    assert_eq!(inner.len(), 2);
+    assert_eq!(
+        inner[0]["function"],
+        "exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))"
+    );
+    assert_eq!(inner[0]["args"][0]["value"][0], USDC);
+    assert_eq!(inner[0]["args"][0]["value"][2], "500");
+    assert_eq!(inner[1]["function"], "unwrapWETH9(uint256,address)");
+    assert_eq!(inner[1]["args"][1]["value"], SENDER);
+
+    let logs = result["logs"].as_array().unwrap();
+    assert_eq!(logs[0]["abi"], "ERC20");
+    assert_eq!(logs[0]["event"], "Transfer(address,address,uint256)");
+    assert_eq!(logs[0]["args"][2], json!({ "name": "value", "type": "uint256", "value": "1000000000" }));
+    assert_eq!(logs[1]["abi"], "UniswapV3Pool");
+    assert_eq!(logs[1]["args"][3]["value"], "-500000000000000000");
+    assert_eq!(logs[1]["args"][6], json!({ "name": "tick", "type": "int24", "value": "-200000" }));
+    assert_eq!(logs[2]["abi"], "WETH");
+    assert_eq!(logs[2]["event"], "Withdrawal(address,uint256)");
+    // 不认识的事件保留原始 topics / data
+    assert_eq!(logs[3]["abi"], Value::Null);
+    assert_eq!(logs[3]["args"], json!([]));
+    assert_eq!(logs[3]["topics"].as_array().unwrap().len(), 4);
+    assert_eq!(logs[3]["data"], "0x");
+
+    client.cancel().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pending_and_unknown_transactions() -> Result<()> {
+    let (node, url) = FakeNode::start(handle).await?;
+    let client = connect(service_for(&url, AppConfig::default())).await?;
+
+    let result = call(&client, "get_transaction", json!({ "hash": PENDING_TX })).await?;
+    assert_eq!(result["status"], "pending");
+    assert_eq!(result["block"], Value::Null);
+    assert_eq!(result["fee"], Value::Null);
+    assert_eq!(result["logs"], json!([]));
+    // calldata 不依赖回执
+    assert_eq!(result["call"]["abi"], "UniswapV3SwapRouter02");
+    assert_eq!(node.count("eth_getTransactionReceipt"), 0);
+
+    let missing = format!("0x{}", "33".repeat(32));
+    let err = call(&client, "get_transaction", json!({ "hash": missing })).await.unwrap_err();
+    assert!(err.to_string().contains("not found"), "{}", err);
+
+    let err = call(&client, "get_transaction", json!({ "hash": "0x1234" })).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid hash"), "{}", err);
+
+    client.cancel().await?;
+    Ok(())
+}
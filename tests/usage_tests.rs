@@ -0,0 +1,159 @@
+// tests/usage_tests.rs
+mod common;
+
+use anyhow::Result;
+use common::{block_header, serve_http, service_for, word, FakeNode};
+use eth_mcp_server::auth::{AuthConfig, ClientIdentity};
+use eth_mcp_server::config::{AppConfig, ClientConfig, RateLimits};
+use eth_mcp_server::http::HttpServer;
+use eth_mcp_server::usage::{ToolUsage, UsageReport, UsageTracker};
+use rmcp::model::CallToolRequestParam;
+use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
+use rmcp::transport::StreamableHttpClientTransport;
+use rmcp::ServiceExt;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WALLET: &str = "0x00000000000000000000000000000000000000aa";
+
+fn client_with(limits: RateLimits) -> ClientIdentity {
+    let config = ClientConfig {
+        id: "bot".into(),
+        token: "bot-token".into(),
+        tools: None,
+        wallets: None,
+        allow_execution: false,
+        allow_registry: false,
+        limits: Some(limits),
+    };
+    AuthConfig::new(&[config]).unwrap().authenticate("bot-token").unwrap().clone()
+}
+
+#[test]
+fn test_calls_per_minute() {
+    let tracker = UsageTracker::new(RateLimits::default());
+    let client = client_with(RateLimits {
+        calls_per_minute: Some(2),
+        ..Default::default()
+    });
+
+    assert!(tracker.check(&client, "get_price").is_ok());
+    assert!(tracker.check(&client, "get_balance").is_ok());
+
+    let err = tracker.check(&client, "get_price").unwrap_err();
+    assert!(err.retry_after > Duration::ZERO && err.retry_after <= Duration::from_secs(30));
+    assert_eq!(tracker.report(&client).rejected_calls, 1);
+}
+
+#[test]
+fn test_per_tool_limit_does_not_block_other_tools() {
+    let tracker = UsageTracker::new(RateLimits::default());
+    let client = client_with(RateLimits {
+        tools: HashMap::from([("get_price".to_string(), 1)]),
+        ..Default::default()
+    });
+
+    assert!(tracker.check(&client, "get_price").is_ok());
+    let err = tracker.check(&client, "get_price").unwrap_err();
+    assert!(err.reason.contains("get_price"));
+    assert!(tracker.check(&client, "get_balance").is_ok());
+}
+
+#[test]
+fn test_rpc_budget_exhaustion() {
+    let tracker = UsageTracker::new(RateLimits::default());
+    let client = client_with(RateLimits {
+        rpc_calls_per_hour: Some(3),
+        ..Default::default()
+    });
+
+    assert!(tracker.check(&client, "swap_tokens").is_ok());
+    tracker.record(&client, "swap_tokens", 5);
+
+    let err = tracker.check(&client, "get_price").unwrap_err();
+    assert!(err.reason.contains("RPC budget"));
+    // 透支 2 次 + 需要 1 个令牌，按 3 次/小时回填 → 大约 1 小时
+    assert!(err.retry_after > Duration::from_secs(3000));
+
+    let report = tracker.report(&client);
+    assert_eq!(report.total_rpc_calls, 5);
+    assert_eq!(report.rpc_budget_remaining, Some(0));
+}
+
+#[test]
+fn test_default_limits_apply_to_local_client() {
+    let tracker = UsageTracker::new(RateLimits {
+        calls_per_minute: Some(1),
+        ..Default::default()
+    });
+    let local = ClientIdentity::local();
+
+    assert!(tracker.check(&local, "get_price").is_ok());
+    assert!(tracker.check(&local, "get_price").is_err());
+}
+
+#[tokio::test]
+async fn test_rpc_calls_are_attributed_to_client() -> Result<()> {
+    // 余额 1 ETH；eth_call 只有 ENS 反查，返回空 resolver
+    let (_node, node_url) = FakeNode::start(|method, params| match method {
+        "eth_call" => Ok(word(0)),
+        "eth_getBlockByNumber" => Ok(block_header(params, 0xde0b6b3a7640000)),
+        _ => Ok(json!("0xde0b6b3a7640000")),
+    })
+    .await?;
+    let service = service_for(&node_url, AppConfig::default());
+
+    let clients = vec![ClientConfig {
+        id: "bot".into(),
+        token: "bot-token".into(),
+        tools: None,
+        wallets: None,
+        allow_execution: false,
+        allow_registry: false,
+        limits: Some(RateLimits {
+            tools: HashMap::from([("get_balance".to_string(), 2)]),
+            ..Default::default()
+        }),
+    }];
+
+    let server = HttpServer::new(service, None).with_auth(AuthConfig::new(&clients)?);
+    let (url, shutdown, _) = serve_http(Arc::new(server)).await?;
+
+    let transport = StreamableHttpClientTransport::from_config(
+        StreamableHttpClientTransportConfig::with_uri(url).auth_header("bot-token"),
+    );
+    let client = ().serve(transport).await?;
+
+    let get_balance = || CallToolRequestParam {
+        name: "get_balance".into(),
+        arguments: json!({ "address": WALLET }).as_object().cloned(),
+    };
+    let result = client.call_tool(get_balance()).await?;
+    assert_eq!(result.structured_content.unwrap()["balance"], "1.000000000000000000");
+    client.call_tool(get_balance()).await?;
+
+    // 第三次超过每分钟 2 次的限制
+    let err = client.call_tool(get_balance()).await.unwrap_err();
+    assert!(err.to_string().contains("retry after"));
+
+    let usage = client
+        .call_tool(CallToolRequestParam {
+            name: "get_usage".into(),
+            arguments: json!({}).as_object().cloned(),
+        })
+        .await?;
+    let report: UsageReport = serde_json::from_value(usage.structured_content.unwrap())?;
+    assert_eq!(report.client, "bot");
+    // 第一次：eth_chainId + eth_blockNumber + 块头 + eth_getBalance + ENS 反查 + 确认块哈希；
+    // 第二次同一块内只有确认块哈希不走缓存
+    assert_eq!(report.tools["get_balance"], ToolUsage { calls: 2, rpc_calls: 7 });
+    assert_eq!(report.rejected_calls, 1);
+    // 共享缓存的计数也在里面（stdio 模式没有 /metrics）
+    assert!(report.cache.unwrap().block_hits > 0);
+
+    client.cancel().await?;
+    shutdown.cancel();
+    Ok(())
+}